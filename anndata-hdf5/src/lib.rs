@@ -3,7 +3,8 @@ use anndata::{
     data::{DynArray, DynCowArray, DynScalar, SelectInfoBounds, SelectInfoElem, SelectInfoElemBounds, Shape},
 };
 
-use anyhow::{bail, Ok, Result};
+use anyhow::{bail, ensure, Ok, Result};
+use half::f16;
 use hdf5::{
     dataset::Dataset,
     types::IntSize::*,
@@ -21,7 +22,14 @@ use std::path::{Path, PathBuf};
 
 pub struct H5;
 
-pub struct H5File(File);
+/// Registered identifier of the HDF5 Zstandard filter. It is provided by a plugin
+/// (e.g., the one shipped with `hdf5plugin`) that must be discoverable through
+/// `HDF5_PLUGIN_PATH` both when writing and reading. Blosc and deflate are built
+/// into this crate.
+const ZSTD_FILTER_ID: hdf5_sys::h5z::H5Z_filter_t = 32015;
+
+/// An HDF5 file together with the default compression of the datasets created in it.
+pub struct H5File(File, Compression);
 
 impl Deref for H5File {
     type Target = File;
@@ -31,7 +39,7 @@ impl Deref for H5File {
     }
 }
 
-pub struct H5Group(Group);
+pub struct H5Group(Group, Compression);
 
 impl Deref for H5Group {
     type Target = Group;
@@ -41,7 +49,8 @@ impl Deref for H5Group {
     }
 }
 
-pub struct H5Dataset(Dataset);
+/// A dataset together with the default compression of its store.
+pub struct H5Dataset(Dataset, Compression);
 
 impl Deref for H5Dataset {
     type Target = Dataset;
@@ -63,17 +72,17 @@ impl Backend for H5 {
     type Dataset = H5Dataset;
 
     fn new<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
-        Ok(H5File(File::create(path)?))
+        Ok(H5File(File::create(path)?, Compression::default()))
     }
 
     /// Opens a file as read-only, file must exist.
     fn open<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
-        Ok(File::open(path).map(H5File)?)
+        Ok(H5File(File::open(path)?, Compression::default()))
     }
 
     /// Opens a file as read/write, file must exist.
     fn open_rw<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
-        Ok(File::open_rw(path).map(H5File)?)
+        Ok(H5File(File::open_rw(path)?, Compression::default()))
     }
 }
 
@@ -85,6 +94,10 @@ impl StoreOp<H5> for H5File {
     fn close(self) -> Result<()> {
         Ok(self.0.close()?)
     }
+
+    fn set_compression(&mut self, compression: Compression) {
+        self.1 = compression;
+    }
 }
 
 // Generic GroupOp implementations
@@ -93,12 +106,12 @@ fn list(group: &Group) -> Result<Vec<String>> {
     Ok(group.member_names()?)
 }
 
-fn create_group(group: &Group, name: &str) -> Result<H5Group> {
-    Ok(H5Group(group.create_group(name)?))
+fn create_group(group: &Group, name: &str, compression: Compression) -> Result<H5Group> {
+    Ok(H5Group(group.create_group(name)?, compression))
 }
 
-fn open_group(group: &Group, name: &str) -> Result<H5Group> {
-    Ok(H5Group(group.group(name)?))
+fn open_group(group: &Group, name: &str, compression: Compression) -> Result<H5Group> {
+    Ok(H5Group(group.group(name)?, compression))
 }
 
 fn new_dataset<T: BackendData>(
@@ -106,6 +119,7 @@ fn new_dataset<T: BackendData>(
     name: &str,
     shape: &Shape,
     config: WriteConfig,
+    default_compression: Compression,
 ) -> Result<H5Dataset> {
     let mut builder = match T::DTYPE {
         ScalarType::U8 => group.new_dataset::<u8>(),
@@ -116,17 +130,44 @@ fn new_dataset<T: BackendData>(
         ScalarType::I16 => group.new_dataset::<i16>(),
         ScalarType::I32 => group.new_dataset::<i32>(),
         ScalarType::I64 => group.new_dataset::<i64>(),
+        ScalarType::F16 => group.new_dataset::<f16>(),
         ScalarType::F32 => group.new_dataset::<f32>(),
         ScalarType::F64 => group.new_dataset::<f64>(),
         ScalarType::Bool => group.new_dataset::<bool>(),
         ScalarType::String => group.new_dataset::<VarLenUnicode>(),
     };
 
-    builder = if let Some(compression) = config.compression {
-        builder.deflate(compression)
-        //builder.blosc_blosclz(compression, hdf5::filters::BloscShuffle::Byte)
-    } else {
-        builder
+    let chunk_len = match &config.block_size {
+        Some(s) if s.as_ref().iter().all(|&x| x > 0) => s.as_ref().iter().product(),
+        _ => shape.as_ref().iter().product(),
+    };
+    builder = match config.resolve_compression(default_compression, chunk_len) {
+        Compression::None => builder,
+        Compression::Gzip(level) => builder.deflate(level),
+        Compression::Zstd(level) => {
+            ensure!(
+                filter_available(ZSTD_FILTER_ID),
+                "the HDF5 Zstandard filter (id {}) is not available, install the plugin and set HDF5_PLUGIN_PATH, or use Blosc with Zstd instead",
+                ZSTD_FILTER_ID,
+            );
+            builder.add_filter(ZSTD_FILTER_ID, &[level as u32])
+        }
+        Compression::BloscLz4 => builder.blosc_lz4(5, hdf5::filters::BloscShuffle::None),
+        Compression::Blosc { compressor, level, shuffle } => {
+            let shuffle = match shuffle {
+                BloscShuffle::None => hdf5::filters::BloscShuffle::None,
+                BloscShuffle::Byte => hdf5::filters::BloscShuffle::Byte,
+                BloscShuffle::Bit => hdf5::filters::BloscShuffle::Bit,
+            };
+            match compressor {
+                BloscCompressor::BloscLZ => builder.blosc_blosclz(level, shuffle),
+                BloscCompressor::LZ4 => builder.blosc_lz4(level, shuffle),
+                BloscCompressor::LZ4HC => builder.blosc_lz4hc(level, shuffle),
+                BloscCompressor::Snappy => builder.blosc_snappy(level, shuffle),
+                BloscCompressor::Zlib => builder.blosc_zlib(level, shuffle),
+                BloscCompressor::Zstd => builder.blosc_zstd(level, shuffle),
+            }
+        }
     };
 
     builder = if let Some(s) = config.block_size {
@@ -141,22 +182,37 @@ fn new_dataset<T: BackendData>(
 
     let s: hdf5::Extents = hdf5::SimpleExtents::resizable(shape.as_ref()).into();
     let dataset = builder.shape(s).create(name)?;
-    Ok(H5Dataset(dataset))
+    Ok(H5Dataset(dataset, default_compression))
+}
+
+/// Returns whether the filter is registered or can be loaded as a plugin.
+fn filter_available(id: hdf5_sys::h5z::H5Z_filter_t) -> bool {
+    unsafe { hdf5_sys::h5z::H5Zfilter_avail(id) > 0 }
 }
 
-fn open_dataset(group: &Group, name: &str) -> Result<H5Dataset> {
-    Ok(H5Dataset(group.dataset(name)?))
+fn open_dataset(group: &Group, name: &str, compression: Compression) -> Result<H5Dataset> {
+    Ok(H5Dataset(group.dataset(name)?, compression))
 }
 
 fn delete(group: &Group, name: &str) -> Result<()> {
     Ok(group.unlink(name)?)
 }
 
+fn rename(group: &Group, from: &str, to: &str) -> Result<()> {
+    ensure!(!group.link_exists(to), "'{}' already exists", to);
+    Ok(group.relink(from, to)?)
+}
+
 fn exists(group: &Group, name: &str) -> Result<bool> {
     Ok(group.link_exists(name))
 }
 
-fn create_scalar_data<D: BackendData>(group: &Group, name: &str, data: &D) -> Result<H5Dataset> {
+fn create_scalar_data<D: BackendData>(
+    group: &Group,
+    name: &str,
+    data: &D,
+    compression: Compression,
+) -> Result<H5Dataset> {
     match data.into_dyn() {
         DynScalar::U8(x) => {
             let dataset = group.new_dataset::<u8>().create(name)?;
@@ -198,6 +254,11 @@ fn create_scalar_data<D: BackendData>(group: &Group, name: &str, data: &D) -> Re
             dataset.write_scalar(&x)?;
             Ok(dataset)
         }
+        DynScalar::F16(x) => {
+            let dataset = group.new_dataset::<f16>().create(name)?;
+            dataset.write_scalar(&x)?;
+            Ok(dataset)
+        }
         DynScalar::F32(x) => {
             let dataset = group.new_dataset::<f32>().create(name)?;
             dataset.write_scalar(&x)?;
@@ -219,7 +280,7 @@ fn create_scalar_data<D: BackendData>(group: &Group, name: &str, data: &D) -> Re
             Ok(dataset)
         }
     }
-    .map(H5Dataset)
+    .map(|x| H5Dataset(x, compression))
 }
 
 impl DatasetOp<H5> for H5Dataset {
@@ -233,11 +294,14 @@ impl DatasetOp<H5> for H5Dataset {
             TypeDescriptor::Integer(U2) => ScalarType::I16,
             TypeDescriptor::Integer(U4) => ScalarType::I32,
             TypeDescriptor::Integer(U8) => ScalarType::I64,
+            TypeDescriptor::Float(FloatSize::U2) => ScalarType::F16,
             TypeDescriptor::Float(FloatSize::U4) => ScalarType::F32,
             TypeDescriptor::Float(FloatSize::U8) => ScalarType::F64,
             TypeDescriptor::Boolean => ScalarType::Bool,
             TypeDescriptor::VarLenAscii => ScalarType::String,
             TypeDescriptor::VarLenUnicode => ScalarType::String,
+            TypeDescriptor::FixedAscii(_) => ScalarType::String,
+            TypeDescriptor::FixedUnicode(_) => ScalarType::String,
             ty => bail!("Unsupported type: {:?}", ty),
         };
         Ok(ty)
@@ -262,6 +326,7 @@ impl DatasetOp<H5> for H5Dataset {
             ScalarType::I16 => self.deref().read_scalar::<i16>()?.into_dyn(),
             ScalarType::I32 => self.deref().read_scalar::<i32>()?.into_dyn(),
             ScalarType::I64 => self.deref().read_scalar::<i64>()?.into_dyn(),
+            ScalarType::F16 => self.deref().read_scalar::<f16>()?.into_dyn(),
             ScalarType::F32 => self.deref().read_scalar::<f32>()?.into_dyn(),
             ScalarType::F64 => self.deref().read_scalar::<f64>()?.into_dyn(),
             ScalarType::String => {
@@ -348,11 +413,21 @@ impl DatasetOp<H5> for H5Dataset {
             ScalarType::U16 => read_arr::<u16, _, D>(self, selection)?.into(),
             ScalarType::U32 => read_arr::<u32, _, D>(self, selection)?.into(),
             ScalarType::U64 => read_arr::<u64, _, D>(self, selection)?.into(),
+            ScalarType::F16 => read_arr::<f16, _, D>(self, selection)?.into(),
             ScalarType::F32 => read_arr::<f32, _, D>(self, selection)?.into(),
             ScalarType::F64 => read_arr::<f64, _, D>(self, selection)?.into(),
             ScalarType::Bool => read_arr::<bool, _, D>(self, selection)?.into(),
             ScalarType::String => {
-                if selection.as_ref().iter().any(|x| x.as_ref().is_index()) {
+                let fancy = selection.as_ref().iter().any(|x| x.as_ref().is_index());
+                if let Some(size) = fixed_string_size(self)? {
+                    if fancy {
+                        let arr = read_fixed_strings::<D>(self, size, Selection::All, self.shape())?;
+                        Ok(select(&arr, selection))
+                    } else {
+                        let (select, shape) = into_selection(selection, self.shape());
+                        read_fixed_strings::<D>(self, size, select, shape)
+                    }
+                } else if fancy {
                     // fancy indexing is too slow, just read all
                     let arr = self.deref().read::<VarLenUnicode, D>()?;
                     let arr_ = arr.map(|s| s.to_string());
@@ -412,6 +487,7 @@ impl DatasetOp<H5> for H5Dataset {
             DynCowArray::I16(x) => write_array_impl(self, x, selection),
             DynCowArray::I32(x) => write_array_impl(self, x, selection),
             DynCowArray::I64(x) => write_array_impl(self, x, selection),
+            DynCowArray::F16(x) => write_array_impl(self, x, selection),
             DynCowArray::F32(x) => write_array_impl(self, x, selection),
             DynCowArray::F64(x) => write_array_impl(self, x, selection),
             DynCowArray::Bool(x) => write_array_impl(self, x, selection),
@@ -425,8 +501,8 @@ impl DatasetOp<H5> for H5Dataset {
 
 // Generic `LocationOp` functions
 
-fn file(loc: &Location) -> Result<H5File> {
-    Ok(H5File(hdf5::Location::file(loc)?))
+fn file(loc: &Location, compression: Compression) -> Result<H5File> {
+    Ok(H5File(hdf5::Location::file(loc)?, compression))
 }
 
 fn path(loc: &Location) -> PathBuf {
@@ -443,11 +519,11 @@ impl GroupOp<H5> for H5File {
     }
 
     fn new_group(&self, name: &str) -> Result<<H5 as Backend>::Group> {
-        create_group(self, name)
+        create_group(self, name, self.1)
     }
 
     fn open_group(&self, name: &str) -> Result<<H5 as Backend>::Group> {
-        open_group(self, name)
+        open_group(self, name, self.1)
     }
 
     fn new_empty_dataset<T: BackendData>(
@@ -456,17 +532,21 @@ impl GroupOp<H5> for H5File {
         shape: &Shape,
         config: WriteConfig,
     ) -> Result<<H5 as Backend>::Dataset> {
-        new_dataset::<T>(self, name, shape, config)
+        new_dataset::<T>(self, name, shape, config, self.1)
     }
 
     fn open_dataset(&self, name: &str) -> Result<<H5 as Backend>::Dataset> {
-        open_dataset(self, name)
+        open_dataset(self, name, self.1)
     }
 
     fn delete(&self, name: &str) -> Result<()> {
         delete(self, name)
     }
 
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        rename(self, from, to)
+    }
+
     fn exists(&self, name: &str) -> Result<bool> {
         exists(self, name)
     }
@@ -476,7 +556,7 @@ impl GroupOp<H5> for H5File {
         name: &str,
         data: &D,
     ) -> Result<<H5 as Backend>::Dataset> {
-        create_scalar_data(self, name, data)
+        create_scalar_data(self, name, data, self.1)
     }
 }
 
@@ -486,11 +566,11 @@ impl GroupOp<H5> for H5Group {
     }
 
     fn new_group(&self, name: &str) -> Result<<H5 as Backend>::Group> {
-        create_group(self, name)
+        create_group(self, name, self.1)
     }
 
     fn open_group(&self, name: &str) -> Result<<H5 as Backend>::Group> {
-        open_group(self, name)
+        open_group(self, name, self.1)
     }
 
     fn new_empty_dataset<T: BackendData>(
@@ -499,17 +579,21 @@ impl GroupOp<H5> for H5Group {
         shape: &Shape,
         config: WriteConfig,
     ) -> Result<<H5 as Backend>::Dataset> {
-        new_dataset::<T>(self, name, shape, config)
+        new_dataset::<T>(self, name, shape, config, self.1)
     }
 
     fn open_dataset(&self, name: &str) -> Result<<H5 as Backend>::Dataset> {
-        open_dataset(self, name)
+        open_dataset(self, name, self.1)
     }
 
     fn delete(&self, name: &str) -> Result<()> {
         delete(self, name)
     }
 
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        rename(self, from, to)
+    }
+
     fn exists(&self, name: &str) -> Result<bool> {
         exists(self, name)
     }
@@ -519,13 +603,13 @@ impl GroupOp<H5> for H5Group {
         name: &str,
         data: &D,
     ) -> Result<<H5 as Backend>::Dataset> {
-        create_scalar_data(self, name, data)
+        create_scalar_data(self, name, data, self.1)
     }
 }
 
 impl AttributeOp<H5> for H5Group {
     fn store(&self) -> Result<<H5 as Backend>::Store> {
-        file(self)
+        file(self, self.1)
     }
 
     fn path(&self) -> PathBuf {
@@ -560,7 +644,7 @@ impl AttributeOp<H5> for H5Group {
 
 impl AttributeOp<H5> for H5Dataset {
     fn store(&self) -> Result<<H5 as Backend>::Store> {
-        file(self)
+        file(self, self.1)
     }
 
     fn path(&self) -> PathBuf {
@@ -644,6 +728,7 @@ where
         DynCowArray::I16(x) => loc.new_attr_builder().with_data(x.view()).create(name)?,
         DynCowArray::I32(x) => loc.new_attr_builder().with_data(x.view()).create(name)?,
         DynCowArray::I64(x) => loc.new_attr_builder().with_data(x.view()).create(name)?,
+        DynCowArray::F16(x) => loc.new_attr_builder().with_data(x.view()).create(name)?,
         DynCowArray::F32(x) => loc.new_attr_builder().with_data(x.view()).create(name)?,
         DynCowArray::F64(x) => loc.new_attr_builder().with_data(x.view()).create(name)?,
         DynCowArray::Bool(x) => loc.new_attr_builder().with_data(x.view()).create(name)?,
@@ -666,6 +751,7 @@ fn write_scalar_attr<D: BackendData>(loc: &Location, name: &str, value: D) -> Re
         DynScalar::I16(x) => loc.new_attr::<i16>().create(name)?.write_scalar(&x)?,
         DynScalar::I32(x) => loc.new_attr::<i32>().create(name)?.write_scalar(&x)?,
         DynScalar::I64(x) => loc.new_attr::<i64>().create(name)?.write_scalar(&x)?,
+        DynScalar::F16(x) => loc.new_attr::<f16>().create(name)?.write_scalar(&x)?,
         DynScalar::F32(x) => loc.new_attr::<f32>().create(name)?.write_scalar(&x)?,
         DynScalar::F64(x) => loc.new_attr::<f64>().create(name)?.write_scalar(&x)?,
         DynScalar::Bool(x) => loc.new_attr::<bool>().create(name)?.write_scalar(&x)?,
@@ -698,6 +784,52 @@ where
     }
 }
 
+/// The size of the fixed-length strings stored in the dataset, e.g., numpy's
+/// bytes arrays as written by Cell Ranger, which HDF5 cannot convert to
+/// variable-length strings. Returns `None` for other types.
+fn fixed_string_size(dataset: &H5Dataset) -> Result<Option<usize>> {
+    match hdf5::Container::dtype(dataset)?.to_descriptor()? {
+        TypeDescriptor::FixedAscii(n) | TypeDescriptor::FixedUnicode(n) => Ok(Some(n)),
+        _ => Ok(None),
+    }
+}
+
+/// Reads the selected fixed-length strings of the given size. Only the
+/// selected elements are read from the file.
+fn read_fixed_strings<D: Dimension>(
+    dataset: &H5Dataset,
+    size: usize,
+    selection: Selection,
+    shape: Shape,
+) -> Result<Array<String, D>> {
+    let len: usize = shape.as_ref().iter().product();
+    let mut buf = vec![0u8; len * size];
+    if !buf.is_empty() {
+        let dtype = hdf5::Container::dtype(dataset)?;
+        let file_space = hdf5::Container::space(dataset)?.select(selection)?;
+        let mem_space = hdf5::Dataspace::try_new(len)?;
+        let status = unsafe {
+            hdf5_sys::h5d::H5Dread(
+                dataset.id(),
+                dtype.id(),
+                mem_space.id(),
+                file_space.id(),
+                hdf5_sys::h5p::H5P_DEFAULT,
+                buf.as_mut_ptr().cast(),
+            )
+        };
+        ensure!(status >= 0, "cannot read fixed-length strings");
+    }
+    let strings = (0..len)
+        .map(|i| {
+            let s = &buf[i * size..(i + 1) * size];
+            let end = s.iter().position(|c| *c == 0).unwrap_or(size);
+            String::from_utf8_lossy(&s[..end]).into_owned()
+        })
+        .collect();
+    Ok(ArrayD::from_shape_vec(shape.as_ref(), strings)?.into_dimensionality::<D>()?)
+}
+
 fn del_attr(loc: &Location, name: &str) {
     unsafe {
         let c_name = std::ffi::CString::new(name).unwrap().into_raw();
@@ -819,6 +951,108 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_compression() -> Result<()> {
+        with_tmp_path(|path| {
+            let file = H5::new(&path)?;
+            let arr = Array::random((200, 50), Uniform::new(0, 100));
+            // Zstd relies on an external plugin and is tested separately.
+            let compressions = [
+                None,
+                Some(Compression::Gzip(9)),
+                Some(Compression::BloscLz4),
+                Some(Compression::Blosc { compressor: BloscCompressor::Zstd, level: 5, shuffle: BloscShuffle::Byte }),
+                Some(Compression::Blosc { compressor: BloscCompressor::LZ4, level: 9, shuffle: BloscShuffle::Bit }),
+                Some(Compression::Blosc { compressor: BloscCompressor::BloscLZ, level: 1, shuffle: BloscShuffle::None }),
+            ];
+            for (i, compression) in compressions.into_iter().enumerate() {
+                let config = WriteConfig { compression, ..Default::default() };
+                file.new_array_dataset(&i.to_string(), arr.view().into(), config)?;
+            }
+
+            let file = H5::open(&path)?;
+            for i in 0..compressions.len() {
+                assert_eq!(arr, file.open_dataset(&i.to_string())?.read_array::<i32, _>()?);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_zstd_plugin() -> Result<()> {
+        with_tmp_path(|path| {
+            let file = H5::new(&path)?;
+            let arr = Array::random((200, 50), Uniform::new(0, 100));
+            let config = WriteConfig { compression: Some(Compression::Zstd(3)), ..Default::default() };
+            let result = file.new_array_dataset("zstd", arr.view().into(), config);
+            if filter_available(ZSTD_FILTER_ID) {
+                assert_eq!(arr, result?.read_array::<i32, _>()?);
+            } else {
+                assert!(result.is_err());
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_default_compression() -> Result<()> {
+        with_tmp_path(|path| {
+            let mut file = H5::new(&path)?;
+            let arr = Array::random((200, 50), Uniform::new(0, 100));
+            let gzip1 = file.new_array_dataset("gzip1", arr.view().into(), WriteConfig::default())?;
+            assert_eq!(gzip1.filters(), vec![hdf5::filters::Filter::Deflate(1)]);
+
+            file.set_compression(Compression::Gzip(5));
+            let group = file.new_group("group")?;
+            let large = group.new_array_dataset("large", arr.view().into(), WriteConfig::default())?;
+            let small = group.new_array_dataset("small", Array1::from_vec(vec![1, 2, 3]).view().into(), WriteConfig::default())?;
+            let config = WriteConfig { compression: Some(Compression::BloscLz4), ..Default::default() };
+            let explicit = group.new_array_dataset("explicit", arr.view().into(), config.clone())?;
+            let small_explicit = group.new_array_dataset("small_explicit", Array1::from_vec(vec![1, 2, 3]).view().into(), config)?;
+            let config = WriteConfig { compression: Some(Compression::None), ..Default::default() };
+            let uncompressed = group.new_array_dataset("uncompressed", arr.view().into(), config)?;
+
+            assert_eq!(large.filters(), vec![hdf5::filters::Filter::Deflate(5)]);
+            assert!(small.filters().is_empty());
+            assert!(!explicit.filters().contains(&hdf5::filters::Filter::Deflate(5)));
+            assert!(!small_explicit.filters().is_empty());
+            assert!(uncompressed.filters().is_empty());
+            assert_eq!(arr, large.read_array::<i32, _>()?);
+
+            // The store reopened from a dataset keeps the default compression.
+            let reopened = large.store()?.new_array_dataset("reopened", arr.view().into(), WriteConfig::default())?;
+            assert_eq!(reopened.filters(), vec![hdf5::filters::Filter::Deflate(5)]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_fixed_strings() -> Result<()> {
+        with_tmp_path(|path| {
+            let names = ["AAACCTGAGAAACCAT-1", "AAACCTGAGAAACCGC-1", "ENSG0001", ""];
+            let data: Vec<hdf5::types::FixedAscii<18>> = names
+                .iter()
+                .map(|x| hdf5::types::FixedAscii::from_ascii(x).unwrap())
+                .collect();
+            let file = File::create(&path)?;
+            file.new_dataset_builder().with_data(&data).create("names")?;
+            file.close()?;
+
+            let file = H5::open(&path)?;
+            let dataset = file.open_dataset("names")?;
+            assert_eq!(dataset.dtype()?, ScalarType::String);
+            let expected: Array1<String> = names.iter().map(|x| x.to_string()).collect();
+            assert_eq!(dataset.read_array::<String, Ix1>()?, expected);
+            let slice: Array1<String> = dataset.read_array_slice(s![1..3].as_ref())?;
+            assert_eq!(slice, expected.slice(ndarray::s![1..3]).to_owned());
+            let stepped: Array1<String> = dataset.read_array_slice(&[SelectInfoElem::from(ndarray::Slice::new(0, Some(4), 2))])?;
+            assert_eq!(stepped, expected.slice(ndarray::s![0..4;2]).to_owned());
+            let indexed: Array1<String> = dataset.read_array_slice(&[SelectInfoElem::from(vec![3, 0])])?;
+            assert_eq!(indexed, Array1::from(vec![String::new(), names[0].to_string()]));
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_write_slice() -> Result<()> {
         with_tmp_path(|path| -> Result<()> {
@@ -0,0 +1,831 @@
+use anndata::{
+    backend::*,
+    data::{DynArray, DynCowArray, DynScalar, Element, SelectInfoBounds, SelectInfoElem, SelectInfoElemBounds, Shape},
+    Selectable,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use half::f16;
+use ndarray::{Array, ArrayD, ArrayViewD, CowArray, Dimension, IxDyn, SliceInfoElem};
+use parking_lot::{Mutex, RwLock};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+
+///////////////////////////////////////////////////////////////////////////////
+/// Type definitions
+///////////////////////////////////////////////////////////////////////////////
+
+/// The in-memory backend.
+///
+/// Nothing is written to the disk. Stores are registered by the path given to
+/// `Memory::new` and can be reopened with `Memory::open` as long as a handle to
+/// them is alive. The memory is released when the last handle is closed or
+/// dropped, unless persistent stores are enabled with `Memory::set_persistent`.
+pub struct Memory;
+
+/// All stores created by `Memory::new`. Persistent stores hold a strong
+/// reference, the others are only reachable while some handle is alive.
+static STORES: Mutex<BTreeMap<PathBuf, Entry>> = Mutex::new(BTreeMap::new());
+
+/// Whether stores created by `Memory::new` outlive their handles.
+static PERSISTENT: AtomicBool = AtomicBool::new(false);
+
+struct Entry {
+    tree: Weak<RwLock<Tree>>,
+    pinned: Option<Arc<RwLock<Tree>>>,
+}
+
+type Attributes = serde_json::Map<String, Value>;
+
+/// Groups and datasets indexed by their absolute paths, e.g., "/obsm/X_pca".
+type Tree = BTreeMap<String, Node>;
+
+enum Node {
+    Group(Attributes),
+    Dataset(Attributes, DynArray),
+}
+
+impl Node {
+    fn attrs(&self) -> &Attributes {
+        match self {
+            Node::Group(attrs) => attrs,
+            Node::Dataset(attrs, _) => attrs,
+        }
+    }
+
+    fn attrs_mut(&mut self) -> &mut Attributes {
+        match self {
+            Node::Group(attrs) => attrs,
+            Node::Dataset(attrs, _) => attrs,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MemStore {
+    tree: Arc<RwLock<Tree>>,
+    path: PathBuf,
+}
+
+pub struct MemGroup {
+    store: MemStore,
+    path: String,
+}
+
+pub struct MemDataset {
+    store: MemStore,
+    path: String,
+}
+
+impl Memory {
+    /// Keep the stores created from now on in memory after their last handle
+    /// is dropped, so that they can be reopened by path. Such stores must be
+    /// released explicitly with `Memory::remove`.
+    pub fn set_persistent(persistent: bool) {
+        PERSISTENT.store(persistent, Ordering::Relaxed);
+    }
+
+    /// Unregister the store at the given path. Its memory is released once
+    /// the remaining handles are dropped. Returns `false` if there is no such store.
+    pub fn remove<P: AsRef<Path>>(path: P) -> bool {
+        STORES
+            .lock()
+            .remove(path.as_ref())
+            .map_or(false, |entry| entry.tree.strong_count() > 0)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Backend implementation
+///////////////////////////////////////////////////////////////////////////////
+
+impl Backend for Memory {
+    const NAME: &'static str = "memory";
+
+    type Store = MemStore;
+    type Group = MemGroup;
+    type Dataset = MemDataset;
+
+    /// Create a new store, replacing any existing store registered at the same path.
+    fn new<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
+        let mut tree = Tree::new();
+        tree.insert("/".to_string(), Node::Group(Attributes::new()));
+        let tree = Arc::new(RwLock::new(tree));
+        let entry = Entry {
+            tree: Arc::downgrade(&tree),
+            pinned: PERSISTENT.load(Ordering::Relaxed).then(|| tree.clone()),
+        };
+        let mut stores = STORES.lock();
+        stores.retain(|_, entry| entry.tree.strong_count() > 0);
+        stores.insert(path.as_ref().to_path_buf(), entry);
+        Ok(MemStore {
+            tree,
+            path: path.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Opens a store created by `Memory::new` that is still alive.
+    fn open<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
+        let tree = STORES
+            .lock()
+            .get(path.as_ref())
+            .and_then(|entry| entry.tree.upgrade())
+            .with_context(|| format!("No in-memory store at '{}'", path.as_ref().display()))?;
+        Ok(MemStore {
+            tree,
+            path: path.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Same as `open`, in-memory stores are always writable.
+    fn open_rw<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
+        Self::open(path)
+    }
+}
+
+impl StoreOp<Memory> for MemStore {
+    fn filename(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn close(self) -> Result<()> {
+        drop(self);
+        Ok(())
+    }
+}
+
+impl MemStore {
+    fn node<R>(&self, path: &str, f: impl FnOnce(&Node) -> Result<R>) -> Result<R> {
+        let tree = self.tree.read();
+        f(tree.get(path).with_context(|| format!("'{}' does not exist", path))?)
+    }
+
+    fn node_mut<R>(&self, path: &str, f: impl FnOnce(&mut Node) -> Result<R>) -> Result<R> {
+        let mut tree = self.tree.write();
+        f(tree.get_mut(path).with_context(|| format!("'{}' does not exist", path))?)
+    }
+
+    fn dataset<R>(&self, path: &str, f: impl FnOnce(&DynArray) -> Result<R>) -> Result<R> {
+        self.node(path, |node| match node {
+            Node::Dataset(_, data) => f(data),
+            Node::Group(_) => bail!("'{}' is not a dataset", path),
+        })
+    }
+
+    fn dataset_mut<R>(&self, path: &str, f: impl FnOnce(&mut DynArray) -> Result<R>) -> Result<R> {
+        self.node_mut(path, |node| match node {
+            Node::Dataset(_, data) => f(data),
+            Node::Group(_) => bail!("'{}' is not a dataset", path),
+        })
+    }
+
+    fn insert(&self, path: String, node: Node) -> Result<()> {
+        let mut tree = self.tree.write();
+        ensure!(!tree.contains_key(&path), "'{}' already exists", path);
+        let parent = parent_path(&path);
+        ensure!(
+            matches!(tree.get(parent), Some(Node::Group(_))),
+            "Group '{}' does not exist",
+            parent
+        );
+        tree.insert(path, node);
+        Ok(())
+    }
+}
+
+// Generic `GroupOp` functions
+
+fn list(store: &MemStore, path: &str) -> Result<Vec<String>> {
+    let tree = store.tree.read();
+    ensure!(
+        matches!(tree.get(path), Some(Node::Group(_))),
+        "'{}' is not a group",
+        path
+    );
+    let prefix = child_prefix(path);
+    let result = tree
+        .range(prefix.clone()..)
+        .take_while(|(k, _)| k.starts_with(&prefix))
+        .filter_map(|(k, _)| {
+            let name = &k[prefix.len()..];
+            if name.is_empty() || name.contains('/') {
+                None
+            } else {
+                Some(name.to_string())
+            }
+        })
+        .collect();
+    Ok(result)
+}
+
+fn new_group(store: &MemStore, base: &str, name: &str) -> Result<MemGroup> {
+    let path = join_path(base, name);
+    store.insert(path.clone(), Node::Group(Attributes::new()))?;
+    Ok(MemGroup {
+        store: store.clone(),
+        path,
+    })
+}
+
+fn open_group(store: &MemStore, base: &str, name: &str) -> Result<MemGroup> {
+    let path = join_path(base, name);
+    store.node(&path, |node| match node {
+        Node::Group(_) => Ok(()),
+        Node::Dataset(..) => bail!("'{}' is not a group", path),
+    })?;
+    Ok(MemGroup {
+        store: store.clone(),
+        path,
+    })
+}
+
+/// Compression and block sizes in `WriteConfig` are meaningless in memory and are ignored.
+fn new_dataset<T: BackendData>(store: &MemStore, base: &str, name: &str, shape: &Shape) -> Result<MemDataset> {
+    let path = join_path(base, name);
+    let data = ArrayD::from_elem(IxDyn(shape.as_ref()), fill_value::<T>());
+    store.insert(path.clone(), Node::Dataset(Attributes::new(), into_dyn_array(data)))?;
+    Ok(MemDataset {
+        store: store.clone(),
+        path,
+    })
+}
+
+fn open_dataset(store: &MemStore, base: &str, name: &str) -> Result<MemDataset> {
+    let path = join_path(base, name);
+    store.dataset(&path, |_| Ok(()))?;
+    Ok(MemDataset {
+        store: store.clone(),
+        path,
+    })
+}
+
+fn delete(store: &MemStore, base: &str, name: &str) -> Result<()> {
+    let path = join_path(base, name);
+    ensure!(path != "/", "Cannot delete the root group");
+    let mut tree = store.tree.write();
+    ensure!(tree.remove(&path).is_some(), "'{}' does not exist", path);
+    let prefix = child_prefix(&path);
+    let children: Vec<_> = tree
+        .range(prefix.clone()..)
+        .take_while(|(k, _)| k.starts_with(&prefix))
+        .map(|(k, _)| k.clone())
+        .collect();
+    children.into_iter().for_each(|k| {
+        tree.remove(&k);
+    });
+    Ok(())
+}
+
+fn rename(store: &MemStore, base: &str, from: &str, to: &str) -> Result<()> {
+    let (from, to) = (join_path(base, from), join_path(base, to));
+    let mut tree = store.tree.write();
+    ensure!(!tree.contains_key(&to), "'{}' already exists", to);
+    let node = tree.remove(&from).with_context(|| format!("'{}' does not exist", from))?;
+    tree.insert(to.clone(), node);
+    let (from_prefix, to_prefix) = (child_prefix(&from), child_prefix(&to));
+    let children: Vec<_> = tree
+        .range(from_prefix.clone()..)
+        .take_while(|(k, _)| k.starts_with(&from_prefix))
+        .map(|(k, _)| k.clone())
+        .collect();
+    children.into_iter().for_each(|k| {
+        let node = tree.remove(&k).unwrap();
+        tree.insert(format!("{}{}", to_prefix, &k[from_prefix.len()..]), node);
+    });
+    Ok(())
+}
+
+fn exists(store: &MemStore, base: &str, name: &str) -> Result<bool> {
+    Ok(store.tree.read().contains_key(&join_path(base, name)))
+}
+
+impl DatasetOp<Memory> for MemDataset {
+    fn dtype(&self) -> Result<ScalarType> {
+        self.store.dataset(&self.path, dtype)
+    }
+
+    fn shape(&self) -> Shape {
+        self.store.dataset(&self.path, |data| Ok(data.shape())).unwrap()
+    }
+
+    fn reshape(&mut self, shape: &Shape) -> Result<()> {
+        fn resize<T: BackendData>(data: &mut DynArray, shape: &Shape) -> Result<()> {
+            let arr: ArrayD<T> = take_array(data)?;
+            let mut new = ArrayD::from_elem(IxDyn(shape.as_ref()), fill_value::<T>());
+            let overlap: Vec<SliceInfoElem> = arr
+                .shape()
+                .iter()
+                .zip(shape.as_ref())
+                .map(|(a, b)| (0..*a.min(b)).into())
+                .collect();
+            new.slice_mut(overlap.as_slice())
+                .assign(&arr.slice(overlap.as_slice()));
+            *data = into_dyn_array(new);
+            Ok(())
+        }
+
+        self.store.dataset_mut(&self.path, |data| {
+            ensure!(
+                data.ndim() == shape.ndim(),
+                "Cannot reshape a {}-dimensional dataset to {}",
+                data.ndim(),
+                shape
+            );
+            match dtype(data)? {
+                ScalarType::I8 => resize::<i8>(data, shape),
+                ScalarType::I16 => resize::<i16>(data, shape),
+                ScalarType::I32 => resize::<i32>(data, shape),
+                ScalarType::I64 => resize::<i64>(data, shape),
+                ScalarType::U8 => resize::<u8>(data, shape),
+                ScalarType::U16 => resize::<u16>(data, shape),
+                ScalarType::U32 => resize::<u32>(data, shape),
+                ScalarType::U64 => resize::<u64>(data, shape),
+                ScalarType::F16 => resize::<f16>(data, shape),
+                ScalarType::F32 => resize::<f32>(data, shape),
+                ScalarType::F64 => resize::<f64>(data, shape),
+                ScalarType::Bool => resize::<bool>(data, shape),
+                ScalarType::String => resize::<String>(data, shape),
+            }
+        })
+    }
+
+    fn read_array_slice<T: BackendData, S, D>(&self, selection: &[S]) -> Result<Array<T, D>>
+    where
+        S: AsRef<SelectInfoElem>,
+        D: Dimension,
+    {
+        let arr = self.store.dataset(&self.path, |data| {
+            bound_check(selection, &data.shape())?;
+            Ok(data.select(selection))
+        })?;
+        Ok(T::from_dyn_arr(arr)?.into_dimensionality::<D>()?)
+    }
+
+    fn write_array_slice<S, T, D>(&self, arr: CowArray<'_, T, D>, selection: &[S]) -> Result<()>
+    where
+        T: BackendData,
+        S: AsRef<SelectInfoElem>,
+        D: Dimension,
+    {
+        let arr = arr.into_dyn();
+        self.store.dataset_mut(&self.path, |data| {
+            let mut target: ArrayD<T> = take_array(data)?;
+            let result = write_slice(&mut target, arr.view(), selection);
+            *data = into_dyn_array(target);
+            result
+        })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Derived implementations
+////////////////////////////////////////////////////////////////////////////////
+
+impl GroupOp<Memory> for MemStore {
+    fn list(&self) -> Result<Vec<String>> {
+        list(self, "/")
+    }
+
+    fn new_group(&self, name: &str) -> Result<<Memory as Backend>::Group> {
+        new_group(self, "/", name)
+    }
+
+    fn open_group(&self, name: &str) -> Result<<Memory as Backend>::Group> {
+        open_group(self, "/", name)
+    }
+
+    fn new_empty_dataset<T: BackendData>(
+        &self,
+        name: &str,
+        shape: &Shape,
+        _config: WriteConfig,
+    ) -> Result<<Memory as Backend>::Dataset> {
+        new_dataset::<T>(self, "/", name, shape)
+    }
+
+    fn open_dataset(&self, name: &str) -> Result<<Memory as Backend>::Dataset> {
+        open_dataset(self, "/", name)
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        delete(self, "/", name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        rename(self, "/", from, to)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        exists(self, "/", name)
+    }
+}
+
+impl GroupOp<Memory> for MemGroup {
+    fn list(&self) -> Result<Vec<String>> {
+        list(&self.store, &self.path)
+    }
+
+    fn new_group(&self, name: &str) -> Result<<Memory as Backend>::Group> {
+        new_group(&self.store, &self.path, name)
+    }
+
+    fn open_group(&self, name: &str) -> Result<<Memory as Backend>::Group> {
+        open_group(&self.store, &self.path, name)
+    }
+
+    fn new_empty_dataset<T: BackendData>(
+        &self,
+        name: &str,
+        shape: &Shape,
+        _config: WriteConfig,
+    ) -> Result<<Memory as Backend>::Dataset> {
+        new_dataset::<T>(&self.store, &self.path, name, shape)
+    }
+
+    fn open_dataset(&self, name: &str) -> Result<<Memory as Backend>::Dataset> {
+        open_dataset(&self.store, &self.path, name)
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        delete(&self.store, &self.path, name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        rename(&self.store, &self.path, from, to)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        exists(&self.store, &self.path, name)
+    }
+}
+
+impl AttributeOp<Memory> for MemGroup {
+    fn store(&self) -> Result<<Memory as Backend>::Store> {
+        Ok(self.store.clone())
+    }
+
+    fn path(&self) -> PathBuf {
+        PathBuf::from(&self.path)
+    }
+
+    fn new_json_attr(&mut self, name: &str, value: &Value) -> Result<()> {
+        new_json_attr(&self.store, &self.path, name, value)
+    }
+
+    fn get_json_attr(&self, name: &str) -> Result<Value> {
+        get_json_attr(&self.store, &self.path, name)
+    }
+}
+
+impl AttributeOp<Memory> for MemDataset {
+    fn store(&self) -> Result<<Memory as Backend>::Store> {
+        Ok(self.store.clone())
+    }
+
+    fn path(&self) -> PathBuf {
+        PathBuf::from(&self.path)
+    }
+
+    fn new_json_attr(&mut self, name: &str, value: &Value) -> Result<()> {
+        new_json_attr(&self.store, &self.path, name, value)
+    }
+
+    fn get_json_attr(&self, name: &str) -> Result<Value> {
+        get_json_attr(&self.store, &self.path, name)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Auxiliary functions
+///////////////////////////////////////////////////////////////////////////////
+
+fn new_json_attr(store: &MemStore, path: &str, name: &str, value: &Value) -> Result<()> {
+    store.node_mut(path, |node| {
+        node.attrs_mut().insert(name.to_string(), value.clone());
+        Ok(())
+    })
+}
+
+fn get_json_attr(store: &MemStore, path: &str, name: &str) -> Result<Value> {
+    store.node(path, |node| {
+        node.attrs()
+            .get(name)
+            .cloned()
+            .with_context(|| format!("Attribute {} not found", name))
+    })
+}
+
+/// Resolve `name` against the group located at `base`. Absolute names are
+/// resolved against the root.
+fn join_path(base: &str, name: &str) -> String {
+    let base = if name.starts_with('/') { "" } else { base };
+    let components: Vec<_> = base
+        .split('/')
+        .chain(name.split('/'))
+        .filter(|x| !x.is_empty())
+        .collect();
+    format!("/{}", components.join("/"))
+}
+
+fn parent_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &path[..i],
+    }
+}
+
+/// All descendants of `path` start with this prefix.
+fn child_prefix(path: &str) -> String {
+    if path == "/" {
+        path.to_string()
+    } else {
+        format!("{}/", path)
+    }
+}
+
+fn dtype(data: &DynArray) -> Result<ScalarType> {
+    match data.data_type() {
+        DataType::Array(ty) => Ok(ty),
+        ty => bail!("Unsupported type: {}", ty),
+    }
+}
+
+fn fill_value<T: BackendData>() -> T {
+    let val = match T::DTYPE {
+        ScalarType::I8 => DynScalar::I8(0),
+        ScalarType::I16 => DynScalar::I16(0),
+        ScalarType::I32 => DynScalar::I32(0),
+        ScalarType::I64 => DynScalar::I64(0),
+        ScalarType::U8 => DynScalar::U8(0),
+        ScalarType::U16 => DynScalar::U16(0),
+        ScalarType::U32 => DynScalar::U32(0),
+        ScalarType::U64 => DynScalar::U64(0),
+        ScalarType::F16 => DynScalar::F16(f16::ZERO),
+        ScalarType::F32 => DynScalar::F32(0.0),
+        ScalarType::F64 => DynScalar::F64(0.0),
+        ScalarType::Bool => DynScalar::Bool(false),
+        ScalarType::String => DynScalar::String(String::new()),
+    };
+    T::from_dyn(val).unwrap()
+}
+
+/// Move the array out of `data`, leaving an empty placeholder behind.
+fn take_array<T: BackendData>(data: &mut DynArray) -> Result<ArrayD<T>> {
+    let ty = dtype(data)?;
+    ensure!(
+        ty == T::DTYPE,
+        "Cannot write {} values into a {} dataset",
+        T::DTYPE,
+        ty
+    );
+    let placeholder = DynArray::Bool(ArrayD::from_elem(IxDyn(&[0]), false));
+    T::from_dyn_arr(std::mem::replace(data, placeholder))
+}
+
+fn into_dyn_array<T: BackendData>(arr: ArrayD<T>) -> DynArray {
+    match T::into_dyn_arr(CowArray::from(arr)) {
+        DynCowArray::I8(x) => DynArray::I8(x.into_owned()),
+        DynCowArray::I16(x) => DynArray::I16(x.into_owned()),
+        DynCowArray::I32(x) => DynArray::I32(x.into_owned()),
+        DynCowArray::I64(x) => DynArray::I64(x.into_owned()),
+        DynCowArray::U8(x) => DynArray::U8(x.into_owned()),
+        DynCowArray::U16(x) => DynArray::U16(x.into_owned()),
+        DynCowArray::U32(x) => DynArray::U32(x.into_owned()),
+        DynCowArray::U64(x) => DynArray::U64(x.into_owned()),
+        DynCowArray::F16(x) => DynArray::F16(x.into_owned()),
+        DynCowArray::F32(x) => DynArray::F32(x.into_owned()),
+        DynCowArray::F64(x) => DynArray::F64(x.into_owned()),
+        DynCowArray::Bool(x) => DynArray::Bool(x.into_owned()),
+        DynCowArray::String(x) => DynArray::String(x.into_owned()),
+    }
+}
+
+fn bound_check<S: AsRef<SelectInfoElem>>(selection: &[S], shape: &Shape) -> Result<()> {
+    ensure!(
+        selection.len() == shape.ndim(),
+        "Selection has {} dimensions but the dataset has {}",
+        selection.len(),
+        shape.ndim()
+    );
+    selection
+        .iter()
+        .zip(shape.as_ref())
+        .try_for_each(|(s, n)| s.as_ref().bound_check(*n))
+}
+
+fn write_slice<T, S>(target: &mut ArrayD<T>, arr: ArrayViewD<'_, T>, selection: &[S]) -> Result<()>
+where
+    T: Clone,
+    S: AsRef<SelectInfoElem>,
+{
+    let shape: Shape = target.shape().into();
+    bound_check(selection, &shape)?;
+    let selection = SelectInfoBounds::new(&selection, &shape);
+    ensure!(
+        arr.shape() == selection.out_shape().as_ref(),
+        "Cannot write an array of shape {:?} into a selection of shape {}",
+        arr.shape(),
+        selection.out_shape()
+    );
+    let slices = selection
+        .iter()
+        .map(|x| match x {
+            SelectInfoElemBounds::Slice(slice) => Some::<SliceInfoElem>((*slice).into()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    if let Some(slices) = slices {
+        target.slice_mut(slices.as_slice()).assign(&arr);
+    } else {
+        let select: Vec<_> = selection.iter().collect();
+        arr.indexed_iter().for_each(|(idx, val)| {
+            let new_idx: Vec<_> = (0..idx.ndim())
+                .map(|i| select[i].index(idx[i]))
+                .collect();
+            target[new_idx.as_slice()] = val.clone();
+        });
+    }
+    Ok(())
+}
+
+/// test module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anndata::s;
+    use ndarray::{array, concatenate, Array2, Axis, Ix1, Ix2};
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+
+    #[test]
+    fn test_basic() -> Result<()> {
+        let store = Memory::new("test_basic")?;
+        store.open_group("/")?;
+
+        store.new_scalar_dataset("data", &4)?;
+        assert_eq!(4, store.open_dataset("data")?.read_scalar::<i32>()?);
+
+        let group = store.new_group("group")?;
+        assert!(store.exists("group")?);
+        assert!(store.new_group("group").is_err());
+
+        let subgroup = group.new_group("group")?;
+        assert!(group.exists("group")?);
+
+        let mut data = subgroup.new_scalar_dataset("group", &"test".to_string())?;
+        data.new_attr("encoding-type", "string")?;
+        assert!(subgroup.open_group("group").is_err());
+
+        {
+            let store = Memory::open("test_basic")?;
+            let container = DataContainer::open(&store.open_group("group")?, "group")?;
+            assert_eq!(container.encoding_type()?, DataType::Scalar(ScalarType::String));
+        }
+
+        assert_eq!(group.path(), PathBuf::from("/group"));
+        assert_eq!(subgroup.path(), PathBuf::from("/group/group"));
+        assert_eq!(data.path(), PathBuf::from("/group/group/group"));
+
+        assert!(Memory::remove("test_basic"));
+        assert!(Memory::open("test_basic").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_release() -> Result<()> {
+        let store = Memory::new("test_release")?;
+        store.new_scalar_dataset("data", &4)?;
+        store.close()?;
+        assert!(Memory::open("test_release").is_err());
+        assert!(!Memory::remove("test_release"));
+
+        Memory::set_persistent(true);
+        let store = Memory::new("test_release")?;
+        Memory::set_persistent(false);
+        store.new_scalar_dataset("data", &4)?;
+        store.close()?;
+        let store = Memory::open("test_release")?;
+        assert_eq!(4, store.open_dataset("data")?.read_scalar::<i32>()?);
+        assert!(Memory::remove("test_release"));
+        assert!(Memory::open("test_release").is_err());
+        assert_eq!(4, store.open_dataset("data")?.read_scalar::<i32>()?);
+        Ok(())
+    }
+
+    /// A group that relies on the default `rename` of `GroupOp`.
+    struct CopyRename(MemGroup);
+
+    impl GroupOp<Memory> for CopyRename {
+        fn list(&self) -> Result<Vec<String>> {
+            self.0.list()
+        }
+
+        fn new_group(&self, name: &str) -> Result<MemGroup> {
+            self.0.new_group(name)
+        }
+
+        fn open_group(&self, name: &str) -> Result<MemGroup> {
+            self.0.open_group(name)
+        }
+
+        fn new_empty_dataset<T: BackendData>(
+            &self,
+            name: &str,
+            shape: &Shape,
+            config: WriteConfig,
+        ) -> Result<MemDataset> {
+            self.0.new_empty_dataset::<T>(name, shape, config)
+        }
+
+        fn open_dataset(&self, name: &str) -> Result<MemDataset> {
+            self.0.open_dataset(name)
+        }
+
+        fn delete(&self, name: &str) -> Result<()> {
+            self.0.delete(name)
+        }
+
+        fn exists(&self, name: &str) -> Result<bool> {
+            self.0.exists(name)
+        }
+    }
+
+    #[test]
+    fn test_default_rename() -> Result<()> {
+        use anndata::data::{ArrayData, Readable, Writable};
+
+        let store = Memory::new("test_default_rename")?;
+        let group = CopyRename(store.new_group("group")?);
+        let arr: ArrayData = Array2::<i32>::random((10, 4), Uniform::new(0, 100)).into();
+        arr.write(&group, "x")?;
+        arr.write(&group, "z")?;
+        assert!(group.rename("x", "z").is_err());
+        group.rename("x", "y")?;
+        assert!(!group.exists("x")?);
+        assert_eq!(arr, ArrayData::read(&DataContainer::<Memory>::open(&group, "y")?)?);
+        assert!(group.rename("x", "w").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_empty() -> Result<()> {
+        let store = Memory::new("test_write_empty")?;
+        let group = store.new_group("group")?;
+        let empty: Array2<i64> = array![[]];
+        let dataset = group.new_array_dataset("test", empty.view().into(), WriteConfig::default())?;
+        assert_eq!(empty, dataset.read_array::<i64, Ix2>()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_slice() -> Result<()> {
+        let store = Memory::new("test_write_slice")?;
+        let group = store.new_group("group")?;
+        let mut dataset = group.new_empty_dataset::<i32>("test", &[20, 50].as_slice().into(), WriteConfig::default())?;
+
+        let arr = Array::random((10, 10), Uniform::new(0, 100));
+        dataset.write_array_slice(arr.view().into(), s![5..15, 10..20].as_ref())?;
+        assert_eq!(
+            arr,
+            dataset.read_array_slice::<i32, _, _>(s![5..15, 10..20].as_ref())?
+        );
+
+        // Out-of-bounds writes should fail
+        assert!(dataset.write_array_slice(arr.view().into(), s![15..25, ..10].as_ref()).is_err());
+
+        // Writing with indices
+        let idx: SelectInfoElem = vec![3, 1, 19].into();
+        let col = array![[1, 2], [3, 4], [5, 6]];
+        dataset.write_array_slice(col.view().into(), &[idx.clone(), (0..2usize).into()])?;
+        assert_eq!(col, dataset.read_array_slice::<i32, _, Ix2>(&[idx, (0..2usize).into()])?);
+
+        // Reshape and write
+        let arr = Array::random((20, 50), Uniform::new(0, 100));
+        dataset.write_array(arr.view().into())?;
+        dataset.reshape(&[40, 50].as_slice().into())?;
+        dataset.write_array_slice(arr.view().into(), s![20..40, ..].as_ref())?;
+        let merged = concatenate(Axis(0), &[arr.view(), arr.view()])?;
+        assert_eq!(merged, dataset.read_array::<i32, _>()?);
+
+        // Shrinking is OK
+        dataset.reshape(&[20, 50].as_slice().into())?;
+        assert_eq!(arr, dataset.read_array::<i32, _>()?);
+
+        // Type mismatch
+        assert!(dataset.write_array(array![1.0f64].view().into()).is_err());
+        assert_eq!(arr, dataset.read_array::<i32, _>()?);
+
+        let strings = store.new_array_dataset("strings", array!["a".to_string(), "b".to_string()].into(), WriteConfig::default())?;
+        assert_eq!(array!["b".to_string()], strings.read_array_slice::<String, _, Ix1>(s![1..2].as_ref())?);
+
+        assert_eq!(vec!["group", "strings"], store.list()?);
+        assert_eq!(vec!["test"], group.list()?);
+
+        store.delete("group")?;
+        assert!(!store.exists("group")?);
+        assert!(!group.exists("test")?);
+        Ok(())
+    }
+}
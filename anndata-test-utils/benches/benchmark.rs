@@ -1,8 +1,9 @@
 use anndata_hdf5::H5;
 use anndata_zarr::Zarr;
 use criterion::{criterion_group, criterion_main, Criterion};
-use anndata_test_utils::with_tmp_dir;
+use anndata_test_utils::{rand_csc, rand_csr, with_tmp_dir};
 use anndata::*;
+use anndata::data::SelectInfoElem;
 use ndarray::{Array, Array2};
 use ndarray_rand::rand_distr::Uniform;
 use ndarray_rand::RandomExt;
@@ -61,6 +62,44 @@ fn bench_array_slice(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_sparse_slice(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Sparse slice");
+    group.sample_size(30);
+
+    let (n_obs, n_vars, nnz) = (20000usize, 5000usize, 2000000usize);
+    let csr = rand_csr::<i32>(n_obs, n_vars, nnz, 1, 100);
+    let csc = rand_csc::<i32>(n_obs, n_vars, nnz, 1, 100);
+    let idx: Vec<usize> = (0..n_vars).step_by(50).collect();
+    with_tmp_dir(|dir| {
+        macro_rules! bench {
+            ($backend:ident) => {
+                let adata = AnnData::<$backend>::new(dir.join(stringify!($backend))).unwrap();
+                adata.set_x(&csr).unwrap();
+                group.bench_function(&format!("{} CSR rows", stringify!($backend)), |b| b.iter(||
+                    adata.x().slice::<ArrayData, _>(s![1000..3000, ..]).unwrap()
+                ));
+                group.bench_function(&format!("{} CSR columns", stringify!($backend)), |b| b.iter(||
+                    adata.x().slice::<ArrayData, _>(s![.., 1000..1200]).unwrap()
+                ));
+                group.bench_function(&format!("{} CSR column indices", stringify!($backend)), |b| b.iter(||
+                    adata.x().slice_axis::<ArrayData, _>(1, SelectInfoElem::from(&idx)).unwrap()
+                ));
+
+                adata.set_x(&csc).unwrap();
+                group.bench_function(&format!("{} CSC rows", stringify!($backend)), |b| b.iter(||
+                    adata.x().slice::<ArrayData, _>(s![1000..3000, ..]).unwrap()
+                ));
+                group.bench_function(&format!("{} CSC columns", stringify!($backend)), |b| b.iter(||
+                    adata.x().slice::<ArrayData, _>(s![.., 1000..1200]).unwrap()
+                ));
+            };
+        }
+        bench!(H5);
+        bench!(Zarr);
+    });
+    group.finish();
+}
+
 fn bench_par_read(c: &mut Criterion) {
     let mut group = c.benchmark_group("Multi-read");
     group.sample_size(30);
@@ -103,5 +142,5 @@ fn bench_par_read(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_array_io, bench_array_slice, bench_par_read);
+criterion_group!(benches, bench_array_io, bench_array_slice, bench_sparse_slice, bench_par_read);
 criterion_main!(benches);
\ No newline at end of file
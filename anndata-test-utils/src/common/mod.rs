@@ -7,7 +7,7 @@ use itertools::Itertools;
 use nalgebra::base::DMatrix;
 use nalgebra::{ClosedAddAssign, Scalar};
 use nalgebra_sparse::{coo::CooMatrix, csc::CscMatrix, csr::CsrMatrix};
-use ndarray::{Array, Axis, Dimension, RemoveAxis};
+use ndarray::{Array, Array2, Axis, Dimension, RemoveAxis};
 use ndarray_rand::rand_distr::uniform::SampleUniform;
 use ndarray_rand::rand_distr::Uniform;
 use ndarray_rand::RandomExt;
@@ -353,10 +353,48 @@ pub fn anndata_eq<B1: Backend, B2: Backend>(
         })
         && adata1.layers().keys().iter().all(|k| {
             adata1.layers().get_item::<ArrayData>(k).unwrap() == adata2.layers().get_item(k).unwrap()
-        });
+        })
+        && {
+            let raw1 = adata1.get_raw();
+            let raw2 = adata2.get_raw();
+            raw1.is_none() == raw2.is_none()
+                && raw1.x().get::<ArrayData>()? == raw2.x().get()?
+                && raw1.var_names() == raw2.var_names()
+                && raw1.read_var()? == raw2.read_var()?
+                && (raw1.is_none() || {
+                    let (varm1, varm2) = (raw1.varm()?, raw2.varm()?);
+                    varm1.keys().iter().all(|k| {
+                        varm1.get_item::<ArrayData>(k).unwrap() == varm2.get_item(k).unwrap()
+                    })
+                })
+        };
     Ok(is_equal)
 }
 
+/// A random 50 x 20 integer matrix stored as the CSR X and the "csc" and
+/// "dense" layers of an AnnData, and as the X of an AnnDataSet whose
+/// components hold the rows 0..20 and 20..50. Returns the matrix as a dense
+/// array of f64 together with the AnnData and the AnnDataSet.
+pub fn sparse_fixture<B: Backend>(dir: &Path) -> (Array2<f64>, AnnData<B>, AnnDataSet<B>) {
+    let csr = rand_csr::<i32>(50, 20, 300, -50, 50);
+    let mut dense = Array2::<f64>::zeros((50, 20));
+    csr.triplet_iter().for_each(|(i, j, v)| dense[[i, j]] = *v as f64);
+
+    let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+    adata.set_x(&csr).unwrap();
+    adata.layers().add("csc", CscMatrix::from(&csr)).unwrap();
+    adata.layers().add("dense", dense.clone()).unwrap();
+
+    let x: ArrayData = csr.into();
+    let adatas = [0..20, 20..50].into_iter().enumerate().map(|(i, rows)| {
+        let adata = AnnData::<B>::new(dir.join(format!("{}", i))).unwrap();
+        adata.set_x(array_select(&x, &[rows.into(), SelectInfoElem::full()])).unwrap();
+        (i.to_string(), adata)
+    });
+    let dataset = AnnDataSet::<B>::new(adatas, dir.join("dataset"), "sample").unwrap();
+    (dense, adata, dataset)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 /// Array operations
 ////////////////////////////////////////////////////////////////////////////////
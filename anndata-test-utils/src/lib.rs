@@ -1,10 +1,11 @@
 mod common;
 pub use common::*;
 
-use anndata::{data::CsrNonCanonical, *};
-use data::ArrayConvert;
-use nalgebra_sparse::{CooMatrix, CsrMatrix};
+use anndata::{data::{CompactCsrMatrix, CsrNonCanonical}, *};
+use data::{ArrayConvert, DataFrameIndex, DynCsrMatrix, Reduction, SelectInfoElem, SparseFormat};
+use nalgebra_sparse::{CooMatrix, CscMatrix, CsrMatrix};
 use ndarray::Array2;
+use polars::prelude::{df, Series};
 use proptest::prelude::*;
 
 pub fn test_basic<B: Backend>() {
@@ -54,6 +55,1319 @@ pub fn test_save<B: Backend>() {
     });
 }
 
+/// The compression of the output store must not change the content.
+pub fn test_save_compressed<B: Backend>() {
+    use anndata::backend::{BloscCompressor, BloscShuffle, Compression, StoreOp};
+
+    with_tmp_dir(|dir| {
+        let input = dir.join("input");
+        let output = dir.join("output");
+        let compressions = [
+            Compression::None,
+            Compression::Gzip(4),
+            Compression::BloscLz4,
+            Compression::Blosc { compressor: BloscCompressor::Zstd, level: 5, shuffle: BloscShuffle::Bit },
+        ];
+        let anndatas = ((0 as usize..100), (0 as usize..100), (0..compressions.len()))
+            .prop_flat_map(|(n_obs, n_vars, i)| (anndata_strat::<B, _>(&input, n_obs, n_vars), Just(i)));
+        proptest!(ProptestConfig::with_cases(20), |((adata, i) in anndatas)| {
+            let mut store = B::new(&output).unwrap();
+            store.set_compression(compressions[i]);
+            adata.write_to_store::<B>(store).unwrap();
+            let adata_in = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+            prop_assert!(anndata_eq(&adata, &adata_in).unwrap());
+            adata_in.close().unwrap();
+        });
+    });
+}
+
+/// Views of views should be equivalent to materializing the selections one by one.
+pub fn test_view<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let input = dir.join("input");
+        let nested = |n: usize| {
+            select_strat(n).prop_flat_map(move |s| {
+                let m = data::SelectInfoElemBounds::new(&s, n).len();
+                (Just(s), select_strat(m))
+            })
+        };
+        let anndatas = ((0 as usize..50), (0 as usize..50)).prop_flat_map(move |(n_obs, n_vars)| {
+            (
+                anndata_strat::<B, _>(&input, n_obs, n_vars),
+                nested(n_obs),
+                nested(n_vars),
+            )
+        });
+        proptest!(ProptestConfig::with_cases(50), |((adata, (obs1, obs2), (var1, var2)) in anndatas)| {
+            let view = adata.view([obs1.clone(), var1.clone()]).unwrap();
+            let view = view.view([obs2.clone(), var2.clone()]).unwrap();
+
+            adata.write_select::<B, _, _>([obs1, var1], dir.join("expected")).unwrap();
+            let expected = AnnData::<B>::open(B::open(dir.join("expected")).unwrap()).unwrap();
+            expected.subset([obs2, var2]).unwrap();
+
+            prop_assert_eq!(view.n_obs(), expected.n_obs());
+            prop_assert_eq!(view.n_vars(), expected.n_vars());
+            prop_assert!(view.obs_names() == expected.obs_names());
+            prop_assert!(view.read_obs().unwrap() == expected.read_obs().unwrap());
+            prop_assert!(view.x().get::<ArrayData>().unwrap() == expected.x().get().unwrap());
+            for k in expected.obsm().keys() {
+                prop_assert!(view.obsm().get_item::<ArrayData>(&k).unwrap() == expected.obsm().get_item(&k).unwrap());
+            }
+            for k in expected.layers().keys() {
+                prop_assert!(view.layers().get_item::<ArrayData>(&k).unwrap() == expected.layers().get_item(&k).unwrap());
+            }
+            prop_assert_eq!(view.uns().keys().len(), expected.uns().keys().len());
+            prop_assert!(view.uns().add("view_item", 1i32).is_err());
+            prop_assert!(view.uns().remove("view_item").is_err());
+            prop_assert!(adata.uns().get_item::<Data>("view_item").unwrap().is_none());
+
+            view.write::<B, _>(dir.join("view")).unwrap();
+            let written = AnnData::<B>::open(B::open(dir.join("view")).unwrap()).unwrap();
+            prop_assert!(anndata_eq(&expected, &written).unwrap());
+
+            // Chunks of the view are the rows of the selection, and are returned
+            // as errors once the element is deleted.
+            for chunk in view.x().iter(7) {
+                let (data, start, end) = chunk.unwrap();
+                prop_assert!(Some(data) == expected.x().slice_axis::<ArrayData, _>(0, SelectInfoElem::from(start..end)).unwrap());
+            }
+            let mut chunks = view.x().iter(7);
+            adata.del_x().unwrap();
+            prop_assert!(chunks.all(|x| x.is_err()));
+            expected.close().unwrap();
+            written.close().unwrap();
+        });
+    });
+}
+
+/// Convert between two backends and back, checking that the content is preserved.
+pub fn test_convert<B1: Backend, B2: Backend>() {
+    with_tmp_dir(|dir| {
+        let input = dir.join("input");
+        let converted = dir.join("converted");
+        let output = dir.join("output");
+        let anndatas = ((0 as usize..100), (0 as usize..100))
+            .prop_flat_map(|(n_obs, n_vars)| anndata_strat::<B1, _>(&input, n_obs, n_vars));
+        proptest!(ProptestConfig::with_cases(20), |(adata in anndatas)| {
+            adata.write::<B2, _>(&converted).unwrap();
+            let adata_b2 = AnnData::<B2>::open(B2::open(&converted).unwrap()).unwrap();
+            prop_assert!(anndata_eq(&adata, &adata_b2).unwrap());
+
+            adata_b2.write::<B1, _>(&output).unwrap();
+            let adata_b1 = AnnData::<B1>::open(B1::open(&output).unwrap()).unwrap();
+            prop_assert!(anndata_eq(&adata, &adata_b1).unwrap());
+            adata_b1.close().unwrap();
+            adata_b2.close().unwrap();
+        });
+
+        // Large matrices spanning multiple chunks.
+        let adata = AnnData::<B1>::new(&input).unwrap();
+        adata.set_x(rand_csr::<i32>(25000, 30, 50000, 1, 100)).unwrap();
+        adata.obsm().add("dense", Array2::<u8>::ones((25000, 700))).unwrap();
+        let n = 25000;
+        let csc = CscMatrix::try_from_csc_data(n, n, (0..=n).collect(), (0..n).rev().collect(), vec![1.0f32; n]).unwrap();
+        adata.obsp().add("csc", csc).unwrap();
+        adata.write::<B2, _>(&converted).unwrap();
+        let adata_b2 = AnnData::<B2>::open(B2::open(&converted).unwrap()).unwrap();
+        assert!(anndata_eq(&adata, &adata_b2).unwrap());
+        adata_b2.close().unwrap();
+    });
+}
+
+pub fn test_raw<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        adata.set_x(Array2::<f64>::zeros((20, 10))).unwrap();
+        assert!(adata.raw().unwrap().is_none());
+        assert!(adata.get_raw().is_none());
+
+        // The raw matrix must have the same number of observations as the parent.
+        assert!(adata.set_raw_x(rand_csr::<i32>(10, 30, 10, 1, 100)).is_err());
+        assert!(adata.raw().unwrap().is_none());
+        let counts: ArrayData = rand_csr::<i32>(20, 30, 100, 1, 100).into();
+        let raw = adata.set_raw_x(counts.clone()).unwrap();
+        let var_names: DataFrameIndex = (0..30).map(|i| format!("gene_{}", i)).collect();
+        raw.set_var_names(var_names).unwrap();
+        raw.varm().unwrap().add("pca", Array2::<f32>::ones((30, 5))).unwrap();
+        assert!(raw.varm().unwrap().add("pca", Array2::<f32>::ones((10, 5))).is_err());
+        assert_eq!(raw.n_vars(), 30);
+        assert_eq!(adata.n_vars(), 10);
+
+        adata.write::<B, _>(dir.join("copy")).unwrap();
+        let copy = AnnData::<B>::open(B::open(dir.join("copy")).unwrap()).unwrap();
+        assert!(anndata_eq(&adata, &copy).unwrap());
+        copy.close().unwrap();
+
+        // Subsetting only affects the observations of the raw slot.
+        let select = [SelectInfoElem::from(vec![0, 3, 5]), SelectInfoElem::from(0..5)];
+        adata.write_select::<B, _, _>(&select, dir.join("subset")).unwrap();
+        adata.subset(&select).unwrap();
+        let subset = AnnData::<B>::open(B::open(dir.join("subset")).unwrap()).unwrap();
+        assert!(anndata_eq(&adata, &subset).unwrap());
+        subset.close().unwrap();
+        assert_eq!(
+            adata.raw().unwrap().unwrap().x().get::<ArrayData>().unwrap().unwrap(),
+            array_select(&counts, &[select[0].clone(), SelectInfoElem::full()]),
+        );
+
+        adata.del_raw().unwrap();
+        assert!(adata.raw().unwrap().is_none());
+        let raw = adata.set_raw_x(rand_csr::<i32>(3, 7, 5, 1, 100)).unwrap();
+        assert_eq!(raw.n_vars(), 7);
+
+        // The raw X of the components of an AnnDataSet are stacked by rows.
+        let counts = rand_csr::<i32>(20, 30, 100, 1, 100);
+        let raw_var_names: DataFrameIndex = (0..30).map(|i| format!("gene_{}", i)).collect();
+        let counts: ArrayData = counts.into();
+        let adatas = [0..8, 8..20].into_iter().enumerate().map(|(i, rows)| {
+            let adata = AnnData::<B>::new(dir.join(format!("component_{}", i))).unwrap();
+            adata.set_x(Array2::<f64>::zeros((rows.len(), 10))).unwrap();
+            let raw = adata.set_raw_x(array_select(&counts, &[rows.into(), SelectInfoElem::full()])).unwrap();
+            raw.set_var_names(raw_var_names.clone()).unwrap();
+            raw.varm().unwrap().add("pca", Array2::<f32>::ones((30, 5))).unwrap();
+            (i.to_string(), adata)
+        });
+        let dataset = AnnDataSet::<B>::new(adatas, dir.join("dataset"), "sample").unwrap();
+        let raw = dataset.raw().unwrap().unwrap();
+        assert_eq!(raw.n_obs(), 20);
+        assert_eq!(raw.n_vars(), 30);
+        assert!(raw.var_names() == raw_var_names);
+        assert!(raw.varm().unwrap().get_item::<Array2<f32>>("pca").unwrap().is_some());
+        assert_eq!(raw.x().get::<ArrayData>().unwrap().unwrap(), counts);
+        let select = [SelectInfoElem::from(vec![12, 1, 9]), SelectInfoElem::full()];
+        assert_eq!(raw.x().slice::<ArrayData, _>(&select).unwrap().unwrap(), array_select(&counts, &select));
+        assert!(dataset.set_raw_x(counts.clone()).is_err());
+
+        let adata = dataset.to_adata::<B, _>(dir.join("dataset_adata"), true).unwrap();
+        assert_eq!(adata.raw().unwrap().unwrap().x().get::<ArrayData>().unwrap().unwrap(), counts);
+        adata.close().unwrap();
+        dataset.close().unwrap();
+        let dataset = AnnDataSet::<B>::open(
+            B::open(dir.join("dataset")).unwrap(),
+            None::<Result<std::collections::HashMap<String, std::path::PathBuf>, _>>,
+        ).unwrap();
+        assert_eq!(dataset.raw().unwrap().unwrap().x().get::<ArrayData>().unwrap().unwrap(), counts);
+    });
+}
+
+pub fn test_nullable<B: Backend>() {
+    use anndata::backend::{AttributeOp, GroupOp};
+    use data::{DynArray, NullableArray};
+    use ndarray::arr1;
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        let mut obs = df!(
+            "int" => [Some(1i32), None, Some(3), None],
+            "uint" => [Some(1u64), Some(2), None, Some(4)],
+            "bool" => [Some(true), None, Some(false), Some(true)],
+            "str" => [Some("a"), Some("b"), None, Some("")],
+            "float" => [Some(1.5f64), None, Some(2.5), Some(3.5)],
+            "complete" => [1i64, 2, 3, 4],
+        )
+        .unwrap();
+        // Columns without missing values are stored as plain arrays, even if
+        // they have a validity bitmap.
+        let no_missing = NullableArray::new(DynArray::from(arr1(&[1i32, 2, 3, 4])), arr1(&[false; 4]).into_dyn());
+        let mut no_missing: Series = no_missing.unwrap().into();
+        no_missing.rename("no_missing".into());
+        obs.with_column(no_missing).unwrap();
+        adata.set_obs(obs.clone()).unwrap();
+
+        let ints = NullableArray::new(
+            DynArray::from(arr1(&[1i64, 0, 3, 4])),
+            arr1(&[false, true, false, false]).into_dyn(),
+        )
+        .unwrap();
+        let strings = NullableArray::new(
+            DynArray::from(arr1(&["a".to_string(), String::new()])),
+            arr1(&[false, false]).into_dyn(),
+        )
+        .unwrap();
+        adata.uns().add("ints", ints.clone()).unwrap();
+        adata.uns().add("strings", strings.clone()).unwrap();
+        adata.obsm().add("ints", ints.clone()).unwrap();
+        assert_eq!(adata.uns().get_item::<NullableArray>("ints").unwrap().unwrap(), ints);
+        assert_eq!(adata.obsm().get_item::<NullableArray>("ints").unwrap().unwrap(), ints);
+        adata.write::<B, _>(dir.join("copy")).unwrap();
+
+        let copy = AnnData::<B>::open(B::open(dir.join("copy")).unwrap()).unwrap();
+        let obs_in = copy.read_obs().unwrap();
+        for name in ["int", "uint", "bool", "str", "complete", "no_missing"] {
+            let expected: &Series = obs.column(name).unwrap();
+            let actual: &Series = obs_in.column(name).unwrap();
+            assert!(expected.equals_missing(actual), "column {} differs", name);
+        }
+        // Missing floats are stored as NaN.
+        assert!(obs_in.column("float").unwrap().f64().unwrap().get(1).unwrap().is_nan());
+        assert_eq!(copy.uns().get_item::<NullableArray>("ints").unwrap().unwrap(), ints);
+        assert_eq!(copy.uns().get_item::<NullableArray>("strings").unwrap().unwrap(), strings);
+        assert_eq!(copy.obsm().get_item::<NullableArray>("ints").unwrap().unwrap(), ints);
+        let selected = copy.obsm().get_item_slice::<NullableArray, _>("ints", [SelectInfoElem::from(1..3)]);
+        assert_eq!(selected.unwrap().unwrap(), ints.select(&[SelectInfoElem::from(1..3)]));
+        copy.close().unwrap();
+
+        let store = B::open(dir.join("copy")).unwrap();
+        let obs_group = store.open_group("obs").unwrap();
+        for (name, encoding) in [
+            ("int", "nullable-integer"),
+            ("bool", "nullable-boolean"),
+            ("str", "nullable-string-array"),
+        ] {
+            let group = obs_group.open_group(name).unwrap();
+            assert_eq!(group.get_attr::<String>("encoding-type").unwrap(), encoding);
+        }
+        let no_missing = obs_group.open_dataset("no_missing").unwrap();
+        assert_eq!(no_missing.get_attr::<String>("encoding-type").unwrap(), "array");
+        drop((obs_group, no_missing));
+        store.close().unwrap();
+
+        // Columns read from a nullable encoding keep it once they have no missing values.
+        let copy = AnnData::<B>::open(B::open_rw(dir.join("copy")).unwrap()).unwrap();
+        copy.subset([SelectInfoElem::from(vec![0, 2]), SelectInfoElem::full()]).unwrap();
+        assert_eq!(copy.read_obs().unwrap().column("int").unwrap().null_count(), 0);
+        copy.write::<B, _>(dir.join("subset")).unwrap();
+        copy.close().unwrap();
+        for path in ["copy", "subset"] {
+            let store = B::open(dir.join(path)).unwrap();
+            let group = store.open_group("obs").unwrap().open_group("int").unwrap();
+            assert_eq!(group.get_attr::<String>("encoding-type").unwrap(), "nullable-integer");
+        }
+    });
+}
+
+/// Selections of large sparse matrices, spanning several read chunks.
+pub fn test_sparse_select<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        let csr: ArrayData = rand_csr::<i32>(3000, 1000, 1500000, 1, 100).into();
+        let csc: ArrayData = rand_csc::<i32>(3000, 1000, 1500000, 1, 100).into();
+        let selections = [
+            [SelectInfoElem::from(100..2900), SelectInfoElem::full()],
+            [SelectInfoElem::full(), SelectInfoElem::from(10..900)],
+            [SelectInfoElem::from(vec![5, 2999, 5, 0]), SelectInfoElem::from(vec![999, 3, 3, 500])],
+            [SelectInfoElem::from(1000..1200), SelectInfoElem::from(vec![7, 1, 900])],
+        ];
+        for x in [csr, csc] {
+            adata.set_x(x.clone()).unwrap();
+            for select in selections.iter() {
+                assert_eq!(
+                    adata.x().slice::<ArrayData, _>(select).unwrap().unwrap(),
+                    array_select(&x, select),
+                );
+            }
+        }
+    });
+}
+
+/// Adding obsm entries through the stacked arrays of an AnnDataSet writes them
+/// into the component AnnData objects.
+pub fn test_stacked_obsm<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adatas = [3usize, 5, 4].into_iter().enumerate().map(|(i, n)| {
+            let adata = AnnData::<B>::new(dir.join(format!("{}", i))).unwrap();
+            adata.set_x(Array2::<i32>::zeros((n, 2))).unwrap();
+            (i.to_string(), adata)
+        });
+        let dataset = AnnDataSet::<B>::new(adatas, dir.join("dataset"), "sample").unwrap();
+        let adatas = dataset.adatas().inner();
+        let obsm = adatas.get_obsm();
+
+        let arr: ArrayData = rand_csr::<i32>(12, 7, 30, 1, 100).into();
+        assert!(obsm.add("csr", rand_csr::<i32>(10, 7, 30, 1, 100)).is_err());
+        obsm.add("csr", arr.clone()).unwrap();
+        obsm.add_iter("chunked", array_chunks(&arr, 4)).unwrap();
+        for key in ["csr", "chunked"] {
+            assert_eq!(obsm.get_item::<ArrayData>(key).unwrap().unwrap(), arr);
+            let mut offset = 0;
+            for adata in adatas.values() {
+                let n = adata.n_obs();
+                assert_eq!(
+                    adata.obsm().get_item::<ArrayData>(key).unwrap().unwrap(),
+                    array_select(&arr, &[SelectInfoElem::from(offset..offset + n), SelectInfoElem::full()]),
+                );
+                offset += n;
+            }
+        }
+
+        // Stacked elements are cached and rebuilt when they are replaced.
+        let dense = Array2::<f64>::from_elem((12, 3), 2.0);
+        assert_eq!(obsm.get("chunked").unwrap().shape(), Some(vec![12, 7].into()));
+        obsm.add("chunked", &dense).unwrap();
+        assert_eq!(obsm.get("chunked").unwrap().shape(), Some(vec![12, 3].into()));
+        assert_eq!(obsm.get_item::<Array2<f64>>("chunked").unwrap().unwrap(), dense);
+
+        obsm.remove("csr").unwrap();
+        assert!(!obsm.contains_key("csr"));
+        assert!(adatas.values().all(|x| !x.obsm().keys().contains(&"csr".to_string())));
+
+        // Failed writes leave nothing behind in the components.
+        let has_key = |key: &str| adatas.values().map(|x| x.obsm().keys().contains(&key.to_string())).collect::<Vec<_>>();
+        assert!(obsm.add_iter("long", array_chunks(&rand_csr::<i32>(13, 7, 30, 1, 100).into(), 4)).is_err());
+        assert_eq!(has_key("long"), vec![false; 3]);
+        let chunks = vec![
+            ArrayData::from(rand_csr::<i32>(4, 7, 10, 1, 100)),
+            ArrayData::from(rand_csr::<i32>(8, 6, 10, 1, 100)),
+        ];
+        assert!(obsm.add_iter("ragged", chunks.into_iter()).is_err());
+        assert_eq!(has_key("ragged"), vec![false; 3]);
+        assert!(obsm.add_iter("chunked", array_chunks(&rand_csr::<i32>(13, 7, 30, 1, 100).into(), 4)).is_err());
+        assert!(!obsm.contains_key("chunked"));
+        assert_eq!(has_key("chunked"), vec![false; 3]);
+
+        // Keys present in some components only are not replaced.
+        adatas.values().next().unwrap().obsm().add("partial", Array2::<i32>::zeros((3, 1))).unwrap();
+        assert!(obsm.add("partial", Array2::<i32>::ones((12, 1))).is_err());
+        assert!(obsm.add_iter("partial", array_chunks(&Array2::<i32>::ones((12, 1)).into(), 5)).is_err());
+        assert_eq!(has_key("partial"), vec![true, false, false]);
+    });
+}
+
+/// Layers of an AnnDataSet are concatenated from the components, while varm
+/// entries are only kept if they are identical in all components.
+pub fn test_stacked_layers<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let counts: ArrayData = rand_csr::<i32>(12, 7, 30, 1, 100).into();
+        let pca = Array2::<f64>::ones((7, 3));
+        let mut offset = 0;
+        let adatas = [3usize, 5, 4].into_iter().enumerate().map(|(i, n)| {
+            let adata = AnnData::<B>::new(dir.join(format!("{}", i))).unwrap();
+            let select = [SelectInfoElem::from(offset..offset + n), SelectInfoElem::full()];
+            adata.set_x(array_select(&counts, &select)).unwrap();
+            adata.layers().add("counts", array_select(&counts, &select)).unwrap();
+            adata.varm().add("pca", &pca).unwrap();
+            adata.varm().add("sample", Array2::<f64>::from_elem((7, 1), i as f64)).unwrap();
+            offset += n;
+            (i.to_string(), adata)
+        }).collect::<Vec<_>>();
+        let dataset = AnnDataSet::<B>::new(adatas, dir.join("dataset"), "sample").unwrap();
+
+        assert_eq!(dataset.layers().get_item::<ArrayData>("counts").unwrap().unwrap(), counts);
+        assert_eq!(dataset.varm().keys(), vec!["pca".to_string()]);
+        assert_eq!(dataset.varm().get_item::<Array2<f64>>("pca").unwrap().unwrap(), pca);
+
+        dataset.layers().add_iter("chunked", array_chunks(&counts, 5)).unwrap();
+        assert_eq!(dataset.layers().get_item::<ArrayData>("chunked").unwrap().unwrap(), counts);
+        assert!(dataset.layers().add("wrong", Array2::<i32>::zeros((10, 7))).is_err());
+        assert!(dataset.layers().add("wrong", Array2::<i32>::zeros((12, 6))).is_err());
+        assert!(dataset.adatas().inner().values().all(|x| !x.layers().keys().contains(&"wrong".to_string())));
+
+        // Layers stored in the annotation file are listed with the stacked ones,
+        // and `del_layers` deletes both.
+        let norm = Array2::<f64>::from_elem((12, 7), 0.5);
+        dataset.get_anno().layers().add("norm", &norm).unwrap();
+        assert_eq!(dataset.layers().keys(), vec!["chunked", "counts", "norm"]);
+        assert_eq!(dataset.layers().get_item::<Array2<f64>>("norm").unwrap().unwrap(), norm);
+
+        dataset.layers().remove("chunked").unwrap();
+        assert_eq!(dataset.layers().keys(), vec!["counts", "norm"]);
+        assert!(dataset.adatas().inner().values().all(|x| x.layers().keys() == vec!["counts"]));
+        dataset.del_layers().unwrap();
+        assert!(dataset.layers().keys().is_empty());
+        assert!(dataset.adatas().inner().values().all(|x| x.layers().keys().is_empty()));
+    });
+}
+
+/// Components with different variables are joined on their var names, and the
+/// columns are remapped when reading the stacked X and layers.
+pub fn test_join<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let to_csr = |x: &Array2<i32>| {
+            let mut coo = CooMatrix::new(x.nrows(), x.ncols());
+            x.indexed_iter().filter(|(_, v)| **v != 0).for_each(|((i, j), v)| coo.push(i, j, *v));
+            CsrMatrix::from(&coo)
+        };
+        let vars = [vec!["a", "b", "c"], vec!["d", "b", "c"]];
+        let xs = [
+            Array2::from_shape_fn((3, 3), |(i, j)| (i * 3 + j + 1) as i32),
+            Array2::from_shape_fn((2, 3), |(i, j)| (i * 3 + j + 100) as i32),
+        ];
+        for (join, names) in [(Join::Inner, vec!["b", "c"]), (Join::Outer, vec!["a", "b", "c", "d"])] {
+            let adatas = vars.iter().zip(xs.iter()).enumerate().map(|(i, (v, x))| {
+                let adata = AnnData::<B>::new(dir.join(format!("{:?}_{}", join, i))).unwrap();
+                adata.set_x(to_csr(x)).unwrap();
+                adata.set_var_names(v.iter().map(|x| x.to_string()).collect()).unwrap();
+                adata.layers().add("counts", x).unwrap();
+                (i.to_string(), adata)
+            });
+            let file = dir.join(format!("{:?}", join));
+            let dataset = AnnDataSet::<B>::new_with_join(adatas, &file, "sample", join).unwrap();
+
+            let expected = Array2::from_shape_fn((5, names.len()), |(i, j)| {
+                let (k, r) = if i < 3 { (0, i) } else { (1, i - 3) };
+                vars[k].iter().position(|v| *v == names[j]).map_or(0, |c| xs[k][[r, c]])
+            });
+            let expected_x: ArrayData = to_csr(&expected).into();
+            assert_eq!(dataset.var_names().into_vec(), names);
+            assert_eq!(dataset.x().get::<ArrayData>().unwrap().unwrap(), expected_x);
+            assert_eq!(dataset.layers().get_item::<Array2<i32>>("counts").unwrap().unwrap(), expected);
+
+            let select = [
+                SelectInfoElem::from(vec![4, 0, 2]),
+                SelectInfoElem::from((0..names.len()).rev().collect::<Vec<_>>()),
+            ];
+            assert_eq!(
+                dataset.x().slice::<ArrayData, _>(&select).unwrap().unwrap(),
+                array_select(&expected_x, &select),
+            );
+            for x in dataset.layers().get("counts").unwrap().iter(2) {
+                let (data, start, end) = x.unwrap();
+                let rows = [SelectInfoElem::from(start..end), SelectInfoElem::full()];
+                assert_eq!(data, array_select(&expected.clone().into(), &rows));
+            }
+
+            let result = dataset.layers().add("new", expected.clone());
+            if join == Join::Outer {
+                result.unwrap();
+                let adatas = dataset.adatas().inner();
+                for (adata, x) in adatas.values().zip(xs.iter()) {
+                    assert_eq!(&adata.layers().get_item::<Array2<i32>>("new").unwrap().unwrap(), x);
+                }
+            } else {
+                assert!(result.is_err());
+            }
+
+            dataset.close().unwrap();
+            let dataset = AnnDataSet::<B>::open(
+                B::open(&file).unwrap(),
+                None::<Result<std::collections::HashMap<String, std::path::PathBuf>, _>>,
+            ).unwrap();
+            assert_eq!(dataset.x().get::<ArrayData>().unwrap().unwrap(), expected_x);
+        }
+
+        // Without a join, the variables must be the same.
+        let adatas = vars.iter().zip(xs.iter()).enumerate().map(|(i, (v, x))| {
+            let adata = AnnData::<B>::new(dir.join(format!("strict_{}", i))).unwrap();
+            adata.set_x(to_csr(x)).unwrap();
+            adata.set_var_names(v.iter().map(|x| x.to_string()).collect()).unwrap();
+            (i.to_string(), adata)
+        });
+        assert!(AnnDataSet::<B>::new(adatas, dir.join("strict"), "sample").is_err());
+    });
+}
+
+/// Out-of-core reductions agree with the reductions of the in-memory arrays.
+pub fn test_reduce<B: Backend>() {
+    fn check<A: ArrayElemOp>(elem: &A, dense: &Array2<f64>) {
+        for axis in 0..2 {
+            let ax = ndarray::Axis(axis);
+            let n = dense.len_of(ax) as f64;
+            let expected = [
+                (Reduction::Sum, dense.sum_axis(ax)),
+                (Reduction::Mean, dense.sum_axis(ax) / n),
+                (Reduction::Var, dense.var_axis(ax, 0.0)),
+                (Reduction::Min, dense.map_axis(ax, |x| x.fold(f64::INFINITY, |a, b| a.min(*b)))),
+                (Reduction::Max, dense.map_axis(ax, |x| x.fold(f64::NEG_INFINITY, |a, b| a.max(*b)))),
+                (Reduction::Nnz, dense.map_axis(ax, |x| x.iter().filter(|v| **v != 0.0).count() as f64)),
+            ];
+            for (reduction, expected) in expected {
+                for chunk_size in [7, 100] {
+                    let result = elem.reduce(reduction, axis, chunk_size).unwrap();
+                    assert!(
+                        result.iter().zip(expected.iter()).all(|(a, b)| (a - b).abs() < 1e-8),
+                        "{:?} along axis {}: {} != {}", reduction, axis, result, expected,
+                    );
+                }
+            }
+            let nnz = dense.map_axis(ax, |x| x.iter().filter(|v| **v != 0.0).count());
+            assert_eq!(elem.nnz_axis(axis, 7).unwrap(), nnz);
+        }
+    }
+
+    with_tmp_dir(|dir| {
+        let (dense, adata, dataset) = sparse_fixture::<B>(&dir);
+        check(&adata.x(), &dense);
+        check(&adata.layers().get("csc").unwrap(), &dense);
+        check(&adata.layers().get("dense").unwrap(), &dense);
+        check(&dataset.x(), &dense);
+
+        // A large offset makes `E[x²] - E[x]²` lose all precision.
+        let shifted = Array2::from_shape_fn((50, 20), |(i, j)| 1e9 + ((i * 7 + j) % 5) as f64);
+        adata.layers().add("shifted", shifted.clone()).unwrap();
+        for axis in 0..2 {
+            let var = adata.layers().get("shifted").unwrap().var_axis(axis, 7).unwrap();
+            let expected = shifted.var_axis(ndarray::Axis(axis), 0.0);
+            assert!(var.iter().zip(expected.iter()).all(|(a, b)| (a - b).abs() < 1e-4), "{} != {}", var, expected);
+        }
+    });
+}
+
+pub fn test_matmul<B: Backend>() {
+    fn check<A: ArrayElemOp>(elem: &A, dense: &Array2<f64>) {
+        let w = Array2::from_shape_fn((dense.ncols(), 3), |(i, j)| (i * 3 + j) as f64 / 7.0 - 2.0);
+        let v = Array2::from_shape_fn((dense.nrows(), 4), |(i, j)| (i + j * 5) as f64 / 11.0 - 1.0);
+        let approx_eq = |a: &Array2<f64>, b: &Array2<f64>| {
+            a.shape() == b.shape() && a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-8)
+        };
+        for chunk_size in [7, 100] {
+            assert!(approx_eq(&elem.dot(w.view(), chunk_size).unwrap(), &dense.dot(&w)));
+            assert!(approx_eq(&elem.t_dot(v.view(), chunk_size).unwrap(), &dense.t().dot(&v)));
+        }
+        assert!(elem.dot(v.view(), 10).is_err());
+        assert!(elem.t_dot(w.view(), 10).is_err());
+    }
+
+    with_tmp_dir(|dir| {
+        let (dense, adata, dataset) = sparse_fixture::<B>(&dir);
+        check(&adata.x(), &dense);
+        check(&adata.layers().get("csc").unwrap(), &dense);
+        check(&adata.layers().get("dense").unwrap(), &dense);
+        check(&dataset.x(), &dense);
+
+        // Products written into the axis arrays.
+        let w = Array2::from_shape_fn((dense.ncols(), 3), |(i, j)| (i * 3 + j) as f64 / 7.0 - 2.0);
+        let v = Array2::from_shape_fn((dense.nrows(), 4), |(i, j)| (i + j * 5) as f64 / 11.0 - 1.0);
+        let approx_eq = |a: Array2<f64>, b: Array2<f64>| {
+            a.shape() == b.shape() && a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-8)
+        };
+        adata.x().dot_into(w.view(), 7, adata.obsm(), "X_proj").unwrap();
+        assert!(approx_eq(adata.obsm().get_item("X_proj").unwrap().unwrap(), dense.dot(&w)));
+        dataset.x().dot_into(w.view(), 7, dataset.obsm(), "X_proj").unwrap();
+        assert!(approx_eq(dataset.obsm().get_item("X_proj").unwrap().unwrap(), dense.dot(&w)));
+        adata.x().t_dot_into(v.view(), 7, adata.varm(), "loadings").unwrap();
+        assert!(approx_eq(adata.varm().get_item("loadings").unwrap().unwrap(), dense.t().dot(&v)));
+
+        adata.layers().add("strings", Array2::<String>::from_elem((50, 20), "a".to_string())).unwrap();
+        let strings = adata.layers().get("strings").unwrap();
+        assert!(strings.dot_into(w.view(), 7, adata.obsm(), "bad").is_err());
+        assert!(adata.x().dot_into(v.view(), 7, adata.obsm(), "bad").is_err());
+        assert!(!adata.obsm().keys().contains(&"bad".to_string()));
+    });
+}
+
+pub fn test_convert_sparse<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i32>(50, 20, 300, -50, 50);
+        let csc = CscMatrix::from(&csr);
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        adata.set_x(&csr).unwrap();
+        adata.layers().add("dense", Array2::<i32>::zeros((50, 20))).unwrap();
+
+        for buffer_size in [1, 7, 1000] {
+            let layers = adata.layers();
+            layers.inner().add_sparse_from("csc", &adata.x(), SparseFormat::Csc, buffer_size).unwrap();
+            assert_eq!(layers.get_item::<CscMatrix<i32>>("csc").unwrap().unwrap(), csc);
+            let elem = layers.get("csc").unwrap();
+            layers.inner().add_sparse_from("csr", &elem, SparseFormat::Csr, buffer_size).unwrap();
+            assert_eq!(layers.get_item::<CsrMatrix<i32>>("csr").unwrap().unwrap(), csr);
+            assert!(layers.inner().add_sparse_from("csc", &elem, SparseFormat::Csc, buffer_size).is_err());
+        }
+        let dense = adata.layers().get("dense").unwrap();
+        assert!(adata.layers().inner().add_sparse_from("x", &dense, SparseFormat::Csc, 10).is_err());
+
+        adata.convert_x(SparseFormat::Csc, 10).unwrap();
+        assert_eq!(adata.x().get::<CscMatrix<i32>>().unwrap().unwrap(), csc);
+        adata.convert_x(SparseFormat::Csr, 10).unwrap();
+        assert_eq!(adata.x().get::<CsrMatrix<i32>>().unwrap().unwrap(), csr);
+        assert_eq!((adata.n_obs(), adata.n_vars()), (50, 20));
+        adata.close().unwrap();
+
+        // No temporary elements are left behind.
+        let store = B::open_rw(dir.join("test")).unwrap();
+        assert!(!store.exists("__X_converted").unwrap());
+        assert!(!store.exists("__X_old").unwrap());
+        assert!(store.rename("X", "layers").is_err());
+        store.rename("X", "counts").unwrap();
+        assert!(!store.exists("X").unwrap());
+        assert!(store.open_group("counts").unwrap().exists("indptr").unwrap());
+    });
+}
+
+pub fn test_coo<B: Backend>() {
+    use anndata::backend::{DataType, ScalarType};
+    use anndata::data::{DynCooMatrix, Stackable};
+
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i32>(50, 20, 300, -50, 50);
+        let coo = CooMatrix::from(&csr);
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        adata.set_x(&coo).unwrap();
+        assert_eq!(adata.x().get::<CooMatrix<i32>>().unwrap().unwrap(), coo);
+        assert_eq!(adata.x().get::<CsrMatrix<i32>>().unwrap().unwrap(), csr);
+        assert_eq!(adata.x().get::<CscMatrix<i32>>().unwrap().unwrap(), CscMatrix::from(&csr));
+
+        let x: ArrayData = csr.clone().into();
+        let select = [SelectInfoElem::from(vec![3, 1, 3, 40]), SelectInfoElem::from(2..18)];
+        let expected = array_select(&x, &select);
+        let selected = adata.x().slice::<ArrayData, _>(&select).unwrap().unwrap();
+        assert_eq!(selected.shape(), expected.shape());
+        assert_eq!(ArrayData::from(DynCsrMatrix::try_from(selected).unwrap()), expected);
+        let selected = ArrayData::from(coo.clone()).select(&select);
+        assert_eq!(ArrayData::from(DynCsrMatrix::try_from(selected).unwrap()), expected);
+
+        let chunks = [0..20, 20..33, 33..50].into_iter().map(|rows| {
+            let chunk: CsrMatrix<i32> = array_select(&x, &[rows.into(), SelectInfoElem::full()]).try_into().unwrap();
+            CooMatrix::from(&chunk)
+        });
+        adata.layers().add_iter("coo", chunks).unwrap();
+        assert_eq!(adata.layers().get_item::<CsrMatrix<i32>>("coo").unwrap().unwrap(), csr);
+
+        // Duplicated entries are summed when converting to compressed formats.
+        let coo = CooMatrix::try_from_triplets(2, 3, vec![0, 1, 0], vec![2, 0, 2], vec![1.0, 2.0, 3.0]).unwrap();
+        let csr = CsrMatrix::try_from_csr_data(2, 3, vec![0, 1, 2], vec![2, 0], vec![4.0, 2.0]).unwrap();
+        assert_eq!(CsrMatrix::<f64>::try_from(ArrayData::from(coo.clone())).unwrap(), csr);
+
+        // COO is not part of the on-disk format, so it is stored as CSR with
+        // the duplicated entries kept.
+        adata.layers().add("dup", &coo).unwrap();
+        let dup = adata.layers().get("dup").unwrap();
+        assert_eq!(dup.inner().dtype(), DataType::CsrMatrix(ScalarType::F64));
+        assert_eq!(dup.get::<CooMatrix<f64>>().unwrap().unwrap(), coo);
+        assert_eq!(dup.get::<CsrMatrix<f64>>().unwrap().unwrap(), csr);
+        adata.set_x(&coo).unwrap();
+        assert_eq!(adata.x().inner().dtype(), DataType::CsrMatrix(ScalarType::F64));
+        assert_eq!(adata.x().get::<CooMatrix<f64>>().unwrap().unwrap(), coo);
+
+        assert!(DynCooMatrix::vstack(std::iter::empty()).is_err());
+        assert_eq!(CscMatrix::<f64>::try_from(ArrayData::from(coo)).unwrap(), CscMatrix::from(&csr));
+        let coo = CooMatrix::try_from_triplets(1, 1, vec![0, 0], vec![0, 0], vec![true, false]).unwrap();
+        assert!(CsrMatrix::<bool>::try_from(ArrayData::from(coo)).is_err());
+    });
+}
+
+pub fn test_sparse_index_type<B: Backend>() {
+    use anndata::backend::{AttributeOp, DataContainer, GroupOp, ScalarType, StoreOp};
+
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i32>(50, 20, 300, -50, 50);
+        let x: ArrayData = csr.clone().into();
+        let rows = [0..20, 20..33, 33..50];
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+
+        // Chunked writes use 32-bit indices when they fit, as scipy does.
+        let chunks = rows.iter().map(|r| array_select(&x, &[r.clone().into(), SelectInfoElem::full()]));
+        adata.set_x_from_iter(chunks).unwrap();
+        assert_eq!(adata.x().inner().index_type(), Some(ScalarType::I32));
+        assert_eq!(adata.x().get::<CsrMatrix<i32>>().unwrap().unwrap(), csr);
+        let layers = adata.layers();
+        layers.inner().add_sparse_from("csc", &adata.x(), SparseFormat::Csc, 100).unwrap();
+        assert_eq!(layers.get("csc").unwrap().inner().index_type(), Some(ScalarType::I32));
+
+        // Compact matrices are read and written without widening the indices.
+        let select = [SelectInfoElem::from(5..30), SelectInfoElem::from(vec![1, 7, 19])];
+        let expected: CsrMatrix<i32> = array_select(&x, &select).try_into().unwrap();
+        let compact = adata.x().inner().select_compact::<i32, _>(&select).unwrap();
+        assert_eq!(compact, CompactCsrMatrix::try_from(expected.clone()).unwrap());
+        assert_eq!(CsrMatrix::from(compact), expected);
+        let chunks = rows.iter().map(|r| {
+            adata.x().inner().select_compact::<i32, _>(&[r.clone().into(), SelectInfoElem::full()]).unwrap()
+        });
+        layers.add_iter("compact", chunks).unwrap();
+        assert_eq!(layers.get("compact").unwrap().inner().index_type(), Some(ScalarType::I32));
+        assert_eq!(layers.get_item::<CsrMatrix<i32>>("compact").unwrap().unwrap(), csr);
+        assert_eq!(layers.get_item::<CompactCsrMatrix<i32>>("compact").unwrap().unwrap(), CompactCsrMatrix::try_from(csr.clone()).unwrap());
+
+        // The index type of matrices written by other tools is reported as is.
+        let store = B::new(dir.join("wide")).unwrap();
+        let mut group = store.new_group("X").unwrap();
+        group.new_attr("encoding-type", "csr_matrix").unwrap();
+        group.new_attr("encoding-version", "0.1.0").unwrap();
+        group.new_attr("shape", [50u64, 20].as_slice()).unwrap();
+        let (indptr, indices, data) = csr.csr_data();
+        let indptr: Vec<i64> = indptr.iter().map(|x| *x as i64).collect();
+        let indices: Vec<i64> = indices.iter().map(|x| *x as i64).collect();
+        group.new_array_dataset("indptr", indptr.clone().into(), Default::default()).unwrap();
+        group.new_array_dataset("indices", indices.clone().into(), Default::default()).unwrap();
+        group.new_array_dataset("data", data.to_vec().into(), Default::default()).unwrap();
+        let elem = ArrayElem::<B>::try_from(DataContainer::open(&store, "X").unwrap()).unwrap();
+        assert_eq!(elem.inner().index_type(), Some(ScalarType::I64));
+        let compact = elem.inner().select_compact::<i32, _>(&[SelectInfoElem::full(), SelectInfoElem::full()]).unwrap();
+        assert_eq!(CsrMatrix::from(compact), csr);
+
+        // Overwriting a matrix keeps the index type it had on disk.
+        let wide = AnnData::<B>::new(dir.join("wide_x")).unwrap();
+        wide.set_x(&csr).unwrap();
+        wide.close().unwrap();
+        let store = B::open_rw(dir.join("wide_x")).unwrap();
+        let group = store.open_group("X").unwrap();
+        group.delete("indptr").unwrap();
+        group.delete("indices").unwrap();
+        group.new_array_dataset("indptr", indptr.into(), Default::default()).unwrap();
+        group.new_array_dataset("indices", indices.into(), Default::default()).unwrap();
+        drop(group);
+        store.close().unwrap();
+        let wide = AnnData::<B>::open(B::open_rw(dir.join("wide_x")).unwrap()).unwrap();
+        assert_eq!(wide.x().inner().index_type(), Some(ScalarType::I64));
+        wide.subset([SelectInfoElem::from(0..30), SelectInfoElem::full()]).unwrap();
+        assert_eq!(wide.x().inner().index_type(), Some(ScalarType::I64));
+        let head = array_select(&x, &[SelectInfoElem::from(0..30), SelectInfoElem::full()]);
+        assert_eq!(wide.x().get::<CsrMatrix<i32>>().unwrap().unwrap(), head.clone().try_into().unwrap());
+
+        // Rows can be iterated over in compact chunks.
+        let chunks: Vec<_> = wide.x().chunked_compact::<i32>(12).unwrap().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(chunks.len(), 3);
+        for (chunk, i, j) in chunks {
+            let expected: CsrMatrix<i32> = array_select(&head, &[SelectInfoElem::from(i..j), SelectInfoElem::full()]).try_into().unwrap();
+            assert_eq!(CsrMatrix::from(chunk), expected);
+        }
+        assert!(wide.x().chunked_compact::<f32>(12).is_err());
+    });
+}
+
+pub fn test_f16<B: Backend>() {
+    use anndata::backend::{DataType, ScalarType};
+    use half::f16;
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        let emb = Array2::from_shape_fn((30, 4), |(i, j)| f16::from_f32(i as f32 - j as f32 / 4.0));
+        adata.obsm().add("emb", emb.clone()).unwrap();
+        let elem = adata.obsm().get("emb").unwrap();
+        assert_eq!(elem.inner().dtype(), DataType::Array(ScalarType::F16));
+        assert_eq!(adata.obsm().get_item::<Array2<f16>>("emb").unwrap().unwrap(), emb);
+        let selected: Array2<f16> = elem.slice(&[SelectInfoElem::from(5..10), SelectInfoElem::full()]).unwrap().unwrap();
+        assert_eq!(selected, emb.slice(ndarray::s![5..10, ..]).to_owned());
+
+        let (indptr, indices, data) = rand_csr::<f32>(30, 20, 100, -10.0, 10.0).disassemble();
+        let data = data.into_iter().map(f16::from_f32).collect();
+        let csr = CsrMatrix::try_from_csr_data(30, 20, indptr, indices, data).unwrap();
+        adata.set_x(&csr).unwrap();
+        assert_eq!(adata.x().inner().dtype(), DataType::CsrMatrix(ScalarType::F16));
+        assert_eq!(adata.x().get::<CsrMatrix<f16>>().unwrap().unwrap(), csr);
+
+        let x: ArrayData = csr.clone().into();
+        let select = [SelectInfoElem::from(vec![3, 1, 3, 25]), SelectInfoElem::from(2..18)];
+        assert_eq!(adata.x().slice::<ArrayData, _>(&select).unwrap().unwrap(), array_select(&x, &select));
+
+        let expected: Vec<f64> = (0..30)
+            .map(|i| csr.row(i).values().iter().map(|v| v.to_f64()).sum())
+            .collect();
+        assert_eq!(adata.x().sum_axis(1, 7).unwrap().to_vec(), expected);
+    });
+}
+
+pub fn test_mex<B: Backend>() {
+    use anndata::{reader::MMReader, writer::{MMWriter, MexWriter}};
+    use std::io::{BufRead, BufReader};
+
+    fn read_lines(path: std::path::PathBuf) -> Vec<String> {
+        let reader = BufReader::new(flate2::read::MultiGzDecoder::new(std::fs::File::open(path).unwrap()));
+        reader.lines().map(|x| x.unwrap()).collect()
+    }
+
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i64>(50, 20, 300, 1, 100);
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        adata.set_x(&csr).unwrap();
+        let obs_names: Vec<String> = (0..50).map(|i| format!("cell{}", i)).collect();
+        let var_names: Vec<String> = (0..20).map(|i| format!("ENSG{}", i)).collect();
+        let symbols: Vec<String> = (0..20).map(|i| format!("GENE{}", i)).collect();
+        adata.set_obs_names(obs_names.clone().into()).unwrap();
+        adata.set_var(df!("symbol" => symbols.clone()).unwrap()).unwrap();
+        adata.set_var_names(var_names.clone().into()).unwrap();
+
+        let mex = dir.join("mex");
+        MexWriter::new().feature_names("symbol").chunk_size(7).write(&adata, &mex).unwrap();
+        assert_eq!(read_lines(mex.join("barcodes.tsv.gz")), obs_names);
+        let features: Vec<String> = var_names
+            .iter()
+            .zip(symbols.iter())
+            .map(|(id, name)| format!("{}\t{}\tGene Expression", id, name))
+            .collect();
+        assert_eq!(read_lines(mex.join("features.tsv.gz")), features);
+
+        // The matrix is stored as features by barcodes.
+        let imported = AnnData::<B>::new(dir.join("imported")).unwrap();
+        MMReader::from_path(mex.join("matrix.mtx.gz"))
+            .unwrap()
+            .obs_names(mex.join("features.tsv.gz"))
+            .unwrap()
+            .var_names(mex.join("barcodes.tsv.gz"))
+            .unwrap()
+            .finish(&imported)
+            .unwrap();
+        assert_eq!(imported.x().get::<CsrMatrix<i64>>().unwrap().unwrap(), csr.transpose());
+        assert_eq!(imported.obs_names().into_vec(), var_names);
+        assert_eq!(imported.var_names().into_vec(), obs_names);
+
+        // Dense layers are written without their zeros.
+        let dense = Array2::from_shape_fn((50, 20), |(i, j)| if (i + j) % 3 == 0 { 0.0 } else { i as f64 + j as f64 / 8.0 });
+        adata.layers().add("dense", dense.clone()).unwrap();
+        let mtx = dir.join("dense.mtx");
+        MMWriter::new().chunk_size(7).write(&adata.layers().get("dense").unwrap(), &mtx).unwrap();
+        let imported = AnnData::<B>::new(dir.join("dense")).unwrap();
+        MMReader::from_path(&mtx).unwrap().finish(&imported).unwrap();
+        let csr: CsrMatrix<f64> = imported.x().get().unwrap().unwrap();
+        assert_eq!(csr.nnz(), dense.iter().filter(|x| **x != 0.0).count());
+        csr.triplet_iter().for_each(|(i, j, v)| assert_eq!(*v, dense[[i, j]]));
+    });
+}
+
+pub fn test_10x<B: Backend>() {
+    use anndata::{backend::{GroupOp, StoreOp}, reader::TenXReader};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn column(var: &polars::prelude::DataFrame, name: &str) -> Vec<String> {
+        var.column(name)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .map(|x| x.to_string())
+            .collect()
+    }
+
+    with_tmp_dir(|dir| {
+        // Features by barcodes, as stored by Cell Ranger.
+        let csc = rand_csc::<i32>(6, 30, 80, 1, 100);
+        let barcodes: Vec<String> = (0..30).map(|i| format!("AAAC{}-1", i)).collect();
+        let ids = strings(&["ENSG0", "ENSG1", "ENSG2", "ENSG3", "CD3", "CD4"]);
+        let names = strings(&["A", "B", "C", "D", "CD3_TotalSeqB", "CD4_TotalSeqB"]);
+        let types = strings(&[
+            "Gene Expression", "Gene Expression", "Gene Expression", "Gene Expression",
+            "Antibody Capture", "Antibody Capture",
+        ]);
+        let genomes = strings(&["GRCh38", "GRCh38", "GRCh38", "GRCh38", "", ""]);
+
+        let path = dir.join("filtered_feature_bc_matrix.h5");
+        {
+            let store = B::new(&path).unwrap();
+            let matrix = store.new_group("matrix").unwrap();
+            let indices: Vec<i64> = csc.row_indices().iter().map(|x| *x as i64).collect();
+            let indptr: Vec<i64> = csc.col_offsets().iter().map(|x| *x as i64).collect();
+            matrix.new_array_dataset("data", csc.values().into(), Default::default()).unwrap();
+            matrix.new_array_dataset("indices", indices.into(), Default::default()).unwrap();
+            matrix.new_array_dataset("indptr", indptr.into(), Default::default()).unwrap();
+            matrix.new_array_dataset("shape", vec![6i32, 30].into(), Default::default()).unwrap();
+            matrix.new_array_dataset("barcodes", barcodes.clone().into(), Default::default()).unwrap();
+            let features = matrix.new_group("features").unwrap();
+            for (key, values) in [("id", &ids), ("name", &names), ("feature_type", &types), ("genome", &genomes)] {
+                features.new_array_dataset(key, values.clone().into(), Default::default()).unwrap();
+            }
+            let tag_keys = strings(&["genome"]);
+            features.new_array_dataset("_all_tag_keys", tag_keys.into(), Default::default()).unwrap();
+            drop((features, matrix));
+            store.close().unwrap();
+        }
+
+        let reader = TenXReader::<B>::from_path(&path).unwrap();
+        assert_eq!(reader.feature_types().unwrap(), strings(&["Gene Expression", "Antibody Capture"]));
+
+        // The CSC matrix of features by barcodes is the CSR matrix of barcodes by features.
+        let (offsets, indices, values) = csc.clone().disassemble();
+        let expected = CsrMatrix::try_from_csr_data(30, 6, offsets, indices, values).unwrap();
+
+        let adata = AnnData::<B>::new(dir.join("all")).unwrap();
+        reader.chunk_size(7).finish(&adata).unwrap();
+        assert_eq!(adata.x().get::<CsrMatrix<i32>>().unwrap().unwrap(), expected);
+        assert_eq!(adata.obs_names().into_vec(), barcodes);
+        assert_eq!(adata.var_names().into_vec(), ids);
+        let var = adata.read_var().unwrap();
+        assert_eq!(var.width(), 3);
+        assert_eq!(column(&var, "name"), names);
+        assert_eq!(column(&var, "feature_type"), types);
+        assert_eq!(column(&var, "genome"), genomes);
+
+        let adata = AnnData::<B>::new(dir.join("adt")).unwrap();
+        TenXReader::<B>::from_path(&path)
+            .unwrap()
+            .feature_type("Antibody Capture")
+            .chunk_size(7)
+            .finish(&adata)
+            .unwrap();
+        assert_eq!(
+            adata.x().get::<CsrMatrix<i32>>().unwrap().unwrap(),
+            expected.select(&[SelectInfoElem::full(), SelectInfoElem::from(vec![4, 5])]),
+        );
+        assert_eq!(adata.var_names().into_vec(), ids[4..]);
+        assert_eq!(column(&adata.read_var().unwrap(), "name"), names[4..]);
+
+        // A corrupted entry in the last chunk is reported and X is not written.
+        {
+            let store = B::open_rw(&path).unwrap();
+            let matrix = store.open_group("matrix").unwrap();
+            let mut indices: Vec<i64> = csc.row_indices().iter().map(|x| *x as i64).collect();
+            *indices.last_mut().unwrap() = 99;
+            matrix.delete("indices").unwrap();
+            matrix.new_array_dataset("indices", indices.into(), Default::default()).unwrap();
+            drop(matrix);
+            store.close().unwrap();
+        }
+        let adata = AnnData::<B>::new(dir.join("corrupted")).unwrap();
+        let err = TenXReader::<B>::from_path(&path).unwrap().chunk_size(7).finish(&adata).unwrap_err();
+        assert!(err.to_string().contains("out of bounds"));
+        assert!(adata.x().is_none());
+    });
+}
+
+pub fn test_loom<B: Backend>() {
+    use anndata::{backend::{DatasetOp, GroupOp}, reader::LoomReader, writer::LoomWriter};
+
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i32>(50, 20, 300, 1, 100);
+        let mut x = Array2::zeros((50, 20));
+        csr.triplet_iter().for_each(|(i, j, v)| x[[i, j]] = *v);
+        let layer = Array2::from_shape_fn((50, 20), |(i, j)| i as f32 - j as f32 / 4.0);
+        let pca = Array2::from_shape_fn((50, 3), |(i, j)| (i * 3 + j) as f64);
+        let graph = rand_csr::<f64>(50, 50, 100, 0.0, 1.0);
+        let obs_names: Vec<String> = (0..50).map(|i| format!("cell{}", i)).collect();
+        let var_names: Vec<String> = (0..20).map(|i| format!("gene{}", i)).collect();
+        let clusters: Vec<String> = (0..50).map(|i| format!("c{}", i % 4)).collect();
+        let counts: Vec<i64> = (0..50).collect();
+
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        adata.set_x(&csr).unwrap();
+        adata.layers().add("dense", layer.clone()).unwrap();
+        adata.obsm().add("X_pca", pca.clone()).unwrap();
+        adata.obsp().add("connectivities", graph.clone()).unwrap();
+        adata.set_obs(df!("cluster" => clusters.clone(), "n_counts" => counts.clone()).unwrap()).unwrap();
+        adata.set_obs_names(obs_names.clone().into()).unwrap();
+        adata.set_var_names(var_names.clone().into()).unwrap();
+
+        let loom = dir.join("test.loom");
+        LoomWriter::new().chunk_size(7).write::<B, _, _>(&adata, &loom).unwrap();
+
+        // The matrices are stored as genes by cells.
+        {
+            let store = B::open(&loom).unwrap();
+            let matrix: Array2<i32> = store.open_dataset("matrix").unwrap().read_array().unwrap();
+            assert_eq!(matrix, x.t());
+            let genes: ndarray::Array1<String> =
+                store.open_group("row_attrs").unwrap().open_dataset("Gene").unwrap().read_array().unwrap();
+            assert_eq!(genes.to_vec(), var_names);
+            let a: ndarray::Array1<i64> = store
+                .open_group("col_graphs")
+                .unwrap()
+                .open_group("connectivities")
+                .unwrap()
+                .open_dataset("a")
+                .unwrap()
+                .read_array()
+                .unwrap();
+            assert_eq!(a.len(), graph.nnz());
+        }
+
+        let imported = AnnData::<B>::new(dir.join("imported")).unwrap();
+        LoomReader::<B>::from_path(&loom).unwrap().chunk_size(9).finish(&imported).unwrap();
+        assert_eq!(imported.x().get::<Array2<i32>>().unwrap().unwrap(), x);
+        assert_eq!(imported.layers().get_item::<Array2<f32>>("dense").unwrap().unwrap(), layer);
+        assert_eq!(imported.obsm().get_item::<Array2<f64>>("X_pca").unwrap().unwrap(), pca);
+        assert_eq!(imported.obsp().get_item::<CsrMatrix<f64>>("connectivities").unwrap().unwrap(), graph);
+        assert_eq!(imported.obs_names().into_vec(), obs_names);
+        assert_eq!(imported.var_names().into_vec(), var_names);
+        let obs = imported.read_obs().unwrap();
+        assert!(obs.column("cluster").unwrap().equals(&Series::new("cluster".into(), clusters)));
+        assert!(obs.column("n_counts").unwrap().equals(&Series::new("n_counts".into(), counts)));
+
+        // A file in the layout written by loompy, i.e., float32 matrices of
+        // genes by cells and an empty `row_graphs` group.
+        let spliced = Array2::from_shape_fn((20, 50), |(i, j)| (i * 50 + j) as f32);
+        let umap = Array2::from_shape_fn((50, 2), |(i, j)| i as f32 + j as f32 / 2.0);
+        let accessions: Vec<String> = (0..20).map(|i| format!("ENSG{:05}", i)).collect();
+        let write_loompy = |path: &std::path::Path, layer: &Array2<f32>| {
+            let store = B::new(path).unwrap();
+            store.new_array_dataset("matrix", x.t().mapv(|v| v as f32).into(), Default::default()).unwrap();
+            let layers = store.new_group("layers").unwrap();
+            layers.new_array_dataset("spliced", layer.clone().into(), Default::default()).unwrap();
+            let row_attrs = store.new_group("row_attrs").unwrap();
+            row_attrs.new_array_dataset("Gene", ndarray::Array1::from(var_names.clone()).into(), Default::default()).unwrap();
+            row_attrs.new_array_dataset("Accession", ndarray::Array1::from(accessions.clone()).into(), Default::default()).unwrap();
+            let col_attrs = store.new_group("col_attrs").unwrap();
+            col_attrs.new_array_dataset("CellID", ndarray::Array1::from(obs_names.clone()).into(), Default::default()).unwrap();
+            col_attrs.new_array_dataset("ClusterID", ndarray::Array1::from(counts.clone()).into(), Default::default()).unwrap();
+            col_attrs.new_array_dataset("X_umap", umap.clone().into(), Default::default()).unwrap();
+            let knn = store.new_group("col_graphs").unwrap().new_group("KNN").unwrap();
+            let (a, b): (Vec<i64>, Vec<i64>) = (0..50).map(|i| (i, (i + 1) % 50)).unzip();
+            knn.new_array_dataset("a", ndarray::Array1::from(a).into(), Default::default()).unwrap();
+            knn.new_array_dataset("b", ndarray::Array1::from(b).into(), Default::default()).unwrap();
+            knn.new_array_dataset("w", ndarray::Array1::<f64>::ones(50).into(), Default::default()).unwrap();
+            store.new_group("row_graphs").unwrap();
+            drop((layers, row_attrs, col_attrs, knn));
+            store.close().unwrap();
+        };
+
+        let loompy = dir.join("loompy.loom");
+        write_loompy(&loompy, &spliced);
+        let imported = AnnData::<B>::new(dir.join("loompy")).unwrap();
+        LoomReader::<B>::from_path(&loompy).unwrap().chunk_size(9).finish(&imported).unwrap();
+        assert_eq!(imported.x().get::<Array2<f32>>().unwrap().unwrap(), x.mapv(|v| v as f32));
+        assert_eq!(imported.layers().get_item::<Array2<f32>>("spliced").unwrap().unwrap(), spliced.t());
+        assert_eq!(imported.obsm().get_item::<Array2<f32>>("X_umap").unwrap().unwrap(), umap);
+        assert_eq!(imported.obsp().get_item::<CsrMatrix<f64>>("KNN").unwrap().unwrap().nnz(), 50);
+        assert!(imported.varp().keys().is_empty());
+        assert_eq!(imported.obs_names().into_vec(), obs_names);
+        assert_eq!(imported.var_names().into_vec(), var_names);
+        let var = imported.read_var().unwrap();
+        assert!(var.column("Accession").unwrap().equals(&Series::new("Accession".into(), accessions.clone())));
+
+        // A layer that is shorter than the matrix is reported instead of panicking.
+        let truncated = dir.join("truncated.loom");
+        write_loompy(&truncated, &spliced.slice(ndarray::s![.., ..30]).to_owned());
+        let imported = AnnData::<B>::new(dir.join("truncated")).unwrap();
+        let err = LoomReader::<B>::from_path(&truncated).unwrap().chunk_size(9).finish(&imported).unwrap_err();
+        assert!(err.to_string().contains("spliced"));
+        assert!(imported.layers().get("spliced").is_none());
+    });
+}
+
+pub fn test_csv<B: Backend>() {
+    use anndata::reader::CsvReader;
+    use std::io::Write;
+
+    with_tmp_dir(|dir| {
+        let x = Array2::from_shape_fn((30, 8), |(i, j)| if (i + j) % 3 == 0 { i as f64 + j as f64 / 2.0 } else { 0.0 });
+        // The names contain the delimiter of CSV files, and are quoted there.
+        let obs_names: Vec<String> = (0..30).map(|i| format!("cell{}, batch {}", i, i % 2)).collect();
+        let var_names: Vec<String> = (0..8).map(|i| format!("gene{}", i)).collect();
+        let rows: Vec<Vec<String>> = x
+            .rows()
+            .into_iter()
+            .map(|row| row.iter().map(|v| v.to_string()).collect())
+            .collect();
+
+        // Written by pandas, with an empty header for the index column.
+        let csv = dir.join("counts.csv.gz");
+        {
+            let file = std::fs::File::create(&csv).unwrap();
+            let mut writer = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            writeln!(writer, "\"\",{}", var_names.join(",")).unwrap();
+            for (name, row) in obs_names.iter().zip(rows.iter()) {
+                writeln!(writer, "\"{}\",{}", name, row.join(",")).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        let adata = AnnData::<B>::new(dir.join("csv")).unwrap();
+        CsvReader::from_path(&csv).unwrap().index_column(0).chunk_size(7).finish(&adata).unwrap();
+        assert_eq!(adata.x().get::<Array2<f64>>().unwrap().unwrap(), x);
+        assert_eq!(adata.obs_names().into_vec(), obs_names);
+        assert_eq!(adata.var_names().into_vec(), var_names);
+
+        // Written by R, without a name for the index column.
+        let tsv = dir.join("counts.tsv");
+        {
+            let mut writer = std::fs::File::create(&tsv).unwrap();
+            writeln!(writer, "{}", var_names.join("\t")).unwrap();
+            for (name, row) in obs_names.iter().zip(rows.iter()) {
+                writeln!(writer, "{}\t{}", name, row.join("\t")).unwrap();
+            }
+        }
+        let adata = AnnData::<B>::new(dir.join("tsv")).unwrap();
+        CsvReader::from_path(&tsv).unwrap().index_column(0).sparse(0.5).chunk_size(7).finish(&adata).unwrap();
+        let csr: CsrMatrix<f64> = adata.x().get().unwrap().unwrap();
+        assert_eq!(csr.nnz(), x.iter().filter(|v| **v != 0.0).count());
+        csr.triplet_iter().for_each(|(i, j, v)| assert_eq!(*v, x[[i, j]]));
+        assert_eq!(adata.obs_names().into_vec(), obs_names);
+        assert_eq!(adata.var_names().into_vec(), var_names);
+
+        // Parse errors are reported, whether they occur in the first chunk or
+        // in a later one, and X is not written.
+        let bad = dir.join("bad.csv");
+        std::fs::write(&bad, "1,2\n3,a\n").unwrap();
+        let adata = AnnData::<B>::new(dir.join("bad")).unwrap();
+        let err = CsvReader::from_path(&bad).unwrap().has_header(false).finish(&adata).unwrap_err();
+        assert!(err.to_string().contains("cannot parse 'a'"));
+        assert!(adata.x().is_none());
+
+        let lines: Vec<String> = rows.iter().map(|row| row.join(",")).chain(["1,2,3".to_string()]).collect();
+        std::fs::write(&bad, lines.join("\n")).unwrap();
+        let adata = AnnData::<B>::new(dir.join("bad_last")).unwrap();
+        let err = CsvReader::from_path(&bad).unwrap().has_header(false).chunk_size(7).finish(&adata).unwrap_err();
+        assert!(err.to_string().contains("expected 8 values"));
+        assert!(adata.x().is_none());
+
+        std::fs::write(&bad, "a,b\n1,\"2\n").unwrap();
+        let adata = AnnData::<B>::new(dir.join("bad_quote")).unwrap();
+        assert!(CsvReader::from_path(&bad).unwrap().finish(&adata).is_err());
+
+        // A header that does not match the table is rejected before X is written.
+        std::fs::write(&bad, "a,b,c\n1,2\n").unwrap();
+        let adata = AnnData::<B>::new(dir.join("bad_header")).unwrap();
+        assert!(CsvReader::from_path(&bad).unwrap().finish(&adata).is_err());
+        assert!(adata.x().is_none());
+    });
+}
+
+pub fn test_mm_external_sort<B: Backend>() {
+    use anndata::reader::MMReader;
+    use rand::seq::SliceRandom;
+    use std::io::Write;
+
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i64>(60, 25, 400, -100, 100);
+        let mut entries: Vec<_> = csr.triplet_iter().map(|(i, j, v)| (i, j, *v)).collect();
+        entries.shuffle(&mut rand::thread_rng());
+        // Split an entry into two duplicated entries.
+        let (i, j, v) = entries[0];
+        entries[0] = (i, j, v - 1);
+        entries.push((i, j, 1));
+
+        let mtx = dir.join("unsorted.mtx.gz");
+        {
+            let file = std::fs::File::create(&mtx).unwrap();
+            let mut writer = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            writeln!(writer, "%%MatrixMarket matrix coordinate integer general").unwrap();
+            writeln!(writer, "% shuffled").unwrap();
+            writeln!(writer, "60 25 {}", entries.len()).unwrap();
+            for (i, j, v) in entries.iter() {
+                writeln!(writer, "{} {} {}", i + 1, j + 1, v).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        for run_size in [7, 1000] {
+            let adata = AnnData::<B>::new(dir.join(format!("run{}", run_size))).unwrap();
+            MMReader::from_path(&mtx).unwrap().external_sort(run_size).finish(&adata).unwrap();
+            assert_eq!(adata.x().get::<CsrMatrix<i64>>().unwrap().unwrap(), csr);
+        }
+
+        // Pattern entries have no values and are stored as ones.
+        let mtx_pattern = dir.join("pattern.mtx");
+        {
+            let mut writer = std::fs::File::create(&mtx_pattern).unwrap();
+            writeln!(writer, "%%MatrixMarket matrix coordinate pattern general").unwrap();
+            writeln!(writer, "60 25 {}", entries.len() - 1).unwrap();
+            for (i, j, _) in entries[1..].iter() {
+                writeln!(writer, "{} {}", i + 1, j + 1).unwrap();
+            }
+        }
+        let adata = AnnData::<B>::new(dir.join("pattern")).unwrap();
+        MMReader::from_path(&mtx_pattern).unwrap().external_sort(7).chunk_size(9).finish(&adata).unwrap();
+        let pattern = adata.x().get::<CsrMatrix<bool>>().unwrap().unwrap();
+        assert_eq!(pattern.pattern(), csr.pattern());
+        assert!(pattern.values().iter().all(|x| *x));
+
+        // Symmetric matrices are expanded as in the in-memory reader.
+        let mtx = dir.join("symmetric.mtx");
+        std::fs::write(
+            &mtx,
+            "%%MatrixMarket matrix coordinate real symmetric\n4 4 4\n4 1 0.5\n2 2 1.5\n3 1 -2\n4 3 3\n",
+        )
+        .unwrap();
+        let expected = AnnData::<B>::new(dir.join("in_memory")).unwrap();
+        MMReader::from_path(&mtx).unwrap().finish(&expected).unwrap();
+        let adata = AnnData::<B>::new(dir.join("symmetric")).unwrap();
+        MMReader::from_path(&mtx).unwrap().external_sort(3).finish(&adata).unwrap();
+        assert_eq!(
+            adata.x().get::<CsrMatrix<f64>>().unwrap().unwrap(),
+            expected.x().get::<CsrMatrix<f64>>().unwrap().unwrap(),
+        );
+
+        let mtx = dir.join("bad.mtx");
+        std::fs::write(&mtx, "%%MatrixMarket matrix coordinate real general\n2 2 1\n3 1 1.0\n").unwrap();
+        let adata = AnnData::<B>::new(dir.join("bad")).unwrap();
+        assert!(MMReader::from_path(&mtx).unwrap().external_sort(3).finish(&adata).is_err());
+    });
+}
+
+pub fn test_mm_types<B: Backend>() {
+    use anndata::backend::ScalarType;
+    use anndata::reader::MMReader;
+
+    with_tmp_dir(|dir| {
+        let mtx = dir.join("counts.mtx");
+        std::fs::write(&mtx, "%%MatrixMarket matrix coordinate integer general\n2 3 3\n1 1 5\n2 3 7\n1 1 2\n").unwrap();
+        let expected = CsrMatrix::try_from_csr_data(2, 3, vec![0, 1, 2], vec![0, 2], vec![7i64, 7]).unwrap();
+        let adata = AnnData::<B>::new(dir.join("i64")).unwrap();
+        MMReader::from_path(&mtx).unwrap().finish(&adata).unwrap();
+        assert_eq!(adata.x().get::<CsrMatrix<i64>>().unwrap().unwrap(), expected);
+        let adata = AnnData::<B>::new(dir.join("u32")).unwrap();
+        MMReader::from_path(&mtx).unwrap().dtype(ScalarType::U32).finish(&adata).unwrap();
+        let expected = CsrMatrix::try_from_csr_data(2, 3, vec![0, 1, 2], vec![0, 2], vec![7u32, 7]).unwrap();
+        assert_eq!(adata.x().get::<CsrMatrix<u32>>().unwrap().unwrap(), expected);
+        // Duplicated entries must not overflow when they are merged.
+        let overflow = dir.join("overflow.mtx");
+        std::fs::write(&overflow, "%%MatrixMarket matrix coordinate integer general\n1 1 2\n1 1 100\n1 1 100\n").unwrap();
+        assert!(MMReader::from_path(&overflow).unwrap().dtype(ScalarType::I8).read().is_err());
+        // Unsorted entries are rejected before X is replaced.
+        let unsorted = dir.join("unsorted.mtx");
+        std::fs::write(&unsorted, "%%MatrixMarket matrix coordinate integer general\n2 3 2\n2 3 7\n1 1 5\n").unwrap();
+        assert!(MMReader::from_path(&unsorted).unwrap().dtype(ScalarType::U32).is_sorted().finish(&adata).is_err());
+        assert_eq!(adata.x().get::<CsrMatrix<u32>>().unwrap().unwrap(), expected);
+
+        // Pattern matrices have no values, and symmetric entries are mirrored.
+        let mtx = dir.join("graph.mtx");
+        std::fs::write(&mtx, "%%MatrixMarket matrix coordinate pattern symmetric\n3 3 3\n2 1\n3 1\n3 3\n").unwrap();
+        let offsets = vec![0, 2, 3, 5];
+        let indices = vec![1, 2, 0, 0, 2];
+        let graph = MMReader::from_path(&mtx).unwrap().read().unwrap();
+        let expected = CsrMatrix::try_from_csr_data(3, 3, offsets.clone(), indices.clone(), vec![true; 5]).unwrap();
+        assert_eq!(graph, ArrayData::from(expected));
+        let adata = AnnData::<B>::new(dir.join("pattern")).unwrap();
+        assert!(MMReader::from_path(&mtx).unwrap().is_sorted().finish(&adata).is_err());
+        MMReader::from_path(&mtx).unwrap().dtype(ScalarType::U8).external_sort(2).finish(&adata).unwrap();
+        let expected = CsrMatrix::try_from_csr_data(3, 3, offsets, indices, vec![1u8; 5]).unwrap();
+        assert_eq!(adata.x().get::<CsrMatrix<u8>>().unwrap().unwrap(), expected);
+        adata.obsp().add("graph", MMReader::from_path(&mtx).unwrap().dtype(ScalarType::U8).read().unwrap()).unwrap();
+        assert_eq!(adata.obsp().get_item::<CsrMatrix<u8>>("graph").unwrap().unwrap(), expected);
+
+        // The mirrored entries of skew-symmetric matrices are negated.
+        let mtx = dir.join("skew.mtx");
+        std::fs::write(&mtx, "%%MatrixMarket matrix coordinate real skew-symmetric\n3 3 2\n2 1 1.5\n3 2 -2\n").unwrap();
+        let expected =
+            CsrMatrix::try_from_csr_data(3, 3, vec![0, 1, 3, 4], vec![1, 0, 2, 1], vec![-1.5f32, 1.5, 2.0, -2.0]).unwrap();
+        for run_size in [None, Some(1)] {
+            let adata = AnnData::<B>::new(dir.join(format!("skew{:?}", run_size))).unwrap();
+            let reader = MMReader::from_path(&mtx).unwrap().dtype(ScalarType::F32);
+            match run_size {
+                Some(n) => reader.external_sort(n).finish(&adata).unwrap(),
+                None => reader.finish(&adata).unwrap(),
+            }
+            assert_eq!(adata.x().get::<CsrMatrix<f32>>().unwrap().unwrap(), expected);
+        }
+        // Real values cannot be stored as integers, and unsigned integers
+        // cannot hold the negated entries.
+        assert!(MMReader::from_path(&mtx).unwrap().dtype(ScalarType::I32).read().is_err());
+        let mtx = dir.join("skew_int.mtx");
+        std::fs::write(&mtx, "%%MatrixMarket matrix coordinate integer skew-symmetric\n2 2 1\n2 1 3\n").unwrap();
+        assert!(MMReader::from_path(&mtx).unwrap().dtype(ScalarType::U32).read().is_err());
+        let expected = CsrMatrix::try_from_csr_data(2, 2, vec![0, 1, 2], vec![1, 0], vec![-3i32, 3]).unwrap();
+        let skew = MMReader::from_path(&mtx).unwrap().dtype(ScalarType::I32).read().unwrap();
+        assert_eq!(skew, ArrayData::from(expected));
+    });
+}
+
+pub fn test_map_chunks<B: Backend>() {
+    fn log1p(x: ArrayData) -> ArrayData {
+        let mut csr: CsrMatrix<f64> = x.try_into().unwrap();
+        csr.values_mut().iter_mut().for_each(|v| *v = v.ln_1p());
+        csr.into()
+    }
+
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<f64>(50, 20, 300, 1.0, 100.0);
+        let expected = log1p(csr.clone().into());
+
+        let adata = AnnData::<B>::new(dir.join("test")).unwrap();
+        adata.set_x(&csr).unwrap();
+        let chunks = adata.x().map_chunks(7, log1p);
+        assert_eq!(chunks.len(), 8);
+        adata.layers().try_add_iter("log1p", chunks).unwrap();
+        assert_eq!(adata.layers().get_item::<ArrayData>("log1p").unwrap().unwrap(), expected);
+
+        let totals = adata.x().map_chunks(7, |x| {
+            let csr: CsrMatrix<f64> = x.try_into().unwrap();
+            Array2::from_shape_fn((csr.nrows(), 1), |(i, _)| csr.row(i).values().iter().sum::<f64>()).into()
+        });
+        adata.obsm().try_add_iter("total", totals).unwrap();
+        let totals = adata.obsm().get_item::<Array2<f64>>("total").unwrap().unwrap();
+        (0..50).for_each(|i| assert!((totals[[i, 0]] - csr.row(i).values().iter().sum::<f64>()).abs() < 1e-8));
+
+        adata.try_set_x_from_iter(adata.layers().get("log1p").unwrap().map_chunks(10, |x| x)).unwrap();
+        assert_eq!(adata.x().get::<ArrayData>().unwrap().unwrap(), expected);
+
+        // X can be transformed in place, and is kept if the transformation fails.
+        adata.set_x(&csr).unwrap();
+        adata.try_set_x_from_iter(adata.x().map_chunks(7, log1p)).unwrap();
+        assert_eq!(adata.x().get::<ArrayData>().unwrap().unwrap(), expected);
+        let wrong_shape = adata.x().map_chunks(7, |x| x.select_axis(1, SelectInfoElem::from(0..5)));
+        assert!(adata.try_set_x_from_iter(wrong_shape).is_err());
+        assert_eq!(adata.x().get::<ArrayData>().unwrap().unwrap(), expected);
+
+        let x: ArrayData = csr.into();
+        let adatas = [0..20, 20..50].into_iter().enumerate().map(|(i, rows)| {
+            let adata = AnnData::<B>::new(dir.join(format!("{}", i))).unwrap();
+            adata.set_x(array_select(&x, &[rows.into(), SelectInfoElem::full()])).unwrap();
+            (i.to_string(), adata)
+        });
+        let dataset = AnnDataSet::<B>::new(adatas, dir.join("dataset"), "sample").unwrap();
+        dataset.layers().try_add_iter("log1p", dataset.x().map_chunks(7, log1p)).unwrap();
+        assert_eq!(dataset.layers().get_item::<ArrayData>("log1p").unwrap().unwrap(), expected);
+    });
+}
+
 pub fn test_speacial_cases<F, T>(adata_gen: F)
 where
     F: Fn() -> T,
@@ -143,7 +1457,7 @@ where
             adata.obsm().add_iter("test", array_chunks(&x, 7)).unwrap();
             prop_assert_eq!(adata.obsm().get_item::<ArrayData>("test").unwrap().unwrap(), x.clone());
 
-            adata.obsm().add_iter("test2", adata.obsm().get_item_iter("test", 7).unwrap().map(|x| x.0)).unwrap();
+            adata.obsm().try_add_iter("test2", adata.obsm().get_item_iter("test", 7).unwrap().map(|x| x.map(|x| x.0))).unwrap();
             prop_assert_eq!(adata.obsm().get_item::<ArrayData>("test2").unwrap().unwrap(), x);
         }
     });
@@ -1,13 +1,16 @@
 use anndata_test_utils as utils;
 use anndata_test_utils::with_tmp_dir;
 use anndata_hdf5::H5;
-use anndata_zarr::Zarr;
+use anndata_zarr::{Zarr, ZarrVersion};
+use anndata_memory::Memory;
 use anndata::{AnnData, Backend};
 
 #[test]
 fn test_basic() {
     utils::test_basic::<H5>();
     utils::test_basic::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_basic::<Memory>();
 }
 
 #[test]
@@ -20,10 +23,225 @@ fn test_complex_dataframe() {
     })
 }
 
+#[test]
+fn test_zarr_v2() {
+    use anndata::{AnnDataOp, ArrayElemOp, AxisArraysOp, ArrayData, data::SelectInfoElem};
+    use ndarray::Array2;
+
+    with_tmp_dir(|dir| {
+        let path = dir.join("v2.zarr");
+        let adata = AnnData::<Zarr>::new_with_store(Zarr::new_with_version(&path, ZarrVersion::V2).unwrap()).unwrap();
+        adata.set_x(utils::rand_csr::<i32>(20, 10, 50, 1, 100)).unwrap();
+        adata.obsm().add("X_pca", Array2::<f32>::ones((20, 3))).unwrap();
+        adata.layers().add("dense", Array2::<f64>::zeros((20, 10))).unwrap();
+        adata.set_obs_names((0..20).map(|i| format!("cell{}", i)).collect()).unwrap();
+        assert!(path.join(".zgroup").exists());
+        assert!(path.join("X/.zgroup").exists());
+        assert!(path.join("layers/dense/.zarray").exists());
+        assert!(!path.join("zarr.json").exists());
+
+        let copy = dir.join("copy.zarr");
+        adata.write_to_store::<Zarr>(Zarr::new_with_version(&copy, ZarrVersion::V2).unwrap()).unwrap();
+        assert!(copy.join("obsm/X_pca/.zarray").exists());
+        assert!(!copy.join("zarr.json").exists());
+        let subset = dir.join("subset.zarr");
+        let select = [SelectInfoElem::from(0..5), SelectInfoElem::full()];
+        adata.write_select_to_store::<Zarr, _>(&select, Zarr::new_with_version(&subset, ZarrVersion::V2).unwrap()).unwrap();
+        assert!(subset.join("X/.zgroup").exists());
+
+        let copy = AnnData::<Zarr>::open(Zarr::open(&copy).unwrap()).unwrap();
+        assert!(utils::anndata_eq(&adata, &copy).unwrap());
+        let subset = AnnData::<Zarr>::open(Zarr::open(&subset).unwrap()).unwrap();
+        assert_eq!(subset.n_obs(), 5);
+        assert_eq!(
+            subset.x().get::<ArrayData>().unwrap().unwrap(),
+            utils::array_select(&adata.x().get::<ArrayData>().unwrap().unwrap(), &select),
+        );
+    })
+}
+
 #[test]
 fn test_save() {
     utils::test_save::<H5>();
     utils::test_save::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_save::<Memory>();
+}
+
+#[test]
+fn test_save_compressed() {
+    utils::test_save_compressed::<H5>();
+    utils::test_save_compressed::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_save_compressed::<Memory>();
+}
+
+#[test]
+fn test_convert() {
+    utils::test_convert::<H5, Zarr>();
+    utils::test_convert::<Zarr, H5>();
+    Memory::set_persistent(true);
+    utils::test_convert::<H5, Memory>();
+}
+
+#[test]
+fn test_view() {
+    utils::test_view::<H5>();
+    utils::test_view::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_view::<Memory>();
+}
+
+#[test]
+fn test_stacked_obsm() {
+    utils::test_stacked_obsm::<H5>();
+    utils::test_stacked_obsm::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_stacked_obsm::<Memory>();
+}
+
+#[test]
+fn test_stacked_layers() {
+    utils::test_stacked_layers::<H5>();
+    utils::test_stacked_layers::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_stacked_layers::<Memory>();
+}
+
+#[test]
+fn test_join() {
+    utils::test_join::<H5>();
+    utils::test_join::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_join::<Memory>();
+}
+
+#[test]
+fn test_reduce() {
+    utils::test_reduce::<H5>();
+    utils::test_reduce::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_reduce::<Memory>();
+}
+
+#[test]
+fn test_matmul() {
+    utils::test_matmul::<H5>();
+    utils::test_matmul::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_matmul::<Memory>();
+}
+
+#[test]
+fn test_convert_sparse() {
+    utils::test_convert_sparse::<H5>();
+    utils::test_convert_sparse::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_convert_sparse::<Memory>();
+}
+
+#[test]
+fn test_coo() {
+    utils::test_coo::<H5>();
+    utils::test_coo::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_coo::<Memory>();
+}
+
+#[test]
+fn test_sparse_index_type() {
+    utils::test_sparse_index_type::<H5>();
+    utils::test_sparse_index_type::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_sparse_index_type::<Memory>();
+}
+
+#[test]
+fn test_f16() {
+    utils::test_f16::<H5>();
+    utils::test_f16::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_f16::<Memory>();
+}
+
+#[test]
+fn test_mex() {
+    utils::test_mex::<H5>();
+    utils::test_mex::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_mex::<Memory>();
+}
+
+#[test]
+fn test_10x() {
+    utils::test_10x::<H5>();
+    utils::test_10x::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_10x::<Memory>();
+}
+
+#[test]
+fn test_loom() {
+    utils::test_loom::<H5>();
+    utils::test_loom::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_loom::<Memory>();
+}
+
+#[test]
+fn test_csv() {
+    utils::test_csv::<H5>();
+    utils::test_csv::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_csv::<Memory>();
+}
+
+#[test]
+fn test_mm_external_sort() {
+    utils::test_mm_external_sort::<H5>();
+    utils::test_mm_external_sort::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_mm_external_sort::<Memory>();
+}
+
+#[test]
+fn test_mm_types() {
+    utils::test_mm_types::<H5>();
+    utils::test_mm_types::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_mm_types::<Memory>();
+}
+
+#[test]
+fn test_map_chunks() {
+    utils::test_map_chunks::<H5>();
+    utils::test_map_chunks::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_map_chunks::<Memory>();
+}
+
+#[test]
+fn test_raw() {
+    utils::test_raw::<H5>();
+    utils::test_raw::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_raw::<Memory>();
+}
+
+#[test]
+fn test_sparse_select() {
+    utils::test_sparse_select::<H5>();
+    utils::test_sparse_select::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_sparse_select::<Memory>();
+}
+
+#[test]
+fn test_nullable() {
+    utils::test_nullable::<H5>();
+    utils::test_nullable::<Zarr>();
+    Memory::set_persistent(true);
+    utils::test_nullable::<Memory>();
 }
 
 #[test]
@@ -36,6 +254,10 @@ fn test_speacial_cases() {
         let file = dir.join("test.zarr");
         let adata_gen = || AnnData::<Zarr>::new(&file).unwrap();
         utils::test_speacial_cases(|| adata_gen());
+
+        let file = dir.join("test.mem");
+        let adata_gen = || AnnData::<Memory>::new(&file).unwrap();
+        utils::test_speacial_cases(|| adata_gen());
     })
 }
 
@@ -49,6 +271,10 @@ fn test_noncanonical() {
         let file = dir.join("test.zarr");
         let adata_gen = || AnnData::<Zarr>::new(&file).unwrap();
         utils::test_noncanonical(|| adata_gen());
+
+        let file = dir.join("test.mem");
+        let adata_gen = || AnnData::<Memory>::new(&file).unwrap();
+        utils::test_noncanonical(|| adata_gen());
     })
 }
 
@@ -62,6 +288,10 @@ fn test_io() {
         let file = dir.join("test.zarr");
         let adata_gen = || AnnData::<Zarr>::new(&file).unwrap();
         utils::test_io(|| adata_gen());
+
+        let file = dir.join("test.mem");
+        let adata_gen = || AnnData::<Memory>::new(&file).unwrap();
+        utils::test_io(|| adata_gen());
     })
 }
 
@@ -75,6 +305,10 @@ fn test_index_h5() {
         let file = dir.join("test.zarr");
         let adata_gen = || AnnData::<Zarr>::new(&file).unwrap();
         utils::test_index(|| adata_gen());
+
+        let file = dir.join("test.mem");
+        let adata_gen = || AnnData::<Memory>::new(&file).unwrap();
+        utils::test_index(|| adata_gen());
     })
 }
 
@@ -88,5 +322,9 @@ fn test_iterator() {
         let file = dir.join("test.zarr");
         let adata_gen = || AnnData::<Zarr>::new(&file).unwrap();
         utils::test_iterator(|| adata_gen());
+
+        let file = dir.join("test.mem");
+        let adata_gen = || AnnData::<Memory>::new(&file).unwrap();
+        utils::test_iterator(|| adata_gen());
     })
 }
\ No newline at end of file
@@ -3,24 +3,39 @@ use anndata::{
     data::{DynArray, DynCowArray, SelectInfoBounds, SelectInfoElem, SelectInfoElemBounds, Shape},
 };
 
-use anyhow::{bail, Context, Result};
-use ndarray::{Array, ArrayD, ArrayView, CowArray, Dimension, IxDyn, SliceInfoElem};
+use anyhow::{bail, ensure, Context, Result};
+use half::f16;
+use itertools::Itertools;
+use ndarray::{Array, ArrayD, CowArray, Dimension, IxDyn};
 use std::{
-    borrow::Cow, ops::{Deref, Index}, path::{Path, PathBuf}
+    borrow::Cow, ops::Deref, path::{Path, PathBuf}
 };
 use std::{sync::Arc, vec};
-use zarrs::{array::{data_type::DataType, Element}, array_subset::ArraySubset, storage::StorePrefix};
+use zarrs::{array::{data_type::DataType, ArrayMetadata, Element}, array_subset::ArraySubset, storage::{StoreKey, StorePrefix}};
 use zarrs::filesystem::FilesystemStore;
-use zarrs::group::Group;
+use zarrs::group::{Group, GroupMetadata};
 use zarrs::{array::ElementOwned, storage::ReadableWritableListableStorageTraits};
 
 /// The Zarr backend.
 pub struct Zarr;
 
+/// The on-disk format of a Zarr store.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ZarrVersion {
+    /// Zarr v2, i.e., `.zgroup`, `.zarray` and `.zattrs` files. This is the format
+    /// used by the Python anndata package.
+    V2,
+    #[default]
+    V3,
+}
+
 #[derive(Clone)]
 pub struct ZarrStore {
     inner: Arc<dyn ReadableWritableListableStorageTraits>,
     path: PathBuf,
+    version: ZarrVersion,
+    /// Default compression of the arrays created in this store.
+    compression: Compression,
 }
 
 impl Deref for ZarrStore {
@@ -41,6 +56,29 @@ pub struct ZarrDataset {
     store: ZarrStore,
 }
 
+impl Zarr {
+    /// Create a new store using the given on-disk format.
+    pub fn new_with_version<P: AsRef<Path>>(path: P, version: ZarrVersion) -> Result<ZarrStore> {
+        if path.as_ref().try_exists()? {
+            let metadata = std::fs::metadata(&path)?;
+            if metadata.is_file() {
+                std::fs::remove_file(&path)?;
+            } else {
+                std::fs::remove_dir_all(&path)?;
+            }
+        }
+
+        let store = ZarrStore {
+            path: path.as_ref().to_path_buf(),
+            inner: Arc::new(FilesystemStore::new(path.as_ref())?),
+            version,
+            compression: Compression::default(),
+        };
+        new_group(&store, "/")?;
+        Ok(store)
+    }
+}
+
 impl Backend for Zarr {
     const NAME: &'static str = "zarr";
 
@@ -51,38 +89,26 @@ impl Backend for Zarr {
     /// datasets contain arrays.
     type Dataset = ZarrDataset;
 
+    /// Create a new Zarr v3 store. Use `Zarr::new_with_version` to create a v2 store.
     fn new<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
-        if path.as_ref().try_exists()? {
-            let metadata = std::fs::metadata(&path)?;
-            if metadata.is_file() {
-                std::fs::remove_file(&path)?;
-            } else {
-                std::fs::remove_dir_all(&path)?;
-            }
-        }
-
-        let inner = Arc::new(FilesystemStore::new(path.as_ref())?);
-        zarrs::group::GroupBuilder::new().build(inner.clone(), "/")?.store_metadata()?;
-        Ok(ZarrStore {
-            path: path.as_ref().to_path_buf(),
-            inner,
-        })
+        Self::new_with_version(path, ZarrVersion::default())
     }
 
-    /// Opens a file as read-only, file must exist.
+    /// Opens a file as read-only, file must exist. Both v2 and v3 stores are supported.
     fn open<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
+        let inner: Arc<dyn ReadableWritableListableStorageTraits> = Arc::new(FilesystemStore::new(&path)?);
+        let version = detect_version(&inner)?;
         Ok(ZarrStore {
             path: path.as_ref().to_path_buf(),
-            inner: Arc::new(FilesystemStore::new(path)?),
+            inner,
+            version,
+            compression: Compression::default(),
         })
     }
 
-    /// Opens a file as read/write, file must exist.
+    /// Opens a file as read/write, file must exist. Both v2 and v3 stores are supported.
     fn open_rw<P: AsRef<Path>>(path: P) -> Result<Self::Store> {
-        Ok(ZarrStore {
-            path: path.as_ref().to_path_buf(),
-            inner: Arc::new(FilesystemStore::new(path)?),
-        })
+        Self::open(path)
     }
 }
 
@@ -97,6 +123,10 @@ impl StoreOp<Zarr> for ZarrStore {
         drop(self);
         Ok(())
     }
+
+    fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
 }
 
 impl GroupOp<Zarr> for ZarrStore {
@@ -108,13 +138,7 @@ impl GroupOp<Zarr> for ZarrStore {
 
     /// Create a new group.
     fn new_group(&self, name: &str) -> Result<<Zarr as Backend>::Group> {
-        let path = canoincalize_path(name);
-        let group = zarrs::group::GroupBuilder::new().build(self.inner.clone(), &path)?;
-        group.store_metadata()?;
-        Ok(ZarrGroup {
-            group,
-            store: self.clone(),
-        })
+        new_group(self, &canoincalize_path(name))
     }
 
     /// Open an existing group.
@@ -133,49 +157,7 @@ impl GroupOp<Zarr> for ZarrStore {
         shape: &Shape,
         config: WriteConfig,
     ) -> Result<<Zarr as Backend>::Dataset> {
-        let path = canoincalize_path(name);
-        let shape = shape.as_ref();
-        let sizes: Vec<u64> = match config.block_size {
-            Some(s) => s.as_ref().into_iter().map(|x| (*x).max(1) as u64).collect(),
-            _ => {
-                if shape.len() == 1 {
-                    vec![shape[0].min(10000).max(1) as u64]
-                } else {
-                    shape.iter().map(|&x| x.min(100).max(1) as u64).collect()
-                }
-            }
-        };
-        let chunk_size = zarrs::array::chunk_grid::ChunkGrid::new(
-            zarrs::array::chunk_grid::regular::RegularChunkGrid::new(sizes.try_into().unwrap()),
-        );
-
-        let (datatype, fill) = match T::DTYPE {
-            ScalarType::U8 => (DataType::UInt8, 0u8.into()),
-            ScalarType::U16 => (DataType::UInt16, 0u16.into()),
-            ScalarType::U32 => (DataType::UInt32, 0u32.into()),
-            ScalarType::U64 => (DataType::UInt64, 0u64.into()),
-            ScalarType::I8 => (DataType::Int8, 0i8.into()),
-            ScalarType::I16 => (DataType::Int16, 0i16.into()),
-            ScalarType::I32 => (DataType::Int32, 0i32.into()),
-            ScalarType::I64 => (DataType::Int64, 0i64.into()),
-            ScalarType::F32 => (DataType::Float32, zarrs::array::ZARR_NAN_F32.into()),
-            ScalarType::F64 => (DataType::Float64, zarrs::array::ZARR_NAN_F64.into()),
-            ScalarType::Bool => (DataType::Bool, false.into()),
-            ScalarType::String => (DataType::String, "".into()),
-        };
-
-        let array = zarrs::array::ArrayBuilder::new(
-            shape.iter().map(|x| *x as u64).collect(),
-            datatype,
-            chunk_size,
-            fill,
-        )
-        .build(self.inner.clone(), &path)?;
-        array.store_metadata()?;
-        Ok(ZarrDataset {
-            dataset: array,
-            store: self.clone(),
-        })
+        new_array::<T>(self, &canoincalize_path(name), shape, config)
     }
 
     fn open_dataset(&self, name: &str) -> Result<<Zarr as Backend>::Dataset> {
@@ -192,13 +174,13 @@ impl GroupOp<Zarr> for ZarrStore {
         Ok(())
     }
 
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        rename(self, from, to)
+    }
+
     /// Check if a group or dataset exists.
     fn exists(&self, name: &str) -> Result<bool> {
-        let path = format!("/{}", name);
-        Ok(zarrs::node::node_exists(
-            &self.inner,
-            &path.as_str().try_into()?,
-        )?)
+        node_exists(&self.inner, name)
     }
 }
 
@@ -218,12 +200,7 @@ impl GroupOp<Zarr> for ZarrGroup {
     /// Create a new group.
     fn new_group(&self, name: &str) -> Result<<Zarr as Backend>::Group> {
         let path = self.group.path().as_path().join(name);
-        let group = zarrs::group::GroupBuilder::new().build(self.store.inner.clone(), path.to_str().unwrap())?;
-        group.store_metadata()?;
-        Ok(ZarrGroup {
-            group,
-            store: self.store.clone(),
-        })
+        new_group(&self.store, path.to_str().unwrap())
     }
 
     /// Open an existing group.
@@ -243,49 +220,8 @@ impl GroupOp<Zarr> for ZarrGroup {
         shape: &Shape,
         config: WriteConfig,
     ) -> Result<<Zarr as Backend>::Dataset> {
-        let shape = shape.as_ref();
-        let sizes: Vec<u64> = match config.block_size {
-            Some(s) => s.as_ref().into_iter().map(|x| (*x).max(1) as u64).collect(),
-            _ => {
-                if shape.len() == 1 {
-                    vec![shape[0].min(10000).max(1) as u64]
-                } else {
-                    shape.iter().map(|&x| x.min(100).max(1) as u64).collect()
-                }
-            }
-        };
-        let chunk_size = zarrs::array::chunk_grid::ChunkGrid::new(
-            zarrs::array::chunk_grid::regular::RegularChunkGrid::new(sizes.try_into().unwrap()),
-        );
-
-        let (datatype, fill) = match T::DTYPE {
-            ScalarType::U8 => (DataType::UInt8, 0u8.into()),
-            ScalarType::U16 => (DataType::UInt16, 0u16.into()),
-            ScalarType::U32 => (DataType::UInt32, 0u32.into()),
-            ScalarType::U64 => (DataType::UInt64, 0u64.into()),
-            ScalarType::I8 => (DataType::Int8, 0i8.into()),
-            ScalarType::I16 => (DataType::Int16, 0i16.into()),
-            ScalarType::I32 => (DataType::Int32, 0i32.into()),
-            ScalarType::I64 => (DataType::Int64, 0i64.into()),
-            ScalarType::F32 => (DataType::Float32, zarrs::array::ZARR_NAN_F32.into()),
-            ScalarType::F64 => (DataType::Float64, zarrs::array::ZARR_NAN_F64.into()),
-            ScalarType::Bool => (DataType::Bool, false.into()),
-            ScalarType::String => (DataType::String, "".into()),
-        };
-
         let path = self.group.path().as_path().join(name);
-        let array = zarrs::array::ArrayBuilder::new(
-            shape.iter().map(|x| *x as u64).collect(),
-            datatype,
-            chunk_size,
-            fill,
-        )
-        .build(self.store.inner.clone(), path.to_str().unwrap())?;
-        array.store_metadata()?;
-        Ok(ZarrDataset {
-            dataset: array,
-            store: self.store.clone(),
-        })
+        new_array::<T>(&self.store, path.to_str().unwrap(), shape, config)
     }
 
     fn open_dataset(&self, name: &str) -> Result<<Zarr as Backend>::Dataset> {
@@ -304,21 +240,153 @@ impl GroupOp<Zarr> for ZarrGroup {
         Ok(())
     }
 
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let base = self.group.path().as_path();
+        rename(&self.store, base.join(from).to_str().unwrap(), base.join(to).to_str().unwrap())
+    }
+
     /// Check if a group or dataset exists.
     fn exists(&self, name: &str) -> Result<bool> {
-        let path = self
-            .group
-            .path()
-            .as_path()
-            .join(name)
-            .as_os_str()
-            .to_str()
-            .unwrap()
-            .try_into()?;
-        Ok(zarrs::node::node_exists(&self.store.inner, &path)?)
+        let path = self.group.path().as_path().join(name);
+        node_exists(&self.store.inner, path.to_str().unwrap())
     }
 }
 
+/// Move a node by copying every key under its prefix, i.e., the metadata and
+/// the chunks of the node and its children, and then erasing the old prefix.
+fn rename(store: &ZarrStore, from: &str, to: &str) -> Result<()> {
+    ensure!(node_exists(&store.inner, from)?, "'{}' does not exist", from);
+    ensure!(!node_exists(&store.inner, to)?, "'{}' already exists", to);
+    let (from, to) = (str_to_prefix(from), str_to_prefix(to));
+    for key in store.inner.list_prefix(&from)? {
+        let suffix = key
+            .as_str()
+            .strip_prefix(from.as_str())
+            .with_context(|| format!("'{}' is not under '{}'", key, from))?;
+        let value = store
+            .inner
+            .get(&key)?
+            .with_context(|| format!("cannot read '{}'", key))?;
+        store
+            .inner
+            .set(&StoreKey::new(format!("{}{}", to.as_str(), suffix))?, value)?;
+    }
+    store.inner.erase_prefix(&from)?;
+    Ok(())
+}
+
+/// Create a new group, using the on-disk format of the store.
+fn new_group(store: &ZarrStore, path: &str) -> Result<ZarrGroup> {
+    let group = match store.version {
+        ZarrVersion::V3 => zarrs::group::GroupBuilder::new().build(store.inner.clone(), path)?,
+        ZarrVersion::V2 => {
+            let metadata: GroupMetadata = serde_json::from_value(serde_json::json!({
+                "zarr_format": 2,
+            }))?;
+            zarrs::group::Group::new_with_metadata(store.inner.clone(), path, metadata)?
+        }
+    };
+    group.store_metadata()?;
+    Ok(ZarrGroup {
+        group,
+        store: store.clone(),
+    })
+}
+
+/// Create an empty array, using the on-disk format of the store.
+fn new_array<T: BackendData>(
+    store: &ZarrStore,
+    path: &str,
+    shape: &Shape,
+    config: WriteConfig,
+) -> Result<ZarrDataset> {
+    let shape = shape.as_ref();
+    let sizes: Vec<u64> = match config.block_size {
+        Some(s) => s.as_ref().into_iter().map(|x| (*x).max(1) as u64).collect(),
+        _ => {
+            if shape.len() == 1 {
+                vec![shape[0].min(10000).max(1) as u64]
+            } else {
+                shape.iter().map(|&x| x.min(100).max(1) as u64).collect()
+            }
+        }
+    };
+    let compression = config.resolve_compression(store.compression, sizes.iter().product::<u64>() as usize);
+
+    let array = match store.version {
+        ZarrVersion::V3 => {
+            let chunk_size = zarrs::array::chunk_grid::ChunkGrid::new(
+                zarrs::array::chunk_grid::regular::RegularChunkGrid::new(sizes.try_into().unwrap()),
+            );
+
+            let (datatype, fill) = match T::DTYPE {
+                ScalarType::U8 => (DataType::UInt8, 0u8.into()),
+                ScalarType::U16 => (DataType::UInt16, 0u16.into()),
+                ScalarType::U32 => (DataType::UInt32, 0u32.into()),
+                ScalarType::U64 => (DataType::UInt64, 0u64.into()),
+                ScalarType::I8 => (DataType::Int8, 0i8.into()),
+                ScalarType::I16 => (DataType::Int16, 0i16.into()),
+                ScalarType::I32 => (DataType::Int32, 0i32.into()),
+                ScalarType::I64 => (DataType::Int64, 0i64.into()),
+                ScalarType::F16 => (DataType::Float16, zarrs::array::ZARR_NAN_F16.into()),
+                ScalarType::F32 => (DataType::Float32, zarrs::array::ZARR_NAN_F32.into()),
+                ScalarType::F64 => (DataType::Float64, zarrs::array::ZARR_NAN_F64.into()),
+                ScalarType::Bool => (DataType::Bool, false.into()),
+                ScalarType::String => (DataType::String, "".into()),
+            };
+
+            let codecs = to_codec(compression, type_size::<T>())?.into_iter().collect();
+            zarrs::array::ArrayBuilder::new(
+                shape.iter().map(|x| *x as u64).collect(),
+                datatype,
+                chunk_size,
+                fill,
+            )
+            .bytes_to_bytes_codecs(codecs)
+            .build(store.inner.clone(), path)?
+        }
+        ZarrVersion::V2 => {
+            // Strings are stored as numpy objects encoded by the vlen-utf8 filter, which
+            // is what numcodecs produces.
+            let (dtype, fill, filters) = match T::DTYPE {
+                ScalarType::U8 => ("|u1", serde_json::json!(0), None),
+                ScalarType::U16 => ("<u2", serde_json::json!(0), None),
+                ScalarType::U32 => ("<u4", serde_json::json!(0), None),
+                ScalarType::U64 => ("<u8", serde_json::json!(0), None),
+                ScalarType::I8 => ("|i1", serde_json::json!(0), None),
+                ScalarType::I16 => ("<i2", serde_json::json!(0), None),
+                ScalarType::I32 => ("<i4", serde_json::json!(0), None),
+                ScalarType::I64 => ("<i8", serde_json::json!(0), None),
+                ScalarType::F16 => ("<f2", serde_json::json!("NaN"), None),
+                ScalarType::F32 => ("<f4", serde_json::json!("NaN"), None),
+                ScalarType::F64 => ("<f8", serde_json::json!("NaN"), None),
+                ScalarType::Bool => ("|b1", serde_json::json!(false), None),
+                ScalarType::String => (
+                    "|O",
+                    serde_json::json!(""),
+                    Some(serde_json::json!([{"id": "vlen-utf8"}])),
+                ),
+            };
+            let metadata: ArrayMetadata = serde_json::from_value(serde_json::json!({
+                "zarr_format": 2,
+                "shape": shape,
+                "chunks": sizes,
+                "dtype": dtype,
+                "fill_value": fill,
+                "order": "C",
+                "compressor": to_v2_compressor(compression, type_size::<T>()),
+                "filters": filters,
+            }))?;
+            zarrs::array::Array::new_with_metadata(store.inner.clone(), path, metadata)?
+        }
+    };
+    array.store_metadata()?;
+    Ok(ZarrDataset {
+        dataset: array,
+        store: store.clone(),
+    })
+}
+
 impl AttributeOp<Zarr> for ZarrGroup {
     /// Returns the Root.
     fn store(&self) -> Result<<Zarr as Backend>::Store> {
@@ -386,6 +454,7 @@ impl DatasetOp<Zarr> for ZarrDataset {
             DataType::Int16 => Ok(ScalarType::I16),
             DataType::Int32 => Ok(ScalarType::I32),
             DataType::Int64 => Ok(ScalarType::I64),
+            DataType::Float16 => Ok(ScalarType::F16),
             DataType::Float32 => Ok(ScalarType::F32),
             DataType::Float64 => Ok(ScalarType::F64),
             DataType::Bool => Ok(ScalarType::Bool),
@@ -409,7 +478,7 @@ impl DatasetOp<Zarr> for ZarrDataset {
         Ok(())
     }
 
-    /// TODO: current implementation reads the entire array and then selects the slice.
+    /// Only the chunks touched by the selection are retrieved and decoded.
     fn read_array_slice<T: BackendData, S, D>(&self, selection: &[S]) -> Result<Array<T, D>>
     where
         S: AsRef<SelectInfoElem>,
@@ -417,25 +486,56 @@ impl DatasetOp<Zarr> for ZarrDataset {
     {
         fn read_arr<T, S, D>(dataset: &ZarrDataset, selection: &[S]) -> Result<Array<T, D>>
         where
-            T: ElementOwned + BackendData,
+            T: ElementOwned + BackendData + Default,
             S: AsRef<SelectInfoElem>,
             D: Dimension,
         {
-            let sel = SelectInfoBounds::new(&selection, &dataset.shape());
-            if let Some(subset) = to_array_subset(sel) {
+            let shape = dataset.shape();
+            let sel = SelectInfoBounds::new(&selection, &shape);
+            if let Some(subset) = to_array_subset(&sel) {
                 let arr = dataset
                     .dataset
                     .retrieve_array_subset_ndarray(&subset)?
                     .into_dimensionality::<D>()?;
-                Ok(arr)
-            } else {
-                // Read the entire array and then select the slice.
-                let arr = dataset
-                    .dataset
-                    .retrieve_array_subset_ndarray(&dataset.dataset.subset_all())?
-                    .into_dimensionality::<D>()?;
-                Ok(select(arr.view(), selection))
+                return Ok(arr);
             }
+
+            let out_shape = sel.out_shape();
+            if sel.out_size() == 0 {
+                return Ok(ArrayD::from_shape_vec(out_shape.as_ref(), Vec::new())?.into_dimensionality::<D>()?);
+            }
+
+            let chunk_shape: Vec<usize> = dataset
+                .dataset
+                .chunk_shape(&vec![0; shape.ndim()])?
+                .iter()
+                .map(|x| x.get() as usize)
+                .collect();
+            // Every output position falls in exactly one segment of each axis,
+            // so all elements are overwritten.
+            let mut result: ArrayD<T> = ArrayD::default(out_shape.as_ref());
+            let mut out_idx = vec![0; shape.ndim()];
+            let mut in_idx = vec![0; shape.ndim()];
+            sel.iter()
+                .zip(chunk_shape)
+                .map(|(s, c)| AxisSegment::split(s, c))
+                .multi_cartesian_product()
+                .try_for_each(|segments| {
+                    let ranges: Vec<_> = segments.iter().map(|x| x.range.clone()).collect();
+                    let block = dataset
+                        .dataset
+                        .retrieve_array_subset_ndarray::<T>(&ArraySubset::new_with_ranges(&ranges))?;
+                    let lens: Vec<_> = segments.iter().map(|x| x.positions.len()).collect();
+                    ndarray::indices(lens).into_iter().for_each(|idx| {
+                        segments.iter().enumerate().for_each(|(i, x)| {
+                            out_idx[i] = x.positions[idx[i]];
+                            in_idx[i] = x.offsets[idx[i]];
+                        });
+                        result[out_idx.as_slice()] = block[in_idx.as_slice()].clone();
+                    });
+                    anyhow::Ok(())
+                })?;
+            Ok(result.into_dimensionality::<D>()?)
         }
 
         let array: DynArray = match T::DTYPE {
@@ -447,6 +547,7 @@ impl DatasetOp<Zarr> for ZarrDataset {
             ScalarType::I16 => read_arr::<i16, _, D>(self, selection)?.into(),
             ScalarType::I32 => read_arr::<i32, _, D>(self, selection)?.into(),
             ScalarType::I64 => read_arr::<i64, _, D>(self, selection)?.into(),
+            ScalarType::F16 => read_arr::<f16, _, D>(self, selection)?.into(),
             ScalarType::F32 => read_arr::<f32, _, D>(self, selection)?.into(),
             ScalarType::F64 => read_arr::<f64, _, D>(self, selection)?.into(),
             ScalarType::Bool => read_arr::<bool, _, D>(self, selection)?.into(),
@@ -504,6 +605,7 @@ impl DatasetOp<Zarr> for ZarrDataset {
             DynCowArray::I16(x) => write_array_impl(self, x, selection),
             DynCowArray::I32(x) => write_array_impl(self, x, selection),
             DynCowArray::I64(x) => write_array_impl(self, x, selection),
+            DynCowArray::F16(x) => write_array_impl(self, x, selection),
             DynCowArray::F32(x) => write_array_impl(self, x, selection),
             DynCowArray::F64(x) => write_array_impl(self, x, selection),
             DynCowArray::Bool(x) => write_array_impl(self, x, selection),
@@ -512,42 +614,110 @@ impl DatasetOp<Zarr> for ZarrDataset {
     }
 }
 
-fn select<'a, S, T, D>(arr: ArrayView<'a, T, D>, info: &[S]) -> Array<T, D>
-where
-    S: AsRef<SelectInfoElem>,
-    T: Clone,
-    D: Dimension,
-{
-    let arr = arr.into_dyn();
-    let slices = info
-        .as_ref()
-        .into_iter()
-        .map(|x| match x.as_ref() {
-            SelectInfoElem::Slice(slice) => Some(SliceInfoElem::from(slice.clone())),
-            _ => None,
+/// Convert the compression setting into a Zarr v3 codec, or `None` if the
+/// data are not compressed.
+fn to_codec(
+    compression: Compression,
+    type_size: usize,
+) -> Result<Option<Arc<dyn zarrs::array::codec::BytesToBytesCodecTraits>>> {
+    use zarrs::array::codec::{BloscCodec, BloscCompressionLevel, BloscShuffleMode, GzipCodec, ZstdCodec};
+
+    let blosc = |compressor: BloscCompressor, level: u8, shuffle: BloscShuffle| -> Result<BloscCodec> {
+        let compressor = match compressor {
+            BloscCompressor::BloscLZ => zarrs::array::codec::BloscCompressor::BloscLZ,
+            BloscCompressor::LZ4 => zarrs::array::codec::BloscCompressor::LZ4,
+            BloscCompressor::LZ4HC => zarrs::array::codec::BloscCompressor::LZ4HC,
+            BloscCompressor::Snappy => zarrs::array::codec::BloscCompressor::Snappy,
+            BloscCompressor::Zlib => zarrs::array::codec::BloscCompressor::Zlib,
+            BloscCompressor::Zstd => zarrs::array::codec::BloscCompressor::Zstd,
+        };
+        let shuffle = match shuffle {
+            BloscShuffle::None => BloscShuffleMode::NoShuffle,
+            BloscShuffle::Byte => BloscShuffleMode::Shuffle,
+            BloscShuffle::Bit => BloscShuffleMode::BitShuffle,
+        };
+        let level = BloscCompressionLevel::try_from(level)
+            .map_err(|_| anyhow::anyhow!("invalid blosc compression level: {}", level))?;
+        Ok(BloscCodec::new(compressor, level, None, shuffle, Some(type_size))?)
+    };
+
+    let codec: Arc<dyn zarrs::array::codec::BytesToBytesCodecTraits> = match compression {
+        Compression::None => return Ok(None),
+        Compression::Gzip(level) => Arc::new(GzipCodec::new(level.into())?),
+        Compression::Zstd(level) => Arc::new(ZstdCodec::new(level.into(), false)),
+        Compression::BloscLz4 => Arc::new(blosc(BloscCompressor::LZ4, 5, BloscShuffle::None)?),
+        Compression::Blosc { compressor, level, shuffle } => Arc::new(blosc(compressor, level, shuffle)?),
+    };
+    Ok(Some(codec))
+}
+
+/// Convert the compression setting into a numcodecs compressor for Zarr v2,
+/// which is null if the data are not compressed.
+fn to_v2_compressor(compression: Compression, type_size: usize) -> serde_json::Value {
+    let blosc = |compressor: BloscCompressor, level: u8, shuffle: BloscShuffle| {
+        let cname = match compressor {
+            BloscCompressor::BloscLZ => "blosclz",
+            BloscCompressor::LZ4 => "lz4",
+            BloscCompressor::LZ4HC => "lz4hc",
+            BloscCompressor::Snappy => "snappy",
+            BloscCompressor::Zlib => "zlib",
+            BloscCompressor::Zstd => "zstd",
+        };
+        let shuffle = match shuffle {
+            BloscShuffle::None => 0,
+            BloscShuffle::Byte => 1,
+            BloscShuffle::Bit => 2,
+        };
+        serde_json::json!({
+            "id": "blosc",
+            "cname": cname,
+            "clevel": level,
+            "shuffle": shuffle,
+            "blocksize": 0,
+            "typesize": type_size,
         })
-        .collect::<Option<Vec<_>>>();
-    if let Some(slices) = slices {
-        arr.slice(slices.as_slice()).into_owned()
+    };
+
+    match compression {
+        Compression::None => serde_json::Value::Null,
+        Compression::Gzip(level) => serde_json::json!({"id": "gzip", "level": level}),
+        Compression::Zstd(level) => serde_json::json!({"id": "zstd", "level": level}),
+        Compression::BloscLz4 => blosc(BloscCompressor::LZ4, 5, BloscShuffle::None),
+        Compression::Blosc { compressor, level, shuffle } => blosc(compressor, level, shuffle),
+    }
+}
+
+/// Element size used by the shuffle filters. Strings are variable-length and are
+/// shuffled byte by byte.
+fn type_size<T: BackendData>() -> usize {
+    match T::DTYPE {
+        ScalarType::String => 1,
+        _ => std::mem::size_of::<T>(),
+    }
+}
+
+/// Returns the format of an existing store by looking at the metadata of its root group.
+fn detect_version(store: &Arc<dyn ReadableWritableListableStorageTraits>) -> Result<ZarrVersion> {
+    if store.get(&StoreKey::new("zarr.json")?)?.is_none()
+        && store.get(&StoreKey::new(".zgroup")?)?.is_some()
+    {
+        Ok(ZarrVersion::V2)
     } else {
-        let shape = arr.shape();
-        let select: Vec<_> = info
-            .as_ref()
-            .into_iter()
-            .zip(shape)
-            .map(|(x, n)| SelectInfoElemBounds::new(x.as_ref(), *n))
-            .collect();
-        let new_shape = select.iter().map(|x| x.len()).collect::<Vec<_>>();
-        ArrayD::from_shape_fn(new_shape, |idx| {
-            let new_idx: Vec<_> = (0..idx.ndim())
-                .into_iter()
-                .map(|i| select[i].index(idx[i]))
-                .collect();
-            arr.index(new_idx.as_slice()).clone()
-        })
+        Ok(ZarrVersion::V3)
+    }
+}
+
+/// Check if a group or array exists. Unlike `zarrs::node::node_exists`, this also
+/// recognizes Zarr v2 nodes.
+fn node_exists(store: &Arc<dyn ReadableWritableListableStorageTraits>, path: &str) -> Result<bool> {
+    let prefix = str_to_prefix(path);
+    for name in ["zarr.json", ".zarray", ".zgroup"] {
+        let key = StoreKey::new(format!("{}{}", prefix.as_str(), name))?;
+        if store.get(&key)?.is_some() {
+            return Ok(true);
+        }
     }
-    .into_dimensionality::<D>()
-    .unwrap()
+    Ok(false)
 }
 
 fn str_to_prefix(s: &str) -> StorePrefix {
@@ -567,7 +737,8 @@ fn canoincalize_path<'a>(path: &'a str) -> Cow<'a, str> {
     }
 }
 
-fn to_array_subset(info: SelectInfoBounds) -> Option<ArraySubset> {
+/// Convert a selection consisting of contiguous slices into a single array subset.
+fn to_array_subset(info: &SelectInfoBounds) -> Option<ArraySubset> {
     let ranges = info.iter().map(|x| {
         if let SelectInfoElemBounds::Slice(slice) = x {
             if slice.step == 1 {
@@ -583,13 +754,47 @@ fn to_array_subset(info: SelectInfoBounds) -> Option<ArraySubset> {
     Some(ArraySubset::new_with_ranges(&ranges))
 }
 
+/// A range along one axis covering a run of adjacent chunks that are touched by
+/// the selection. Chunks that are not touched by the selection are never included,
+/// so reading the cartesian product of the segments of all axes retrieves exactly
+/// the chunks needed.
+#[derive(Debug, Clone)]
+struct AxisSegment {
+    range: std::ops::Range<u64>,
+    /// Positions in the output.
+    positions: Vec<usize>,
+    /// Offsets relative to `range.start`, one for each position.
+    offsets: Vec<usize>,
+}
+
+impl AxisSegment {
+    fn split(select: &SelectInfoElemBounds, chunk_size: usize) -> Vec<Self> {
+        let mut indices: Vec<(usize, usize)> = select.iter().enumerate().map(|(pos, idx)| (idx, pos)).collect();
+        indices.sort_unstable();
+        let mut segments: Vec<Self> = Vec::new();
+        indices.into_iter().for_each(|(idx, pos)| match segments.last_mut() {
+            Some(seg) if idx / chunk_size <= (seg.range.end as usize - 1) / chunk_size + 1 => {
+                seg.range.end = seg.range.end.max(idx as u64 + 1);
+                seg.positions.push(pos);
+                seg.offsets.push(idx - seg.range.start as usize);
+            }
+            _ => segments.push(Self {
+                range: idx as u64..idx as u64 + 1,
+                positions: vec![pos],
+                offsets: vec![0],
+            }),
+        });
+        segments
+    }
+}
+
 
 
 /// test module
 #[cfg(test)]
 mod tests {
     use super::*;
-    use anndata::s;
+    use anndata::{s, Selectable};
     use ndarray::{array, concatenate, Array2, Axis, Ix2};
     use ndarray_rand::rand_distr::Uniform;
     use ndarray_rand::RandomExt;
@@ -641,6 +846,131 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_read_index() -> Result<()> {
+        with_tmp_path(|path| {
+            let store = Zarr::new(&path)?;
+            let config = WriteConfig {
+                block_size: Some(vec![3, 4].as_slice().into()),
+                ..Default::default()
+            };
+            let arr = Array::random((20, 30), Uniform::new(0, 100));
+            let dataset = store.new_array_dataset("test", arr.view().into(), config)?;
+
+            let rows: SelectInfoElem = vec![19, 0, 4, 4, 5, 11].into();
+            let cols: SelectInfoElem = vec![29, 1, 2, 15].into();
+            let selections = [
+                vec![rows.clone(), cols.clone()],
+                vec![rows.clone(), (2..17).into()],
+                vec![(3..9).into(), cols.clone()],
+                vec![ndarray::Slice::new(1, Some(20), 3).into(), cols],
+                vec![rows, Vec::<usize>::new().into()],
+            ];
+            for selection in selections {
+                assert_eq!(
+                    Selectable::select(&arr, selection.as_slice()),
+                    dataset.read_array_slice::<i32, _, Ix2>(selection.as_slice())?,
+                );
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_compression() -> Result<()> {
+        with_tmp_dir(|dir| {
+            let arr = Array::random((200, 50), Uniform::new(0, 100));
+            let strings = arr.mapv(|x| x.to_string());
+            let compressions = [
+                None,
+                Some(Compression::Gzip(9)),
+                Some(Compression::Zstd(3)),
+                Some(Compression::BloscLz4),
+                Some(Compression::Blosc { compressor: BloscCompressor::Zstd, level: 5, shuffle: BloscShuffle::Byte }),
+                Some(Compression::Blosc { compressor: BloscCompressor::LZ4, level: 9, shuffle: BloscShuffle::Bit }),
+            ];
+            for version in [ZarrVersion::V2, ZarrVersion::V3] {
+                let path = dir.join(format!("{:?}", version));
+                let store = Zarr::new_with_version(&path, version)?;
+                for (i, compression) in compressions.into_iter().enumerate() {
+                    let config = WriteConfig { compression, ..Default::default() };
+                    store.new_array_dataset(&format!("arr_{}", i), arr.view().into(), config.clone())?;
+                    store.new_array_dataset(&format!("str_{}", i), strings.view().into(), config)?;
+                }
+
+                let store = Zarr::open(&path)?;
+                for i in 0..compressions.len() {
+                    assert_eq!(arr, store.open_dataset(&format!("arr_{}", i))?.read_array::<i32, _>()?);
+                    assert_eq!(strings, store.open_dataset(&format!("str_{}", i))?.read_array::<String, _>()?);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_default_compression() -> Result<()> {
+        with_tmp_path(|path| {
+            let mut store = Zarr::new_with_version(&path, ZarrVersion::V2)?;
+            store.set_compression(Compression::Gzip(5));
+            let group = store.new_group("group")?;
+            let arr = Array::random((200, 50), Uniform::new(0, 100));
+            group.new_array_dataset("large", arr.view().into(), WriteConfig::default())?;
+            group.new_array_dataset("small", array![1, 2, 3].view().into(), WriteConfig::default())?;
+            let config = WriteConfig { compression: Some(Compression::BloscLz4), ..Default::default() };
+            group.new_array_dataset("explicit", arr.view().into(), config.clone())?;
+            group.new_array_dataset("small_explicit", array![1, 2, 3].view().into(), config)?;
+            let config = WriteConfig { compression: Some(Compression::None), ..Default::default() };
+            group.new_array_dataset("uncompressed", arr.view().into(), config)?;
+
+            let compressor = |name: &str| -> Result<Value> {
+                let metadata = std::fs::read_to_string(path.join("group").join(name).join(".zarray"))?;
+                Ok(serde_json::from_str::<Value>(&metadata)?["compressor"].clone())
+            };
+            assert_eq!(compressor("large")?["id"], "gzip");
+            assert_eq!(compressor("large")?["level"], 5);
+            assert!(compressor("small")?.is_null());
+            assert_eq!(compressor("explicit")?["cname"], "lz4");
+            assert_eq!(compressor("small_explicit")?["cname"], "lz4");
+            assert!(compressor("uncompressed")?.is_null());
+            assert_eq!(arr, group.open_dataset("large")?.read_array::<i32, _>()?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_v2() -> Result<()> {
+        with_tmp_path(|path| {
+            let store = Zarr::new_with_version(&path, ZarrVersion::V2)?;
+            let mut group = store.new_group("group")?;
+            group.new_attr("encoding-type", "dict")?;
+            let strings = array!["a".to_string(), "bc".to_string(), "".to_string()];
+            group.new_array_dataset("strings", strings.view().into(), WriteConfig::default())?;
+            let arr = Array::random((20, 50), Uniform::new(0, 100));
+            group.new_array_dataset("arr", arr.view().into(), WriteConfig::default())?;
+
+            assert!(path.join(".zgroup").exists());
+            assert!(path.join("group/.zattrs").exists());
+            assert!(path.join("group/arr/.zarray").exists());
+            assert!(!path.join("group/zarr.json").exists());
+
+            let store = Zarr::open(&path)?;
+            assert_eq!(store.version, ZarrVersion::V2);
+            assert!(store.exists("group")?);
+            assert!(store.exists("group/strings")?);
+            let group = store.open_group("group")?;
+            assert_eq!(group.get_attr::<String>("encoding-type")?, "dict");
+            assert_eq!(vec!["arr", "strings"], group.list()?);
+            assert_eq!(strings, group.open_dataset("strings")?.read_array::<String, _>()?);
+            assert_eq!(arr, group.open_dataset("arr")?.read_array::<i32, _>()?);
+
+            // New nodes inherit the format of the store.
+            group.new_group("subgroup")?;
+            assert!(path.join("group/subgroup/.zgroup").exists());
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_write_empty() -> Result<()> {
         with_tmp_path(|path| {
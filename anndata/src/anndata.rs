@@ -1,16 +1,22 @@
 mod dataset;
+mod raw;
+mod view;
 
-pub use dataset::{AnnDataSet, StackedAnnData};
+pub use dataset::{AnnDataSet, Join, StackedAnnData, StackedRaw};
+pub use raw::Raw;
+pub use view::{
+    AnnDataView, ArrayElemView, AxisArraysView, ChunkedArrayElemView, ElemCollectionView, RawView,
+};
 use smallvec::SmallVec;
 
 use crate::{
-    backend::{Backend, DataContainer, GroupOp, StoreOp},
+    backend::{Backend, DataContainer, DataType, GroupOp, StoreOp},
     container::{ArrayElem, Axis, AxisArrays, DataFrameElem, Dim, ElemCollection, Slot},
     data::*,
     traits::AnnDataOp,
 };
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use itertools::Itertools;
 use std::path::{Path, PathBuf};
 
@@ -40,6 +46,8 @@ pub struct AnnData<B: Backend> {
     pub(crate) uns: ElemCollection<B>,
     /// Layers of data.
     pub(crate) layers: AxisArrays<B>,
+    /// Raw data matrix and variable annotations.
+    pub(crate) raw: Raw<B>,
 }
 
 impl<B: Backend> std::fmt::Debug for AnnData<B> {
@@ -97,6 +105,9 @@ impl<B: Backend> std::fmt::Display for AnnData<B> {
                 write!(f, "\n    layers: '{}'", keys)?;
             }
         }
+        if !self.raw.is_none() {
+            write!(f, "\n    raw: n_vars = {}", self.raw.n_vars)?;
+        }
         Ok(())
     }
 }
@@ -142,6 +153,11 @@ impl<B: Backend> AnnData<B> {
         &self.var
     }
 
+    /// Get the raw slot.
+    pub fn get_raw(&self) -> &Raw<B> {
+        &self.raw
+    }
+
     /// Open an existing AnnData store.
     pub fn open(file: B::Store) -> Result<Self> {
         let n_obs = Dim::empty();
@@ -205,6 +221,12 @@ impl<B: Backend> AnnData<B> {
             _ => AxisArrays::empty(),
         };
 
+        let raw = if file.exists("raw")? {
+            Raw::open(file.open_group("raw")?, &n_obs)?
+        } else {
+            Raw::empty(&n_obs)
+        };
+
         Ok(Self {
             file,
             n_obs,
@@ -218,12 +240,18 @@ impl<B: Backend> AnnData<B> {
             varp,
             uns,
             layers,
+            raw,
         })
     }
 
     /// Create a new AnnData file.
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<Self> {
-        let file = B::new(filename)?;
+        Self::new_with_store(B::new(filename)?)
+    }
+
+    /// Create a new AnnData object in an empty store, e.g., a Zarr v2 store
+    /// created by `Zarr::new_with_version`.
+    pub fn new_with_store(file: B::Store) -> Result<Self> {
         let n_obs = Dim::empty();
         let n_vars = Dim::empty();
         Ok(Self {
@@ -236,6 +264,7 @@ impl<B: Backend> AnnData<B> {
             varp: new_varp(file.new_group("varp")?, &n_vars)?,
             uns: ElemCollection::new(file.new_group("uns")?)?,
             layers: new_layers(file.new_group("layers")?, &n_obs, &n_vars)?,
+            raw: Raw::empty(&n_obs),
             file,
             n_obs,
             n_vars,
@@ -244,7 +273,13 @@ impl<B: Backend> AnnData<B> {
 
     /// Write the AnnData object to a new file.
     pub fn write<O: Backend, P: AsRef<Path>>(&self, filename: P) -> Result<()> {
-        let file = O::new(filename)?;
+        self.write_to_store::<O>(O::new(filename)?)
+    }
+
+    /// Write the AnnData object to an empty store, which allows the on-disk
+    /// format of the store to be chosen, e.g., Zarr v2 instead of v3, and the
+    /// compression to be set with [`StoreOp::set_compression`](crate::backend::StoreOp::set_compression).
+    pub fn write_to_store<O: Backend>(&self, file: O::Store) -> Result<()> {
         let _obs_lock = self.n_obs.lock();
         let _vars_lock = self.n_vars.lock();
         self.get_x()
@@ -292,6 +327,7 @@ impl<B: Backend> AnnData<B> {
             .as_mut()
             .map(|x| x.export::<O, _>(&file, "layers"))
             .transpose()?;
+        self.raw.export::<O, _>(&file)?;
         file.close()?;
         Ok(())
     }
@@ -303,14 +339,28 @@ impl<B: Backend> AnnData<B> {
         S: AsRef<[SelectInfoElem]>,
         P: AsRef<Path>,
     {
-        selection.as_ref()[0]
+        self.check_selection(selection.as_ref())?;
+        self.write_select_to_store::<O, _>(selection, O::new(filename)?)
+    }
+
+    fn check_selection(&self, selection: &[SelectInfoElem]) -> Result<()> {
+        selection[0]
             .bound_check(self.n_obs())
             .map_err(|e| anyhow!("AnnData obs {}", e))?;
-        selection.as_ref()[1]
+        selection[1]
             .bound_check(self.n_vars())
             .map_err(|e| anyhow!("AnnData var {}", e))?;
+        Ok(())
+    }
+
+    /// Write a subset of the AnnData object to an empty store.
+    pub fn write_select_to_store<O, S>(&self, selection: S, file: O::Store) -> Result<()>
+    where
+        O: Backend,
+        S: AsRef<[SelectInfoElem]>,
+    {
+        self.check_selection(selection.as_ref())?;
         let slice: SmallVec<[_; 3]> = selection.as_ref().iter().collect();
-        let file = O::new(filename)?;
         let _obs_lock = self.n_obs.lock();
         let _vars_lock = self.n_vars.lock();
         self.get_x()
@@ -359,10 +409,47 @@ impl<B: Backend> AnnData<B> {
             .as_mut()
             .map(|x| x.export_select(slice.as_slice(), &file, "layers"))
             .transpose()?;
+        self.raw.export_select::<O, _>(slice[0], &file)?;
         file.close()?;
         Ok(())
     }
 
+    /// Convert X to the given compressed sparse format, e.g., from CSR to CSC
+    /// for fast access to columns. The conversion is done on disk and holds at
+    /// most `buffer_size` non-zero entries in memory. The converted matrix is
+    /// written to a temporary location in the file before it replaces X.
+    pub fn convert_x(&self, format: SparseFormat, buffer_size: usize) -> Result<()> {
+        let converted = {
+            let x = self.x.lock();
+            let x = x.as_ref().context("X is empty")?;
+            match (x.dtype(), format) {
+                (DataType::CsrMatrix(_), SparseFormat::Csr) | (DataType::CscMatrix(_), SparseFormat::Csc) => {
+                    return Ok(())
+                }
+                _ => {
+                    let converted = x.export_sparse(format, buffer_size, &self.file, "__X_converted");
+                    if converted.is_err() && self.file.exists("__X_converted")? {
+                        self.file.delete("__X_converted")?;
+                    }
+                    ArrayElem::<B>::try_from(converted?)?
+                }
+            }
+        };
+        // X is only replaced once the conversion has succeeded. The old matrix
+        // is moved aside first so that it can be restored if the swap fails.
+        converted.drop();
+        self.x.drop();
+        self.file.rename("X", "__X_old")?;
+        if let Err(e) = self.file.rename("__X_converted", "X") {
+            self.file.rename("__X_old", "X")?;
+            self.x.swap(&ArrayElem::try_from(DataContainer::open(&self.file, "X")?)?);
+            return Err(e);
+        }
+        self.file.delete("__X_old")?;
+        self.x.swap(&ArrayElem::try_from(DataContainer::open(&self.file, "X")?)?);
+        Ok(())
+    }
+
     /// Get the filename of the AnnData file.
     pub fn filename(&self) -> PathBuf {
         self.file.filename()
@@ -382,6 +469,7 @@ impl<B: Backend> AnnData<B> {
         self.obs.drop();
         self.var.drop();
         close!(obsm, obsp, varm, varp, uns);
+        self.raw.close();
         self.file.close()
     }
 
@@ -444,6 +532,8 @@ impl<B: Backend> AnnData<B> {
             .map(|layers| layers.subset(&[obs_ix, var_ix]))
             .transpose()?;
 
+        self.raw.subset(obs_ix)?;
+
         if !obs_lock.is_empty() {
             obs_lock.set(SelectInfoElemBounds::new(obs_ix, obs_lock.get()).len());
         }
@@ -1,22 +1,29 @@
 use crate::{
-    traits::{AnnDataOp, ElemCollectionOp},
-    anndata::AnnData,
-    backend::Backend,
+    traits::{AnnDataOp, AxisArraysOp, ElemCollectionOp, RawOp},
+    anndata::{AnnData, Raw},
+    backend::{Backend, DataType},
     container::{Slot, Dim, Axis, AxisArrays, StackedArrayElem, StackedAxisArrays, StackedDataFrame},
     data::*,
-    data::index::VecVecIndex,
+    data::index::{ColumnMapping, VecVecIndex},
 };
 
 use anyhow::{anyhow, bail, ensure, Context, Result};
-use indexmap::map::IndexMap;
+use indexmap::{map::IndexMap, set::IndexSet};
 use itertools::Itertools;
 use polars::{df, prelude::{DataFrame, NamedFrom, Series}};
-use rayon::iter::{IndexedParallelIterator, ParallelIterator};
-use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::Arc};
 
 pub struct AnnDataSet<B: Backend> {
     pub(crate) annotation: AnnData<B>,
     pub(crate) anndatas: Slot<StackedAnnData<B>>,
+    /// Views of the axis arrays stored in the annotation file.
+    pub(crate) obsm: StackedAxisArrays<B>,
+    pub(crate) obsp: StackedAxisArrays<B>,
+    pub(crate) varm: StackedAxisArrays<B>,
+    pub(crate) varp: StackedAxisArrays<B>,
+    /// Layers concatenated from the components, together with the layers
+    /// stored in the annotation file.
+    pub(crate) layers: StackedAxisArrays<B>,
 }
 
 impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
@@ -114,6 +121,10 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
                 write!(f, "\n    varp: '{}'", keys)?;
             }
         }
+        let layers = self.layers.keys();
+        if !layers.is_empty() {
+            write!(f, "\n    layers: '{}'", layers.join("', '"))?;
+        }
         Ok(())
     }
 }
@@ -127,19 +138,59 @@ impl<B: Backend> AnnDataSet<B> {
         &self.annotation
     }
 
+    fn from_parts(annotation: AnnData<B>, anndatas: StackedAnnData<B>) -> Result<Self> {
+        Ok(Self {
+            obsm: StackedAxisArrays::new(Axis::Row, vec![annotation.obsm().clone()])?,
+            obsp: StackedAxisArrays::new(Axis::Pairwise, vec![annotation.obsp().clone()])?,
+            varm: StackedAxisArrays::new(Axis::Row, vec![annotation.varm().clone()])?,
+            varp: StackedAxisArrays::new(Axis::Pairwise, vec![annotation.varp().clone()])?,
+            layers: anndatas.layers.clone().with_annotation(annotation.layers().clone()),
+            annotation,
+            anndatas: Slot::new(anndatas),
+        })
+    }
+
+    /// Create a new AnnDataSet from the given AnnData objects, which must have
+    /// the same variables. The observation keys are stored in the `add_key`
+    /// column of obs. Use [`AnnDataSet::new_with_join`] to combine AnnData
+    /// objects whose variables differ.
     pub fn new<'a, T, S, P>(data: T, filename: P, add_key: &str) -> Result<Self>
     where
         T: IntoIterator<Item = (S, AnnData<B>)>,
         S: ToString,
         P: AsRef<Path>,
     {
-        let anndatas = StackedAnnData::new(data)?;
+        Self::create(data, filename, add_key, None)
+    }
+
+    /// Create a new AnnDataSet from the given AnnData objects. Their variables
+    /// are combined according to `join`, and the observation keys are stored
+    /// in the `add_key` column of obs.
+    pub fn new_with_join<'a, T, S, P>(data: T, filename: P, add_key: &str, join: Join) -> Result<Self>
+    where
+        T: IntoIterator<Item = (S, AnnData<B>)>,
+        S: ToString,
+        P: AsRef<Path>,
+    {
+        Self::create(data, filename, add_key, Some(join))
+    }
+
+    /// Create a new AnnDataSet. Without `join`, the AnnData objects must have
+    /// the same variables.
+    fn create<'a, T, S, P>(data: T, filename: P, add_key: &str, join: Option<Join>) -> Result<Self>
+    where
+        T: IntoIterator<Item = (S, AnnData<B>)>,
+        S: ToString,
+        P: AsRef<Path>,
+    {
+        let anndatas = StackedAnnData::new(data, join)?;
         let n_obs = anndatas.n_obs;
         let n_vars = anndatas.n_vars;
 
         let mut annotation = AnnData::new(filename)?;
         annotation.n_obs = Dim::new(n_obs);
         annotation.n_vars = Dim::new(n_vars);
+        annotation.raw = Raw::empty(&annotation.n_obs);
         { // Set UNS. UNS includes children anndata locations and shared elements.
             let (keys, filenames): (Vec<_>, Vec<_>) = anndatas
                 .iter()
@@ -173,16 +224,45 @@ impl<B: Backend> AnnDataSet<B> {
             annotation.set_obs(df!(add_key => keys)?)?;
         }
         { // Set VAR.
-            let adata = anndatas.values().next().unwrap();
-            let var_names = adata.var_names();
+            let var_names = anndatas.var_names().clone();
             if !var_names.is_empty() {
                 annotation.set_var_names(var_names)?;
             }
         }
-        Ok(Self {
-            annotation,
-            anndatas: Slot::new(anndatas),
-        })
+        ensure!(
+            anndatas.raw_x.is_some() || anndatas.values().all(|x| x.get_raw().is_none()),
+            "either all or none of the AnnData objects must have a raw X"
+        );
+        if let Some(raw_x) = anndatas.raw_x.as_ref() { // Set RAW.
+            // The variable annotations of the raw slots are stored in the annotation file.
+            let raws: Vec<&Raw<B>> = anndatas.values().map(|x| x.get_raw()).collect();
+            ensure!(
+                raws.iter().map(|x| x.var_names()).all_equal(),
+                "raw var names mismatch"
+            );
+            annotation.raw.init(&annotation.file)?;
+            let raw = &annotation.raw;
+            if let Some(shape) = raw_x.shape.as_ref() {
+                raw.n_vars.try_set(shape[1])?;
+            }
+            let var = raws[0].read_var()?;
+            if var.height() > 0 {
+                raw.set_var(var)?;
+            }
+            let var_names = raws[0].var_names();
+            if !var_names.is_empty() {
+                raw.set_var_names(var_names)?;
+            }
+            let varm: Vec<_> = raws.iter().map(|x| &x.varm).collect();
+            add_shared_arrays(&raw.varm, &varm)?;
+        }
+        if anndatas.columns.is_none() { // Add shared varm and varp elements.
+            let varm: Vec<_> = anndatas.values().map(|x| x.varm()).collect();
+            add_shared_arrays(annotation.varm(), &varm)?;
+            let varp: Vec<_> = anndatas.values().map(|x| x.varp()).collect();
+            add_shared_arrays(annotation.varp(), &varp)?;
+        }
+        Self::from_parts(annotation, anndatas)
     }
 
     pub fn open<P: AsRef<Path>>(
@@ -214,10 +294,8 @@ impl<B: Backend> AnnDataSet<B> {
             })
             .collect::<Result<_>>()?;
 
-        Ok(Self {
-            annotation,
-            anndatas: Slot::new(StackedAnnData::new(anndatas.into_iter())?),
-        })
+        let var_names = annotation.var_names();
+        Self::from_parts(annotation, StackedAnnData::open(anndatas.into_iter(), var_names)?)
     }
 
     /// AnnDataSet will not move data across underlying AnnData objects. So the
@@ -275,8 +353,11 @@ impl<B: Backend> AnnDataSet<B> {
         self.annotation.write::<O, _>(&out)?;
         let adata = AnnData::open(O::open_rw(&out)?)?;
         if copy_x {
-            adata
-                .set_x_from_iter::<_, ArrayData>(self.anndatas.inner().x.chunked(500).map(|x| x.0))?;
+            let anndatas = self.anndatas.inner();
+            adata.try_set_x_from_iter::<_, ArrayData>(anndatas.x.chunked(500).map(|x| x.map(|x| x.0)))?;
+            if let (Some(raw_x), Some(raw)) = (anndatas.raw_x.as_ref(), adata.raw()?) {
+                raw.try_set_x_from_iter::<_, ArrayData>(raw_x.chunked(500).map(|x| x.map(|x| x.0)))?;
+            }
         }
         Ok(adata)
     }
@@ -290,8 +371,14 @@ impl<B: Backend> AnnDataSet<B> {
         self.annotation.write_select::<O, _, _>(&select, &out)?;
         let adata = AnnData::open(O::open_rw(&out)?)?;
         if copy_x {
-            let x: ArrayData = self.anndatas.inner().x.select(select.as_ref())?.unwrap();
+            let anndatas = self.anndatas.inner();
+            let x: ArrayData = anndatas.x.select(select.as_ref())?.unwrap();
             adata.set_x(x)?;
+            if let (Some(raw_x), Some(raw)) = (anndatas.raw_x.as_ref(), adata.raw()?) {
+                let select = [select.as_ref()[0].clone(), SelectInfoElem::full()];
+                let x: ArrayData = raw_x.select(&select)?.unwrap();
+                raw.set_x(x)?;
+            }
         }
         Ok(adata)
     }
@@ -299,8 +386,12 @@ impl<B: Backend> AnnDataSet<B> {
     /// Convert AnnDataSet to AnnData object
     pub fn into_adata(self, copy_x: bool) -> Result<AnnData<B>> {
         if copy_x {
+            let anndatas = self.anndatas.inner();
             self.annotation
-                .set_x_from_iter::<_, ArrayData>(self.anndatas.inner().x.chunked(500).map(|x| x.0))?;
+                .try_set_x_from_iter::<_, ArrayData>(anndatas.x.chunked(500).map(|x| x.map(|x| x.0)))?;
+            if let (Some(raw_x), Some(raw)) = (anndatas.raw_x.as_ref(), self.annotation.raw()?) {
+                raw.try_set_x_from_iter::<_, ArrayData>(raw_x.chunked(500).map(|x| x.map(|x| x.0)))?;
+            }
         }
         for ann in self.anndatas.extract().unwrap().elems.into_values() {
             ann.close()?;
@@ -317,6 +408,168 @@ impl<B: Backend> AnnDataSet<B> {
     }
 }
 
+/// The raw slot of an [`AnnDataSet`]. Its X stacks the raw X of the components
+/// by rows, and its variable annotations are stored in the annotation file.
+pub struct StackedRaw<'a, B: Backend> {
+    pub(crate) x: StackedArrayElem<B>,
+    pub(crate) raw: &'a Raw<B>,
+}
+
+impl<B: Backend> RawOp for StackedRaw<'_, B> {
+    type X = StackedArrayElem<B>;
+    type AxisArraysRef<'b> = &'b AxisArrays<B> where Self: 'b;
+
+    fn x(&self) -> Self::X {
+        self.x.clone()
+    }
+
+    fn set_x_from_iter<I: Iterator<Item = D>, D: ArrayChunk>(&self, _iter: I) -> Result<()> {
+        bail!("cannot set X in the raw slot of AnnDataSet")
+    }
+
+    fn set_x<D: Into<ArrayData>>(&self, _: D) -> Result<()> {
+        bail!("cannot set X in the raw slot of AnnDataSet")
+    }
+
+    fn del_x(&self) -> Result<()> {
+        bail!("cannot delete X in the raw slot of AnnDataSet")
+    }
+
+    fn n_obs(&self) -> usize {
+        self.raw.n_obs()
+    }
+    fn n_vars(&self) -> usize {
+        self.raw.n_vars()
+    }
+
+    fn var_names(&self) -> DataFrameIndex {
+        self.raw.var_names()
+    }
+    fn set_var_names(&self, index: DataFrameIndex) -> Result<()> {
+        self.raw.set_var_names(index)
+    }
+
+    fn read_var(&self) -> Result<DataFrame> {
+        self.raw.read_var()
+    }
+    fn set_var(&self, var: DataFrame) -> Result<()> {
+        self.raw.set_var(var)
+    }
+    fn del_var(&self) -> Result<()> {
+        self.raw.del_var()
+    }
+
+    fn varm(&self) -> Result<Self::AxisArraysRef<'_>> {
+        self.raw.varm()
+    }
+    fn del_varm(&self) -> Result<()> {
+        self.raw.del_varm()
+    }
+}
+
+/// How to combine the variables of the AnnData objects in an AnnDataSet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Join {
+    /// Keep the variables present in all AnnData objects.
+    #[default]
+    Inner,
+    /// Keep the union of the variables. Values of the variables missing from
+    /// an AnnData object are filled with zeros.
+    Outer,
+}
+
+/// Join the var names of the components. Returns `None` if none of the
+/// components has var names. Without `join`, the var names must be the same.
+fn join_var_names<B: Backend>(
+    adatas: &IndexMap<String, AnnData<B>>,
+    join: Option<Join>,
+) -> Result<Option<DataFrameIndex>> {
+    let indices: Vec<Option<DataFrameIndex>> = adatas
+        .values()
+        .map(|x| x.var.lock().as_ref().map(|x| x.index.clone()))
+        .collect();
+    if indices.iter().all_equal() {
+        return Ok(indices.into_iter().next().flatten());
+    }
+    let Some(join) = join else {
+        bail!("var names mismatch");
+    };
+    let indices: Vec<_> = indices
+        .into_iter()
+        .collect::<Option<_>>()
+        .context("var names mismatch: some AnnData objects do not have var names")?;
+    let index_name = indices[0].index_name.clone();
+    let names: Vec<String> = match join {
+        Join::Inner => indices[0]
+            .clone()
+            .into_vec()
+            .into_iter()
+            .filter(|n| indices[1..].iter().all(|x| x.get_index(n).is_some()))
+            .collect(),
+        Join::Outer => indices
+            .into_iter()
+            .flat_map(|x| x.into_vec())
+            .collect::<IndexSet<_>>()
+            .into_iter()
+            .collect(),
+    };
+    let mut index: DataFrameIndex = names.into_iter().collect();
+    index.index_name = index_name;
+    Ok(Some(index))
+}
+
+/// Number of rows compared and copied at a time by `add_shared_arrays`.
+const SHARED_ARRAY_CHUNK: usize = 10000;
+
+/// Copy the arrays that are identical in all components to `target`. The
+/// arrays are compared and copied chunk by chunk, so that they are never
+/// loaded as a whole.
+fn add_shared_arrays<B: Backend>(target: &AxisArrays<B>, arrays: &[&AxisArrays<B>]) -> Result<()> {
+    let shared_keys: HashSet<String> = arrays
+        .iter()
+        .map(|x| x.keys().into_iter().collect::<HashSet<_>>())
+        .reduce(|a, b| a.intersection(&b).cloned().collect())
+        .unwrap_or(HashSet::new());
+    for key in shared_keys {
+        let Some(elems) = arrays.iter().map(|x| x.get(&key)).collect::<Option<Vec<_>>>() else {
+            continue;
+        };
+        let same_type = elems
+            .iter()
+            .map(|x| {
+                let inner = x.inner();
+                (inner.dtype(), inner.shape().clone())
+            })
+            .all_equal();
+        if !same_type {
+            continue;
+        }
+
+        let mut chunks: Vec<_> = elems.iter().map(|x| x.chunked(SHARED_ARRAY_CHUNK)).collect();
+        let equal = loop {
+            let Some((first, _, _)) = chunks[0].next().transpose()? else {
+                break true;
+            };
+            let rest: Vec<_> = chunks[1..]
+                .iter_mut()
+                .map(|x| x.next().transpose())
+                .collect::<Result<_>>()?;
+            if !rest.into_iter().all(|x| x.map_or(false, |(x, _, _)| x == first)) {
+                break false;
+            }
+        };
+        if equal {
+            if let DataType::DataFrame = elems[0].inner().dtype() {
+                target.add(&key, elems[0].inner().data()?)?;
+            } else {
+                let chunks = elems[0].chunked(SHARED_ARRAY_CHUNK).map(|x| x.map(|x| x.0));
+                target.try_add_iter::<_, ArrayData>(&key, chunks)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Update the locations of AnnData files.
 fn update_anndata_locations_by_map<B: Backend, P: AsRef<Path>>(
     ann: &AnnData<B>,
@@ -395,9 +648,17 @@ pub struct StackedAnnData<B: Backend> {
     elems: IndexMap<String, AnnData<B>>,
     pub(crate) n_obs: usize,
     pub(crate) n_vars: usize,
+    /// Joined var names of the components.
+    var_names: DataFrameIndex,
+    /// Column mappings of the components, `None` if they have the same variables.
+    columns: Option<Arc<Vec<ColumnMapping>>>,
     pub(crate) x: StackedArrayElem<B>,
+    /// The raw X of the components stacked by rows, `None` if some components
+    /// do not have one.
+    pub(crate) raw_x: Option<StackedArrayElem<B>>,
     pub(crate) obs: StackedDataFrame<B>,
     pub(crate) obsm: StackedAxisArrays<B>,
+    pub(crate) layers: StackedAxisArrays<B>,
 }
 
 impl<B: Backend> std::fmt::Display for StackedAnnData<B> {
@@ -409,12 +670,28 @@ impl<B: Backend> std::fmt::Display for StackedAnnData<B> {
             self.obs.get_column_names().iter().join("', '")
         )?;
         write!(f, "\n    obsm: '{}'", self.obsm.keys().join("', '"))?;
+        write!(f, "\n    layers: '{}'", self.layers.keys().join("', '"))?;
         Ok(())
     }
 }
 
 impl<B: Backend> StackedAnnData<B> {
-    fn new<'a, T, S>(iter: T) -> Result<Self>
+    /// Stack the AnnData objects, joining their variables according to `join`.
+    /// Without `join`, the AnnData objects must have the same variables.
+    fn new<'a, T, S>(iter: T, join: Option<Join>) -> Result<Self>
+    where
+        T: IntoIterator<Item = (S, AnnData<B>)>,
+        S: ToString,
+    {
+        let adatas: IndexMap<String, AnnData<B>> =
+            iter.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        ensure!(!adatas.is_empty(), "no AnnData objects to stack");
+        let var_names = join_var_names(&adatas, join)?;
+        Self::stack(adatas, var_names)
+    }
+
+    /// Stack the AnnData objects, mapping their variables to `var_names`.
+    fn open<'a, T, S>(iter: T, var_names: DataFrameIndex) -> Result<Self>
     where
         T: IntoIterator<Item = (S, AnnData<B>)>,
         S: ToString,
@@ -422,20 +699,43 @@ impl<B: Backend> StackedAnnData<B> {
         let adatas: IndexMap<String, AnnData<B>> =
             iter.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
         ensure!(!adatas.is_empty(), "no AnnData objects to stack");
+        let var_names = if var_names.is_empty() { None } else { Some(var_names) };
+        Self::stack(adatas, var_names)
+    }
 
-        if let Some((_, first)) = adatas.first() {
-            let lock = first.var.lock();
-            let var_names = lock.as_ref().map(|x| &x.index);
-            if !adatas
-                .par_values()
-                .skip(1)
-                .all(|x| x.var.lock().as_ref().map(|x| &x.index).eq(&var_names))
-            {
-                bail!("var names mismatch");
+    fn stack(adatas: IndexMap<String, AnnData<B>>, var_names: Option<DataFrameIndex>) -> Result<Self> {
+        let columns = if let Some(names) = var_names.as_ref() {
+            let names = names.clone().into_vec();
+            let columns = adatas
+                .values()
+                .map(|x| {
+                    let lock = x.var.lock();
+                    let index = &lock.as_ref().context("var names mismatch")?.index;
+                    let columns = names.iter().map(|n| index.get_index(n)).collect();
+                    Ok(ColumnMapping::new(index.len(), columns))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if columns.iter().all(|x| x.is_identity()) {
+                None
+            } else {
+                Some(Arc::new(columns))
             }
-        }
+        } else {
+            None
+        };
 
-        let x = StackedArrayElem::new(adatas.values().map(|x| x.get_x().clone()).collect())?;
+        let x = StackedArrayElem::new_joined(
+            adatas.values().map(|x| x.get_x().clone()).collect(),
+            columns.clone(),
+        )?;
+
+        let raw_x = if adatas.values().all(|x| !x.get_raw().get_x().is_none()) {
+            Some(StackedArrayElem::new(
+                adatas.values().map(|x| x.get_raw().get_x().clone()).collect(),
+            )?)
+        } else {
+            None
+        };
 
         let obs = if adatas.values().any(|x| x.obs.is_none()) {
             StackedDataFrame::new(Vec::new())
@@ -444,18 +744,29 @@ impl<B: Backend> StackedAnnData<B> {
         }?;
 
         let obsm = {
-            let arrays: Vec<AxisArrays<_>> = adatas.values().map(|x| x.obsm.clone()).collect();
+            // Make sure every component has an obsm group so that the stacked arrays are writable.
+            let arrays: Vec<AxisArrays<_>> = adatas.values().map(|x| x.obsm().clone()).collect();
             StackedAxisArrays::new(Axis::Row, arrays)?
         };
+        let layers = {
+            let arrays: Vec<AxisArrays<_>> = adatas.values().map(|x| x.layers().clone()).collect();
+            StackedAxisArrays::new_joined(Axis::RowColumn, arrays, columns.clone())?
+        };
 
         Ok(Self {
             index: adatas.values().map(|x| x.n_obs()).collect(),
             n_obs: adatas.values().map(|x| x.n_obs()).sum(),
-            n_vars: adatas.values().next().unwrap().n_vars(),
+            n_vars: var_names
+                .as_ref()
+                .map_or(adatas.values().next().unwrap().n_vars(), |x| x.len()),
+            var_names: var_names.unwrap_or(DataFrameIndex::empty()),
+            columns,
             elems: adatas,
             x,
+            raw_x,
             obs,
             obsm,
+            layers,
         })
     }
 
@@ -467,6 +778,10 @@ impl<B: Backend> StackedAnnData<B> {
         self.n_vars
     }
 
+    pub fn var_names(&self) -> &DataFrameIndex {
+        &self.var_names
+    }
+
     pub fn get_x(&self) -> &StackedArrayElem<B> {
         &self.x
     }
@@ -479,6 +794,10 @@ impl<B: Backend> StackedAnnData<B> {
         &self.obsm
     }
 
+    pub fn get_layers(&self) -> &StackedAxisArrays<B> {
+        &self.layers
+    }
+
     pub fn len(&self) -> usize {
         self.elems.len()
     }
@@ -520,10 +839,14 @@ impl<B: Backend> StackedAnnData<B> {
             .map(|(i, (k, adata))| {
                 let name = k.to_owned() + suffix;
                 let file = dir.as_ref().join(&name);
+                let var_select = match self.columns.as_ref() {
+                    Some(columns) => columns[i].split_select(&slice[1]).0,
+                    None => slice[1].clone(),
+                };
                 let select = if let Some(s) = slices.get(&i) {
-                    [s.clone(), slice[1].clone()]
+                    [s.clone(), var_select]
                 } else {
-                    [Vec::new().into(), slice[1].clone()]
+                    [Vec::new().into(), var_select]
                 };
                 adata.write_select::<O, _, _>(select, file)?;
                 Ok((k.clone(), name))
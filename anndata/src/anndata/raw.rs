@@ -0,0 +1,167 @@
+use crate::{
+    backend::{AttributeOp, Backend, DataContainer, GroupOp},
+    container::{ArrayElem, AxisArrays, DataFrameElem, Dim, Slot},
+    data::*,
+};
+
+use anyhow::Result;
+
+use super::new_varm;
+
+/// The `raw` slot of an AnnData object. It holds a data matrix with its own
+/// variable annotations, typically the unnormalized counts, and shares the
+/// observations with the parent object.
+pub struct Raw<B: Backend> {
+    /// The `raw` group. None means that the raw slot is not present.
+    pub(crate) group: Slot<B::Group>,
+    /// Number of observations, shared with the parent object.
+    pub(crate) n_obs: Dim,
+    /// Number of variables.
+    pub(crate) n_vars: Dim,
+    /// Data matrix.
+    pub(crate) x: ArrayElem<B>,
+    /// Variables metadata.
+    pub(crate) var: DataFrameElem<B>,
+    /// Variable multi-dimensional annotation.
+    pub(crate) varm: AxisArrays<B>,
+}
+
+impl<B: Backend> Raw<B> {
+    pub(crate) fn empty(n_obs: &Dim) -> Self {
+        Self {
+            group: Slot::none(),
+            n_obs: n_obs.clone(),
+            n_vars: Dim::empty(),
+            x: Slot::none(),
+            var: Slot::none(),
+            varm: AxisArrays::empty(),
+        }
+    }
+
+    /// Create the `raw` group under the given location.
+    pub(crate) fn init<G: GroupOp<B>>(&self, location: &G) -> Result<()> {
+        let mut group = location.new_group("raw")?;
+        group.new_attr("encoding-type", "raw")?;
+        group.new_attr("encoding-version", "0.1.0")?;
+        let varm = new_varm(group.new_group("varm")?, &self.n_vars)?;
+        self.varm.swap(&varm);
+        self.group.insert(group);
+        Ok(())
+    }
+
+    /// Delete the `raw` group and all its elements.
+    pub(crate) fn clear(&self) -> Result<()> {
+        self.x.clear()?;
+        self.var.clear()?;
+        self.varm.clear()?;
+        if let Some(group) = self.group.extract() {
+            DataContainer::<B>::delete(DataContainer::Group(group))?;
+        }
+        self.n_vars.lock().reset();
+        Ok(())
+    }
+
+    /// Open the `raw` group.
+    pub(crate) fn open(group: B::Group, n_obs: &Dim) -> Result<Self> {
+        let n_vars = Dim::empty();
+
+        let x = if group.exists("X")? {
+            let x = ArrayElem::try_from(DataContainer::open(&group, "X")?)?;
+            n_obs.try_set(x.inner().shape()[0])?;
+            n_vars.try_set(x.inner().shape()[1])?;
+            x
+        } else {
+            Slot::none()
+        };
+
+        let var = if group.exists("var")? {
+            let var = DataFrameElem::try_from(DataContainer::open(&group, "var")?)?;
+            n_vars.try_set(var.inner().height())?;
+            var
+        } else {
+            Slot::none()
+        };
+
+        let varm = match group.open_group("varm").or(group.new_group("varm")) {
+            Ok(g) => new_varm(g, &n_vars)?,
+            _ => AxisArrays::empty(),
+        };
+
+        Ok(Self {
+            group: Slot::new(group),
+            n_obs: n_obs.clone(),
+            n_vars,
+            x,
+            var,
+            varm,
+        })
+    }
+
+    /// Whether the raw slot is present.
+    pub fn is_none(&self) -> bool {
+        self.group.is_none()
+    }
+
+    pub fn get_x(&self) -> &ArrayElem<B> {
+        &self.x
+    }
+
+    pub fn get_var(&self) -> &DataFrameElem<B> {
+        &self.var
+    }
+
+    pub(crate) fn export<O: Backend, G: GroupOp<O>>(&self, location: &G) -> Result<()> {
+        self.export_select::<O, _>(&SelectInfoElem::full(), location)
+    }
+
+    /// Write the raw slot to a new location, keeping only the selected observations.
+    pub(crate) fn export_select<O, G>(&self, obs_ix: &SelectInfoElem, location: &G) -> Result<()>
+    where
+        O: Backend,
+        G: GroupOp<O>,
+    {
+        if self.is_none() {
+            return Ok(());
+        }
+        let mut group = location.new_group("raw")?;
+        group.new_attr("encoding-type", "raw")?;
+        group.new_attr("encoding-version", "0.1.0")?;
+
+        let full = SelectInfoElem::full();
+        let slice = [obs_ix, &full];
+        self.x
+            .lock()
+            .as_mut()
+            .map(|x| x.export_select::<O, _>(&slice, &group, "X"))
+            .transpose()?;
+        self.var
+            .lock()
+            .as_ref()
+            .map(|x| x.export::<O, _>(&group, "var"))
+            .transpose()?;
+        self.varm
+            .lock()
+            .as_ref()
+            .map(|x| x.export::<O, _>(&group, "varm"))
+            .transpose()?;
+        Ok(())
+    }
+
+    /// Subset the observations of the raw slot in place.
+    pub(crate) fn subset(&self, obs_ix: &SelectInfoElem) -> Result<()> {
+        self.x
+            .lock()
+            .as_mut()
+            .map(|x| x.subset_axis(0, obs_ix))
+            .transpose()?;
+        Ok(())
+    }
+
+    pub(crate) fn close(&self) {
+        self.x.drop();
+        self.var.drop();
+        self.varm.lock().as_ref().map(|x| x.values().for_each(|x| x.drop()));
+        self.varm.drop();
+        self.group.drop();
+    }
+}
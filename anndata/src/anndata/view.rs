@@ -0,0 +1,543 @@
+use crate::{
+    container::{ArrayElem, AxisArrays, ElemCollection},
+    data::*,
+    traits::{AnnDataOp, ArrayElemOp, AxisArraysOp, ElemCollectionOp, RawOp},
+    AnnData, Backend, Raw,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use polars::prelude::DataFrame;
+use smallvec::SmallVec;
+use std::path::Path;
+
+/// A lazy view of a subset of observations and variables of an AnnData object.
+/// Creating a view does not read or copy any data. Selections are applied when
+/// the elements are read, and views of views compose their selections.
+pub struct AnnDataView<'a, B: Backend> {
+    adata: &'a AnnData<B>,
+    obs_ix: SelectInfoElem,
+    var_ix: SelectInfoElem,
+}
+
+impl<B: Backend> std::fmt::Debug for AnnDataView<'_, B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl<B: Backend> std::fmt::Display for AnnDataView<'_, B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "View of AnnData object with n_obs x n_vars = {} x {} backed at '{}'",
+            self.n_obs(),
+            self.n_vars(),
+            self.adata.filename().to_str().unwrap().to_string(),
+        )
+    }
+}
+
+impl<B: Backend> AnnData<B> {
+    /// Create a view of the AnnData object. The selection must contain two
+    /// elements, for observations and variables respectively.
+    pub fn view<S>(&self, selection: S) -> Result<AnnDataView<'_, B>>
+    where
+        S: AsRef<[SelectInfoElem]>,
+    {
+        let view = AnnDataView {
+            adata: self,
+            obs_ix: SelectInfoElem::full(),
+            var_ix: SelectInfoElem::full(),
+        };
+        view.view(selection)
+    }
+}
+
+impl<'a, B: Backend> AnnDataView<'a, B> {
+    /// Create a view of this view. The selection is relative to the current view.
+    pub fn view<S>(&self, selection: S) -> Result<AnnDataView<'a, B>>
+    where
+        S: AsRef<[SelectInfoElem]>,
+    {
+        let slice = selection.as_ref();
+        ensure!(
+            slice.len() == 2,
+            format!("view only supports 2D selections, got {}", slice.len())
+        );
+        slice[0]
+            .bound_check(self.n_obs())
+            .map_err(|e| anyhow!("AnnDataView obs {}", e))?;
+        slice[1]
+            .bound_check(self.n_vars())
+            .map_err(|e| anyhow!("AnnDataView var {}", e))?;
+        Ok(AnnDataView {
+            adata: self.adata,
+            obs_ix: self.obs_ix.compose(&slice[0], self.adata.n_obs()),
+            var_ix: self.var_ix.compose(&slice[1], self.adata.n_vars()),
+        })
+    }
+
+    /// Returns the underlying AnnData object.
+    pub fn parent(&self) -> &'a AnnData<B> {
+        self.adata
+    }
+
+    /// Returns the selection of this view with respect to the underlying AnnData object.
+    pub fn selection(&self) -> [&SelectInfoElem; 2] {
+        [&self.obs_ix, &self.var_ix]
+    }
+
+    /// Materialize the view in a new file.
+    pub fn write<O: Backend, P: AsRef<Path>>(&self, filename: P) -> Result<()> {
+        self.adata
+            .write_select::<O, _, _>([self.obs_ix.clone(), self.var_ix.clone()], filename)
+    }
+
+    /// Materialize the view in an empty store.
+    pub fn write_to_store<O: Backend>(&self, file: O::Store) -> Result<()> {
+        self.adata
+            .write_select_to_store::<O, _>([self.obs_ix.clone(), self.var_ix.clone()], file)
+    }
+
+    fn axis_arrays<'b>(
+        &'b self,
+        arrays: &'b AxisArrays<B>,
+        selection: &[&SelectInfoElem],
+    ) -> AxisArraysView<'b, B> {
+        AxisArraysView {
+            arrays,
+            selection: selection.iter().map(|x| (*x).clone()).collect(),
+        }
+    }
+}
+
+impl<'a, B: Backend> AnnDataOp for AnnDataView<'a, B> {
+    type X = ArrayElemView<B>;
+    type AxisArraysRef<'b> = AxisArraysView<'b, B> where Self: 'b;
+    type ElemCollectionRef<'b> = ElemCollectionView<'b, B> where Self: 'b;
+    type RawRef<'b> = RawView<'b, B> where Self: 'b;
+
+    fn x(&self) -> Self::X {
+        ArrayElemView::new(
+            self.adata.x.clone(),
+            [self.obs_ix.clone(), self.var_ix.clone()],
+        )
+    }
+
+    fn set_x_from_iter<I: Iterator<Item = D>, D: ArrayChunk>(&self, _iter: I) -> Result<()> {
+        bail!("cannot set X in AnnDataView")
+    }
+
+    fn set_x<D: Into<ArrayData>>(&self, _: D) -> Result<()> {
+        bail!("cannot set X in AnnDataView")
+    }
+
+    fn del_x(&self) -> Result<()> {
+        bail!("cannot delete X in AnnDataView")
+    }
+
+    fn n_obs(&self) -> usize {
+        SelectInfoElemBounds::new(&self.obs_ix, self.adata.n_obs()).len()
+    }
+    fn n_vars(&self) -> usize {
+        SelectInfoElemBounds::new(&self.var_ix, self.adata.n_vars()).len()
+    }
+    fn set_n_obs(&self, _: usize) -> Result<()> {
+        bail!("cannot set n_obs in AnnDataView")
+    }
+    fn set_n_vars(&self, _: usize) -> Result<()> {
+        bail!("cannot set n_vars in AnnDataView")
+    }
+
+    fn obs_names(&self) -> DataFrameIndex {
+        if self.adata.obs.is_none() {
+            DataFrameIndex::empty()
+        } else {
+            self.adata.obs_names().select(&self.obs_ix)
+        }
+    }
+    fn var_names(&self) -> DataFrameIndex {
+        if self.adata.var.is_none() {
+            DataFrameIndex::empty()
+        } else {
+            self.adata.var_names().select(&self.var_ix)
+        }
+    }
+    fn set_obs_names(&self, _: DataFrameIndex) -> Result<()> {
+        bail!("cannot set obs_names in AnnDataView")
+    }
+    fn set_var_names(&self, _: DataFrameIndex) -> Result<()> {
+        bail!("cannot set var_names in AnnDataView")
+    }
+
+    fn obs_ix<'b, I: IntoIterator<Item = &'b str>>(&self, names: I) -> Result<Vec<usize>> {
+        let index = self.obs_names();
+        names
+            .into_iter()
+            .map(|i| {
+                index
+                    .get_index(i)
+                    .context(format!("'{}' does not exist in obs_names", i))
+            })
+            .collect()
+    }
+    fn var_ix<'b, I: IntoIterator<Item = &'b str>>(&self, names: I) -> Result<Vec<usize>> {
+        let index = self.var_names();
+        names
+            .into_iter()
+            .map(|i| {
+                index
+                    .get_index(i)
+                    .context(format!("'{}' does not exist in var_names", i))
+            })
+            .collect()
+    }
+
+    fn read_obs(&self) -> Result<DataFrame> {
+        self.adata
+            .obs
+            .lock()
+            .as_mut()
+            .map_or(Ok(DataFrame::empty()), |x| x.select_axis(0, &self.obs_ix))
+    }
+    fn read_var(&self) -> Result<DataFrame> {
+        self.adata
+            .var
+            .lock()
+            .as_mut()
+            .map_or(Ok(DataFrame::empty()), |x| x.select_axis(0, &self.var_ix))
+    }
+    fn set_obs(&self, _: DataFrame) -> Result<()> {
+        bail!("cannot set obs in AnnDataView")
+    }
+    fn set_var(&self, _: DataFrame) -> Result<()> {
+        bail!("cannot set var in AnnDataView")
+    }
+    fn del_obs(&self) -> Result<()> {
+        bail!("cannot delete obs in AnnDataView")
+    }
+    fn del_var(&self) -> Result<()> {
+        bail!("cannot delete var in AnnDataView")
+    }
+
+    fn uns(&self) -> Self::ElemCollectionRef<'_> {
+        ElemCollectionView { uns: &self.adata.uns }
+    }
+    fn obsm(&self) -> Self::AxisArraysRef<'_> {
+        self.axis_arrays(&self.adata.obsm, &[&self.obs_ix])
+    }
+    fn obsp(&self) -> Self::AxisArraysRef<'_> {
+        self.axis_arrays(&self.adata.obsp, &[&self.obs_ix, &self.obs_ix])
+    }
+    fn varm(&self) -> Self::AxisArraysRef<'_> {
+        self.axis_arrays(&self.adata.varm, &[&self.var_ix])
+    }
+    fn varp(&self) -> Self::AxisArraysRef<'_> {
+        self.axis_arrays(&self.adata.varp, &[&self.var_ix, &self.var_ix])
+    }
+    fn layers(&self) -> Self::AxisArraysRef<'_> {
+        self.axis_arrays(&self.adata.layers, &[&self.obs_ix, &self.var_ix])
+    }
+    fn raw(&self) -> Result<Option<Self::RawRef<'_>>> {
+        if self.adata.raw.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(RawView {
+            raw: &self.adata.raw,
+            obs_ix: self.obs_ix.clone(),
+        }))
+    }
+    fn set_raw_x<D: Into<ArrayData>>(&self, _data: D) -> Result<Self::RawRef<'_>> {
+        bail!("cannot set raw in AnnDataView")
+    }
+
+    fn del_uns(&self) -> Result<()> {
+        bail!("cannot delete uns in AnnDataView")
+    }
+    fn del_obsm(&self) -> Result<()> {
+        bail!("cannot delete obsm in AnnDataView")
+    }
+    fn del_obsp(&self) -> Result<()> {
+        bail!("cannot delete obsp in AnnDataView")
+    }
+    fn del_varm(&self) -> Result<()> {
+        bail!("cannot delete varm in AnnDataView")
+    }
+    fn del_varp(&self) -> Result<()> {
+        bail!("cannot delete varp in AnnDataView")
+    }
+    fn del_layers(&self) -> Result<()> {
+        bail!("cannot delete layers in AnnDataView")
+    }
+    fn del_raw(&self) -> Result<()> {
+        bail!("cannot delete raw in AnnDataView")
+    }
+}
+
+/// A view of the raw slot, restricted to the observations of an [`AnnDataView`].
+pub struct RawView<'a, B: Backend> {
+    raw: &'a Raw<B>,
+    obs_ix: SelectInfoElem,
+}
+
+impl<B: Backend> RawOp for RawView<'_, B> {
+    type X = ArrayElemView<B>;
+    type AxisArraysRef<'b> = &'b AxisArrays<B> where Self: 'b;
+
+    fn x(&self) -> Self::X {
+        ArrayElemView::new(self.raw.x.clone(), [self.obs_ix.clone()])
+    }
+
+    fn set_x_from_iter<I: Iterator<Item = D>, D: ArrayChunk>(&self, _iter: I) -> Result<()> {
+        bail!("cannot set X in a view of raw")
+    }
+
+    fn set_x<D: Into<ArrayData>>(&self, _: D) -> Result<()> {
+        bail!("cannot set X in a view of raw")
+    }
+
+    fn del_x(&self) -> Result<()> {
+        bail!("cannot delete X in a view of raw")
+    }
+
+    fn n_obs(&self) -> usize {
+        SelectInfoElemBounds::new(&self.obs_ix, self.raw.n_obs.get()).len()
+    }
+    fn n_vars(&self) -> usize {
+        self.raw.n_vars.get()
+    }
+
+    fn var_names(&self) -> DataFrameIndex {
+        self.raw.var_names()
+    }
+    fn set_var_names(&self, _: DataFrameIndex) -> Result<()> {
+        bail!("cannot set var_names in a view of raw")
+    }
+
+    fn read_var(&self) -> Result<DataFrame> {
+        self.raw.read_var()
+    }
+    fn set_var(&self, _: DataFrame) -> Result<()> {
+        bail!("cannot set var in a view of raw")
+    }
+    fn del_var(&self) -> Result<()> {
+        bail!("cannot delete var in a view of raw")
+    }
+
+    fn varm(&self) -> Result<Self::AxisArraysRef<'_>> {
+        Ok(&self.raw.varm)
+    }
+    fn del_varm(&self) -> Result<()> {
+        bail!("cannot delete varm in a view of raw")
+    }
+}
+
+/// A read-only view of the unstructured annotations of an [`AnnDataView`].
+/// The items are not subset, as they are not aligned to any axis.
+pub struct ElemCollectionView<'a, B: Backend> {
+    uns: &'a ElemCollection<B>,
+}
+
+impl<B: Backend> ElemCollectionOp for ElemCollectionView<'_, B> {
+    fn keys(&self) -> Vec<String> {
+        ElemCollectionOp::keys(&self.uns)
+    }
+
+    fn get_item<D>(&self, key: &str) -> Result<Option<D>>
+    where
+        D: TryFrom<Data>,
+        <D as TryFrom<Data>>::Error: Into<anyhow::Error>,
+    {
+        ElemCollectionOp::get_item(&self.uns, key)
+    }
+
+    fn add<D: Into<Data>>(&self, _key: &str, _data: D) -> Result<()> {
+        bail!("cannot add items to uns in AnnDataView")
+    }
+
+    fn remove(&self, _key: &str) -> Result<()> {
+        bail!("cannot remove items from uns in AnnDataView")
+    }
+}
+
+/// A view of the arrays aligned to one or two axes of an [`AnnDataView`].
+pub struct AxisArraysView<'a, B: Backend> {
+    arrays: &'a AxisArrays<B>,
+    selection: SmallVec<[SelectInfoElem; 3]>,
+}
+
+impl<B: Backend> AxisArraysOp for AxisArraysView<'_, B> {
+    type ArrayElem = ArrayElemView<B>;
+
+    fn keys(&self) -> Vec<String> {
+        self.arrays
+            .lock()
+            .as_ref()
+            .map_or(Vec::new(), |x| x.keys().cloned().collect())
+    }
+
+    fn get(&self, key: &str) -> Option<Self::ArrayElem> {
+        self.arrays
+            .lock()
+            .as_ref()
+            .and_then(|x| x.get(key).cloned())
+            .map(|elem| ArrayElemView::new(elem, self.selection.clone()))
+    }
+
+    fn add<D: Into<ArrayData>>(&self, _key: &str, _data: D) -> Result<()> {
+        bail!("cannot add arrays to AnnDataView")
+    }
+
+    fn add_iter<I, D>(&self, _key: &str, _data: I) -> Result<()>
+    where
+        I: Iterator<Item = D>,
+        D: ArrayChunk,
+    {
+        bail!("cannot add arrays to AnnDataView")
+    }
+
+    fn remove(&self, _key: &str) -> Result<()> {
+        bail!("cannot remove arrays from AnnDataView")
+    }
+}
+
+/// A view of an array element. The selection covers the leading axes of the
+/// array; the remaining axes are kept in full.
+pub struct ArrayElemView<B: Backend> {
+    elem: ArrayElem<B>,
+    selection: SmallVec<[SelectInfoElem; 3]>,
+}
+
+impl<B: Backend> Clone for ArrayElemView<B> {
+    fn clone(&self) -> Self {
+        Self {
+            elem: self.elem.clone(),
+            selection: self.selection.clone(),
+        }
+    }
+}
+
+impl<B: Backend> ArrayElemView<B> {
+    pub(crate) fn new<S>(elem: ArrayElem<B>, selection: S) -> Self
+    where
+        S: IntoIterator<Item = SelectInfoElem>,
+    {
+        Self {
+            elem,
+            selection: selection.into_iter().collect(),
+        }
+    }
+
+    /// Read the data, optionally applying a selection relative to the view.
+    fn read<S>(&self, slice: Option<&[S]>) -> Result<Option<ArrayData>>
+    where
+        S: AsRef<SelectInfoElem>,
+    {
+        let mut lock = self.elem.lock();
+        if let Some(elem) = lock.as_mut() {
+            let shape = elem.shape().clone();
+            let full = SelectInfoElem::full();
+            let selection: SmallVec<[SelectInfoElem; 3]> = (0..shape.ndim())
+                .map(|i| {
+                    let base = self.selection.get(i).unwrap_or(&full);
+                    match slice {
+                        Some(s) => base.compose(s.get(i).map_or(&full, |x| x.as_ref()), shape[i]),
+                        None => base.clone(),
+                    }
+                })
+                .collect();
+            elem.select(selection.as_slice()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<B: Backend> ArrayElemOp for ArrayElemView<B> {
+    type ArrayIter = ChunkedArrayElemView<B>;
+
+    fn shape(&self) -> Option<Shape> {
+        self.elem.lock().as_ref().map(|x| {
+            x.shape()
+                .as_ref()
+                .iter()
+                .enumerate()
+                .map(|(i, &n)| {
+                    self.selection
+                        .get(i)
+                        .map_or(n, |s| SelectInfoElemBounds::new(s, n).len())
+                })
+                .collect()
+        })
+    }
+
+    fn get<D>(&self) -> Result<Option<D>>
+    where
+        D: TryFrom<ArrayData>,
+        <D as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
+    {
+        self.read::<SelectInfoElem>(None)?
+            .map(|x| x.try_into().map_err(Into::into))
+            .transpose()
+    }
+
+    fn slice<D, S>(&self, slice: S) -> Result<Option<D>>
+    where
+        D: TryFrom<ArrayData>,
+        S: AsRef<[SelectInfoElem]>,
+        <D as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
+    {
+        self.read(Some(slice.as_ref()))?
+            .map(|x| x.try_into().map_err(Into::into))
+            .transpose()
+    }
+
+    fn iter(&self, chunk_size: usize) -> Self::ArrayIter {
+        ChunkedArrayElemView::new(self.clone(), chunk_size)
+    }
+}
+
+/// Iterate over the rows of an [`ArrayElemView`] in chunks.
+pub struct ChunkedArrayElemView<B: Backend> {
+    elem: ArrayElemView<B>,
+    chunk_size: usize,
+    num_items: usize,
+    current_position: usize,
+}
+
+impl<B: Backend> ChunkedArrayElemView<B> {
+    fn new(elem: ArrayElemView<B>, chunk_size: usize) -> Self {
+        let num_items = elem.shape().map_or(0, |x| x[0]);
+        Self {
+            elem,
+            chunk_size,
+            num_items,
+            current_position: 0,
+        }
+    }
+}
+
+impl<B: Backend> Iterator for ChunkedArrayElemView<B> {
+    type Item = Result<(ArrayData, usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current_position >= self.num_items {
+            None
+        } else {
+            let i = self.current_position;
+            let j = std::cmp::min(self.num_items, self.current_position + self.chunk_size);
+            self.current_position = j;
+            let data = self
+                .elem
+                .slice_axis(0, SelectInfoElem::from(i..j))
+                .and_then(|x| x.context("the element of the view is empty"));
+            Some(data.map(|data| (data, i, j)))
+        }
+    }
+}
+
+impl<B: Backend> ExactSizeIterator for ChunkedArrayElemView<B> {
+    fn len(&self) -> usize {
+        (self.num_items - self.current_position).div_ceil(self.chunk_size)
+    }
+}
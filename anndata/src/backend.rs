@@ -1,30 +1,102 @@
 mod datatype;
-use crate::data::{ArrayConvert, DynArray, SelectInfo, SelectInfoElem, Shape};
+use crate::data::{ArrayConvert, Data, DynArray, Readable, SelectInfo, SelectInfoElem, Shape, Writable};
 pub use datatype::{BackendData, DataType, ScalarType};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
+use half::f16;
 use core::fmt::{Debug, Formatter};
 use ndarray::{arr0, Array, CowArray, Dimension, Ix0, IxDyn};
 use std::path::{Path, PathBuf};
 pub use serde_json::Value;
 use serde::Deserialize;
 
+/// Compression filters that can be applied to datasets. Both backends can read
+/// datasets written with any of these filters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    /// No compression.
+    None,
+    /// Deflate (gzip) with a compression level between 0 and 9.
+    Gzip(u8),
+    /// Zstandard with a compression level between 1 and 22. The HDF5 backend
+    /// needs the Zstandard filter plugin for this and reports an error if the
+    /// plugin cannot be found.
+    Zstd(u8),
+    /// LZ4 through Blosc at level 5 without shuffling, a shorthand for
+    /// `Blosc { compressor: BloscCompressor::LZ4, level: 5, shuffle: BloscShuffle::None }`.
+    BloscLz4,
+    /// The Blosc meta-compressor.
+    Blosc {
+        compressor: BloscCompressor,
+        /// Compression level between 0 and 9.
+        level: u8,
+        shuffle: BloscShuffle,
+    },
+}
+
+/// Stores compress their datasets with gzip at level 1 by default.
+impl Default for Compression {
+    fn default() -> Self {
+        Self::Gzip(1)
+    }
+}
+
+/// The compressor used internally by Blosc.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BloscCompressor {
+    BloscLZ,
+    LZ4,
+    LZ4HC,
+    Snappy,
+    Zlib,
+    Zstd,
+}
+
+/// The shuffle filter applied by Blosc before compression.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BloscShuffle {
+    None,
+    Byte,
+    Bit,
+}
+
 #[derive(Debug, Clone)]
 pub struct WriteConfig {
-    pub compression: Option<u8>,
+    /// Compression filter, `None` means using the default compression of the
+    /// store, which is gzip at level 1 unless changed with
+    /// [`StoreOp::set_compression`]. Use `Some(Compression::None)` to store a
+    /// dataset uncompressed.
+    pub compression: Option<Compression>,
     pub block_size: Option<Shape>,
 }
 
 impl Default for WriteConfig {
     fn default() -> Self {
         Self {
-            compression: Some(1),
-            //compression: None,
+            compression: None,
             block_size: None,
         }
     }
 }
 
+impl WriteConfig {
+    /// Chunks of at most this many elements are not worth compressing, so the
+    /// default compression of the store is not applied to them.
+    pub const MIN_COMPRESSED_CHUNK_LEN: usize = 101;
+
+    /// Returns the compression of a dataset whose chunks hold `chunk_len`
+    /// elements. An explicit compression is always used. Otherwise, the
+    /// default compression of the store is used, unless the chunks are
+    /// shorter than [`WriteConfig::MIN_COMPRESSED_CHUNK_LEN`].
+    pub fn resolve_compression(&self, default: Compression, chunk_len: usize) -> Compression {
+        match self.compression {
+            Some(compression) => compression,
+            None if chunk_len >= Self::MIN_COMPRESSED_CHUNK_LEN => default,
+            None => Compression::None,
+        }
+    }
+}
+
 pub trait Backend: 'static {
     /// The name of the backend.
     const NAME: &'static str;
@@ -54,6 +126,12 @@ pub trait StoreOp<B: Backend + ?Sized> {
 
     /// Close the file.
     fn close(self) -> Result<()>;
+
+    /// Set the compression of the datasets subsequently created in this store,
+    /// including those in its groups, unless their [`WriteConfig`] specifies
+    /// one. Stores start with [`Compression::default`]. Backends that do not
+    /// compress their data ignore it.
+    fn set_compression(&mut self, _compression: Compression) {}
 }
 
 pub trait GroupOp<B: Backend + ?Sized> {
@@ -78,6 +156,21 @@ pub trait GroupOp<B: Backend + ?Sized> {
     /// Delete a group or dataset.
     fn delete(&self, name: &str) -> Result<()>;
 
+    /// Move a group or dataset to a new name within the same group. The
+    /// destination must not exist. The default implementation reads the whole
+    /// element into memory, writes it under the new name and deletes the old
+    /// one. Backends should override it with a cheaper move.
+    fn rename(&self, from: &str, to: &str) -> Result<()>
+    where
+        Self: Sized,
+        B: Sized,
+    {
+        ensure!(!self.exists(to)?, "'{}' already exists", to);
+        let data = Data::read(&DataContainer::<B>::open(self, from)?)?;
+        data.write(self, to)?;
+        self.delete(from)
+    }
+
     /// Check if a group or dataset exists.
     fn exists(&self, name: &str) -> Result<bool>;
 
@@ -99,13 +192,8 @@ pub trait GroupOp<B: Backend + ?Sized> {
                 shape.iter().map(|&x| x.min(100)).collect()
             }
         });
-        let compression = if arr.len() > 100 {
-            config.compression
-        } else {
-            None
-        };
         let new_config = WriteConfig {
-            compression: compression,
+            compression: config.compression,
             block_size: Some(block_size),
         };
         let dataset = self.new_empty_dataset::<D>(name, &shape.into(), new_config)?;
@@ -176,6 +264,7 @@ pub trait DatasetOp<B: Backend + ?Sized> {
             ScalarType::U16 => self.read_array_slice::<u16, _, IxDyn>(selection)?.into(),
             ScalarType::U32 => self.read_array_slice::<u32, _, IxDyn>(selection)?.into(),
             ScalarType::U64 => self.read_array_slice::<u64, _, IxDyn>(selection)?.into(),
+            ScalarType::F16 => self.read_array_slice::<f16, _, IxDyn>(selection)?.into(),
             ScalarType::F32 => self.read_array_slice::<f32, _, IxDyn>(selection)?.into(),
             ScalarType::F64 => self.read_array_slice::<f64, _, IxDyn>(selection)?.into(),
             ScalarType::Bool => self.read_array_slice::<bool, _, IxDyn>(selection)?.into(),
@@ -321,6 +410,12 @@ impl<B: Backend> DataContainer<B> {
             "numeric-scalar" => DataType::Scalar(self.as_dataset()?.dtype()?),
             "categorical" => DataType::Categorical,
             "string-array" => DataType::Array(ScalarType::String),
+            "nullable-integer" => {
+                let ty = self.as_group()?.open_dataset("values")?.dtype()?;
+                DataType::Nullable(ty)
+            }
+            "nullable-boolean" => DataType::Nullable(ScalarType::Bool),
+            "nullable-string-array" => DataType::Nullable(ScalarType::String),
             "array" => DataType::Array(self.as_dataset()?.dtype()?),
             "csc_matrix" => {
                 let ty = self.as_group()?.open_dataset("data")?.dtype()?;
@@ -1,6 +1,7 @@
 use crate::data::{DynArray, DynCowArray, DynScalar};
 
 use anyhow::{bail, Result};
+use half::f16;
 use core::fmt::{Display, Formatter, Debug};
 use ndarray::{ArrayD, CowArray, IxDyn};
 use serde::{Serialize, Deserialize};
@@ -11,10 +12,13 @@ pub enum DataType {
     Array(ScalarType),
     CsrMatrix(ScalarType),
     CscMatrix(ScalarType),
+    CooMatrix(ScalarType),
     DataFrame,
     Mapping,
     Scalar(ScalarType),
     Categorical,
+    /// Nullable integer, boolean or string arrays, stored as values and a mask.
+    Nullable(ScalarType),
 }
 
 impl Display for DataType {
@@ -24,9 +28,11 @@ impl Display for DataType {
             DataType::Categorical => write!(f, "Categorical"),
             DataType::CsrMatrix(t) => write!(f, "CsrMatrix({})", t),
             DataType::CscMatrix(t) => write!(f, "CscMatrix({})", t),
+            DataType::CooMatrix(t) => write!(f, "CooMatrix({})", t),
             DataType::DataFrame => write!(f, "DataFrame"),
             DataType::Scalar(t) => write!(f, "Scalar({})", t),
             DataType::Mapping => write!(f, "Mapping"),
+            DataType::Nullable(t) => write!(f, "Nullable({})", t),
         }
     }
 }
@@ -42,6 +48,7 @@ pub enum ScalarType {
     U16,
     U32,
     U64,
+    F16,
     F32,
     F64,
     Bool,
@@ -59,6 +66,7 @@ impl Display for ScalarType {
             ScalarType::U16 => write!(f, "u16"),
             ScalarType::U32 => write!(f, "u32"),
             ScalarType::U64 => write!(f, "u64"),
+            ScalarType::F16 => write!(f, "f16"),
             ScalarType::F32 => write!(f, "f32"),
             ScalarType::F64 => write!(f, "f64"),
             ScalarType::Bool => write!(f, "bool"),
@@ -308,6 +316,34 @@ impl BackendData for u64 {
     }
 }
 
+impl BackendData for f16 {
+    const DTYPE: ScalarType = ScalarType::F16;
+
+    fn into_dyn(&self) -> DynScalar {
+        DynScalar::F16(*self)
+    }
+
+    fn into_dyn_arr<'a>(arr: CowArray<'a, Self, IxDyn>) -> DynCowArray<'a> {
+        DynCowArray::F16(arr)
+    }
+
+    fn from_dyn(x: DynScalar) -> Result<Self> {
+        if let DynScalar::F16(x) = x {
+            Ok(x)
+        } else {
+            bail!("Expecting f16")
+        }
+    }
+
+    fn from_dyn_arr(x: DynArray) -> Result<ArrayD<Self>> {
+        if let DynArray::F16(x) = x {
+            Ok(x)
+        } else {
+            bail!("Expecting f16 array")
+        }
+    }
+}
+
 impl BackendData for f32 {
     const DTYPE: ScalarType = ScalarType::F32;
 
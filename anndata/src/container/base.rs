@@ -1,12 +1,20 @@
 use crate::{
-    backend::{AttributeOp, Backend, DataContainer, DataType, GroupOp},
-    data::index::VecVecIndex,
+    backend::{AttributeOp, Backend, BackendData, DataContainer, DatasetOp, DataType, GroupOp, ScalarType},
+    data::index::{ColumnMapping, VecVecIndex},
     data::*,
+    data::array::{
+        dataframe::{nullable_columns, overwrite_dataframe, write_dataframe},
+        utils::{cs_convert, expand_columns},
+        write_csc_by_column_chunk,
+    },
 };
 
 use anyhow::{bail, ensure, Result};
+use half::f16;
 use indexmap::set::IndexSet;
 use itertools::Itertools;
+use nalgebra_sparse::CsrMatrix;
+use ndarray::Array1;
 use num::integer::div_rem;
 use parking_lot::{Mutex, MutexGuard};
 use polars::{
@@ -17,6 +25,7 @@ use polars::{
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use smallvec::SmallVec;
 use std::{
+    collections::HashSet,
     ops::{Deref, DerefMut},
     sync::Arc,
 };
@@ -117,6 +126,9 @@ pub struct InnerDataFrameElem<B: Backend> {
     element: Option<DataFrame>,
     container: DataContainer<B>,
     column_names: IndexSet<String>,
+    /// Columns stored with a nullable encoding. They keep it when the
+    /// dataframe is written again, even if they have no missing values.
+    nullable: HashSet<String>,
     pub index: DataFrameIndex,
 }
 
@@ -140,10 +152,12 @@ impl<B: Backend> InnerDataFrameElem<B> {
             .into_iter()
             .map(|x| x.to_string())
             .collect();
+        let nullable = nullable_columns(&container)?;
         Ok(Self {
             element: None,
             container,
             column_names,
+            nullable,
             index,
         })
     }
@@ -206,7 +220,7 @@ impl<B: Backend> InnerDataFrameElem<B> {
             Some(ref df) => df.clone(),
             None => DataFrame::read(&self.container)?,
         };
-        let mut container = df.write(location, name)?;
+        let mut container = write_dataframe(&df, location, name, &self.nullable)?;
         self.index.overwrite(&mut container)
     }
 
@@ -223,7 +237,8 @@ impl<B: Backend> InnerDataFrameElem<B> {
         if selection.as_ref().into_iter().all(|x| x.is_full()) {
             self.export::<O, _>(location, name)
         } else {
-            let mut container = self.select(selection)?.write(location, name)?;
+            let df = self.select(selection)?;
+            let mut container = write_dataframe(&df, location, name, &self.nullable)?;
             self.index.select(&selection[0]).overwrite(&mut container)
         }
     }
@@ -265,8 +280,9 @@ impl<B: Backend> InnerDataFrameElem<B> {
             num_recs == 0 || self.index.len() == num_recs,
             "cannot update dataframe as lengths differ"
         );
-        let new = data.overwrite(std::mem::take(&mut self.container))?;
+        let new = overwrite_dataframe(&data, std::mem::take(&mut self.container), &self.nullable)?;
         let _ = std::mem::replace(&mut self.container, new);
+        self.nullable = nullable_columns(&self.container)?;
         self.column_names = data
             .get_column_names()
             .into_iter()
@@ -313,10 +329,12 @@ impl<B: Backend> TryFrom<DataContainer<B>> for DataFrameElem<B> {
                     .get_attr::<Vec<String>>("column-order")?
                     .into_iter()
                     .collect();
+                let nullable = nullable_columns(&container)?;
                 let df = InnerDataFrameElem {
                     element: None,
                     container,
                     column_names,
+                    nullable,
                     index,
                 };
                 Ok(Slot::new(df))
@@ -389,9 +407,11 @@ impl<B: Backend> InnerElem<B> {
     pub(crate) fn save(&mut self, data: Data) -> Result<()> {
         let new = data.overwrite(std::mem::take(&mut self.container))?;
         let _ = std::mem::replace(&mut self.container, new);
-        self.dtype = data.data_type();
+        self.dtype = self.container.encoding_type()?;
+        // COO matrices are stored as CSR, so the cache is dropped when the
+        // stored type differs from the one that was written.
         if self.element.is_some() {
-            self.element = Some(data.into());
+            self.element = (data.data_type() == self.dtype).then(|| data.into());
         }
         Ok(())
     }
@@ -434,10 +454,17 @@ impl<B: Backend> Elem<B> {
     }
 }
 
+/// Maximum number of elements per chunk when exporting dense arrays.
+const EXPORT_CHUNK_ITEMS: usize = 1 << 24;
+/// Number of rows (CSR) or columns (CSC) per chunk when exporting sparse matrices.
+const EXPORT_CHUNK_MAJOR: usize = 10000;
+
 #[derive(Debug)]
 pub struct InnerArrayElem<B: Backend> {
     dtype: DataType,
     shape: Shape,
+    /// The on-disk type of the indices of a sparse matrix.
+    index_type: Option<ScalarType>,
     cache_enabled: bool,
     container: DataContainer<B>,
     element: Option<ArrayData>,
@@ -464,6 +491,14 @@ impl<B: Backend> InnerArrayElem<B> {
         &self.shape
     }
 
+    /// The type of the indices of a sparse matrix as stored on disk, e.g.,
+    /// `I32` for matrices written by scipy with fewer than 2^31 entries.
+    /// The type is kept when the matrix is overwritten, as long as the new
+    /// indices fit in it. Returns `None` for dense arrays and data frames.
+    pub fn index_type(&self) -> Option<ScalarType> {
+        self.index_type
+    }
+
     pub fn enable_cache(&mut self) {
         self.cache_enabled = true;
     }
@@ -491,22 +526,126 @@ impl<B: Backend> InnerArrayElem<B> {
     pub(crate) fn save(&mut self, data: ArrayData) -> Result<()> {
         let new = data.overwrite(std::mem::take(&mut self.container))?;
         let _ = std::mem::replace(&mut self.container, new);
-        self.dtype = data.data_type();
+        self.dtype = self.container.encoding_type()?;
         self.shape = data.shape();
+        self.index_type = restore_index_type(&self.container, self.dtype, self.index_type)?;
+        // COO matrices are stored as CSR, so the cache is dropped when the
+        // stored type differs from the one that was written.
         if self.element.is_some() {
-            self.element = Some(data.into());
+            self.element = (data.data_type() == self.dtype).then(|| data.into());
         }
         Ok(())
     }
 
+    /// Write the element to another location. Dense arrays and sparse matrices
+    /// that are not cached are copied chunk by chunk, so that the whole matrix
+    /// is never loaded into memory.
     pub fn export<O: Backend, G: GroupOp<O>>(&self, location: &G, name: &str) -> Result<()> {
-        match self.element.as_ref() {
-            Some(data) => data.write(location, name)?,
-            None => ArrayData::read(&self.container)?.write(location, name)?,
-        };
+        if let Some(data) = self.element.as_ref() {
+            data.write(location, name)?;
+            return Ok(());
+        }
+
+        match self.dtype {
+            DataType::Array(_) | DataType::CsrMatrix(_)
+                if self.shape.ndim() > 0 && self.shape[0] > 0 =>
+            {
+                let full = SelectInfoElem::full();
+                let chunks = self.chunk_ranges(0).map(|(i, j)| {
+                    let rows = SelectInfoElem::from(i..j);
+                    let slice = rows.set_axis(0, self.shape.ndim(), &full);
+                    ArrayData::read_select(&self.container, slice.as_slice())
+                });
+                itertools::process_results(chunks, |iter| {
+                    ArrayData::write_by_chunk(iter, location, name)
+                })??;
+            }
+            DataType::CscMatrix(_) if self.shape[1] > 0 => {
+                let chunks = self.chunk_ranges(1).map(|(i, j)| {
+                    let slice = [SelectInfoElem::full(), SelectInfoElem::from(i..j)];
+                    DynCscMatrix::read_select(&self.container, slice.as_slice())
+                });
+                itertools::process_results(chunks, |iter| {
+                    write_csc_by_column_chunk(iter, location, name)
+                })??;
+            }
+            _ => {
+                ArrayData::read(&self.container)?.write(location, name)?;
+            }
+        }
         Ok(())
     }
 
+    /// Write the sparse matrix to another location in the given compressed
+    /// format. Converting between CSR and CSC happens on disk and holds at most
+    /// `buffer_size` non-zero entries in memory, at the cost of one scan of the
+    /// matrix per `buffer_size` entries.
+    pub fn export_sparse<O: Backend, G: GroupOp<O>>(
+        &self,
+        format: SparseFormat,
+        buffer_size: usize,
+        location: &G,
+        name: &str,
+    ) -> Result<DataContainer<O>> {
+        let (ty, shape) = match (self.dtype, format) {
+            (DataType::CsrMatrix(_), SparseFormat::Csr) | (DataType::CscMatrix(_), SparseFormat::Csc) => {
+                self.export::<O, _>(location, name)?;
+                return DataContainer::open(location, name);
+            }
+            (DataType::CsrMatrix(ty), SparseFormat::Csc) => (ty, (self.shape[0], self.shape[1])),
+            (DataType::CscMatrix(ty), SparseFormat::Csr) => (ty, (self.shape[1], self.shape[0])),
+            (ty, _) => bail!("cannot convert a {} element to {}", ty, format),
+        };
+        ensure!(buffer_size > 0, "buffer size must be positive");
+
+        let src = self.container.as_group()?;
+        let mut group = location.new_group(name)?;
+        match ty {
+            ScalarType::U8 => cs_convert::<B, O, u8>(src, &group, shape, buffer_size),
+            ScalarType::U16 => cs_convert::<B, O, u16>(src, &group, shape, buffer_size),
+            ScalarType::U32 => cs_convert::<B, O, u32>(src, &group, shape, buffer_size),
+            ScalarType::U64 => cs_convert::<B, O, u64>(src, &group, shape, buffer_size),
+            ScalarType::I8 => cs_convert::<B, O, i8>(src, &group, shape, buffer_size),
+            ScalarType::I16 => cs_convert::<B, O, i16>(src, &group, shape, buffer_size),
+            ScalarType::I32 => cs_convert::<B, O, i32>(src, &group, shape, buffer_size),
+            ScalarType::I64 => cs_convert::<B, O, i64>(src, &group, shape, buffer_size),
+            ScalarType::F16 => cs_convert::<B, O, f16>(src, &group, shape, buffer_size),
+            ScalarType::F32 => cs_convert::<B, O, f32>(src, &group, shape, buffer_size),
+            ScalarType::F64 => cs_convert::<B, O, f64>(src, &group, shape, buffer_size),
+            ScalarType::Bool => cs_convert::<B, O, bool>(src, &group, shape, buffer_size),
+            ScalarType::String => cs_convert::<B, O, String>(src, &group, shape, buffer_size),
+        }?;
+        let metadata = std::collections::HashMap::from([("shape".to_string(), self.shape.clone().into())]);
+        MetaData::new(
+            match format {
+                SparseFormat::Csr => "csr_matrix",
+                SparseFormat::Csc => "csc_matrix",
+            },
+            "0.1.0",
+            Some(metadata),
+        )
+        .save_metadata(&mut group)?;
+        Ok(DataContainer::Group(group))
+    }
+
+    /// Split the given axis into ranges such that each chunk holds a bounded
+    /// number of items.
+    fn chunk_ranges(&self, axis: usize) -> impl Iterator<Item = (usize, usize)> {
+        let n = self.shape[axis];
+        let chunk_size = match self.dtype {
+            DataType::Array(_) => {
+                let stride: usize = self.shape.as_ref().iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != axis)
+                    .map(|(_, x)| *x)
+                    .product();
+                (EXPORT_CHUNK_ITEMS / stride.max(1)).max(1)
+            }
+            _ => EXPORT_CHUNK_MAJOR,
+        };
+        (0..n).step_by(chunk_size).map(move |i| (i, (i + chunk_size).min(n)))
+    }
+
     pub fn select<S>(&mut self, selection: &[S]) -> Result<ArrayData>
     where
         S: AsRef<SelectInfoElem>,
@@ -521,6 +660,20 @@ impl<B: Backend> InnerArrayElem<B> {
         }
     }
 
+    /// Read a selection of a CSR matrix as a [`CompactCsrMatrix`], whose
+    /// column indices take half the memory of those of `CsrMatrix`.
+    pub fn select_compact<T, S>(&mut self, selection: &[S]) -> Result<CompactCsrMatrix<T>>
+    where
+        T: BackendData,
+        CsrMatrix<T>: TryFrom<ArrayData, Error = anyhow::Error>,
+        S: AsRef<SelectInfoElem>,
+    {
+        match self.element.as_ref() {
+            Some(data) => data.select(selection).try_into(),
+            None => CompactCsrMatrix::read_select(&self.container, selection),
+        }
+    }
+
     pub fn select_axis<S>(&mut self, axis: usize, selection: S) -> Result<ArrayData>
     where
         S: AsRef<SelectInfoElem>,
@@ -580,6 +733,7 @@ impl<B: Backend> InnerArrayElem<B> {
         self.shape = data.shape();
         let new = data.overwrite(std::mem::take(&mut self.container))?;
         let _ = std::mem::replace(&mut self.container, new);
+        self.index_type = restore_index_type(&self.container, self.dtype, self.index_type)?;
         if self.element.is_some() {
             self.element = Some(data);
         }
@@ -600,6 +754,57 @@ impl<B: Backend> InnerArrayElem<B> {
 
 pub type ArrayElem<B> = Slot<InnerArrayElem<B>>;
 
+fn read_index_type<B: Backend>(container: &DataContainer<B>, dtype: DataType) -> Result<Option<ScalarType>> {
+    let name = match dtype {
+        DataType::CsrMatrix(_) | DataType::CscMatrix(_) => "indices",
+        _ => return Ok(None),
+    };
+    Ok(Some(container.as_group()?.open_dataset(name)?.dtype()?))
+}
+
+/// Rewrite `indptr` and `indices` of a sparse matrix that has just been
+/// written with the index type it had on disk before, if the indices fit in
+/// it, so that overwriting a matrix does not change its index type.
+/// Returns the resulting index type.
+fn restore_index_type<B: Backend>(
+    container: &DataContainer<B>,
+    dtype: DataType,
+    index_type: Option<ScalarType>,
+) -> Result<Option<ScalarType>> {
+    let current = read_index_type(container, dtype)?;
+    let (Some(current), Some(previous)) = (current, index_type) else {
+        return Ok(current);
+    };
+    if current == previous {
+        return Ok(Some(current));
+    }
+
+    let group = container.as_group()?;
+    let indptr: Array1<i64> = group.open_dataset("indptr")?.read_array_cast()?;
+    let indices: Array1<i64> = group.open_dataset("indices")?.read_array_cast()?;
+    let max = indptr.iter().chain(indices.iter()).copied().max().unwrap_or(0);
+    match previous {
+        ScalarType::I32 if i32::try_from(max).is_ok() => {
+            rewrite_indices::<B, _>(group, indptr.mapv(|x| x as i32), indices.mapv(|x| x as i32))?
+        }
+        ScalarType::I64 => rewrite_indices::<B, _>(group, indptr, indices)?,
+        _ => return Ok(Some(current)),
+    }
+    Ok(Some(previous))
+}
+
+fn rewrite_indices<B: Backend, T: BackendData>(
+    group: &B::Group,
+    indptr: Array1<T>,
+    indices: Array1<T>,
+) -> Result<()> {
+    group.delete("indptr")?;
+    group.new_array_dataset("indptr", indptr.into(), Default::default())?;
+    group.delete("indices")?;
+    group.new_array_dataset("indices", indices.into(), Default::default())?;
+    Ok(())
+}
+
 /// Container holding matrix data types.
 impl<B: Backend> TryFrom<DataContainer<B>> for ArrayElem<B> {
     type Error = anyhow::Error;
@@ -609,6 +814,7 @@ impl<B: Backend> TryFrom<DataContainer<B>> for ArrayElem<B> {
         let elem = InnerArrayElem {
             dtype,
             shape: ArrayData::get_shape(&container)?,
+            index_type: read_index_type(&container, dtype)?,
             cache_enabled: false,
             element: None,
             container,
@@ -629,6 +835,32 @@ impl<B: Backend> ArrayElem<B> {
     pub fn chunked(&self, chunk_size: usize) -> ChunkedArrayElem<B> {
         ChunkedArrayElem::new(self.clone(), chunk_size)
     }
+
+    /// Iterate over the rows of a CSR matrix in chunks of [`CompactCsrMatrix`],
+    /// whose column indices are read as `u32` without being widened.
+    pub fn chunked_compact<T>(&self, chunk_size: usize) -> Result<CompactChunkedArrayElem<B, T>>
+    where
+        T: BackendData,
+        CsrMatrix<T>: TryFrom<ArrayData, Error = anyhow::Error>,
+    {
+        let dtype = self.inner().dtype();
+        ensure!(
+            dtype == DataType::CsrMatrix(T::DTYPE),
+            "cannot read {} as a compact csr matrix of {}",
+            dtype,
+            T::DTYPE
+        );
+        let ncols = self.inner().shape()[1];
+        ensure!(
+            u32::try_from(ncols.saturating_sub(1)).is_ok(),
+            "a matrix with {} columns cannot be stored with 32-bit indices",
+            ncols
+        );
+        Ok(CompactChunkedArrayElem {
+            inner: self.chunked(chunk_size),
+            phantom: std::marker::PhantomData,
+        })
+    }
 }
 
 /// Horizontal concatenated dataframe elements.
@@ -791,6 +1023,8 @@ pub struct InnerStackedArrayElem<B: Backend> {
     pub(crate) shape: Option<Shape>,
     elems: SmallVec<[ArrayElem<B>; 96]>,
     index: VecVecIndex,
+    /// Column mappings of the elements when they have different columns.
+    columns: Option<Arc<Vec<ColumnMapping>>>,
 }
 
 impl<B: Backend> std::fmt::Display for InnerStackedArrayElem<B> {
@@ -833,7 +1067,8 @@ impl<B: Backend> InnerStackedArrayElem<B> {
             let array = self
                 .elems
                 .iter()
-                .flat_map(|x| x.lock().as_mut().map(|i| i.data()))
+                .enumerate()
+                .flat_map(|(i, x)| x.lock().as_mut().map(|el| self.remap(i, el.data()?)))
                 .process_results(|x| Stackable::vstack(x).unwrap())?;
             Some(array.try_into().map_err(Into::into)?)
         };
@@ -851,7 +1086,8 @@ impl<B: Backend> InnerStackedArrayElem<B> {
             let array = self
                 .elems
                 .par_iter()
-                .flat_map(|x| x.lock().as_mut().map(|i| i.data()))
+                .enumerate()
+                .flat_map(|(i, x)| x.lock().as_mut().map(|el| self.remap(i, el.data()?)))
                 .collect::<Vec<_>>()
                 .into_iter()
                 .process_results(|x| Stackable::vstack(x).unwrap())?;
@@ -894,16 +1130,10 @@ impl<B: Backend> InnerStackedArrayElem<B> {
                 .enumerate()
                 .map(|(i, el)| {
                     if let Some(idx) = indices.get(&i) {
-                        let select: SmallVec<[_; 3]> = std::iter::once(idx)
-                            .chain(selection.as_ref()[1..].iter().map(|x| x.as_ref()))
-                            .collect();
-                        el.inner().select(select.as_slice())
+                        self.select_elem(i, el, idx, &selection.as_ref()[1..])
                     } else {
                         let idx = SelectInfoElem::empty();
-                        let select: SmallVec<[_; 3]> = std::iter::once(&idx)
-                            .chain(selection.as_ref()[1..].iter().map(|x| x.as_ref()))
-                            .collect();
-                        el.inner().select(select.as_slice())
+                        self.select_elem(i, el, &idx, &selection.as_ref()[1..])
                     }
                 })
                 .process_results(|x| Stackable::vstack(x).unwrap())?;
@@ -936,12 +1166,9 @@ impl<B: Backend> InnerStackedArrayElem<B> {
                 .par_iter()
                 .enumerate()
                 .flat_map(|(i, el)| {
-                    indices.get(&i).map(|idx| {
-                        let select: SmallVec<[_; 3]> = std::iter::once(idx)
-                            .chain(selection.as_ref()[1..].iter().map(|x| x.as_ref()))
-                            .collect();
-                        el.inner().select(select.as_slice())
-                    })
+                    indices
+                        .get(&i)
+                        .map(|idx| self.select_elem(i, el, idx, &selection.as_ref()[1..]))
                 })
                 .collect::<Vec<_>>()
                 .into_iter()
@@ -960,6 +1187,44 @@ impl<B: Backend> InnerStackedArrayElem<B> {
         Ok(data)
     }
 
+    /// Read the selected rows and the remaining axes of the i-th element,
+    /// placing the columns of the element at their positions in the stacked
+    /// array.
+    fn select_elem<S: AsRef<SelectInfoElem>>(
+        &self,
+        i: usize,
+        el: &ArrayElem<B>,
+        rows: &SelectInfoElem,
+        rest: &[S],
+    ) -> Result<ArrayData> {
+        match self.columns.as_ref().map(|x| &x[i]) {
+            Some(columns) if !columns.is_identity() => {
+                let full = SelectInfoElem::full();
+                let cols = rest.first().map_or(&full, |x| x.as_ref());
+                let (cols, positions, n) = columns.split_select(cols);
+                let select: SmallVec<[_; 3]> = [rows, &cols]
+                    .into_iter()
+                    .chain(rest.iter().skip(1).map(|x| x.as_ref()))
+                    .collect();
+                expand_columns(el.inner().select(select.as_slice())?, &positions, n)
+            }
+            _ => {
+                let select: SmallVec<[_; 3]> = std::iter::once(rows)
+                    .chain(rest.iter().map(|x| x.as_ref()))
+                    .collect();
+                el.inner().select(select.as_slice())
+            }
+        }
+    }
+
+    /// Remap the columns of the data read from the i-th element.
+    fn remap(&self, i: usize, data: ArrayData) -> Result<ArrayData> {
+        match self.columns.as_ref() {
+            Some(columns) => columns[i].remap(data),
+            None => Ok(data),
+        }
+    }
+
     /// Activate the cache for all elements.
     pub fn enable_cache(&self) {
         for el in self.elems.iter() {
@@ -1006,10 +1271,20 @@ impl<B: Backend> StackedArrayElem<B> {
             shape: None,
             elems: SmallVec::new(),
             index: std::iter::empty().collect(),
+            columns: None,
         }))
     }
 
     pub(crate) fn new(elems: SmallVec<[ArrayElem<B>; 96]>) -> Result<Self> {
+        Self::new_joined(elems, None)
+    }
+
+    /// Stack the elements whose columns are mapped to a joined set of columns
+    /// by `columns`, one mapping per element.
+    pub(crate) fn new_joined(
+        elems: SmallVec<[ArrayElem<B>; 96]>,
+        columns: Option<Arc<Vec<ColumnMapping>>>,
+    ) -> Result<Self> {
         ensure!(
             elems
                 .iter()
@@ -1022,18 +1297,36 @@ impl<B: Backend> StackedArrayElem<B> {
             .iter()
             .map(|x| x.lock().as_ref().map(|x| x.shape().clone()))
             .collect();
-        ensure!(
-            shapes
-                .iter()
-                .map(|x| x.as_ref().map(|s| &s.as_ref()[1..]))
-                .all_equal(),
-            "all elements must have the same shape except for the first axis"
-        );
+        if let Some(columns) = columns.as_ref() {
+            ensure!(
+                columns.len() == elems.len(),
+                "expecting {} column mappings, but got {}",
+                elems.len(),
+                columns.len(),
+            );
+            ensure!(
+                shapes.iter().zip(columns.iter()).all(|(s, c)| s
+                    .as_ref()
+                    .map_or(true, |s| s.ndim() == 2 && s[1] == c.n_cols())),
+                "the number of columns does not match the column mapping"
+            );
+        } else {
+            ensure!(
+                shapes
+                    .iter()
+                    .map(|x| x.as_ref().map(|s| &s.as_ref()[1..]))
+                    .all_equal(),
+                "all elements must have the same shape except for the first axis"
+            );
+        }
         let index: VecVecIndex = shapes.iter().flatten().map(|x| x.as_ref()[0]).collect();
         let shape = shapes.get(0).and_then(|x| {
             x.as_ref().map(|s| {
                 let mut ss = s.clone();
                 ss[0] = index.len();
+                if let Some(columns) = columns.as_ref() {
+                    ss[1] = columns[0].len();
+                }
                 ss
             })
         });
@@ -1041,11 +1334,16 @@ impl<B: Backend> StackedArrayElem<B> {
             shape,
             elems,
             index,
+            columns,
         })))
     }
 
     pub fn chunked(&self, chunk_size: usize) -> StackedChunkedArrayElem<B> {
-        StackedChunkedArrayElem::new(self.elems.iter().map(|x| x.clone()), chunk_size)
+        StackedChunkedArrayElem::new(
+            self.elems.iter().map(|x| x.clone()),
+            self.columns.clone(),
+            chunk_size,
+        )
     }
 }
 
@@ -1075,13 +1373,13 @@ impl<B> Iterator for ChunkedArrayElem<B>
 where
     B: Backend,
 {
-    type Item = (ArrayData, usize, usize);
+    type Item = Result<(ArrayData, usize, usize)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_position >= self.num_items {
             if self.current_position == 0 {  // return an empty array
                 self.current_position = 1;
-                Some((self.elem.inner().data().unwrap(), 0, 0))
+                Some(self.elem.inner().data().map(|data| (data, 0, 0)))
             } else {
                 None
             }
@@ -1092,9 +1390,8 @@ where
             let data = self
                 .elem
                 .inner()
-                .select_axis(0, SelectInfoElem::from(i..j))
-                .unwrap();
-            Some((data, i, j))
+                .select_axis(0, SelectInfoElem::from(i..j));
+            Some(data.map(|data| (data, i, j)))
         }
     }
 }
@@ -1113,48 +1410,113 @@ where
     }
 }
 
+/// Chunks of the rows of a CSR matrix as [`CompactCsrMatrix`].
+pub struct CompactChunkedArrayElem<B: Backend, T> {
+    inner: ChunkedArrayElem<B>,
+    phantom: std::marker::PhantomData<T>,
+}
+
+impl<B, T> Iterator for CompactChunkedArrayElem<B, T>
+where
+    B: Backend,
+    T: BackendData,
+    CsrMatrix<T>: TryFrom<ArrayData, Error = anyhow::Error>,
+{
+    type Item = Result<(CompactCsrMatrix<T>, usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunks = &mut self.inner;
+        if chunks.current_position >= chunks.num_items {
+            if chunks.current_position == 0 {  // return an empty matrix
+                chunks.current_position = 1;
+                let data = chunks.elem.inner().select_compact(&[SelectInfoElem::full(), SelectInfoElem::full()]);
+                Some(data.map(|data| (data, 0, 0)))
+            } else {
+                None
+            }
+        } else {
+            let i = chunks.current_position;
+            let j = std::cmp::min(chunks.num_items, chunks.current_position + chunks.chunk_size);
+            chunks.current_position = j;
+            let data = chunks
+                .elem
+                .inner()
+                .select_compact(&[SelectInfoElem::from(i..j), SelectInfoElem::full()]);
+            Some(data.map(|data| (data, i, j)))
+        }
+    }
+}
+
+impl<B, T> ExactSizeIterator for CompactChunkedArrayElem<B, T>
+where
+    B: Backend,
+    T: BackendData,
+    CsrMatrix<T>: TryFrom<ArrayData, Error = anyhow::Error>,
+{
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
 pub struct StackedChunkedArrayElem<B: Backend> {
     arrays: SmallVec<[ChunkedArrayElem<B>; 96]>,
+    columns: Option<Arc<Vec<ColumnMapping>>>,
     current_position: usize,
     current_array: usize,
 }
 
 impl<B: Backend> StackedChunkedArrayElem<B> {
-    pub(crate) fn new<I: Iterator<Item = ArrayElem<B>>>(elems: I, chunk_size: usize) -> Self {
+    pub(crate) fn new<I: Iterator<Item = ArrayElem<B>>>(
+        elems: I,
+        columns: Option<Arc<Vec<ColumnMapping>>>,
+        chunk_size: usize,
+    ) -> Self {
         Self {
             arrays: elems
                 .map(|x| ChunkedArrayElem::new(x, chunk_size))
                 .collect(),
+            columns,
             current_position: 0,
             current_array: 0,
         }
     }
+
+    fn remap(&self, i: usize, data: ArrayData) -> Result<ArrayData> {
+        match self.columns.as_ref() {
+            Some(columns) => columns[i].remap(data),
+            None => Ok(data),
+        }
+    }
 }
 
 impl<B> Iterator for StackedChunkedArrayElem<B>
 where
     B: Backend,
 {
-    type Item = (ArrayData, usize, usize);
+    type Item = Result<(ArrayData, usize, usize)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(mat) = self.arrays.get_mut(self.current_array) {
-            if let Some((data, start, stop)) = mat.next() {
-                let new_start = self.current_position;
-                let new_stop = new_start + stop - start;
-                self.current_position = new_stop;
-                Some((data, new_start, new_stop))
-            } else {
-                self.current_array += 1;
-                self.next()
+            match mat.next() {
+                Some(Ok((data, start, stop))) => {
+                    let new_start = self.current_position;
+                    let new_stop = new_start + stop - start;
+                    self.current_position = new_stop;
+                    let data = self.remap(self.current_array, data);
+                    Some(data.map(|data| (data, new_start, new_stop)))
+                }
+                Some(Err(e)) => Some(Err(e)),
+                None => {
+                    self.current_array += 1;
+                    self.next()
+                }
             }
+        } else if self.current_position == 0 {  // return an empty array
+            self.current_position = 1;
+            let data = self.arrays.first()?.elem.inner().data();
+            Some(data.and_then(|data| self.remap(0, data)).map(|data| (data, 0, 0)))
         } else {
-            if self.current_position == 0 {  // return an empty array
-                self.current_position = 1;
-                Some((self.arrays[0].elem.inner().data().unwrap(), 0, 0))
-            } else {
-                None
-            }
+            None
         }
     }
 }
@@ -1168,6 +1530,45 @@ where
     }
 }
 
+/// An iterator that applies a function to each chunk of an array element.
+/// It is created by `ArrayElemOp::map_chunks`.
+pub struct MapChunks<I, F> {
+    iter: I,
+    f: F,
+}
+
+impl<I, F> MapChunks<I, F> {
+    pub(crate) fn new(iter: I, f: F) -> Self {
+        Self { iter, f }
+    }
+}
+
+impl<I, F> Iterator for MapChunks<I, F>
+where
+    I: Iterator<Item = Result<(ArrayData, usize, usize)>>,
+    F: FnMut(ArrayData) -> ArrayData,
+{
+    type Item = Result<ArrayData>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|x| x.map(|(data, _, _)| (self.f)(data)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I, F> ExactSizeIterator for MapChunks<I, F>
+where
+    I: ExactSizeIterator<Item = Result<(ArrayData, usize, usize)>>,
+    F: FnMut(ArrayData) -> ArrayData,
+{
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
 fn reverse_mapping(mapping: Vec<usize>) -> Vec<usize> {
     let mut res = vec![0; mapping.len()];
     for (i, x) in mapping.into_iter().enumerate() {
@@ -1,11 +1,11 @@
 use crate::{
     backend::{iter_containers, AttributeOp, Backend, GroupOp},
     container::base::*,
-    data::*,
+    data::{index::{ColumnMapping, VecVecIndex}, *},
     ElemCollectionOp,
 };
 
-use anyhow::{bail, ensure, Result};
+use anyhow::{bail, ensure, Context, Result};
 use itertools::Itertools;
 use log::warn;
 use parking_lot::{Mutex, MutexGuard};
@@ -250,6 +250,10 @@ impl DimLock<'_> {
     pub(crate) fn set(&mut self, n: usize) {
         *self.0 = Some(n);
     }
+
+    pub(crate) fn reset(&mut self) {
+        *self.0 = None;
+    }
 }
 
 pub struct InnerAxisArrays<B: Backend> {
@@ -339,11 +343,87 @@ impl<B: Backend> InnerAxisArrays<B> {
         I: Iterator<Item = D>,
         D: ArrayChunk,
     {
-        if let Some(elem) = self.get(key) {
+        if let Some(elem) = self.remove(key) {
             elem.clear()?;
         }
-        let elem = ArrayElem::try_from(ArrayChunk::write_by_chunk(data, &self.container, key)?)?;
+        // Do not leave a partially written element behind.
+        let container = match ArrayChunk::write_by_chunk(data, &self.container, key) {
+            Ok(container) => container,
+            Err(e) => {
+                if self.container.exists(key)? {
+                    self.container.delete(key)?;
+                }
+                return Err(e);
+            }
+        };
+        let elem = ArrayElem::try_from(container)?;
+        self.insert_checked(key, elem)
+    }
 
+    /// Check that an array of the given shape can be added, without setting
+    /// the dimensions.
+    pub(crate) fn check_shape(&self, shape: &Shape) -> Result<()> {
+        let check = |dim: &Dim, n: usize| {
+            let lock = dim.lock();
+            ensure!(
+                lock.is_empty() || lock.get() == n,
+                "dimension cannot be changed from {} to {}",
+                lock.get(),
+                n
+            );
+            Ok(())
+        };
+        match (self.axis, self.dim2.as_ref()) {
+            (Axis::Row, _) => check(&self.dim1, shape[0]),
+            (Axis::RowColumn, Some(dim2)) => {
+                ensure!(
+                    shape.ndim() >= 2,
+                    "expecting a 2-dimensional array, but receive a {} array",
+                    shape
+                );
+                check(&self.dim1, shape[0]).and(check(dim2, shape[1]))
+            }
+            (Axis::RowColumn, None) => bail!("the column dimension is not available"),
+            (Axis::Pairwise, _) => {
+                ensure!(
+                    shape.ndim() == 2 && shape[0] == shape[1],
+                    "expecting a square array, but receive a {:?} array",
+                    shape
+                );
+                check(&self.dim1, shape[0])
+            }
+        }
+    }
+
+    /// Add a sparse matrix converted from another element to the given
+    /// compressed format. The conversion is done on disk, holding at most
+    /// `buffer_size` non-zero entries in memory.
+    pub fn add_sparse_from(
+        &mut self,
+        key: &str,
+        elem: &ArrayElem<B>,
+        format: SparseFormat,
+        buffer_size: usize,
+    ) -> Result<()> {
+        if let Some(old) = self.get(key) {
+            ensure!(
+                !Arc::ptr_eq(&old.0, &elem.0),
+                "cannot convert '{}' in place",
+                key
+            );
+            old.clear()?;
+        }
+        let container = elem
+            .lock()
+            .as_ref()
+            .context("cannot convert an empty element")?
+            .export_sparse(format, buffer_size, &self.container, key)?;
+        self.insert_checked(key, ArrayElem::try_from(container)?)
+    }
+
+    /// Insert a newly written element after checking its shape. The element
+    /// is removed if the shape is incompatible.
+    fn insert_checked(&mut self, key: &str, elem: ArrayElem<B>) -> Result<()> {
         let shape = { elem.inner().shape().clone() };
         match self.axis {
             Axis::Row => {
@@ -583,29 +663,39 @@ impl<B: Backend> AxisArrays<B> {
     }
 }
 
-/// Stacked axis arrays, providing Read-only access to the data.
+/// Stacked axis arrays. Elements are concatenated lazily along the rows of the
+/// components the first time they are accessed, while adding or removing data
+/// is forwarded to the arrays of each component.
 pub struct StackedAxisArrays<B: Backend> {
     axis: Axis,
-    pub(crate) data: Arc<HashMap<String, StackedArrayElem<B>>>,
+    /// Axis arrays of the components.
+    arrays: Vec<AxisArrays<B>>,
+    /// Column mappings of the components, used by row/column arrays whose
+    /// components have different columns.
+    columns: Option<Arc<Vec<ColumnMapping>>>,
+    /// Arrays that are stored whole rather than split across the components,
+    /// e.g., in the annotation file of an AnnDataSet. They take precedence over
+    /// the stacked arrays with the same key.
+    annotation: Option<AxisArrays<B>>,
+    /// Stacked elements built so far, or `None` for keys whose components
+    /// cannot be stacked. An entry is dropped when the data under its key is
+    /// added or removed through these arrays, so the components should not be
+    /// modified directly while they are stacked.
+    data: Arc<Mutex<HashMap<String, Option<StackedArrayElem<B>>>>>,
 }
 
 impl<B: Backend> Clone for StackedAxisArrays<B> {
     fn clone(&self) -> Self {
         Self {
             axis: self.axis,
+            arrays: self.arrays.clone(),
+            columns: self.columns.clone(),
+            annotation: self.annotation.clone(),
             data: self.data.clone(),
         }
     }
 }
 
-impl<B: Backend> Deref for StackedAxisArrays<B> {
-    type Target = HashMap<String, StackedArrayElem<B>>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.data
-    }
-}
-
 impl<B: Backend> std::fmt::Display for StackedAxisArrays<B> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let ty = match self.axis {
@@ -613,12 +703,7 @@ impl<B: Backend> std::fmt::Display for StackedAxisArrays<B> {
             Axis::RowColumn => "row/column",
             Axis::Pairwise => "pairwise",
         };
-        let keys = self
-            .data
-            .keys()
-            .map(|x| x.to_string())
-            .collect::<Vec<_>>()
-            .join(", ");
+        let keys = self.keys().join(", ");
         write!(f, "Stacked AxisArrays ({}) with keys: {}", ty, keys)
     }
 }
@@ -627,55 +712,331 @@ impl<B: Backend> StackedAxisArrays<B> {
     pub fn empty(axis: Axis) -> Self {
         Self {
             axis,
-            data: Arc::new(HashMap::new()),
+            arrays: Vec::new(),
+            columns: None,
+            annotation: None,
+            data: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
     pub(crate) fn new(axis: Axis, arrays: Vec<AxisArrays<B>>) -> Result<Self> {
-        if arrays.iter().any(|x| x.is_none()) {
-            return Ok(Self::empty(axis));
-        }
+        Self::new_joined(axis, arrays, None)
+    }
 
+    /// Stack the arrays of the components. The column mappings are only
+    /// applied to row/column arrays.
+    pub(crate) fn new_joined(
+        axis: Axis,
+        arrays: Vec<AxisArrays<B>>,
+        columns: Option<Arc<Vec<ColumnMapping>>>,
+    ) -> Result<Self> {
         ensure!(
-            arrays.iter().all(|x| x.inner().axis == axis),
+            arrays
+                .iter()
+                .all(|x| x.lock().as_ref().map_or(true, |x| x.axis == axis)),
             "Axis mismatch"
         );
+        let columns = columns.filter(|_| matches!(axis, Axis::RowColumn));
+        let stacked = Self {
+            axis,
+            arrays,
+            columns,
+            annotation: None,
+            data: Arc::new(Mutex::new(HashMap::new())),
+        };
 
-        let shared_keys: HashSet<String> = arrays
-            .iter()
-            .map(|x| x.inner().keys().cloned().collect::<HashSet<_>>())
-            .reduce(|a, b| a.intersection(&b).cloned().collect())
-            .unwrap_or(HashSet::new());
-
-        let mut ignore_keys = Vec::new();
-        let data = shared_keys
+        let ignore_keys: Vec<_> = stacked
+            .shared_keys()
             .into_iter()
-            .flat_map(|k| {
-                let elems = arrays
-                    .iter()
-                    .map(|x| x.inner().get(&k).unwrap().clone())
-                    .collect();
-                if let Ok(arr) = StackedArrayElem::new(elems) {
-                    Some((k, arr))
-                } else {
-                    ignore_keys.push(k);
-                    None
-                }
-            })
-            .collect::<HashMap<_, _>>();
+            .filter(|k| stacked.get(k).is_none())
+            .collect();
         if !ignore_keys.is_empty() {
             warn!(
                 "Unable to create stacked arrays for these keys: {}",
                 ignore_keys.join(",")
             );
         }
-        Ok(Self {
-            axis: axis,
-            data: Arc::new(data),
-        })
+        Ok(stacked)
+    }
+
+    /// Also expose the arrays stored whole in `annotation`.
+    pub(crate) fn with_annotation(mut self, annotation: AxisArrays<B>) -> Self {
+        self.annotation = Some(annotation);
+        self
+    }
+
+    /// Keys of the arrays stored whole.
+    fn annotation_keys(&self) -> Vec<String> {
+        self.annotation
+            .as_ref()
+            .and_then(|x| x.lock().as_ref().map(|x| x.keys().cloned().collect()))
+            .unwrap_or_default()
+    }
+
+    /// Keys present in all components.
+    fn shared_keys(&self) -> Vec<String> {
+        let mut iter = self.arrays.iter().map(|x| {
+            x.lock()
+                .as_ref()
+                .map(|x| x.keys().cloned().collect::<HashSet<_>>())
+                .unwrap_or_default()
+        });
+        iter.next()
+            .map(|first| iter.fold(first, |a, b| a.intersection(&b).cloned().collect()))
+            .unwrap_or_default()
+            .into_iter()
+            .sorted()
+            .collect()
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        let stacked = self
+            .shared_keys()
+            .into_iter()
+            .filter(|k| self.get(k).is_some());
+        self.annotation_keys()
+            .into_iter()
+            .chain(stacked)
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<StackedArrayElem<B>> {
+        let whole = self
+            .annotation
+            .as_ref()
+            .and_then(|x| x.lock().as_ref().and_then(|x| x.get(key).cloned()));
+        if let Some(elem) = whole {
+            return StackedArrayElem::new(vec![elem]).ok();
+        }
+        if self.arrays.is_empty() {
+            return None;
+        }
+        let elems: Vec<_> = self
+            .arrays
+            .iter()
+            .map(|x| x.lock().as_ref().and_then(|x| x.get(key).cloned()))
+            .collect::<Option<_>>()?;
+        let mut data = self.data.lock();
+        if let Some(elem) = data.get(key) {
+            return elem.clone();
+        }
+        let elem = StackedArrayElem::new_joined(elems, self.columns.clone()).ok();
+        data.insert(key.to_string(), elem.clone());
+        elem
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
-        self.data.contains_key(key)
+        self.get(key).is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys().is_empty()
+    }
+
+    /// Split the data by rows and add each part to the corresponding component.
+    /// All parts are checked before anything is written.
+    pub fn add_data<D: Into<ArrayData>>(&self, key: &str, data: D) -> Result<()> {
+        let index = self.index()?;
+        self.data.lock().remove(key);
+        let data = data.into();
+        if let [arrays] = self.arrays.as_slice() {
+            arrays.inner().add_data(key, data)?;
+            return self.remove_annotation(key);
+        }
+        self.check_key(key)?;
+        ensure!(
+            data.shape()[0] == index.len(),
+            "expecting an array with {} rows, but receive a {} array",
+            index.len(),
+            data.shape(),
+        );
+        let parts = self
+            .arrays
+            .iter()
+            .enumerate()
+            .map(|(i, arrays)| {
+                let rows = SelectInfoElem::from(index.inner_range(i));
+                let part = match self.component_columns(i)? {
+                    Some((cols, n)) => {
+                        ensure!(
+                            data.shape().ndim() == 2 && data.shape()[1] == n,
+                            "expecting an array with {} columns, but receive a {} array",
+                            n,
+                            data.shape(),
+                        );
+                        data.select(&[rows, cols])
+                    }
+                    None => data.select_axis(0, rows),
+                };
+                arrays.inner().check_shape(&part.shape())?;
+                Ok(part)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let result = self
+            .arrays
+            .iter()
+            .zip(parts)
+            .try_for_each(|(arrays, part)| arrays.inner().add_data(key, part));
+        self.finish_write(key, result)
+    }
+
+    /// Add data from an iterator of row chunks. The chunks are split at the
+    /// component boundaries and streamed into the components one after another.
+    pub fn add_data_from_iter<I, D>(&self, key: &str, data: I) -> Result<()>
+    where
+        I: Iterator<Item = D>,
+        D: ArrayChunk + Into<ArrayData>,
+    {
+        let index = self.index()?;
+        self.data.lock().remove(key);
+        if let [arrays] = self.arrays.as_slice() {
+            arrays.inner().add_data_from_iter(key, data)?;
+            return self.remove_annotation(key);
+        }
+        self.check_key(key)?;
+        let result = self.split_from_iter(key, &index, data.map(Into::<ArrayData>::into));
+        self.finish_write(key, result)
+    }
+
+    fn split_from_iter<I>(&self, key: &str, index: &VecVecIndex, mut data: I) -> Result<()>
+    where
+        I: Iterator<Item = ArrayData>,
+    {
+        let mut pending: Option<ArrayData> = None;
+        for (i, arrays) in self.arrays.iter().enumerate() {
+            let cols = self.component_columns(i)?;
+            // Chunks with unexpected shapes are passed through and rejected by the component.
+            let select_cols = |x: ArrayData| match cols.as_ref() {
+                Some((cols, n)) if x.shape().ndim() == 2 && x.shape()[1] == *n => {
+                    x.select_axis(1, cols)
+                }
+                _ => x,
+            };
+            let mut remaining = index.inner_range(i).len();
+            if remaining == 0 {
+                let chunk = pending
+                    .take()
+                    .or_else(|| data.next())
+                    .ok_or_else(|| anyhow::anyhow!("cannot add data from an empty iterator"))?;
+                let empty = chunk.select_axis(0, SelectInfoElem::from(0..0));
+                arrays.inner().add_data(key, select_cols(empty))?;
+                pending = Some(chunk);
+                continue;
+            }
+
+            let chunks = std::iter::from_fn(|| {
+                if remaining == 0 {
+                    return None;
+                }
+                let chunk = pending.take().or_else(|| data.next())?;
+                let n = chunk.shape()[0];
+                if n <= remaining {
+                    remaining -= n;
+                    Some(chunk)
+                } else {
+                    pending = Some(chunk.select_axis(0, SelectInfoElem::from(remaining..n)));
+                    let head = chunk.select_axis(0, SelectInfoElem::from(0..remaining));
+                    remaining = 0;
+                    Some(head)
+                }
+            });
+            arrays.inner().add_data_from_iter(key, chunks.map(select_cols))?;
+        }
+        let extra = pending
+            .into_iter()
+            .chain(data)
+            .map(|x| x.shape()[0])
+            .sum::<usize>();
+        ensure!(
+            extra == 0,
+            "the iterator yields {} more rows than expected",
+            extra
+        );
+        Ok(())
+    }
+
+    /// Fails if the key is present in some components but not in the others.
+    /// Such arrays were not written through the stacked arrays, and replacing
+    /// them would mix unrelated data.
+    fn check_key(&self, key: &str) -> Result<()> {
+        let n = self
+            .arrays
+            .iter()
+            .filter(|x| x.inner().contains_key(key))
+            .count();
+        ensure!(
+            n == 0 || n == self.arrays.len(),
+            "'{}' already exists in {} of the {} components",
+            key,
+            n,
+            self.arrays.len(),
+        );
+        Ok(())
+    }
+
+    /// Complete a write split across the components. If it failed, the key is
+    /// removed from all components, so that they never hold a mix of old and
+    /// new parts.
+    fn finish_write(&self, key: &str, result: Result<()>) -> Result<()> {
+        if let Err(e) = result {
+            self.arrays
+                .iter()
+                .try_for_each(|arrays| arrays.inner().remove_data(key))
+                .context("failed to remove the parts already written")?;
+            return Err(e);
+        }
+        self.remove_annotation(key)
+    }
+
+    /// Remove the data from all components and from the arrays stored whole.
+    pub fn remove_data(&self, key: &str) -> Result<()> {
+        self.remove_annotation(key)?;
+        if self.annotation.is_some() && self.shared_keys().iter().all(|k| k != key) {
+            return Ok(());
+        }
+        self.index()?;
+        self.data.lock().remove(key);
+        self.arrays
+            .iter()
+            .try_for_each(|arrays| arrays.inner().remove_data(key))
+    }
+
+    /// Remove the array stored whole under `key`, which would otherwise hide
+    /// the stacked array with the same key.
+    fn remove_annotation(&self, key: &str) -> Result<()> {
+        match self.annotation.as_ref() {
+            Some(arrays) if !arrays.is_none() => arrays.inner().remove_data(key),
+            _ => Ok(()),
+        }
+    }
+
+    /// The columns of the data that belong to the i-th component together with
+    /// the expected number of columns of the data, or `None` if all columns are
+    /// kept.
+    fn component_columns(&self, i: usize) -> Result<Option<(SelectInfoElem, usize)>> {
+        match self.columns.as_ref().map(|x| &x[i]) {
+            Some(columns) if !columns.is_identity() => {
+                let inv = columns.inverse().ok_or_else(|| {
+                    anyhow::anyhow!("cannot split the data as some columns of the components are not joined")
+                })?;
+                Ok(Some((inv.into(), columns.len())))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Row offsets of the components. Fails if the arrays cannot be modified.
+    fn index(&self) -> Result<VecVecIndex> {
+        ensure!(
+            !self.arrays.is_empty() && self.arrays.iter().all(|x| !x.is_none()),
+            "stacked arrays are not available in all components"
+        );
+        ensure!(
+            self.arrays.len() == 1 || !matches!(self.axis, Axis::Pairwise),
+            "pairwise stacked arrays cannot be split across components"
+        );
+        Ok(self.arrays.iter().map(|x| x.inner().size()).collect())
     }
 }
@@ -3,6 +3,7 @@ pub(crate) mod collection;
 
 pub use base::{
     InnerDataFrameElem, DataFrameElem, Elem, Inner, ArrayElem, Slot,
-    StackedDataFrame, StackedArrayElem, ChunkedArrayElem, StackedChunkedArrayElem,
+    StackedDataFrame, StackedArrayElem, ChunkedArrayElem, StackedChunkedArrayElem, MapChunks,
+    CompactChunkedArrayElem,
 };
 pub use collection::{Dim, Axis, AxisArrays, ElemCollection, StackedAxisArrays};
\ No newline at end of file
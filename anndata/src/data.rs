@@ -10,6 +10,8 @@ pub use mapping::*;
 use crate::backend::{Backend, DataContainer, DataType, GroupOp};
 
 use anyhow::{bail, Ok, Result};
+use half::f16;
+use nalgebra_sparse::coo::CooMatrix;
 use nalgebra_sparse::csc::CscMatrix;
 use nalgebra_sparse::csr::CsrMatrix;
 use ndarray::{Array, RemoveAxis};
@@ -35,6 +37,12 @@ impl From<DataFrame> for Data {
     }
 }
 
+impl From<NullableArray> for Data {
+    fn from(data: NullableArray) -> Self {
+        Data::ArrayData(ArrayData::Nullable(data))
+    }
+}
+
 macro_rules! impl_into_data {
     ($from_type:ty, $to_type:ident) => {
         impl From<$from_type> for Data {
@@ -57,6 +65,11 @@ macro_rules! impl_into_data {
                 Data::ArrayData(ArrayData::CscMatrix(DynCscMatrix::$to_type(data)))
             }
         }
+        impl From<CooMatrix<$from_type>> for Data {
+            fn from(data: CooMatrix<$from_type>) -> Self {
+                Data::ArrayData(ArrayData::CooMatrix(DynCooMatrix::$to_type(data)))
+            }
+        }
     };
 }
 
@@ -68,6 +81,7 @@ impl_into_data!(u8, U8);
 impl_into_data!(u16, U16);
 impl_into_data!(u32, U32);
 impl_into_data!(u64, U64);
+impl_into_data!(f16, F16);
 impl_into_data!(f32, F32);
 impl_into_data!(f64, F64);
 impl_into_data!(bool, Bool);
@@ -114,8 +128,8 @@ macro_rules! impl_try_from_for_scalar {
 }
 
 impl_try_from_for_scalar!(
-    I8, i8, I16, i16, I32, i32, I64, i64, U8, u8, U16, u16, U32, u32, U64, u64, F32, f32, F64, f64,
-    Bool, bool, String, String
+    I8, i8, I16, i16, I32, i32, I64, i64, U8, u8, U16, u16, U32, u32, U64, u64, F16, f16, F32, f32,
+    F64, f64, Bool, bool, String, String
 );
 
 impl TryFrom<Data> for DataFrame {
@@ -129,6 +143,17 @@ impl TryFrom<Data> for DataFrame {
     }
 }
 
+impl TryFrom<Data> for NullableArray {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Data) -> Result<Self, Self::Error> {
+        match value {
+            Data::ArrayData(data) => data.try_into(),
+            _ => bail!("Cannot convert data to NullableArray"),
+        }
+    }
+}
+
 impl TryFrom<Data> for Mapping {
     type Error = anyhow::Error;
 
@@ -148,12 +173,14 @@ impl Readable for Data {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
         match container.encoding_type()? {
             DataType::Categorical
+            | DataType::Nullable(_)
             | DataType::Array(_)
             | DataType::DataFrame
             | DataType::CscMatrix(_)
             | DataType::CsrMatrix(_) => ArrayData::read(container).map(|x| x.into()),
             DataType::Scalar(_) => DynScalar::read(container).map(|x| x.into()),
             DataType::Mapping => Mapping::read(container).map(|x| x.into()),
+            ty => bail!("Cannot read type '{:?}'", ty),
         }
     }
 }
@@ -1,15 +1,24 @@
 mod chunks;
 pub mod dataframe;
 mod dense;
+mod product;
+mod reduce;
 pub mod slice;
 mod sparse;
 pub mod utils;
 
 pub use chunks::ArrayChunk;
+pub(crate) use chunks::write_csc_by_column_chunk;
 pub use dataframe::DataFrameIndex;
-pub use dense::{ArrayConvert, CategoricalArray, DynArray, DynCowArray, DynScalar};
+pub use dense::{ArrayConvert, CategoricalArray, DynArray, DynCowArray, DynScalar, NullableArray};
+pub use reduce::Reduction;
+pub(crate) use product::{dot_chunk, t_dot_chunk};
+pub(crate) use reduce::AxisStats;
 pub use slice::{SelectInfo, SelectInfoBounds, SelectInfoElem, SelectInfoElemBounds, Shape};
-pub use sparse::{CsrNonCanonical, DynCscMatrix, DynCsrMatrix, DynCsrNonCanonical};
+pub use sparse::{
+    CompactCsrMatrix, CsrNonCanonical, DynCooMatrix, DynCscMatrix, DynCsrMatrix, DynCsrNonCanonical,
+    SparseFormat,
+};
 
 use crate::backend::*;
 use crate::data::utils::from_csr_data;
@@ -17,6 +26,8 @@ use crate::data::{data_traits::*, DataType};
 
 use ::ndarray::{Array, Ix1, RemoveAxis};
 use anyhow::{bail, Result};
+use half::f16;
+use nalgebra_sparse::coo::CooMatrix;
 use nalgebra_sparse::csc::CscMatrix;
 use nalgebra_sparse::csr::CsrMatrix;
 use polars::prelude::DataFrame;
@@ -27,6 +38,8 @@ pub enum ArrayData {
     CsrMatrix(DynCsrMatrix),
     CsrNonCanonical(DynCsrNonCanonical),
     CscMatrix(DynCscMatrix),
+    CooMatrix(DynCooMatrix),
+    Nullable(NullableArray),
     DataFrame(DataFrame),
 }
 
@@ -48,6 +61,12 @@ impl From<DynArray> for ArrayData {
     }
 }
 
+impl From<NullableArray> for ArrayData {
+    fn from(data: NullableArray) -> Self {
+        ArrayData::Nullable(data)
+    }
+}
+
 impl From<DynCsrMatrix> for ArrayData {
     fn from(data: DynCsrMatrix) -> Self {
         ArrayData::CsrMatrix(data)
@@ -63,6 +82,11 @@ impl From<DynCscMatrix> for ArrayData {
         ArrayData::CscMatrix(data)
     }
 }
+impl From<DynCooMatrix> for ArrayData {
+    fn from(data: DynCooMatrix) -> Self {
+        ArrayData::CooMatrix(data)
+    }
+}
 
 impl TryFrom<ArrayData> for DynArray {
     type Error = anyhow::Error;
@@ -79,6 +103,7 @@ impl TryFrom<ArrayData> for DynCsrMatrix {
     fn try_from(value: ArrayData) -> Result<Self, Self::Error> {
         match value {
             ArrayData::CsrMatrix(data) => Ok(data),
+            ArrayData::CooMatrix(data) => data.to_csr(),
             _ => bail!("Cannot convert {:?} to DynCsrMatrix", value.data_type()),
         }
     }
@@ -90,6 +115,7 @@ impl TryFrom<ArrayData> for DynCsrNonCanonical {
         match value {
             ArrayData::CsrNonCanonical(data) => Ok(data),
             ArrayData::CsrMatrix(data) => Ok(data.into()),
+            ArrayData::CooMatrix(data) => Ok(data.to_csr()?.into()),
             _ => bail!(
                 "Cannot convert {:?} to DynCsrNonCanonical",
                 value.data_type()
@@ -103,11 +129,34 @@ impl TryFrom<ArrayData> for DynCscMatrix {
     fn try_from(value: ArrayData) -> Result<Self, Self::Error> {
         match value {
             ArrayData::CscMatrix(data) => Ok(data),
+            ArrayData::CooMatrix(data) => data.to_csc(),
             _ => bail!("Cannot convert {:?} to DynCscMatrix", value.data_type()),
         }
     }
 }
 
+impl TryFrom<ArrayData> for NullableArray {
+    type Error = anyhow::Error;
+    fn try_from(value: ArrayData) -> Result<Self, Self::Error> {
+        match value {
+            ArrayData::Nullable(data) => Ok(data),
+            _ => bail!("Cannot convert {:?} to NullableArray", value.data_type()),
+        }
+    }
+}
+
+impl TryFrom<ArrayData> for DynCooMatrix {
+    type Error = anyhow::Error;
+    fn try_from(value: ArrayData) -> Result<Self, Self::Error> {
+        match value {
+            ArrayData::CooMatrix(data) => Ok(data),
+            ArrayData::CsrMatrix(data) => Ok(DynCsrNonCanonical::from(data).into()),
+            ArrayData::CsrNonCanonical(data) => Ok(data.into()),
+            _ => bail!("Cannot convert {:?} to DynCooMatrix", value.data_type()),
+        }
+    }
+}
+
 impl TryFrom<ArrayData> for DataFrame {
     type Error = anyhow::Error;
     fn try_from(value: ArrayData) -> Result<Self, Self::Error> {
@@ -145,6 +194,15 @@ where CscMatrix<T>: TryFrom<DynCscMatrix, Error = anyhow::Error>
     }
 }
 
+impl<T> TryFrom<ArrayData> for CooMatrix<T>
+where CooMatrix<T>: TryFrom<DynCooMatrix, Error = anyhow::Error>
+{
+    type Error = anyhow::Error;
+    fn try_from(value: ArrayData) -> Result<Self, Self::Error> {
+        DynCooMatrix::try_from(value)?.try_into()
+    }
+}
+
 impl<T> TryFrom<ArrayData> for CsrNonCanonical<T>
 where CsrNonCanonical<T>: TryFrom<DynCsrNonCanonical, Error = anyhow::Error>
 {
@@ -186,11 +244,16 @@ macro_rules! impl_arraydata_traits {
                     ArrayData::CscMatrix(data.into())
                 }
             }
+            impl From<CooMatrix<$ty>> for ArrayData {
+                fn from(data: CooMatrix<$ty>) -> Self {
+                    ArrayData::CooMatrix(data.into())
+                }
+            }
         )*
     };
 }
 
-impl_arraydata_traits!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool, String);
+impl_arraydata_traits!(i8, i16, i32, i64, u8, u16, u32, u64, f16, f32, f64, bool, String);
 
 impl Readable for ArrayData {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
@@ -200,6 +263,7 @@ impl Readable for ArrayData {
             }
             DataType::CsrMatrix(_) => read_csr(container),
             DataType::CscMatrix(_) => DynCscMatrix::read(container).map(ArrayData::CscMatrix),
+            DataType::Nullable(_) => NullableArray::read(container).map(ArrayData::Nullable),
             DataType::DataFrame => DataFrame::read(container).map(ArrayData::DataFrame),
             ty => bail!("Cannot read type '{:?}' as matrix data", ty),
         }
@@ -213,6 +277,8 @@ impl Element for ArrayData {
             ArrayData::CsrMatrix(data) => data.data_type(),
             ArrayData::CsrNonCanonical(data) => data.data_type(),
             ArrayData::CscMatrix(data) => data.data_type(),
+            ArrayData::CooMatrix(data) => data.data_type(),
+            ArrayData::Nullable(data) => data.data_type(),
             ArrayData::DataFrame(data) => data.data_type(),
         }
     }
@@ -223,6 +289,8 @@ impl Element for ArrayData {
             ArrayData::CsrMatrix(data) => data.metadata(),
             ArrayData::CsrNonCanonical(data) => data.metadata(),
             ArrayData::CscMatrix(data) => data.metadata(),
+            ArrayData::CooMatrix(data) => data.metadata(),
+            ArrayData::Nullable(data) => data.metadata(),
             ArrayData::DataFrame(data) => data.metadata(),
         }
     }
@@ -239,6 +307,8 @@ impl Writable for ArrayData {
             ArrayData::CsrMatrix(data) => data.write(location, name),
             ArrayData::CsrNonCanonical(data) => data.write(location, name),
             ArrayData::CscMatrix(data) => data.write(location, name),
+            ArrayData::CooMatrix(data) => data.write(location, name),
+            ArrayData::Nullable(data) => data.write(location, name),
             ArrayData::DataFrame(data) => data.write(location, name),
         }
     }
@@ -251,6 +321,8 @@ impl HasShape for ArrayData {
             ArrayData::CsrMatrix(data) => data.shape(),
             ArrayData::CsrNonCanonical(data) => data.shape(),
             ArrayData::CscMatrix(data) => data.shape(),
+            ArrayData::CooMatrix(data) => data.shape(),
+            ArrayData::Nullable(data) => data.shape(),
             ArrayData::DataFrame(data) => HasShape::shape(data),
         }
     }
@@ -275,6 +347,8 @@ impl Selectable for ArrayData {
             ArrayData::CsrMatrix(data) => data.select(info).into(),
             ArrayData::CsrNonCanonical(data) => data.select(info).into(),
             ArrayData::CscMatrix(data) => data.select(info).into(),
+            ArrayData::CooMatrix(data) => data.select(info).into(),
+            ArrayData::Nullable(data) => data.select(info).into(),
             ArrayData::DataFrame(data) => Selectable::select(data, info).into(),
         }
     }
@@ -303,6 +377,12 @@ impl Stackable for ArrayData {
                 DynCsrNonCanonical::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into())
             }
             ArrayData::CscMatrix(_) => todo!(),
+            ArrayData::CooMatrix(_) => {
+                DynCooMatrix::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into())
+            }
+            ArrayData::Nullable(_) => {
+                NullableArray::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into())
+            }
             ArrayData::DataFrame(_) => {
                 <DataFrame as Stackable>::vstack(iter.map(|x| x.try_into().unwrap()))
                     .map(|x| x.into())
@@ -317,6 +397,7 @@ impl ReadableArray for ArrayData {
             DataType::Categorical | DataType::Array(_) => DynArray::get_shape(container),
             DataType::CsrMatrix(_) => DynCsrMatrix::get_shape(container),
             DataType::CscMatrix(_) => DynCscMatrix::get_shape(container),
+            DataType::Nullable(_) => NullableArray::get_shape(container),
             DataType::DataFrame => DataFrame::get_shape(container),
             ty => bail!("Cannot read shape information from type '{}'", ty),
         }
@@ -335,6 +416,9 @@ impl ReadableArray for ArrayData {
             DataType::CscMatrix(_) => {
                 DynCscMatrix::read_select(container, info).map(ArrayData::CscMatrix)
             }
+            DataType::Nullable(_) => {
+                NullableArray::read_select(container, info).map(ArrayData::Nullable)
+            }
             DataType::DataFrame => {
                 DataFrame::read_select(container, info).map(ArrayData::DataFrame)
             }
@@ -384,6 +468,7 @@ fn read_csr<B: Backend>(container: &DataContainer<B>) -> Result<ArrayData> {
             ScalarType::U16 => _read_csr::<B, u16>(container),
             ScalarType::U32 => _read_csr::<B, u32>(container),
             ScalarType::U64 => _read_csr::<B, u64>(container),
+            ScalarType::F16 => _read_csr::<B, f16>(container),
             ScalarType::F32 => _read_csr::<B, f32>(container),
             ScalarType::F64 => _read_csr::<B, f64>(container),
             ScalarType::Bool => _read_csr::<B, bool>(container),
@@ -458,6 +543,7 @@ where
             ScalarType::U16 => _read_csr::<B, u16, _>(container, info),
             ScalarType::U32 => _read_csr::<B, u32, _>(container, info),
             ScalarType::U64 => _read_csr::<B, u64, _>(container, info),
+            ScalarType::F16 => _read_csr::<B, f16, _>(container, info),
             ScalarType::F32 => _read_csr::<B, f32, _>(container, info),
             ScalarType::F64 => _read_csr::<B, f64, _>(container, info),
             ScalarType::Bool => _read_csr::<B, bool, _>(container, info),
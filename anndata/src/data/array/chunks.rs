@@ -1,16 +1,19 @@
 use crate::backend::{Backend, DataContainer, GroupOp, AttributeOp, BackendData, ScalarType};
-use crate::Selectable;
+use crate::{Selectable, Writable};
+use crate::data::Stackable;
 use crate::data::{
     array::DynArray,
     ArrayData,
-    array::utils::ExtendableDataset,
+    array::utils::{write_indptr, ExtendableDataset, ExtendableIndices},
 };
 
 use anyhow::{bail, Result, Context};
+use half::f16;
 use ndarray::{Array, ArrayView1, ArrayD, RemoveAxis};
 use nalgebra_sparse::na::Scalar;
-use nalgebra_sparse::{CsrMatrix, CscMatrix};
-use super::{DynCsrMatrix, DynCscMatrix, DynCsrNonCanonical, CsrNonCanonical};
+use nalgebra_sparse::{CooMatrix, CsrMatrix, CscMatrix};
+use super::sparse::coo_to_csr_non_canonical;
+use super::{NullableArray, CompactCsrMatrix, DynCooMatrix, DynCsrMatrix, DynCscMatrix, DynCsrNonCanonical, CsrNonCanonical};
 
 pub trait ArrayChunk: Selectable {
     fn write_by_chunk<B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
@@ -33,6 +36,9 @@ impl ArrayChunk for ArrayData {
             ArrayData::CsrMatrix(_) | ArrayData::CsrNonCanonical(_) =>
                 DynCsrNonCanonical::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             ArrayData::CscMatrix(_) => DynCscMatrix::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            ArrayData::CooMatrix(_) => DynCooMatrix::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            ArrayData::Nullable(_) => NullableArray::vstack(iter.map(|x| x.try_into().unwrap()))?
+                .write(location, name),
             ArrayData::DataFrame(_) => todo!(),
         }
     }
@@ -55,6 +61,7 @@ impl ArrayChunk for DynArray {
             DynArray::I16(_) => ArrayD::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::I32(_) => ArrayD::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::I64(_) => ArrayD::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynArray::F16(_) => ArrayD::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::F32(_) => ArrayD::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::F64(_) => ArrayD::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::Bool(_) => ArrayD::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -112,6 +119,7 @@ impl ArrayChunk for DynCsrMatrix {
             DynCsrMatrix::I16(_) => CsrMatrix::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::I32(_) => CsrMatrix::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::I64(_) => CsrMatrix::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCsrMatrix::F16(_) => CsrMatrix::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::F32(_) => CsrMatrix::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::F64(_) => CsrMatrix::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::Bool(_) => CsrMatrix::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -122,12 +130,13 @@ impl ArrayChunk for DynCsrMatrix {
 
 
 impl<T: BackendData> ArrayChunk for CsrMatrix<T> {
-    fn write_by_chunk<B, G, I>(mut iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
+    fn write_by_chunk<B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
     where
         I: Iterator<Item = Self>,
         B: Backend,
         G: GroupOp<B>,
     {
+        let mut iter = iter.peekable();
         let mut group = location.new_group(name)?;
         group.new_attr("encoding-type", "csr_matrix")?;
         group.new_attr("encoding-version", "0.1.0")?;
@@ -136,10 +145,9 @@ impl<T: BackendData> ArrayChunk for CsrMatrix<T> {
         let mut data: ExtendableDataset<B, T> = ExtendableDataset::with_capacity(
             &group, "data", 1000.into(),
         )?;
-        let mut indices: ExtendableDataset<B, i64> = ExtendableDataset::with_capacity(
-            &group, "indices", 1000.into(),
-        )?;
-        let mut indptr: Vec<i64> = Vec::new();
+        let max_col = iter.peek().map_or(0, |csr| csr.ncols().saturating_sub(1));
+        let mut indices: ExtendableIndices<B> = ExtendableIndices::with_capacity(&group, "indices", 1000, max_col)?;
+        let mut indptr: Vec<usize> = Vec::new();
         let mut num_rows = 0;
         let mut num_cols: Option<usize> = None;
         let mut nnz = 0;
@@ -154,10 +162,10 @@ impl<T: BackendData> ArrayChunk for CsrMatrix<T> {
                 let (indptr_, indices_, data_) = csr.csr_data();
                 indptr_[..indptr_.len() - 1]
                     .iter()
-                    .for_each(|x| indptr.push(i64::try_from(*x).unwrap() + nnz));
-                nnz += *indptr_.last().unwrap_or(&0) as i64;
+                    .for_each(|x| indptr.push(*x + nnz));
+                nnz += *indptr_.last().unwrap_or(&0);
                 data.extend(0, ArrayView1::from_shape(data_.len(), data_)?)?;
-                indices.extend(0, ArrayView1::from_shape(indices_.len(), indices_)?.mapv(|x| i64::try_from(x).unwrap()).view())
+                indices.extend(indices_.iter().copied())
             } else {
                 bail!("All matrices must have the same number of columns");
             }
@@ -166,7 +174,59 @@ impl<T: BackendData> ArrayChunk for CsrMatrix<T> {
         indices.finish()?;
         data.finish()?;
         indptr.push(nnz);
-        group.new_array_dataset("indptr", indptr.into(), Default::default())?;
+        write_indptr::<B, _>(&group, &indptr)?;
+        group.new_attr("shape", [num_rows as u64, num_cols.unwrap_or(0) as u64].as_slice())?;
+        Ok(DataContainer::Group(group))
+    }
+}
+
+/// The column indices are written as they are, without being widened first.
+impl<T: BackendData> ArrayChunk for CompactCsrMatrix<T> {
+    fn write_by_chunk<B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
+    where
+        I: Iterator<Item = Self>,
+        B: Backend,
+        G: GroupOp<B>,
+    {
+        let mut iter = iter.peekable();
+        let mut group = location.new_group(name)?;
+        group.new_attr("encoding-type", "csr_matrix")?;
+        group.new_attr("encoding-version", "0.1.0")?;
+        group.new_attr("h5sparse_format", "csr")?;
+
+        let mut data: ExtendableDataset<B, T> = ExtendableDataset::with_capacity(
+            &group, "data", 1000.into(),
+        )?;
+        let max_col = iter.peek().map_or(0, |csr| csr.ncols().saturating_sub(1));
+        let mut indices: ExtendableIndices<B> = ExtendableIndices::with_capacity(&group, "indices", 1000, max_col)?;
+        let mut indptr: Vec<usize> = Vec::new();
+        let mut num_rows = 0;
+        let mut num_cols: Option<usize> = None;
+        let mut nnz = 0;
+
+        iter.try_for_each(|csr| {
+            let c = csr.ncols();
+            if num_cols.is_none() {
+                num_cols = Some(c);
+            }
+            if num_cols.unwrap() == c {
+                num_rows += csr.nrows();
+                let indptr_ = csr.row_offsets();
+                indptr_[..indptr_.len() - 1]
+                    .iter()
+                    .for_each(|x| indptr.push(*x + nnz));
+                nnz += csr.nnz();
+                data.extend(0, ArrayView1::from(csr.values()))?;
+                indices.extend(csr.col_indices().iter().map(|x| *x as usize))
+            } else {
+                bail!("All matrices must have the same number of columns");
+            }
+        })?;
+
+        indices.finish()?;
+        data.finish()?;
+        indptr.push(nnz);
+        write_indptr::<B, _>(&group, &indptr)?;
         group.new_attr("shape", [num_rows as u64, num_cols.unwrap_or(0) as u64].as_slice())?;
         Ok(DataContainer::Group(group))
     }
@@ -189,6 +249,7 @@ impl ArrayChunk for DynCsrNonCanonical {
             DynCsrNonCanonical::I16(_) => CsrNonCanonical::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::I32(_) => CsrNonCanonical::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::I64(_) => CsrNonCanonical::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCsrNonCanonical::F16(_) => CsrNonCanonical::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::F32(_) => CsrNonCanonical::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::F64(_) => CsrNonCanonical::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::Bool(_) => CsrNonCanonical::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -198,12 +259,13 @@ impl ArrayChunk for DynCsrNonCanonical {
 }
 
 impl<T: BackendData> ArrayChunk for CsrNonCanonical<T> {
-    fn write_by_chunk<B, G, I>(mut iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
+    fn write_by_chunk<B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
     where
         I: Iterator<Item = Self>,
         B: Backend,
         G: GroupOp<B>,
     {
+        let mut iter = iter.peekable();
         let mut group = location.new_group(name)?;
         group.new_attr("encoding-type", "csr_matrix")?;
         group.new_attr("encoding-version", "0.1.0")?;
@@ -212,10 +274,9 @@ impl<T: BackendData> ArrayChunk for CsrNonCanonical<T> {
         let mut data: ExtendableDataset<B, T> = ExtendableDataset::with_capacity(
             &group, "data", 1000.into(),
         )?;
-        let mut indices: ExtendableDataset<B, i64> = ExtendableDataset::with_capacity(
-            &group, "indices", 1000.into(),
-        )?;
-        let mut indptr: Vec<i64> = Vec::new();
+        let max_col = iter.peek().map_or(0, |csr| csr.ncols().saturating_sub(1));
+        let mut indices: ExtendableIndices<B> = ExtendableIndices::with_capacity(&group, "indices", 1000, max_col)?;
+        let mut indptr: Vec<usize> = Vec::new();
         let mut num_rows = 0;
         let mut num_cols: Option<usize> = None;
         let mut nnz = 0;
@@ -230,10 +291,10 @@ impl<T: BackendData> ArrayChunk for CsrNonCanonical<T> {
                 let (indptr_, indices_, data_) = csr.csr_data();
                 indptr_[..indptr_.len() - 1]
                     .iter()
-                    .for_each(|x| indptr.push(i64::try_from(*x).unwrap() + nnz));
-                nnz += *indptr_.last().unwrap_or(&0) as i64;
+                    .for_each(|x| indptr.push(*x + nnz));
+                nnz += *indptr_.last().unwrap_or(&0);
                 data.extend(0, ArrayView1::from_shape(data_.len(), data_)?)?;
-                indices.extend(0, ArrayView1::from_shape(indices_.len(), indices_)?.mapv(|x| i64::try_from(x).unwrap()).view())
+                indices.extend(indices_.iter().copied())
             } else {
                 bail!("All matrices must have the same number of columns");
             }
@@ -242,7 +303,7 @@ impl<T: BackendData> ArrayChunk for CsrNonCanonical<T> {
         indices.finish()?;
         data.finish()?;
         indptr.push(nnz);
-        group.new_array_dataset("indptr", indptr.into(), Default::default())?;
+        write_indptr::<B, _>(&group, &indptr)?;
         group.new_attr("shape", [num_rows as u64, num_cols.unwrap_or(0) as u64].as_slice())?;
         Ok(DataContainer::Group(group))
     }
@@ -266,6 +327,7 @@ impl ArrayChunk for DynCscMatrix {
             DynCscMatrix::I16(_) => CscMatrix::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::I32(_) => CscMatrix::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::I64(_) => CscMatrix::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCscMatrix::F16(_) => CscMatrix::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::F32(_) => CscMatrix::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::F64(_) => CscMatrix::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::Bool(_) => CscMatrix::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -275,6 +337,123 @@ impl ArrayChunk for DynCscMatrix {
 }
 
 
+impl ArrayChunk for DynCooMatrix {
+    fn write_by_chunk<B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
+    where
+        I: Iterator<Item = Self>,
+        B: Backend,
+        G: GroupOp<B>,
+    {
+        let mut iter = iter.peekable();
+        match iter.peek().context("input iterator is empty")? {
+            DynCooMatrix::U8(_) => CooMatrix::<u8>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::U16(_) => CooMatrix::<u16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::U32(_) => CooMatrix::<u32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::U64(_) => CooMatrix::<u64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::I8(_) => CooMatrix::<i8>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::I16(_) => CooMatrix::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::I32(_) => CooMatrix::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::I64(_) => CooMatrix::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::F16(_) => CooMatrix::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::F32(_) => CooMatrix::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::F64(_) => CooMatrix::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::Bool(_) => CooMatrix::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCooMatrix::String(_) => CooMatrix::<String>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+        }
+    }
+}
+
+/// Chunks are stacked vertically and written as a CSR matrix, since COO is not
+/// part of the anndata on-disk format. Duplicated entries are kept.
+impl<T: BackendData> ArrayChunk for CooMatrix<T> {
+    fn write_by_chunk<B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
+    where
+        I: Iterator<Item = Self>,
+        B: Backend,
+        G: GroupOp<B>,
+    {
+        CsrNonCanonical::<T>::write_by_chunk(iter.map(|coo| coo_to_csr_non_canonical(&coo)), location, name)
+    }
+}
+
+
+/// Write a CSC matrix from an iterator of column chunks. In contrast to
+/// [`ArrayChunk::write_by_chunk`], which stacks chunks vertically, the chunks
+/// are concatenated horizontally, which matches the on-disk layout of CSC matrices.
+pub(crate) fn write_csc_by_column_chunk<B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
+where
+    I: Iterator<Item = DynCscMatrix>,
+    B: Backend,
+    G: GroupOp<B>,
+{
+    let mut iter = iter.peekable();
+    match iter.peek().context("input iterator is empty")? {
+        DynCscMatrix::U8(_) => hstack_csc_by_chunk::<u8, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::U16(_) => hstack_csc_by_chunk::<u16, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::U32(_) => hstack_csc_by_chunk::<u32, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::U64(_) => hstack_csc_by_chunk::<u64, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::I8(_) => hstack_csc_by_chunk::<i8, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::I16(_) => hstack_csc_by_chunk::<i16, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::I32(_) => hstack_csc_by_chunk::<i32, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::I64(_) => hstack_csc_by_chunk::<i64, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::F16(_) => hstack_csc_by_chunk::<f16, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::F32(_) => hstack_csc_by_chunk::<f32, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::F64(_) => hstack_csc_by_chunk::<f64, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::Bool(_) => hstack_csc_by_chunk::<bool, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+        DynCscMatrix::String(_) => hstack_csc_by_chunk::<String, _, _, _>(iter.map(|x| x.try_into().unwrap()), location, name),
+    }
+}
+
+fn hstack_csc_by_chunk<T, B, G, I>(iter: I, location: &G, name: &str) -> Result<DataContainer<B>>
+where
+    T: BackendData,
+    I: Iterator<Item = CscMatrix<T>>,
+    B: Backend,
+    G: GroupOp<B>,
+{
+    let mut iter = iter.peekable();
+    let mut group = location.new_group(name)?;
+    group.new_attr("encoding-type", "csc_matrix")?;
+    group.new_attr("encoding-version", "0.1.0")?;
+    group.new_attr("h5sparse_format", "csc")?;
+
+    let mut data: ExtendableDataset<B, T> = ExtendableDataset::with_capacity(
+        &group, "data", 1000.into(),
+    )?;
+    let max_row = iter.peek().map_or(0, |csc| csc.nrows().saturating_sub(1));
+    let mut indices: ExtendableIndices<B> = ExtendableIndices::with_capacity(&group, "indices", 1000, max_row)?;
+    let mut indptr: Vec<usize> = Vec::new();
+    let mut num_cols = 0;
+    let mut num_rows: Option<usize> = None;
+    let mut nnz = 0;
+
+    iter.try_for_each(|csc| {
+        let r = csc.nrows();
+        if num_rows.is_none() {
+            num_rows = Some(r);
+        }
+        if num_rows.unwrap() == r {
+            num_cols += csc.ncols();
+            let (indptr_, indices_, data_) = csc.csc_data();
+            indptr_[..indptr_.len() - 1]
+                .iter()
+                .for_each(|x| indptr.push(*x + nnz));
+            nnz += *indptr_.last().unwrap_or(&0);
+            data.extend(0, ArrayView1::from_shape(data_.len(), data_)?)?;
+            indices.extend(indices_.iter().copied())
+        } else {
+            bail!("All matrices must have the same number of rows");
+        }
+    })?;
+
+    indices.finish()?;
+    data.finish()?;
+    indptr.push(nnz);
+    write_indptr::<B, _>(&group, &indptr)?;
+    group.new_attr("shape", [num_rows.unwrap_or(0) as u64, num_cols as u64].as_slice())?;
+    Ok(DataContainer::Group(group))
+}
+
 impl<T: BackendData+Scalar> ArrayChunk for CscMatrix<T> {
         // TODO! more efficent way should be implement
         // Ok(iter.reduce(|acc, x| vstack_csc(acc, x)).unwrap())
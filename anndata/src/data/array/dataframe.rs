@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::backend::{AttributeOp, Backend, DataContainer, DatasetOp, GroupOp, ScalarType};
 use crate::data::array::{
     slice::{SelectInfoElem, Shape},
-    CategoricalArray, DynArray,
+    CategoricalArray, DynArray, NullableArray,
 };
 use crate::data::data_traits::*;
 use crate::data::index::{Index, Interval};
@@ -42,53 +42,79 @@ impl Writable for DataFrame {
         location: &G,
         name: &str,
     ) -> Result<DataContainer<B>> {
-        let mut group = if location.exists(name)? {
-            location.open_group(name)?
-        } else {
-            location.new_group(name)?
-        };
-        self.metadata().save_metadata(&mut group)?;
+        write_dataframe(self, location, name, &HashSet::new())
+    }
 
-        self.iter().try_for_each(|x| {
-            write_series(x, &group, x.name())?;
-            anyhow::Ok(())
-        })?;
+    /// Overwrite the data inplace.
+    fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
+        overwrite_dataframe(self, container, &HashSet::new())
+    }
+}
 
-        let mut container = DataContainer::Group(group);
+/// Write a dataframe. Columns with missing values use the nullable encodings,
+/// and so do the columns in `nullable`, e.g., the columns read from a nullable
+/// encoding, so that they keep their encoding when written back.
+pub(crate) fn write_dataframe<B: Backend, G: GroupOp<B>>(
+    df: &DataFrame,
+    location: &G,
+    name: &str,
+    nullable: &HashSet<String>,
+) -> Result<DataContainer<B>> {
+    let mut group = if location.exists(name)? {
+        location.open_group(name)?
+    } else {
+        location.new_group(name)?
+    };
+    df.metadata().save_metadata(&mut group)?;
+    write_columns(df, &group, nullable)?;
 
-        // Create an index as the python anndata package enforce it. This is not used by this library
-        DataFrameIndex::from(self.height()).overwrite(&mut container)?;
+    let mut container = DataContainer::Group(group);
 
-        Ok(container)
-    }
+    // Create an index as the python anndata package enforce it. This is not used by this library
+    DataFrameIndex::from(df.height()).overwrite(&mut container)?;
 
-    /// Overwrite the data inplace.
-    fn overwrite<B: Backend>(&self, mut container: DataContainer<B>) -> Result<DataContainer<B>> {
-        if let Ok(index_name) = container.get_attr::<String>("_index") {
-            for obj in container.as_group()?.list()? {
-                if obj != index_name {
-                    container.as_group()?.delete(&obj)?;
-                }
-            }
-            let n = self.height();
-            if n != 0 && n != container.as_group()?.open_dataset(&index_name)?.shape()[0] {
-                DataFrameIndex::from(self.height()).overwrite(&mut container)?;
-            }
-        } else {
-            for obj in container.as_group()?.list()? {
+    Ok(container)
+}
+
+/// Overwrite a stored dataframe inplace, see [`write_dataframe`].
+pub(crate) fn overwrite_dataframe<B: Backend>(
+    df: &DataFrame,
+    mut container: DataContainer<B>,
+    nullable: &HashSet<String>,
+) -> Result<DataContainer<B>> {
+    if let Ok(index_name) = container.get_attr::<String>("_index") {
+        for obj in container.as_group()?.list()? {
+            if obj != index_name {
                 container.as_group()?.delete(&obj)?;
             }
-            DataFrameIndex::from(self.height()).overwrite(&mut container)?;
         }
+        let n = df.height();
+        if n != 0 && n != container.as_group()?.open_dataset(&index_name)?.shape()[0] {
+            DataFrameIndex::from(df.height()).overwrite(&mut container)?;
+        }
+    } else {
+        for obj in container.as_group()?.list()? {
+            container.as_group()?.delete(&obj)?;
+        }
+        DataFrameIndex::from(df.height()).overwrite(&mut container)?;
+    }
+
+    write_columns(df, container.as_group()?, nullable)?;
+    df.metadata().save_metadata(&mut container)?;
 
-        self.iter().try_for_each(|x| {
-            write_series(x, container.as_group()?, x.name())?;
-            anyhow::Ok(())
-        })?;
-        self.metadata().save_metadata(&mut container)?;
+    Ok(container)
+}
 
-        Ok(container)
+/// The columns of a stored dataframe that use a nullable encoding.
+pub(crate) fn nullable_columns<B: Backend>(container: &DataContainer<B>) -> Result<HashSet<String>> {
+    let group = container.as_group()?;
+    let mut nullable = HashSet::new();
+    for name in container.get_attr::<Vec<String>>("column-order")? {
+        if let crate::backend::DataType::Nullable(_) = DataContainer::open(group, &name)?.encoding_type()? {
+            nullable.insert(name);
+        }
     }
+    Ok(nullable)
 }
 
 impl Readable for DataFrame {
@@ -167,10 +193,7 @@ impl ReadableArray for DataFrame {
             .iter()
             .map(|i| {
                 let name = &columns[i];
-                let series = container
-                    .as_group()?
-                    .open_dataset(name)
-                    .map(DataContainer::Dataset)
+                let series = DataContainer::<B>::open(container.as_group()?, name)
                     .and_then(|x| read_series::<B>(&x))
                     .with_context(|| format!("Failed to read series: {}", name))?;
  
@@ -347,11 +370,17 @@ where
 /// Helper functions
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Write a series. Nullable series use the nullable encodings, except for
+/// categorical ones, which encode missing values in their codes.
 fn write_series<B: Backend, G: GroupOp<B>>(
     series: &Series,
     location: &G,
     name: &str,
+    nullable: bool,
 ) -> Result<DataContainer<B>> {
+    if nullable && !matches!(series.dtype(), DataType::Categorical(_, _)) {
+        return write_nullable_series(series, location, name);
+    }
     match series.dtype() {
         DataType::UInt8 => series
             .u8()?
@@ -446,10 +475,71 @@ fn read_series<B: Backend>(container: &DataContainer<B>) -> Result<Series> {
             Ok(s)
         },
         crate::backend::DataType::Array(_) => Ok(DynArray::read(container)?.into()),
+        crate::backend::DataType::Nullable(_) => Ok(NullableArray::read(container)?.into()),
         _ => bail!("Unsupported data type: {:?}", ty),
     }
 }
 
+fn write_columns<B: Backend, G: GroupOp<B>>(
+    df: &DataFrame,
+    group: &G,
+    nullable: &HashSet<String>,
+) -> Result<()> {
+    df.iter().try_for_each(|x| {
+        let name: &str = x.name();
+        write_series(x, group, name, x.null_count() > 0 || nullable.contains(name))?;
+        anyhow::Ok(())
+    })
+}
+
+/// Write series with a nullable dtype. Integers, booleans and strings use the
+/// nullable encodings, i.e., a `values` array and a `mask` array that is true
+/// for missing entries. Missing floats are stored as NaN.
+fn write_nullable_series<B: Backend, G: GroupOp<B>>(
+    series: &Series,
+    location: &G,
+    name: &str,
+) -> Result<DataContainer<B>> {
+    let mask: Array1<bool> = series
+        .is_null()
+        .into_iter()
+        .map(|x| x.unwrap_or(true))
+        .collect();
+
+    macro_rules! nullable {
+        ($ca:expr) => {{
+            let values: Array1<_> = $ca.into_iter().map(|x| x.unwrap_or_default()).collect();
+            NullableArray::new(values.into(), mask.into_dyn())?.write(location, name)
+        }};
+    }
+
+    match series.dtype() {
+        DataType::UInt8 => nullable!(series.u8()?),
+        DataType::UInt16 => nullable!(series.u16()?),
+        DataType::UInt32 => nullable!(series.u32()?),
+        DataType::UInt64 => nullable!(series.u64()?),
+        DataType::Int8 => nullable!(series.i8()?),
+        DataType::Int16 => nullable!(series.i16()?),
+        DataType::Int32 => nullable!(series.i32()?),
+        DataType::Int64 => nullable!(series.i64()?),
+        DataType::Boolean => nullable!(series.bool()?),
+        DataType::String => nullable!(series.str()?.into_iter().map(|x| x.map(|x| x.to_string()))),
+        DataType::Float32 => series
+            .f32()?
+            .into_iter()
+            .map(|x| x.unwrap_or(f32::NAN))
+            .collect::<Array1<_>>()
+            .write(location, name),
+        DataType::Float64 => series
+            .f64()?
+            .into_iter()
+            .map(|x| x.unwrap_or(f64::NAN))
+            .collect::<Array1<_>>()
+            .write(location, name),
+        other => bail!("Unsupported series data type: {:?}", other),
+    }
+}
+
 /// Used to read non-string categorical data into regular arrays. After all, such
 /// data should not be stored as categorical data.
 fn read_cat_as_series<B: Backend>(container: &DataContainer<B>) -> Result<Series> {
@@ -459,6 +549,11 @@ fn read_cat_as_series<B: Backend>(container: &DataContainer<B>) -> Result<Series
     let categories = group.open_dataset("categories")?.read_dyn_array().unwrap();
 
     macro_rules! fun {
+        // Polars has no half-precision floats.
+        (F16, $value:expr) => {{
+            let value = $value.mapv(|x| x.to_f32());
+            fun!(F32, value)
+        }};
         ($variant:ident, $value:expr) => {
             codes.iter().map(|x| x.map(|i| $value[i].clone())).collect()
         };
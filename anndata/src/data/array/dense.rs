@@ -10,7 +10,7 @@ use crate::{
     },
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use ndarray::{Array, Array1, ArrayD, ArrayView, Axis, Dimension, RemoveAxis, SliceInfoElem};
 use polars::{
     prelude::CategoricalChunkedBuilder,
@@ -311,3 +311,149 @@ impl ReadableArray for CategoricalArray {
         Ok(CategoricalArray { codes, categories })
     }
 }
+
+/// NullableArrays store integers, booleans or strings with missing values.
+/// The values are stored together with a mask that is true for the missing
+/// entries. The values at the masked positions are unspecified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullableArray {
+    pub values: DynArray,
+    pub mask: ArrayD<bool>,
+}
+
+impl NullableArray {
+    pub fn new(values: DynArray, mask: ArrayD<bool>) -> Result<Self> {
+        if values.shape().as_ref() != mask.shape() {
+            bail!(
+                "the values ({}) and the mask ({:?}) of a nullable array must have the same shape",
+                values.shape(),
+                mask.shape()
+            );
+        }
+        Ok(Self { values, mask })
+    }
+}
+
+impl Into<Series> for NullableArray {
+    fn into(self) -> Series {
+        let mask = self.mask;
+        macro_rules! fun {
+            // Polars has no half-precision floats.
+            (F16, $value:expr) => {{
+                let value = $value.mapv(|x| x.to_f32());
+                fun!(F32, value)
+            }};
+            ($variant:ident, $value:expr) => {
+                $value
+                    .iter()
+                    .zip(mask.iter())
+                    .map(|(x, m)| if *m { None } else { Some(x.clone()) })
+                    .collect::<Series>()
+            };
+        }
+        let series = crate::macros::dyn_map!(self.values, DynArray, fun).rechunk();
+        // Keep the validity even if no value is missing, so that the series
+        // is written with the nullable encoding again.
+        let validity = mask.iter().map(|m| !m).collect();
+        let chunk = series.chunks()[0].with_validity(Some(validity));
+        Series::try_from((series.name().clone(), chunk)).unwrap()
+    }
+}
+
+impl Element for NullableArray {
+    fn metadata(&self) -> MetaData {
+        let encoding_type = match self.values {
+            DynArray::Bool(_) => "nullable-boolean",
+            DynArray::String(_) => "nullable-string-array",
+            _ => "nullable-integer",
+        };
+        MetaData::new(encoding_type, "0.1.0", None)
+    }
+
+    fn data_type(&self) -> DataType {
+        match self.values.data_type() {
+            DataType::Array(ty) => DataType::Nullable(ty),
+            ty => ty,
+        }
+    }
+}
+
+impl Writable for NullableArray {
+    fn write<B: Backend, G: GroupOp<B>>(
+        &self,
+        location: &G,
+        name: &str,
+    ) -> Result<DataContainer<B>> {
+        let mut group = location.new_group(name)?;
+        self.metadata().save_metadata(&mut group)?;
+        self.values.write(&group, "values")?;
+        group.new_array_dataset("mask", self.mask.view().into(), Default::default())?;
+        Ok(DataContainer::Group(group))
+    }
+}
+
+impl HasShape for NullableArray {
+    fn shape(&self) -> Shape {
+        self.mask.shape().to_vec().into()
+    }
+}
+
+impl Indexable for NullableArray {
+    fn get(&self, index: &[usize]) -> Option<DynScalar> {
+        if *self.mask.get(index)? {
+            None
+        } else {
+            self.values.get(index)
+        }
+    }
+}
+
+impl Selectable for NullableArray {
+    fn select<S>(&self, info: &[S]) -> Self
+    where
+        S: AsRef<SelectInfoElem>,
+    {
+        NullableArray {
+            values: self.values.select(info),
+            mask: Selectable::select(&self.mask, info),
+        }
+    }
+}
+
+impl Stackable for NullableArray {
+    fn vstack<I: Iterator<Item = Self>>(iter: I) -> Result<Self> {
+        let (values, masks): (Vec<_>, Vec<_>) = iter.map(|x| (x.values, x.mask)).unzip();
+        let masks: Vec<_> = masks.iter().map(|x| x.view()).collect();
+        let mask = ndarray::concatenate(Axis(0), &masks)?;
+        NullableArray::new(DynArray::vstack(values.into_iter())?, mask)
+    }
+}
+
+impl WritableArray for NullableArray {}
+
+impl Readable for NullableArray {
+    fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        let group = container.as_group()?;
+        let values = group.open_dataset("values")?.read_dyn_array()?;
+        let mask = group.open_dataset("mask")?.read_array()?;
+        NullableArray::new(values, mask)
+    }
+}
+
+impl ReadableArray for NullableArray {
+    fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
+        let group = container.as_group()?;
+        Ok(group.open_dataset("mask")?.shape())
+    }
+
+    fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+    {
+        let group = container.as_group()?;
+        let values = group.open_dataset("values")?.read_dyn_array_slice(info)?;
+        let mask = group.open_dataset("mask")?.read_array_slice(info)?;
+        NullableArray::new(values, mask)
+    }
+}
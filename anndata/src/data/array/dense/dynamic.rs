@@ -7,6 +7,7 @@ use crate::{
 };
 
 use anyhow::{bail, ensure, Result};
+use half::f16;
 use ndarray::{arr0, Array, ArrayD, ArrayView, CowArray, Dimension, IxDyn};
 use paste::paste;
 use polars::series::Series;
@@ -21,6 +22,7 @@ pub enum DynScalar {
     U16(u16),
     U32(u32),
     U64(u64),
+    F16(f16),
     F32(f32),
     F64(f64),
     Bool(bool),
@@ -75,8 +77,8 @@ macro_rules! impl_from_dynscalar {
 }
 
 impl_from_dynscalar!(
-    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f32, F32, f64, F64,
-    bool, Bool, String, String
+    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f16, F16, f32, F32,
+    f64, F64, bool, Bool, String, String
 );
 
 impl Element for DynScalar {
@@ -124,6 +126,7 @@ pub enum DynArray {
     U16(ArrayD<u16>),
     U32(ArrayD<u32>),
     U64(ArrayD<u64>),
+    F16(ArrayD<f16>),
     F32(ArrayD<f32>),
     F64(ArrayD<f64>),
     Bool(ArrayD<bool>),
@@ -161,8 +164,8 @@ impl DynArray {
     }
 
     impl_dynarray_into_array!(
-        I8, i8, I16, i16, I32, i32, I64, i64, U8, u8, U16, u16, U32, u32, U64, u64, F32, f32, F64,
-        f64, Bool, bool, String, String
+        I8, i8, I16, i16, I32, i32, I64, i64, U8, u8, U16, u16, U32, u32, U64, u64, F16, f16, F32, f32,
+        F64, f64, Bool, bool, String, String
     );
 }
 
@@ -189,8 +192,8 @@ macro_rules! impl_dynarray_traits{
 }
 
 impl_dynarray_traits!(
-    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f32, F32, f64, F64,
-    bool, Bool, String, String
+    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f16, F16, f32, F32,
+    f64, F64, bool, Bool, String, String
 );
 
 impl Into<Series> for DynArray {
@@ -204,6 +207,7 @@ impl Into<Series> for DynArray {
             DynArray::U16(x) => x.iter().collect(),
             DynArray::U32(x) => x.iter().collect(),
             DynArray::U64(x) => x.iter().collect(),
+            DynArray::F16(x) => x.iter().map(|x| x.to_f32()).collect(),
             DynArray::F32(x) => x.iter().collect(),
             DynArray::F64(x) => x.iter().collect(),
             DynArray::Bool(x) => x.iter().collect(),
@@ -299,6 +303,9 @@ impl Stackable for DynArray {
             DynArray::I64(_) => {
                 ArrayD::<i64>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into())
             }
+            DynArray::F16(_) => {
+                ArrayD::<f16>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into())
+            }
             DynArray::F32(_) => {
                 ArrayD::<f32>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into())
             }
@@ -340,6 +347,7 @@ pub enum DynCowArray<'a> {
     U16(CowArray<'a, u16, IxDyn>),
     U32(CowArray<'a, u32, IxDyn>),
     U64(CowArray<'a, u64, IxDyn>),
+    F16(CowArray<'a, f16, IxDyn>),
     F32(CowArray<'a, f32, IxDyn>),
     F64(CowArray<'a, f64, IxDyn>),
     Bool(CowArray<'a, bool, IxDyn>),
@@ -422,6 +430,7 @@ impl_dyn_cowarray_convert!(u8, U8);
 impl_dyn_cowarray_convert!(u16, U16);
 impl_dyn_cowarray_convert!(u32, U32);
 impl_dyn_cowarray_convert!(u64, U64);
+impl_dyn_cowarray_convert!(f16, F16);
 impl_dyn_cowarray_convert!(f32, F32);
 impl_dyn_cowarray_convert!(f64, F64);
 impl_dyn_cowarray_convert!(bool, Bool);
@@ -589,6 +598,7 @@ impl<D: Dimension> ArrayConvert<Array<usize, D>> for DynArray {
 impl<D: Dimension> ArrayConvert<Array<f32, D>> for DynArray {
     fn try_convert(self) -> Result<Array<f32, D>> {
         match self {
+            DynArray::F16(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
             DynArray::F32(data) => Ok(data.into_dimensionality()?),
             DynArray::I8(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
             DynArray::I16(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
@@ -610,6 +620,7 @@ impl<D: Dimension> ArrayConvert<Array<f64, D>> for DynArray {
             DynArray::U8(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
             DynArray::U16(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
             DynArray::U32(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
+            DynArray::F16(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
             DynArray::F32(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
             DynArray::Bool(data) => Ok(data.mapv(|x| x.into()).into_dimensionality()?),
             _ => bail!("Cannot convert to f64 Array"),
@@ -638,6 +649,7 @@ impl<D: Dimension> ArrayConvert<Array<String, D>> for DynArray {
             DynArray::U16(data) => Ok(data.mapv(|x| x.to_string()).into_dimensionality()?),
             DynArray::U32(data) => Ok(data.mapv(|x| x.to_string()).into_dimensionality()?),
             DynArray::U64(data) => Ok(data.mapv(|x| x.to_string()).into_dimensionality()?),
+            DynArray::F16(data) => Ok(data.mapv(|x| x.to_string()).into_dimensionality()?),
             DynArray::F32(data) => Ok(data.mapv(|x| x.to_string()).into_dimensionality()?),
             DynArray::F64(data) => Ok(data.mapv(|x| x.to_string()).into_dimensionality()?),
             DynArray::Bool(data) => Ok(data.mapv(|x| x.to_string()).into_dimensionality()?),
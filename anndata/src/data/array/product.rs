@@ -0,0 +1,161 @@
+use crate::data::{ArrayData, DynArray, DynCscMatrix, DynCsrMatrix};
+use super::reduce::group_by_minor;
+
+use anyhow::{bail, ensure, Result};
+use nalgebra_sparse::{CscMatrix, CsrMatrix};
+use ndarray::{Array2, ArrayD, ArrayView2, Ix2};
+use num::ToPrimitive;
+use rayon::prelude::*;
+
+/// Computes `chunk · rhs`, i.e., the rows of the product that are covered by
+/// a chunk of rows.
+pub(crate) fn dot_chunk(chunk: &ArrayData, rhs: ArrayView2<f64>) -> Result<Array2<f64>> {
+    let shape = chunk.shape();
+    ensure!(
+        shape.ndim() == 2 && shape[1] == rhs.nrows(),
+        "cannot multiply a {} array with a {:?} matrix",
+        shape,
+        rhs.shape()
+    );
+    let mut out = Array2::zeros((shape[0], rhs.ncols()));
+    multiply(chunk, rhs, &mut out, false)?;
+    Ok(out)
+}
+
+/// Adds `chunkᵀ · rhs` to `out`, where `rhs` contains the rows of the right
+/// hand side matrix that correspond to the rows of the chunk.
+pub(crate) fn t_dot_chunk(chunk: &ArrayData, rhs: ArrayView2<f64>, out: &mut Array2<f64>) -> Result<()> {
+    let shape = chunk.shape();
+    ensure!(
+        shape.ndim() == 2 && shape[0] == rhs.nrows(),
+        "cannot multiply the transpose of a {} array with a {:?} matrix",
+        shape,
+        rhs.shape()
+    );
+    ensure!(
+        out.nrows() == shape[1] && out.ncols() == rhs.ncols(),
+        "the product of the chunk does not match the output"
+    );
+    multiply(chunk, rhs, out, true)
+}
+
+fn multiply(chunk: &ArrayData, rhs: ArrayView2<f64>, out: &mut Array2<f64>, transpose: bool) -> Result<()> {
+    macro_rules! numeric {
+        ($data:expr, $enum:ident, $fun:ident) => {
+            match $data {
+                $enum::I8(x) => $fun(x, rhs, out, transpose),
+                $enum::I16(x) => $fun(x, rhs, out, transpose),
+                $enum::I32(x) => $fun(x, rhs, out, transpose),
+                $enum::I64(x) => $fun(x, rhs, out, transpose),
+                $enum::U8(x) => $fun(x, rhs, out, transpose),
+                $enum::U16(x) => $fun(x, rhs, out, transpose),
+                $enum::U32(x) => $fun(x, rhs, out, transpose),
+                $enum::U64(x) => $fun(x, rhs, out, transpose),
+                $enum::F16(x) => $fun(x, rhs, out, transpose),
+                $enum::F32(x) => $fun(x, rhs, out, transpose),
+                $enum::F64(x) => $fun(x, rhs, out, transpose),
+                $enum::Bool(_) | $enum::String(_) => {
+                    bail!("cannot multiply an array of {:?}", chunk.data_type())
+                }
+            }
+        };
+    }
+    match chunk {
+        ArrayData::Array(x) => numeric!(x, DynArray, multiply_dense),
+        ArrayData::CsrMatrix(x) => numeric!(x, DynCsrMatrix, multiply_csr),
+        ArrayData::CsrNonCanonical(x) => match x.clone().canonicalize() {
+            Ok(x) => numeric!(&x, DynCsrMatrix, multiply_csr),
+            Err(_) => bail!("cannot multiply a CSR matrix with duplicated entries"),
+        },
+        ArrayData::CscMatrix(x) => numeric!(x, DynCscMatrix, multiply_csc),
+        ArrayData::CooMatrix(x) => numeric!(&x.to_csr()?, DynCsrMatrix, multiply_csr),
+        ArrayData::Nullable(_) => bail!("cannot multiply a nullable array"),
+        ArrayData::DataFrame(_) => bail!("cannot multiply a DataFrame"),
+    }
+}
+
+fn multiply_dense<T>(arr: &ArrayD<T>, rhs: ArrayView2<f64>, out: &mut Array2<f64>, transpose: bool) -> Result<()>
+where
+    T: ToPrimitive,
+{
+    let arr = arr
+        .map(|x| x.to_f64().unwrap_or(f64::NAN))
+        .into_dimensionality::<Ix2>()?;
+    if transpose {
+        *out += &arr.t().dot(&rhs);
+    } else {
+        *out += &arr.dot(&rhs);
+    }
+    Ok(())
+}
+
+fn multiply_csr<T>(csr: &CsrMatrix<T>, rhs: ArrayView2<f64>, out: &mut Array2<f64>, transpose: bool) -> Result<()>
+where
+    T: ToPrimitive + Clone + Sync,
+{
+    if transpose {
+        let (offsets, indices, values) =
+            transpose_lanes(csr.ncols(), csr.row_offsets(), csr.col_indices(), csr.values());
+        compressed_dot(&offsets, &indices, &values, rhs, out)
+    } else {
+        compressed_dot(csr.row_offsets(), csr.col_indices(), csr.values(), rhs, out)
+    }
+}
+
+fn multiply_csc<T>(csc: &CscMatrix<T>, rhs: ArrayView2<f64>, out: &mut Array2<f64>, transpose: bool) -> Result<()>
+where
+    T: ToPrimitive + Clone + Sync,
+{
+    if transpose {
+        compressed_dot(csc.col_offsets(), csc.row_indices(), csc.values(), rhs, out)
+    } else {
+        let (offsets, indices, values) =
+            transpose_lanes(csc.nrows(), csc.col_offsets(), csc.row_indices(), csc.values());
+        compressed_dot(&offsets, &indices, &values, rhs, out)
+    }
+}
+
+/// Adds the product of a compressed matrix and a dense matrix to `out`.
+/// Row `i` of the product is the combination of the rows of `rhs` given by
+/// lane `i` of the compressed matrix, so the rows are computed in parallel.
+fn compressed_dot<T: ToPrimitive + Sync>(
+    offsets: &[usize],
+    indices: &[usize],
+    values: &[T],
+    rhs: ArrayView2<f64>,
+    out: &mut Array2<f64>,
+) -> Result<()> {
+    let k = rhs.ncols().max(1);
+    let Some(out) = out.as_slice_mut() else {
+        bail!("the output of a product must be in standard layout");
+    };
+    out.par_chunks_mut(k)
+        .zip(offsets.par_windows(2))
+        .for_each(|(row, w)| {
+            (w[0]..w[1]).for_each(|p| {
+                let v = values[p].to_f64().unwrap_or(f64::NAN);
+                row.iter_mut()
+                    .zip(rhs.row(indices[p]))
+                    .for_each(|(o, r)| *o += v * r);
+            })
+        });
+    Ok(())
+}
+
+/// Convert the lanes of a compressed matrix to the lanes of its transpose.
+fn transpose_lanes<T: Clone>(
+    n_minor: usize,
+    offsets: &[usize],
+    indices: &[usize],
+    values: &[T],
+) -> (Vec<usize>, Vec<usize>, Vec<T>) {
+    let entries: Vec<_> = offsets
+        .windows(2)
+        .enumerate()
+        .flat_map(|(i, w)| (w[0]..w[1]).map(move |p| (i, p)))
+        .map(|(i, p)| (i, values[p].clone()))
+        .collect();
+    let (offsets, entries) = group_by_minor(n_minor, indices, &entries);
+    let (indices, values) = entries.into_iter().unzip();
+    (offsets, indices, values)
+}
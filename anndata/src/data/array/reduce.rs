@@ -0,0 +1,299 @@
+use crate::data::{ArrayData, DynArray, DynCscMatrix, DynCsrMatrix, Shape};
+
+use anyhow::{bail, ensure, Result};
+use nalgebra_sparse::{CscMatrix, CsrMatrix};
+use ndarray::{Array1, ArrayD, ArrayView2, Axis, Ix2};
+use num::ToPrimitive;
+use rayon::prelude::*;
+
+/// Reductions that can be computed along an axis of a 2D array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    Sum,
+    Mean,
+    /// Population variance, i.e., the sum of squared deviations divided by
+    /// the number of elements.
+    Var,
+    Min,
+    Max,
+    /// Number of non-zero elements.
+    Nnz,
+}
+
+/// Running statistics of one lane of the result. The variance is tracked as
+/// the sum of squared deviations from the mean (M2) with Welford's algorithm,
+/// and the statistics of different chunks are merged with the pairwise update
+/// of Chan et al., which avoids the cancellation of `E[x²] - E[x]²`.
+#[derive(Debug, Clone, Copy)]
+struct LaneStats {
+    /// Number of values that have been seen. Sparse matrices do not visit
+    /// their implicit zeros.
+    seen: usize,
+    nnz: usize,
+    sum: f64,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for LaneStats {
+    fn default() -> Self {
+        Self {
+            seen: 0,
+            nnz: 0,
+            sum: 0.0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl LaneStats {
+    /// The statistics of `n` zeros.
+    fn zeros(n: usize) -> Self {
+        Self {
+            seen: n,
+            nnz: 0,
+            sum: 0.0,
+            mean: 0.0,
+            m2: 0.0,
+            min: 0.0,
+            max: 0.0,
+        }
+    }
+
+    fn from_values<'a, T, I>(values: I) -> Self
+    where
+        T: ToPrimitive + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let mut stats = Self::default();
+        values
+            .into_iter()
+            .for_each(|x| stats.push(x.to_f64().unwrap_or(f64::NAN)));
+        stats
+    }
+
+    fn push(&mut self, value: f64) {
+        self.seen += 1;
+        if value != 0.0 {
+            self.nnz += 1;
+        }
+        self.sum += value;
+        let delta = value - self.mean;
+        self.mean += delta / self.seen as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if other.seen == 0 {
+            return;
+        }
+        if self.seen == 0 {
+            *self = *other;
+            return;
+        }
+        let (n1, n2) = (self.seen as f64, other.seen as f64);
+        let n = n1 + n2;
+        let delta = other.mean - self.mean;
+        self.mean += delta * n2 / n;
+        self.m2 += other.m2 + delta * delta * n1 * n2 / n;
+        self.seen += other.seen;
+        self.nnz += other.nnz;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// The value of the reduction over a lane of length `n`.
+    fn finish(&self, reduction: Reduction, n: usize) -> f64 {
+        let mut stats = *self;
+        stats.merge(&Self::zeros(n - self.seen));
+        match reduction {
+            Reduction::Sum => stats.sum,
+            Reduction::Mean => stats.sum / n as f64,
+            Reduction::Var => stats.m2 / n as f64,
+            Reduction::Min => stats.min,
+            Reduction::Max => stats.max,
+            Reduction::Nnz => stats.nnz as f64,
+        }
+    }
+}
+
+/// Statistics along an axis of a 2D array, accumulated from chunks of rows.
+/// The statistics of each chunk are computed and merged into the result in
+/// parallel, so the chunk can be dropped afterwards.
+pub(crate) struct AxisStats {
+    /// The axis being reduced. Reducing along axis 0 produces one value per
+    /// column, and reducing along axis 1 produces one value per row.
+    axis: usize,
+    /// Length of the reduced axis.
+    n: usize,
+    lanes: Vec<LaneStats>,
+}
+
+impl AxisStats {
+    pub fn new(shape: &Shape, axis: usize) -> Result<Self> {
+        ensure!(
+            shape.ndim() == 2,
+            "reductions are only supported for 2D arrays, but got a {} array",
+            shape
+        );
+        ensure!(axis < 2, "axis {} is out of bounds for a 2D array", axis);
+        Ok(Self {
+            axis,
+            n: shape[axis],
+            lanes: vec![LaneStats::default(); shape[1 - axis]],
+        })
+    }
+
+    /// Add a chunk of rows starting at row `offset`.
+    pub fn update(&mut self, chunk: &ArrayData, offset: usize) -> Result<()> {
+        macro_rules! numeric {
+            ($data:expr, $enum:ident, $fun:ident) => {
+                match $data {
+                    $enum::I8(x) => self.$fun(x, offset),
+                    $enum::I16(x) => self.$fun(x, offset),
+                    $enum::I32(x) => self.$fun(x, offset),
+                    $enum::I64(x) => self.$fun(x, offset),
+                    $enum::U8(x) => self.$fun(x, offset),
+                    $enum::U16(x) => self.$fun(x, offset),
+                    $enum::U32(x) => self.$fun(x, offset),
+                    $enum::U64(x) => self.$fun(x, offset),
+                    $enum::F16(x) => self.$fun(x, offset),
+                    $enum::F32(x) => self.$fun(x, offset),
+                    $enum::F64(x) => self.$fun(x, offset),
+                    $enum::Bool(_) | $enum::String(_) => {
+                        bail!("cannot reduce an array of {:?}", chunk.data_type())
+                    }
+                }
+            };
+        }
+        match chunk {
+            ArrayData::Array(x) => numeric!(x, DynArray, update_dense),
+            ArrayData::CsrMatrix(x) => numeric!(x, DynCsrMatrix, update_csr),
+            ArrayData::CsrNonCanonical(x) => match x.clone().canonicalize() {
+                Ok(x) => numeric!(&x, DynCsrMatrix, update_csr),
+                Err(_) => bail!("cannot reduce a CSR matrix with duplicated entries"),
+            },
+            ArrayData::CscMatrix(x) => numeric!(x, DynCscMatrix, update_csc),
+            ArrayData::CooMatrix(x) => numeric!(&x.to_csr()?, DynCsrMatrix, update_csr),
+            ArrayData::Nullable(_) => bail!("cannot reduce a nullable array"),
+            ArrayData::DataFrame(_) => bail!("cannot reduce a DataFrame"),
+        }
+    }
+
+    pub fn finish(self, reduction: Reduction) -> Array1<f64> {
+        self.lanes
+            .into_iter()
+            .map(|x| x.finish(reduction, self.n))
+            .collect()
+    }
+
+    /// Number of non-zero elements of each lane.
+    pub fn nnz(self) -> Array1<usize> {
+        self.lanes.into_iter().map(|x| x.nnz).collect()
+    }
+
+    /// The lanes of the result that are covered by a chunk.
+    fn lanes_mut(&mut self, offset: usize, nrows: usize, ncols: usize) -> Result<&mut [LaneStats]> {
+        ensure!(
+            ncols == if self.axis == 0 { self.lanes.len() } else { self.n },
+            "the chunk has {} columns, which does not match the array",
+            ncols
+        );
+        if self.axis == 0 {
+            Ok(&mut self.lanes)
+        } else {
+            ensure!(offset + nrows <= self.lanes.len(), "the chunk is out of bounds");
+            Ok(&mut self.lanes[offset..offset + nrows])
+        }
+    }
+
+    fn update_dense<T>(&mut self, arr: &ArrayD<T>, offset: usize) -> Result<()>
+    where
+        T: ToPrimitive + Sync,
+    {
+        let arr: ArrayView2<T> = arr.view().into_dimensionality::<Ix2>()?;
+        let axis = self.axis;
+        let lanes = self.lanes_mut(offset, arr.nrows(), arr.ncols())?;
+        let chunk: Vec<_> = (0..lanes.len())
+            .into_par_iter()
+            .map(|i| LaneStats::from_values(arr.index_axis(Axis(1 - axis), i)))
+            .collect();
+        merge_lanes(lanes, chunk);
+        Ok(())
+    }
+
+    fn update_csr<T>(&mut self, csr: &CsrMatrix<T>, offset: usize) -> Result<()>
+    where
+        T: ToPrimitive + Clone + Sync,
+    {
+        let (nrows, ncols) = (csr.nrows(), csr.ncols());
+        let axis = self.axis;
+        let lanes = self.lanes_mut(offset, nrows, ncols)?;
+        if axis == 1 {
+            update_lanes(lanes, csr.row_offsets(), csr.values());
+        } else {
+            let (offsets, values) = group_by_minor(ncols, csr.col_indices(), csr.values());
+            update_lanes(lanes, &offsets, &values);
+        }
+        Ok(())
+    }
+
+    fn update_csc<T>(&mut self, csc: &CscMatrix<T>, offset: usize) -> Result<()>
+    where
+        T: ToPrimitive + Clone + Sync,
+    {
+        let (nrows, ncols) = (csc.nrows(), csc.ncols());
+        let axis = self.axis;
+        let lanes = self.lanes_mut(offset, nrows, ncols)?;
+        if axis == 0 {
+            update_lanes(lanes, csc.col_offsets(), csc.values());
+        } else {
+            let (offsets, values) = group_by_minor(nrows, csc.row_indices(), csc.values());
+            update_lanes(lanes, &offsets, &values);
+        }
+        Ok(())
+    }
+}
+
+/// Add the values of the compressed lanes to the statistics in parallel.
+fn update_lanes<T: ToPrimitive + Sync>(lanes: &mut [LaneStats], offsets: &[usize], values: &[T]) {
+    let chunk: Vec<_> = offsets
+        .par_windows(2)
+        .map(|w| LaneStats::from_values(&values[w[0]..w[1]]))
+        .collect();
+    merge_lanes(lanes, chunk);
+}
+
+/// Merge the statistics of a chunk into the result in parallel.
+fn merge_lanes(lanes: &mut [LaneStats], chunk: Vec<LaneStats>) {
+    lanes
+        .par_iter_mut()
+        .zip(chunk.into_par_iter())
+        .for_each(|(lane, x)| lane.merge(&x));
+}
+
+/// Group the values of a compressed matrix by their minor indices, i.e.,
+/// collect the values of each lane of the transposed matrix. The order of the
+/// values within a lane is not preserved.
+pub(super) fn group_by_minor<T: Clone>(n_minor: usize, minor_indices: &[usize], values: &[T]) -> (Vec<usize>, Vec<T>) {
+    let mut offsets = vec![0; n_minor + 1];
+    minor_indices.iter().for_each(|i| offsets[i + 1] += 1);
+    for i in 0..n_minor {
+        offsets[i + 1] += offsets[i];
+    }
+    let mut position = offsets.clone();
+    let mut grouped = values.to_vec();
+    minor_indices.iter().zip(values).for_each(|(i, v)| {
+        grouped[position[*i]] = v.clone();
+        position[*i] += 1;
+    });
+    (offsets, grouped)
+}
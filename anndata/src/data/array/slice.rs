@@ -330,6 +330,33 @@ impl SelectInfoElem {
             })
         )
     }
+
+    /// Composes two selections on an axis of length `bound`. The result is
+    /// equivalent to applying `self` first and then `other` to the outcome.
+    pub fn compose(&self, other: &SelectInfoElem, bound: usize) -> Self {
+        if other.is_full() {
+            return self.clone();
+        }
+        if self.is_full() {
+            return other.clone();
+        }
+        let outer = SelectInfoElemBounds::new(self, bound);
+        let inner = SelectInfoElemBounds::new(other, outer.len());
+        match (&outer, &inner) {
+            (SelectInfoElemBounds::Slice(a), SelectInfoElemBounds::Slice(b))
+                if a.step > 0 && b.step > 0 =>
+            {
+                let start = a.start + b.start * a.step as usize;
+                let end = (a.start + b.end * a.step as usize).min(a.end);
+                SelectInfoElem::Slice(Slice {
+                    start: start as isize,
+                    end: Some(end.max(start) as isize),
+                    step: a.step * b.step,
+                })
+            }
+            _ => inner.iter().map(|i| outer.index(i)).collect(),
+        }
+    }
 }
 
 /// `SelectInfoBounds` represents bounds-aware selection information. 
@@ -579,7 +606,7 @@ impl SliceBounds {
     }
 
     pub(crate) fn len(&self) -> usize {
-        (self.end - self.start).checked_div(self.step.unsigned_abs()).unwrap()
+        (self.end - self.start).div_ceil(self.step.unsigned_abs())
     }
 
     pub(crate) fn index(&self, i: usize) -> usize {
@@ -690,6 +717,48 @@ mod tests {
         }
     }
 
+    proptest! {
+        #[test]
+        fn test_compose(
+            n in 1usize..50,
+            start in 0usize..50,
+            len in 0usize..50,
+            step in 1isize..4,
+            index in prop::collection::vec(0usize..50, 0..20),
+        ) {
+            let start = start % n;
+            let end = (start + len).min(n);
+            let select = |s: &SelectInfoElem, bound: usize| -> Vec<usize> {
+                SelectInfoElemBounds::new(s, bound).to_vec()
+            };
+            let first = SelectInfoElem::from(Slice { start: start as isize, end: Some(end as isize), step });
+            let m = select(&first, n).len();
+            let seconds = [
+                SelectInfoElem::full(),
+                SelectInfoElem::from(Slice { start: 0, end: Some((m / 2) as isize), step: 2 }),
+                index.iter().filter(|&&i| i < m).copied().collect(),
+            ];
+            for second in seconds.iter() {
+                let expected: Vec<usize> = select(second, m).into_iter()
+                    .map(|i| select(&first, n)[i]).collect();
+                prop_assert_eq!(select(&first.compose(second, n), n), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_slice_len() {
+        for start in 0..10 {
+            for end in start..12 {
+                for step in [-4isize, -3, -2, -1, 1, 2, 3, 4] {
+                    let bounds = SliceBounds { start, end, step };
+                    let expected = (start..end).step_by(step.unsigned_abs()).count();
+                    assert_eq!(bounds.len(), expected, "{}..{} by {}", start, end, step);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_basic() {
         assert_eq!(
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use crate::backend::*;
+use crate::data::{
+    array::utils::{cs_read_select, write_indptr},
+    data_traits::*,
+    slice::{SelectInfoElem, Shape},
+    ArrayData, SelectInfoElemBounds,
+};
+
+use anyhow::{bail, ensure, Result};
+use nalgebra_sparse::csr::CsrMatrix;
+use ndarray::Ix1;
+
+/// A CSR matrix whose column indices are stored as `u32` rather than `usize`,
+/// which halves the memory used by the indices. Any matrix with at most 2^32
+/// columns can be held this way, which includes the matrices scipy writes with
+/// 32-bit indices, so such matrices are read and written without widening
+/// their indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactCsrMatrix<T> {
+    nrows: usize,
+    ncols: usize,
+    row_offsets: Vec<usize>,
+    col_indices: Vec<u32>,
+    values: Vec<T>,
+}
+
+impl<T> CompactCsrMatrix<T> {
+    /// Create a matrix from its raw parts. The column indices of each row
+    /// must be sorted and unique, as in `CsrMatrix`.
+    pub fn try_from_csr_data(
+        nrows: usize,
+        ncols: usize,
+        row_offsets: Vec<usize>,
+        col_indices: Vec<u32>,
+        values: Vec<T>,
+    ) -> Result<Self> {
+        ensure!(
+            row_offsets.len() == nrows + 1 && row_offsets[0] == 0,
+            "the row offsets do not match the number of rows"
+        );
+        ensure!(
+            col_indices.len() == values.len() && row_offsets[nrows] == values.len(),
+            "the number of column indices and values do not match the row offsets"
+        );
+        for w in row_offsets.windows(2) {
+            ensure!(w[0] <= w[1], "the row offsets must be non-decreasing");
+            let row = &col_indices[w[0]..w[1]];
+            ensure!(
+                row.windows(2).all(|x| x[0] < x[1]),
+                "the column indices of each row must be sorted and unique"
+            );
+            ensure!(
+                row.last().map_or(true, |j| (*j as usize) < ncols),
+                "column index out of bounds"
+            );
+        }
+        Ok(Self {
+            nrows,
+            ncols,
+            row_offsets,
+            col_indices,
+            values,
+        })
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn row_offsets(&self) -> &[usize] {
+        &self.row_offsets
+    }
+
+    pub fn col_indices(&self) -> &[u32] {
+        &self.col_indices
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// The column indices and values of row `i`.
+    pub fn row(&self, i: usize) -> (&[u32], &[T]) {
+        let range = self.row_offsets[i]..self.row_offsets[i + 1];
+        (&self.col_indices[range.clone()], &self.values[range])
+    }
+
+    pub fn disassemble(self) -> (Vec<usize>, Vec<u32>, Vec<T>) {
+        (self.row_offsets, self.col_indices, self.values)
+    }
+
+    /// Create a matrix from a `CsrMatrix`, whose indices must fit in `u32`.
+    fn from_csr(nrows: usize, ncols: usize, row_offsets: Vec<usize>, col_indices: Vec<usize>, values: Vec<T>) -> Result<Self> {
+        check_ncols(ncols)?;
+        Ok(Self {
+            nrows,
+            ncols,
+            row_offsets,
+            col_indices: col_indices.into_iter().map(|j| j as u32).collect(),
+            values,
+        })
+    }
+}
+
+fn check_ncols(ncols: usize) -> Result<()> {
+    ensure!(
+        u32::try_from(ncols.saturating_sub(1)).is_ok(),
+        "a matrix with {} columns cannot be stored with 32-bit indices",
+        ncols
+    );
+    Ok(())
+}
+
+impl<T> TryFrom<CsrMatrix<T>> for CompactCsrMatrix<T> {
+    type Error = anyhow::Error;
+
+    fn try_from(csr: CsrMatrix<T>) -> Result<Self> {
+        let (nrows, ncols) = (csr.nrows(), csr.ncols());
+        let (row_offsets, col_indices, values) = csr.disassemble();
+        Self::from_csr(nrows, ncols, row_offsets, col_indices, values)
+    }
+}
+
+impl<T> From<CompactCsrMatrix<T>> for CsrMatrix<T> {
+    fn from(csr: CompactCsrMatrix<T>) -> Self {
+        let (nrows, ncols) = (csr.nrows, csr.ncols);
+        let col_indices = csr.col_indices.into_iter().map(|j| j as usize).collect();
+        CsrMatrix::try_from_csr_data(nrows, ncols, csr.row_offsets, col_indices, csr.values).unwrap()
+    }
+}
+
+impl<T> TryFrom<ArrayData> for CompactCsrMatrix<T>
+where
+    CsrMatrix<T>: TryFrom<ArrayData, Error = anyhow::Error>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(data: ArrayData) -> Result<Self> {
+        CsrMatrix::<T>::try_from(data)?.try_into()
+    }
+}
+
+impl<T> From<CompactCsrMatrix<T>> for ArrayData
+where
+    CsrMatrix<T>: Into<ArrayData>,
+{
+    fn from(data: CompactCsrMatrix<T>) -> Self {
+        CsrMatrix::from(data).into()
+    }
+}
+
+impl<T> HasShape for CompactCsrMatrix<T> {
+    fn shape(&self) -> Shape {
+        vec![self.nrows, self.ncols].into()
+    }
+}
+
+impl<T: Clone> Selectable for CompactCsrMatrix<T> {
+    fn select<S>(&self, info: &[S]) -> Self
+    where
+        S: AsRef<SelectInfoElem>,
+    {
+        CsrMatrix::from(self.clone()).select(info).try_into().unwrap()
+    }
+}
+
+impl<T: BackendData> Element for CompactCsrMatrix<T> {
+    fn data_type(&self) -> DataType {
+        DataType::CsrMatrix(T::DTYPE)
+    }
+
+    fn metadata(&self) -> MetaData {
+        let mut metadata = HashMap::new();
+        metadata.insert("shape".to_string(), self.shape().into());
+        MetaData::new("csr_matrix", "0.1.0", Some(metadata))
+    }
+}
+
+impl<T: BackendData> Writable for CompactCsrMatrix<T> {
+    fn write<B: Backend, G: GroupOp<B>>(
+        &self,
+        location: &G,
+        name: &str,
+    ) -> Result<DataContainer<B>> {
+        let mut group = location.new_group(name)?;
+        self.metadata().save_metadata(&mut group)?;
+        group.new_array_dataset("data", self.values.as_slice().into(), Default::default())?;
+        write_indptr::<B, _>(&group, &self.row_offsets)?;
+        if i32::try_from(self.ncols.saturating_sub(1)).is_ok() {
+            let indices: Vec<i32> = self.col_indices.iter().map(|x| *x as i32).collect();
+            group.new_array_dataset("indices", indices.into(), Default::default())?;
+        } else {
+            let indices: Vec<i64> = self.col_indices.iter().map(|x| *x as i64).collect();
+            group.new_array_dataset("indices", indices.into(), Default::default())?;
+        }
+        Ok(DataContainer::Group(group))
+    }
+}
+
+impl<T: BackendData> Readable for CompactCsrMatrix<T> {
+    fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        let data_type = container.encoding_type()?;
+        if let DataType::CsrMatrix(_) = data_type {
+            let group = container.as_group()?;
+            let shape: Vec<u64> = group.get_attr("shape")?;
+            check_ncols(shape[1] as usize)?;
+            let data = group
+                .open_dataset("data")?
+                .read_array::<_, Ix1>()?
+                .into_raw_vec_and_offset()
+                .0;
+            let indptr: Vec<usize> = group
+                .open_dataset("indptr")?
+                .read_array_cast::<_, Ix1>()?
+                .into_raw_vec_and_offset()
+                .0;
+            let indices: Vec<u32> = group
+                .open_dataset("indices")?
+                .read_array_cast::<_, Ix1>()?
+                .into_raw_vec_and_offset()
+                .0;
+            CompactCsrMatrix::try_from_csr_data(shape[0] as usize, shape[1] as usize, indptr, indices, data)
+        } else {
+            bail!(
+                "cannot read csr matrix from container with data type {:?}",
+                data_type
+            )
+        }
+    }
+}
+
+impl<T: BackendData> ReadableArray for CompactCsrMatrix<T> {
+    fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
+        Ok(container
+            .as_group()?
+            .get_attr::<Vec<usize>>("shape")?
+            .into_iter()
+            .collect())
+    }
+
+    fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+    {
+        let data_type = container.encoding_type()?;
+        if let DataType::CsrMatrix(_) = data_type {
+            if info.as_ref().len() != 2 {
+                panic!("index must have length 2");
+            }
+
+            if info.iter().all(|s| s.as_ref().is_full()) {
+                return Self::read(container);
+            }
+
+            let shape = Self::get_shape(container)?;
+            let ncols = SelectInfoElemBounds::new(&info[1], shape[1]).len();
+            check_ncols(ncols)?;
+            if check_ncols(shape[1]).is_err() {
+                // The indices on disk may not fit in `u32`, even though the
+                // selected ones do.
+                let (indptr, indices, data) = cs_read_select::<B, T, usize>(
+                    container.as_group()?,
+                    info[0].as_ref(),
+                    info[1].as_ref(),
+                    (shape[0], shape[1]),
+                )?;
+                return Self::from_csr(indptr.len() - 1, ncols, indptr, indices, data);
+            }
+            let (indptr, indices, data) = cs_read_select::<B, T, u32>(
+                container.as_group()?,
+                info[0].as_ref(),
+                info[1].as_ref(),
+                (shape[0], shape[1]),
+            )?;
+            Ok(Self {
+                nrows: indptr.len() - 1,
+                ncols,
+                row_offsets: indptr,
+                col_indices: indices,
+                values: data,
+            })
+        } else {
+            bail!(
+                "cannot read csr matrix from container with data type {:?}",
+                data_type
+            )
+        }
+    }
+}
+
+impl<T: BackendData> WritableArray for &CompactCsrMatrix<T> {}
+impl<T: BackendData> WritableArray for CompactCsrMatrix<T> {}
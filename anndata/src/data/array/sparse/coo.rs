@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use crate::backend::*;
+use crate::data::{
+    array::utils::{coo_to_unsorted_cs, MinorIndex},
+    data_traits::*,
+    slice::{SelectInfoElem, Shape},
+    SelectInfoElemBounds,
+};
+
+use anyhow::{anyhow, bail, ensure, Result};
+use nalgebra_sparse::{CooMatrix, CscMatrix, CsrMatrix};
+
+use super::CsrNonCanonical;
+
+impl<T> HasShape for CooMatrix<T> {
+    fn shape(&self) -> Shape {
+        vec![self.nrows(), self.ncols()].into()
+    }
+}
+
+impl<T: Clone> Selectable for CooMatrix<T> {
+    fn select<S>(&self, info: &[S]) -> Self
+    where
+        S: AsRef<SelectInfoElem>,
+    {
+        if info.len() != 2 {
+            panic!("index must have length 2");
+        }
+        let row_select = info[0].as_ref();
+        let col_select = info[1].as_ref();
+        let rows = MinorIndex::new(row_select, self.nrows());
+        let cols = MinorIndex::new(col_select, self.ncols());
+        let mut new_rows = Vec::new();
+        let mut new_cols = Vec::new();
+        let mut new_values = Vec::new();
+        self.triplet_iter().for_each(|(i, j, v)| {
+            rows.for_each(i, |i| {
+                cols.for_each(j, |j| {
+                    new_rows.push(i);
+                    new_cols.push(j);
+                    new_values.push(v.clone());
+                })
+            })
+        });
+        CooMatrix::try_from_triplets(
+            SelectInfoElemBounds::new(row_select, self.nrows()).len(),
+            SelectInfoElemBounds::new(col_select, self.ncols()).len(),
+            new_rows,
+            new_cols,
+            new_values,
+        )
+        .unwrap()
+    }
+}
+
+impl<T: Clone> Stackable for CooMatrix<T> {
+    fn vstack<I: Iterator<Item = Self>>(iter: I) -> Result<Self> {
+        let mut iter = iter.peekable();
+        let ncols = iter.peek().map(|x| x.ncols()).unwrap_or(0);
+        let mut nrows = 0;
+        let mut rows = Vec::new();
+        let mut cols = Vec::new();
+        let mut values = Vec::new();
+        for coo in iter {
+            ensure!(
+                coo.ncols() == ncols,
+                "cannot stack COO matrices with {} and {} columns",
+                ncols,
+                coo.ncols()
+            );
+            let n = coo.nrows();
+            let (r, c, v) = coo.disassemble();
+            rows.extend(r.into_iter().map(|i| i + nrows));
+            cols.extend(c);
+            values.extend(v);
+            nrows += n;
+        }
+        CooMatrix::try_from_triplets(nrows, ncols, rows, cols, values)
+            .map_err(|e| anyhow!("{}", e))
+    }
+}
+
+/// `coo_matrix` is not part of the AnnData on-disk format, so COO matrices are
+/// stored as (possibly non-canonical) CSR matrices and read back from them.
+impl<T: BackendData> Element for CooMatrix<T> {
+    fn data_type(&self) -> DataType {
+        DataType::CooMatrix(T::DTYPE)
+    }
+
+    fn metadata(&self) -> MetaData {
+        let mut metadata = HashMap::new();
+        metadata.insert("shape".to_string(), self.shape().into());
+        MetaData::new("csr_matrix", "0.1.0", Some(metadata))
+    }
+}
+
+impl<T: BackendData> Writable for CooMatrix<T> {
+    fn write<B: Backend, G: GroupOp<B>>(
+        &self,
+        location: &G,
+        name: &str,
+    ) -> Result<DataContainer<B>> {
+        coo_to_csr_non_canonical(self).write(location, name)
+    }
+}
+
+impl<T: BackendData> Readable for CooMatrix<T> {
+    fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        let data_type = container.encoding_type()?;
+        if let DataType::CsrMatrix(_) = data_type {
+            Ok(CooMatrix::from(&CsrNonCanonical::<T>::read(container)?))
+        } else {
+            bail!(
+                "cannot read coo matrix from container with data type {:?}",
+                data_type
+            )
+        }
+    }
+}
+
+impl<T: BackendData> ReadableArray for CooMatrix<T> {
+    fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
+        CsrNonCanonical::<T>::get_shape(container)
+    }
+
+    fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+    {
+        let data_type = container.encoding_type()?;
+        if let DataType::CsrMatrix(_) = data_type {
+            Ok(CooMatrix::from(&CsrNonCanonical::<T>::read_select(container, info)?))
+        } else {
+            bail!(
+                "cannot read coo matrix from container with data type {:?}",
+                data_type
+            )
+        }
+    }
+}
+
+impl<T: BackendData> WritableArray for &CooMatrix<T> {}
+impl<T: BackendData> WritableArray for CooMatrix<T> {}
+
+/// Convert a COO matrix to CSR. Duplicated entries are combined with `merge`,
+/// in the order in which they appear in the COO matrix.
+pub(crate) fn coo_to_csr<T, F>(coo: &CooMatrix<T>, merge: F) -> Result<CsrMatrix<T>>
+where
+    T: Clone,
+    F: Fn(&mut T, &T) -> Result<()>,
+{
+    let (offsets, indices, values) = coo_to_cs(
+        coo.nrows(),
+        coo.row_indices(),
+        coo.col_indices(),
+        coo.values(),
+        Some(merge),
+    )?;
+    CsrMatrix::try_from_csr_data(coo.nrows(), coo.ncols(), offsets, indices, values)
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// Convert a COO matrix to CSC. Duplicated entries are combined with `merge`,
+/// in the order in which they appear in the COO matrix.
+pub(crate) fn coo_to_csc<T, F>(coo: &CooMatrix<T>, merge: F) -> Result<CscMatrix<T>>
+where
+    T: Clone,
+    F: Fn(&mut T, &T) -> Result<()>,
+{
+    let (offsets, indices, values) = coo_to_cs(
+        coo.ncols(),
+        coo.col_indices(),
+        coo.row_indices(),
+        coo.values(),
+        Some(merge),
+    )?;
+    CscMatrix::try_from_csc_data(coo.nrows(), coo.ncols(), offsets, indices, values)
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// Convert a COO matrix to CSR, keeping duplicated entries in the order in
+/// which they appear in the COO matrix.
+pub(crate) fn coo_to_csr_non_canonical<T: Clone>(coo: &CooMatrix<T>) -> CsrNonCanonical<T> {
+    let (offsets, indices, values) = coo_to_cs(
+        coo.nrows(),
+        coo.row_indices(),
+        coo.col_indices(),
+        coo.values(),
+        None::<fn(&mut T, &T) -> Result<()>>,
+    )
+    .unwrap();
+    CsrNonCanonical::from_csr_data(coo.nrows(), coo.ncols(), offsets, indices, values)
+}
+
+/// Build sorted compressed lanes from triplets. Duplicated entries are merged
+/// if `merge` is given and kept otherwise.
+fn coo_to_cs<T, F>(
+    major_dim: usize,
+    major_indices: &[usize],
+    minor_indices: &[usize],
+    values: &[T],
+    merge: Option<F>,
+) -> Result<(Vec<usize>, Vec<usize>, Vec<T>)>
+where
+    T: Clone,
+    F: Fn(&mut T, &T) -> Result<()>,
+{
+    let nnz = values.len();
+    let mut offsets = vec![0; major_dim + 1];
+    let mut unsorted_indices = vec![0; nnz];
+    let mut unsorted_values = values.to_vec();
+    coo_to_unsorted_cs(
+        &mut offsets,
+        &mut unsorted_indices,
+        &mut unsorted_values,
+        major_dim,
+        major_indices,
+        minor_indices,
+        values,
+    );
+
+    let mut new_offsets = Vec::with_capacity(major_dim + 1);
+    let mut new_indices: Vec<usize> = Vec::with_capacity(nnz);
+    let mut new_values: Vec<T> = Vec::with_capacity(nnz);
+    new_offsets.push(0);
+    for lane in offsets.windows(2) {
+        let lane_start = new_indices.len();
+        // The sort is stable, so duplicates are merged in their original order.
+        let mut order: Vec<usize> = (lane[0]..lane[1]).collect();
+        order.sort_by_key(|k| unsorted_indices[*k]);
+        for k in order {
+            let j = unsorted_indices[k];
+            let duplicated = new_indices.len() > lane_start && new_indices[new_indices.len() - 1] == j;
+            if let (true, Some(merge)) = (duplicated, merge.as_ref()) {
+                merge(new_values.last_mut().unwrap(), &unsorted_values[k])?;
+            } else {
+                new_indices.push(j);
+                new_values.push(unsorted_values[k].clone());
+            }
+        }
+        new_offsets.push(new_indices.len());
+    }
+    Ok((new_offsets, new_indices, new_values))
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::backend::*;
 use crate::data::{
-    array::utils::{cs_major_index, cs_major_minor_index, cs_major_slice},
+    array::utils::{cs_major_index, cs_major_minor_index, cs_major_slice, cs_read_select},
     data_traits::*,
     slice::{SelectInfoElem, Shape},
     SelectInfoBounds, SelectInfoElemBounds,
@@ -341,7 +341,6 @@ impl<T: BackendData> ReadableArray for CsrMatrix<T> {
             .collect())
     }
 
-    // TODO: efficient implementation for slice
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
     where
         B: Backend,
@@ -357,41 +356,15 @@ impl<T: BackendData> ReadableArray for CsrMatrix<T> {
                 return Self::read(container);
             }
 
-            let data = if let SelectInfoElem::Slice(s) = info[0].as_ref() {
-                let group = container.as_group()?;
-                let indptr_slice = if let Some(end) = s.end {
-                    SelectInfoElem::from(s.start..end + 1)
-                } else {
-                    SelectInfoElem::from(s.start..)
-                };
-                let mut indptr: Vec<usize> = group
-                    .open_dataset("indptr")?
-                    .read_array_slice_cast(&[indptr_slice])?
-                    .to_vec();
-                let lo = indptr[0];
-                let slice = SelectInfoElem::from(lo..indptr[indptr.len() - 1]);
-                let data: Vec<T> = group
-                    .open_dataset("data")?
-                    .read_array_slice(&[&slice])?
-                    .to_vec();
-                let indices: Vec<usize> = group
-                    .open_dataset("indices")?
-                    .read_array_slice_cast(&[&slice])?
-                    .to_vec();
-                indptr.iter_mut().for_each(|x| *x -= lo);
-                CsrMatrix::try_from_csr_data(
-                    indptr.len() - 1,
-                    Self::get_shape(container)?[1],
-                    indptr,
-                    indices,
-                    data,
-                )
-                .unwrap()
-                .select_axis(1, info[1].as_ref())
-            } else {
-                Self::read(container)?.select(info)
-            };
-            Ok(data)
+            let shape = Self::get_shape(container)?;
+            let (indptr, indices, data) = cs_read_select::<B, T, usize>(
+                container.as_group()?,
+                info[0].as_ref(),
+                info[1].as_ref(),
+                (shape[0], shape[1]),
+            )?;
+            let ncols = SelectInfoElemBounds::new(&info[1], shape[1]).len();
+            Ok(CsrMatrix::try_from_csr_data(indptr.len() - 1, ncols, indptr, indices, data).unwrap())
         } else {
             bail!(
                 "cannot read csr matrix from container with data type {:?}",
@@ -5,7 +5,11 @@ use crate::data::{
     slice::{SelectInfoElem, Shape},
 };
 
-use anyhow::{bail, Result};
+use super::coo::{coo_to_csc, coo_to_csr};
+
+use anyhow::{bail, Context, Result};
+use half::f16;
+use nalgebra_sparse::coo::CooMatrix;
 use nalgebra_sparse::csc::CscMatrix;
 use nalgebra_sparse::csr::CsrMatrix;
 use num::FromPrimitive;
@@ -20,6 +24,7 @@ pub enum DynCsrMatrix {
     U16(CsrMatrix<u16>),
     U32(CsrMatrix<u32>),
     U64(CsrMatrix<u64>),
+    F16(CsrMatrix<f16>),
     F32(CsrMatrix<f32>),
     F64(CsrMatrix<f64>),
     Bool(CsrMatrix<bool>),
@@ -52,8 +57,8 @@ macro_rules! impl_dyncsr_traits {
 }
 
 impl_dyncsr_traits!(
-    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f32, F32, f64, F64,
-    bool, Bool, String, String
+    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f16, F16, f32, F32,
+    f64, F64, bool, Bool, String, String
 );
 
 impl Element for DynCsrMatrix {
@@ -140,6 +145,9 @@ impl Stackable for DynCsrMatrix {
             DynCsrMatrix::I64(_) => Ok(DynCsrMatrix::I64(CsrMatrix::<i64>::vstack(
                 iter.map(|x| x.try_into().unwrap()),
             )?)),
+            DynCsrMatrix::F16(_) => Ok(DynCsrMatrix::F16(CsrMatrix::<f16>::vstack(
+                iter.map(|x| x.try_into().unwrap()),
+            )?)),
             DynCsrMatrix::F32(_) => Ok(DynCsrMatrix::F32(CsrMatrix::<f32>::vstack(
                 iter.map(|x| x.try_into().unwrap()),
             )?)),
@@ -194,6 +202,7 @@ pub enum DynCscMatrix {
     U16(CscMatrix<u16>),
     U32(CscMatrix<u32>),
     U64(CscMatrix<u64>),
+    F16(CscMatrix<f16>),
     F32(CscMatrix<f32>),
     F64(CscMatrix<f64>),
     Bool(CscMatrix<bool>),
@@ -226,8 +235,8 @@ macro_rules! impl_dyncsc_traits {
 }
 
 impl_dyncsc_traits!(
-    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f32, F32, f64, F64,
-    bool, Bool, String, String
+    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f16, F16, f32, F32,
+    f64, F64, bool, Bool, String, String
 );
 
 impl Element for DynCscMatrix {
@@ -314,6 +323,210 @@ impl ReadableArray for DynCscMatrix {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynCooMatrix {
+    I8(CooMatrix<i8>),
+    I16(CooMatrix<i16>),
+    I32(CooMatrix<i32>),
+    I64(CooMatrix<i64>),
+    U8(CooMatrix<u8>),
+    U16(CooMatrix<u16>),
+    U32(CooMatrix<u32>),
+    U64(CooMatrix<u64>),
+    F16(CooMatrix<f16>),
+    F32(CooMatrix<f32>),
+    F64(CooMatrix<f64>),
+    Bool(CooMatrix<bool>),
+    String(CooMatrix<String>),
+}
+
+macro_rules! impl_dyncoo_traits {
+    ($($from_type:ty, $to_type:ident),*) => {
+        $(
+            impl From<CooMatrix<$from_type>> for DynCooMatrix {
+                fn from(data: CooMatrix<$from_type>) -> Self {
+                    DynCooMatrix::$to_type(data)
+                }
+            }
+            impl TryFrom<DynCooMatrix> for CooMatrix<$from_type> {
+                type Error = anyhow::Error;
+                fn try_from(data: DynCooMatrix) -> Result<Self> {
+                    match data {
+                        DynCooMatrix::$to_type(data) => Ok(data),
+                        _ => bail!(
+                            "Cannot convert {:?} to {} CooMatrix",
+                            data.data_type(),
+                            stringify!($from_type)
+                        ),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_dyncoo_traits!(
+    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f16, F16, f32, F32,
+    f64, F64, bool, Bool, String, String
+);
+
+impl DynCooMatrix {
+    /// Convert to a CSR matrix. Duplicated entries are summed, and are an
+    /// error for boolean and string matrices.
+    pub fn to_csr(&self) -> Result<DynCsrMatrix> {
+        macro_rules! fun {
+            ($variant:ident, $data:expr) => {
+                coo_to_csr($data, sum_duplicates).map(DynCsrMatrix::$variant)
+            };
+        }
+        match self {
+            DynCooMatrix::I8(x) => fun!(I8, x),
+            DynCooMatrix::I16(x) => fun!(I16, x),
+            DynCooMatrix::I32(x) => fun!(I32, x),
+            DynCooMatrix::I64(x) => fun!(I64, x),
+            DynCooMatrix::U8(x) => fun!(U8, x),
+            DynCooMatrix::U16(x) => fun!(U16, x),
+            DynCooMatrix::U32(x) => fun!(U32, x),
+            DynCooMatrix::U64(x) => fun!(U64, x),
+            DynCooMatrix::F16(x) => fun!(F16, x),
+            DynCooMatrix::F32(x) => fun!(F32, x),
+            DynCooMatrix::F64(x) => fun!(F64, x),
+            DynCooMatrix::Bool(x) => coo_to_csr(x, reject_duplicates).map(DynCsrMatrix::Bool),
+            DynCooMatrix::String(x) => coo_to_csr(x, reject_duplicates).map(DynCsrMatrix::String),
+        }
+    }
+
+    /// Convert to a CSC matrix. Duplicated entries are summed, and are an
+    /// error for boolean and string matrices.
+    pub fn to_csc(&self) -> Result<DynCscMatrix> {
+        macro_rules! fun {
+            ($variant:ident, $data:expr) => {
+                coo_to_csc($data, sum_duplicates).map(DynCscMatrix::$variant)
+            };
+        }
+        match self {
+            DynCooMatrix::I8(x) => fun!(I8, x),
+            DynCooMatrix::I16(x) => fun!(I16, x),
+            DynCooMatrix::I32(x) => fun!(I32, x),
+            DynCooMatrix::I64(x) => fun!(I64, x),
+            DynCooMatrix::U8(x) => fun!(U8, x),
+            DynCooMatrix::U16(x) => fun!(U16, x),
+            DynCooMatrix::U32(x) => fun!(U32, x),
+            DynCooMatrix::U64(x) => fun!(U64, x),
+            DynCooMatrix::F16(x) => fun!(F16, x),
+            DynCooMatrix::F32(x) => fun!(F32, x),
+            DynCooMatrix::F64(x) => fun!(F64, x),
+            DynCooMatrix::Bool(x) => coo_to_csc(x, reject_duplicates).map(DynCscMatrix::Bool),
+            DynCooMatrix::String(x) => coo_to_csc(x, reject_duplicates).map(DynCscMatrix::String),
+        }
+    }
+}
+
+fn sum_duplicates<T: Copy + std::ops::AddAssign>(acc: &mut T, x: &T) -> Result<()> {
+    *acc += *x;
+    Ok(())
+}
+
+fn reject_duplicates<T>(_: &mut T, _: &T) -> Result<()> {
+    bail!("cannot convert a COO matrix with duplicated entries of non-numeric values")
+}
+
+impl Element for DynCooMatrix {
+    fn data_type(&self) -> DataType {
+        crate::macros::dyn_map_fun!(self, DynCooMatrix, data_type)
+    }
+
+    fn metadata(&self) -> MetaData {
+        crate::macros::dyn_map_fun!(self, DynCooMatrix, metadata)
+    }
+}
+
+impl Writable for DynCooMatrix {
+    fn write<B: Backend, G: GroupOp<B>>(
+        &self,
+        location: &G,
+        name: &str,
+    ) -> Result<DataContainer<B>> {
+        crate::macros::dyn_map_fun!(self, DynCooMatrix, write, location, name)
+    }
+}
+
+impl Readable for DynCooMatrix {
+    fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        if let DataType::CsrMatrix(ty) = container.encoding_type()? {
+            macro_rules! fun {
+                ($variant:ident) => {
+                    CooMatrix::read(container).map(DynCooMatrix::$variant)
+                };
+            }
+            crate::macros::dyn_match!(ty, ScalarType, fun)
+        } else {
+            bail!("the container does not contain a csr matrix");
+        }
+    }
+}
+
+impl HasShape for DynCooMatrix {
+    fn shape(&self) -> Shape {
+        crate::macros::dyn_map_fun!(self, DynCooMatrix, shape)
+    }
+}
+
+impl Selectable for DynCooMatrix {
+    fn select<S>(&self, info: &[S]) -> Self
+    where
+        S: AsRef<SelectInfoElem>,
+    {
+        macro_rules! select {
+            ($variant:ident, $data:expr) => {
+                $data.select(info).into()
+            };
+        }
+        crate::macros::dyn_map!(self, DynCooMatrix, select)
+    }
+}
+
+impl Stackable for DynCooMatrix {
+    fn vstack<I: Iterator<Item = Self>>(iter: I) -> Result<Self> {
+        let mut iter = iter.peekable();
+        macro_rules! fun {
+            ($variant:ident, $data:expr) => {
+                CooMatrix::vstack(iter.map(|x| x.try_into()).collect::<Result<Vec<_>>>()?.into_iter())
+                    .map(DynCooMatrix::$variant)
+            };
+        }
+        crate::macros::dyn_map!(
+            iter.peek().context("cannot stack an empty sequence of COO matrices")?,
+            DynCooMatrix,
+            fun
+        )
+    }
+}
+
+impl WritableArray for DynCooMatrix {}
+impl ReadableArray for DynCooMatrix {
+    fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
+        DynCsrMatrix::get_shape(container)
+    }
+
+    fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+    {
+        if let DataType::CsrMatrix(ty) = container.encoding_type()? {
+            macro_rules! fun {
+                ($variant:ident) => {
+                    CooMatrix::read_select(container, info).map(DynCooMatrix::$variant)
+                };
+            }
+            crate::macros::dyn_match!(ty, ScalarType, fun)
+        } else {
+            bail!("the container does not contain a csr matrix");
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // ArrayConvert implementations
 ////////////////////////////////////////////////////////////////////////////////
@@ -351,6 +564,7 @@ macro_rules! impl_arrayconvert {
                         [<Dyn $ty>]::U16(data) => $fun(data, |x| Ok(x.into())),
                         [<Dyn $ty>]::U32(data) => $fun(data, |x| Ok(f32::from_u32(x).unwrap())),
                         [<Dyn $ty>]::U64(data) => $fun(data, |x| Ok(f32::from_u64(x).unwrap())),
+                        [<Dyn $ty>]::F16(data) => $fun(data, |x| Ok(x.into())),
                         [<Dyn $ty>]::F64(data) => $fun(data, |x| Ok(f32::from_f64(x).unwrap())),
                         [<Dyn $ty>]::Bool(data) => $fun(data, |x| Ok(x.into())),
                         v => bail!("Cannot convert {} to {}<f32>", v.data_type(), stringify!($ty)),
@@ -370,6 +584,7 @@ macro_rules! impl_arrayconvert {
                         [<Dyn $ty>]::U16(data) => $fun(data, |x| Ok(x.into())),
                         [<Dyn $ty>]::U32(data) => $fun(data, |x| Ok(x.into())),
                         [<Dyn $ty>]::U64(data) => $fun(data, |x| Ok(f64::from_u64(x).unwrap())),
+                        [<Dyn $ty>]::F16(data) => $fun(data, |x| Ok(x.into())),
                         [<Dyn $ty>]::F32(data) => $fun(data, |x| Ok(x.into())),
                         [<Dyn $ty>]::Bool(data) => $fun(data, |x| Ok(x.into())),
                         v => bail!("Cannot convert {} to {}<f64>", v.data_type(), stringify!($ty)),
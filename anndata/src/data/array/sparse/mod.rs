@@ -1,7 +1,29 @@
 mod csr;
 mod csc;
+mod coo;
+mod compact;
 mod noncanonical;
 mod dynamic;
 
+pub use compact::CompactCsrMatrix;
+pub(crate) use coo::coo_to_csr_non_canonical;
 pub use noncanonical::*;
-pub use dynamic::*;
\ No newline at end of file
+pub use dynamic::*;
+
+/// Compressed sparse matrix formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SparseFormat {
+    /// Compressed sparse row format.
+    Csr,
+    /// Compressed sparse column format.
+    Csc,
+}
+
+impl std::fmt::Display for SparseFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SparseFormat::Csr => write!(f, "csr_matrix"),
+            SparseFormat::Csc => write!(f, "csc_matrix"),
+        }
+    }
+}
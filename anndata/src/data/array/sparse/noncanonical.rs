@@ -2,19 +2,20 @@ use std::collections::HashMap;
 
 use crate::backend::*;
 use crate::data::{
-    array::utils::{cs_major_index, cs_major_minor_index, cs_major_slice},
+    array::utils::{cs_major_index, cs_major_minor_index, cs_major_slice, cs_read_select},
     data_traits::*,
     slice::{SelectInfoElem, Shape},
     SelectInfoBounds, SelectInfoElemBounds,
 };
 
 use anyhow::{bail, Result};
+use half::f16;
 use nalgebra_sparse::pattern::SparsityPattern;
 use nalgebra_sparse::{coo::CooMatrix, csr::CsrMatrix};
 use ndarray::Ix1;
 
 use super::super::slice::SliceBounds;
-use super::DynCsrMatrix;
+use super::{DynCooMatrix, DynCsrMatrix};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DynCsrNonCanonical {
@@ -26,6 +27,7 @@ pub enum DynCsrNonCanonical {
     U16(CsrNonCanonical<u16>),
     U32(CsrNonCanonical<u32>),
     U64(CsrNonCanonical<u64>),
+    F16(CsrNonCanonical<f16>),
     F32(CsrNonCanonical<f32>),
     F64(CsrNonCanonical<f64>),
     Bool(CsrNonCanonical<bool>),
@@ -67,6 +69,10 @@ impl DynCsrNonCanonical {
                 .canonicalize()
                 .map(DynCsrMatrix::U64)
                 .map_err(Into::into),
+            DynCsrNonCanonical::F16(data) => data
+                .canonicalize()
+                .map(DynCsrMatrix::F16)
+                .map_err(Into::into),
             DynCsrNonCanonical::F32(data) => data
                 .canonicalize()
                 .map(DynCsrMatrix::F32)
@@ -113,8 +119,8 @@ macro_rules! impl_noncanonicalcsr_traits {
 }
 
 impl_noncanonicalcsr_traits!(
-    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f32, F32, f64, F64,
-    bool, Bool, String, String
+    i8, I8, i16, I16, i32, I32, i64, I64, u8, U8, u16, U16, u32, U32, u64, U64, f16, F16, f32, F32,
+    f64, F64, bool, Bool, String, String
 );
 
 impl From<DynCsrMatrix> for DynCsrNonCanonical {
@@ -128,6 +134,17 @@ impl From<DynCsrMatrix> for DynCsrNonCanonical {
     }
 }
 
+impl From<DynCsrNonCanonical> for DynCooMatrix {
+    fn from(value: DynCsrNonCanonical) -> Self {
+        macro_rules! fun {
+            ($variant:ident, $data:expr) => {
+                DynCooMatrix::$variant(CooMatrix::from(&$data))
+            };
+        }
+        crate::macros::dyn_map!(value, DynCsrNonCanonical, fun)
+    }
+}
+
 impl Element for DynCsrNonCanonical {
     fn data_type(&self) -> DataType {
         crate::macros::dyn_map_fun!(self, DynCsrNonCanonical, data_type)
@@ -212,6 +229,9 @@ impl Stackable for DynCsrNonCanonical {
             DynCsrNonCanonical::I64(_) => Ok(DynCsrNonCanonical::I64(
                 CsrNonCanonical::<i64>::vstack(iter.map(|x| x.try_into().unwrap()))?,
             )),
+            DynCsrNonCanonical::F16(_) => Ok(DynCsrNonCanonical::F16(
+                CsrNonCanonical::<f16>::vstack(iter.map(|x| x.try_into().unwrap()))?,
+            )),
             DynCsrNonCanonical::F32(_) => Ok(DynCsrNonCanonical::F32(
                 CsrNonCanonical::<f32>::vstack(iter.map(|x| x.try_into().unwrap()))?,
             )),
@@ -761,7 +781,6 @@ impl<T: BackendData> ReadableArray for CsrNonCanonical<T> {
             .collect())
     }
 
-    // TODO: efficient implementation for slice
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
     where
         B: Backend,
@@ -775,40 +794,15 @@ impl<T: BackendData> ReadableArray for CsrNonCanonical<T> {
             return Self::read(container);
         }
 
-        let data = if let SelectInfoElem::Slice(s) = info[0].as_ref() {
-            let group = container.as_group()?;
-            let indptr_slice = if let Some(end) = s.end {
-                SelectInfoElem::from(s.start..end + 1)
-            } else {
-                SelectInfoElem::from(s.start..)
-            };
-            let mut indptr: Vec<usize> = group
-                .open_dataset("indptr")?
-                .read_array_slice_cast(&[indptr_slice])?
-                .to_vec();
-            let lo = indptr[0];
-            let slice = SelectInfoElem::from(lo..indptr[indptr.len() - 1]);
-            let data: Vec<T> = group
-                .open_dataset("data")?
-                .read_array_slice(&[&slice])?
-                .to_vec();
-            let indices: Vec<usize> = group
-                .open_dataset("indices")?
-                .read_array_slice_cast(&[&slice])?
-                .to_vec();
-            indptr.iter_mut().for_each(|x| *x -= lo);
-            Self::from_csr_data(
-                indptr.len() - 1,
-                Self::get_shape(container)?[1],
-                indptr,
-                indices,
-                data,
-            )
-            .select_axis(1, info[1].as_ref())
-        } else {
-            Self::read(container)?.select(info)
-        };
-        Ok(data)
+        let shape = Self::get_shape(container)?;
+        let (indptr, indices, data) = cs_read_select::<B, T, usize>(
+            container.as_group()?,
+            info[0].as_ref(),
+            info[1].as_ref(),
+            (shape[0], shape[1]),
+        )?;
+        let ncols = SelectInfoElemBounds::new(&info[1], shape[1]).len();
+        Ok(Self::from_csr_data(indptr.len() - 1, ncols, indptr, indices, data))
     }
 }
 
@@ -1,14 +1,15 @@
 use crate::backend::{Backend, BackendData, DatasetOp, GroupOp, WriteConfig};
-use crate::data::{SelectInfoElem, Shape};
+use crate::data::{ArrayConvert, SelectInfoElem, SelectInfoElemBounds, Shape};
 use crate::ArrayData;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use itertools::Itertools;
-use ndarray::{ArrayView, RemoveAxis};
+use ndarray::{Array1, Array2, ArrayView, ArrayView1, Ix1, Ix2, RemoveAxis};
 use smallvec::SmallVec;
-use nalgebra_sparse::{CsrMatrix, pattern::{ SparsityPattern, SparsityPatternFormatError}};
+use nalgebra_sparse::{CooMatrix, CscMatrix, CsrMatrix, pattern::{ SparsityPattern, SparsityPatternFormatError}};
 
-use super::CsrNonCanonical;
+use super::slice::SliceBounds;
+use super::{CsrNonCanonical, DynArray, DynCooMatrix, DynCscMatrix, DynCsrMatrix, DynCsrNonCanonical};
 
 pub(crate) struct ExtendableDataset<B: Backend, T> {
     dataset: B::Dataset,
@@ -96,6 +97,67 @@ impl<B: Backend, T: BackendData> ExtendableDataset<B, T> {
     }
 }
 
+/// An extendable dataset of sparse matrix indices. As in scipy, indices are
+/// stored as i32 when all of them are known to fit, and as i64 otherwise.
+pub(crate) enum ExtendableIndices<B: Backend> {
+    I32(ExtendableDataset<B, i32>),
+    I64(ExtendableDataset<B, i64>),
+}
+
+impl<B: Backend> ExtendableIndices<B> {
+    /// `max_index` is an upper bound of the indices that will be written.
+    pub fn with_capacity<G>(group: &G, name: &str, capacity: usize, max_index: usize) -> Result<Self>
+    where
+        G: GroupOp<B>,
+    {
+        if i32::try_from(max_index).is_ok() {
+            Ok(Self::I32(ExtendableDataset::with_capacity(group, name, capacity.into())?))
+        } else {
+            Ok(Self::I64(ExtendableDataset::with_capacity(group, name, capacity.into())?))
+        }
+    }
+
+    pub fn extend<I: IntoIterator<Item = usize>>(&mut self, indices: I) -> Result<()> {
+        match self {
+            Self::I32(dataset) => {
+                let indices = indices
+                    .into_iter()
+                    .map(i32::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                dataset.extend(0, ArrayView1::from(&indices))
+            }
+            Self::I64(dataset) => {
+                let indices = indices
+                    .into_iter()
+                    .map(i64::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                dataset.extend(0, ArrayView1::from(&indices))
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::I32(dataset) => dataset.finish().map(|_| ()),
+            Self::I64(dataset) => dataset.finish().map(|_| ()),
+        }
+    }
+}
+
+/// Write the `indptr` of a compressed sparse matrix, as i32 if the number of
+/// non-zero entries fits and as i64 otherwise.
+pub(crate) fn write_indptr<B: Backend, G: GroupOp<B>>(group: &G, indptr: &[usize]) -> Result<()> {
+    let nnz = indptr.last().copied().unwrap_or(0);
+    if i32::try_from(nnz).is_ok() {
+        let indptr: Vec<i32> = indptr.iter().map(|x| *x as i32).collect();
+        group.new_array_dataset("indptr", indptr.into(), Default::default())?;
+    } else {
+        let indptr: Vec<i64> = indptr.iter().map(|x| *x as i64).collect();
+        group.new_array_dataset("indptr", indptr.into(), Default::default())?;
+    }
+    Ok(())
+}
+
 /// select rows of csr_matrix, or columns of csc_matrix
 /// - major_indices: row_indices/col_indices of csr/csc matrix
 /// - offset: indptr
@@ -213,6 +275,303 @@ where
     (new_offsets, new_indices, new_values)
 }
 
+/// In-memory type of the minor indices of a compressed sparse matrix.
+pub(crate) trait CsIndex: Copy + Ord {
+    fn from_usize(i: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+impl CsIndex for usize {
+    fn from_usize(i: usize) -> Self {
+        i
+    }
+
+    fn to_usize(self) -> usize {
+        self
+    }
+}
+
+impl CsIndex for u32 {
+    fn from_usize(i: usize) -> Self {
+        i as u32
+    }
+
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+/// Number of `indices`/`data` entries read at a time by `cs_read_select`.
+const CS_READ_CHUNK: usize = 1 << 20;
+
+/// Maps the minor indices of a compressed sparse matrix, or the indices along
+/// any axis of a sparse matrix, to their new positions.
+pub(crate) enum MinorIndex {
+    Full,
+    Slice(SliceBounds),
+    /// Pairs of (old index, new position) sorted by the old index. An old
+    /// index may appear several times.
+    Index(Vec<(usize, usize)>),
+}
+
+impl MinorIndex {
+    pub fn new(select: &SelectInfoElem, len_minor: usize) -> Self {
+        match SelectInfoElemBounds::new(select, len_minor) {
+            SelectInfoElemBounds::Slice(s) if s.step > 0 => {
+                if s.start == 0 && s.end == len_minor && s.step == 1 {
+                    Self::Full
+                } else {
+                    Self::Slice(s)
+                }
+            }
+            bounds => {
+                let mut index: Vec<_> = bounds.iter().enumerate().map(|(new, old)| (old, new)).collect();
+                index.sort_unstable();
+                Self::Index(index)
+            }
+        }
+    }
+
+    pub fn for_each<F: FnMut(usize)>(&self, i: usize, mut f: F) {
+        match self {
+            Self::Full => f(i),
+            Self::Slice(s) => {
+                let step = s.step as usize;
+                if i >= s.start && i < s.end && (i - s.start) % step == 0 {
+                    f((i - s.start) / step)
+                }
+            }
+            Self::Index(index) => {
+                let start = index.partition_point(|(old, _)| *old < i);
+                index[start..]
+                    .iter()
+                    .take_while(|(old, _)| *old == i)
+                    .for_each(|(_, j)| f(*j));
+            }
+        }
+    }
+
+    pub fn is_sorted(&self) -> bool {
+        !matches!(self, Self::Index(_))
+    }
+}
+
+/// Read a selection of a compressed sparse matrix (CSR or CSC) stored in `group`.
+/// Only the `indptr` range spanning the selected lanes is loaded. `indices` and
+/// `data` are streamed lane block by lane block, keeping only the entries that
+/// fall in the minor selection; `data` is only read where entries are kept.
+/// - major: row selection of csr_matrix, column selection of csc_matrix
+/// - minor: column selection of csr_matrix, row selection of csc_matrix
+/// - shape: (len_major, len_minor)
+///
+/// The minor indices are read directly as `I`, so they are never widened when
+/// `I` is `u32`. The new minor indices must fit in `I`.
+pub(crate) fn cs_read_select<B, T, I>(
+    group: &B::Group,
+    major: &SelectInfoElem,
+    minor: &SelectInfoElem,
+    shape: (usize, usize),
+) -> Result<(Vec<usize>, Vec<I>, Vec<T>)>
+where
+    B: Backend,
+    T: BackendData,
+    I: CsIndex,
+    DynArray: ArrayConvert<Array1<I>>,
+{
+    let (len_major, len_minor) = shape;
+    let major = SelectInfoElemBounds::new(major, len_major);
+    let minor = MinorIndex::new(minor, len_minor);
+
+    // A contiguous range of lanes is read directly, only rebasing `indptr`.
+    if let (SelectInfoElemBounds::Slice(s), MinorIndex::Full) = (&major, &minor) {
+        if s.step == 1 {
+            let end = s.end.max(s.start);
+            let mut indptr: Vec<usize> = group
+                .open_dataset("indptr")?
+                .read_array_slice_cast::<usize, Ix1, _>(&[SelectInfoElem::from(s.start..end + 1)])?
+                .to_vec();
+            let lo = indptr[0];
+            let slice = [SelectInfoElem::from(lo..indptr[indptr.len() - 1])];
+            let indices: Vec<I> =
+                group.open_dataset("indices")?.read_array_slice_cast(&slice)?.to_vec();
+            let data: Vec<T> = group.open_dataset("data")?.read_array_slice(&slice)?.to_vec();
+            indptr.iter_mut().for_each(|x| *x -= lo);
+            return Ok((indptr, indices, data));
+        }
+    }
+
+    let lanes = major.to_vec();
+    let unique: Vec<usize> = lanes.iter().copied().sorted_unstable().dedup().collect();
+    if unique.is_empty() {
+        return Ok((vec![0], Vec::new(), Vec::new()));
+    }
+
+    let lo = unique[0];
+    let hi = unique[unique.len() - 1] + 1;
+    let offsets: Vec<usize> = group
+        .open_dataset("indptr")?
+        .read_array_slice_cast::<usize, Ix1, _>(&[SelectInfoElem::from(lo..hi + 1)])?
+        .to_vec();
+    let indices_dataset = group.open_dataset("indices")?;
+    let data_dataset = group.open_dataset("data")?;
+
+    let mut filtered: Vec<(Vec<I>, Vec<T>)> =
+        (0..unique.len()).map(|_| (Vec::new(), Vec::new())).collect();
+    let mut block_start = 0;
+    while block_start < unique.len() {
+        // Lanes that are adjacent on disk are read together.
+        let mut block_end = block_start + 1;
+        while block_end < unique.len() && unique[block_end] == unique[block_end - 1] + 1 {
+            block_end += 1;
+        }
+        let start = offsets[unique[block_start] - lo];
+        let end = offsets[unique[block_end - 1] + 1 - lo];
+        let mut lane = block_start;
+        for chunk_start in (start..end).step_by(CS_READ_CHUNK) {
+            let chunk_end = end.min(chunk_start + CS_READ_CHUNK);
+            let indices: Array1<I> = indices_dataset
+                .read_array_slice_cast(&[SelectInfoElem::from(chunk_start..chunk_end)])?;
+            let mut kept = Vec::new();
+            indices
+                .iter()
+                .enumerate()
+                .for_each(|(k, i)| minor.for_each(i.to_usize(), |j| kept.push((k, j))));
+            if kept.is_empty() {
+                continue;
+            }
+
+            let first = kept[0].0;
+            let last = kept[kept.len() - 1].0 + 1;
+            let data: Array1<T> = data_dataset.read_array_slice(&[SelectInfoElem::from(
+                chunk_start + first..chunk_start + last,
+            )])?;
+            kept.into_iter().for_each(|(k, j)| {
+                while chunk_start + k >= offsets[unique[lane] + 1 - lo] {
+                    lane += 1;
+                }
+                filtered[lane].0.push(I::from_usize(j));
+                filtered[lane].1.push(data[k - first].clone());
+            });
+        }
+        block_start = block_end;
+    }
+
+    if !minor.is_sorted() {
+        filtered.iter_mut().for_each(|(indices, data)| {
+            let mut permutation = permutation::sort(&indices[..]);
+            permutation.apply_slice_in_place(&mut indices[..]);
+            permutation.apply_slice_in_place(&mut data[..]);
+        });
+    }
+
+    let mut new_offsets = Vec::with_capacity(lanes.len() + 1);
+    let mut new_indices = Vec::new();
+    let mut new_data = Vec::new();
+    new_offsets.push(0);
+    lanes.into_iter().for_each(|i| {
+        let (indices, data) = &filtered[unique.binary_search(&i).unwrap()];
+        new_indices.extend_from_slice(indices);
+        new_data.extend_from_slice(data);
+        new_offsets.push(new_indices.len());
+    });
+    Ok((new_offsets, new_indices, new_data))
+}
+
+/// Converts a compressed sparse matrix stored in `src` to the compressed format
+/// of the other axis, i.e., CSR to CSC or vice versa, and writes `indptr`,
+/// `indices` and `data` to `dst`. The matrix is never loaded as a whole.
+/// A counting pass over `indices` computes the new `indptr`, after which the
+/// source is read once in blocks of at most `buffer_size` entries, and every
+/// entry is written directly to its final position in the new lanes.
+/// - shape: (len_major, len_minor) of the source
+pub(crate) fn cs_convert<B: Backend, O: Backend, T: BackendData>(
+    src: &B::Group,
+    dst: &O::Group,
+    shape: (usize, usize),
+    buffer_size: usize,
+) -> Result<()> {
+    let (len_major, len_minor) = shape;
+    let indptr_dataset = src.open_dataset("indptr")?;
+    let indices_dataset = src.open_dataset("indices")?;
+    let data_dataset = src.open_dataset("data")?;
+    let nnz = indices_dataset.shape()[0];
+
+    let mut new_offsets = vec![0usize; len_minor + 1];
+    for start in (0..nnz).step_by(CS_READ_CHUNK) {
+        let indices: Array1<usize> = indices_dataset
+            .read_array_slice_cast(&[SelectInfoElem::from(start..nnz.min(start + CS_READ_CHUNK))])?;
+        for i in indices {
+            if i >= len_minor {
+                bail!("index {} is out of bounds for a matrix with {} minor lanes", i, len_minor);
+            }
+            new_offsets[i] += 1;
+        }
+    }
+    convert_counts_to_offsets(&mut new_offsets);
+
+    let config = || WriteConfig { block_size: Some(vec![1000].into()), ..Default::default() };
+    let capacity: Shape = nnz.max(1).into();
+    let mut data_out = dst.new_empty_dataset::<T>("data", &capacity, config())?;
+    let wide_indices = i32::try_from(len_major.saturating_sub(1)).is_err();
+    let mut indices_out = if wide_indices {
+        dst.new_empty_dataset::<i64>("indices", &capacity, config())?
+    } else {
+        dst.new_empty_dataset::<i32>("indices", &capacity, config())?
+    };
+
+    // Next position to be written in each new lane. Entries are visited in
+    // the order of their major lanes, which keeps the new minor indices of
+    // every new lane sorted.
+    let mut position = new_offsets[..len_minor].to_vec();
+    for major_start in (0..len_major).step_by(CS_READ_CHUNK) {
+        let major_end = len_major.min(major_start + CS_READ_CHUNK);
+        let offsets: Vec<usize> = indptr_dataset
+            .read_array_slice_cast::<usize, Ix1, _>(&[SelectInfoElem::from(major_start..major_end + 1)])?
+            .to_vec();
+        let (start, end) = (offsets[0], offsets[offsets.len() - 1]);
+        let mut lane = 0;
+        for chunk_start in (start..end).step_by(buffer_size) {
+            let chunk_end = end.min(chunk_start + buffer_size);
+            let slice = [SelectInfoElem::from(chunk_start..chunk_end)];
+            let chunk_indices: Array1<usize> = indices_dataset.read_array_slice_cast(&slice)?;
+            let chunk_data: Array1<T> = data_dataset.read_array_slice(&slice)?;
+            let majors: Vec<usize> = (chunk_start..chunk_end)
+                .map(|k| {
+                    while k >= offsets[lane + 1] {
+                        lane += 1;
+                    }
+                    major_start + lane
+                })
+                .collect();
+
+            // The entries of a new lane in this block are contiguous in the output.
+            let mut order: Vec<usize> = (0..chunk_indices.len()).collect();
+            order.sort_by_key(|k| chunk_indices[*k]);
+            for (i, run) in &order.into_iter().chunk_by(|k| chunk_indices[*k]) {
+                let run: Vec<usize> = run.collect();
+                let p = position[i];
+                let out = [SelectInfoElem::from(p..p + run.len())];
+                let run_data: Array1<T> = run.iter().map(|k| chunk_data[*k].clone()).collect();
+                data_out.write_array_slice(run_data.into(), &out)?;
+                if wide_indices {
+                    let run_indices: Array1<i64> = run.iter().map(|k| majors[*k] as i64).collect();
+                    indices_out.write_array_slice(run_indices.into(), &out)?;
+                } else {
+                    let run_indices: Array1<i32> = run.iter().map(|k| majors[*k] as i32).collect();
+                    indices_out.write_array_slice(run_indices.into(), &out)?;
+                }
+                position[i] += run.len();
+            }
+        }
+    }
+    if nnz == 0 {
+        data_out.reshape(&nnz.into())?;
+        indices_out.reshape(&nnz.into())?;
+    }
+
+    write_indptr::<O, _>(dst, &new_offsets)
+}
+
 /// Converts matrix data given in triplet format to unsorted CSR/CSC, retaining any duplicated
 /// indices.
 ///
@@ -318,6 +677,109 @@ pub(crate) fn compute_sort_permutation(permutation: &mut [usize], indices: &[usi
     permutation.sort_unstable_by_key(|idx| indices[*idx]);
 }
 
+/// Build an array with `ncols` columns in which the i-th column of `data` is
+/// placed at `positions[i]`. The other columns are filled with zeros, i.e.,
+/// default values for dense arrays and missing entries for sparse arrays.
+pub(crate) fn expand_columns(data: ArrayData, positions: &[usize], ncols: usize) -> Result<ArrayData> {
+    if positions.len() == ncols && positions.iter().enumerate().all(|(i, j)| i == *j) {
+        return Ok(data);
+    }
+    macro_rules! dense {
+        ($variant:ident, $data:expr) => {{
+            let arr = $data.into_dimensionality::<Ix2>()?;
+            let mut out = Array2::default((arr.nrows(), ncols));
+            positions
+                .iter()
+                .zip(arr.columns())
+                .for_each(|(j, col)| out.column_mut(*j).assign(&col));
+            DynArray::$variant(out.into_dyn()).into()
+        }};
+    }
+    macro_rules! csr {
+        ($variant:ident, $data:expr) => {
+            csr_expand_columns($data, positions, ncols).into()
+        };
+    }
+    macro_rules! csr_noncanonical {
+        ($variant:ident, $data:expr) => {{
+            let nrows = $data.nrows();
+            let (offsets, indices, values) = $data.disassemble();
+            let indices = indices.into_iter().map(|i| positions[i]).collect();
+            CsrNonCanonical::from_csr_data(nrows, ncols, offsets, indices, values).into()
+        }};
+    }
+    macro_rules! csc {
+        ($variant:ident, $data:expr) => {
+            csc_expand_columns($data, positions, ncols).into()
+        };
+    }
+    macro_rules! coo {
+        ($variant:ident, $data:expr) => {{
+            let nrows = $data.nrows();
+            let (rows, cols, values) = $data.disassemble();
+            let cols = cols.into_iter().map(|j| positions[j]).collect();
+            CooMatrix::try_from_triplets(nrows, ncols, rows, cols, values)
+                .map_err(|e| anyhow!("{}", e))?
+                .into()
+        }};
+    }
+    let data = match data {
+        ArrayData::Array(arr) => crate::macros::dyn_map!(arr, DynArray, dense),
+        ArrayData::CsrMatrix(csr) => crate::macros::dyn_map!(csr, DynCsrMatrix, csr),
+        ArrayData::CsrNonCanonical(csr) => {
+            crate::macros::dyn_map!(csr, DynCsrNonCanonical, csr_noncanonical)
+        }
+        ArrayData::CscMatrix(csc) => crate::macros::dyn_map!(csc, DynCscMatrix, csc),
+        ArrayData::CooMatrix(coo) => crate::macros::dyn_map!(coo, DynCooMatrix, coo),
+        ArrayData::Nullable(_) => bail!("cannot remap the columns of a nullable array"),
+        ArrayData::DataFrame(_) => bail!("cannot remap the columns of a DataFrame"),
+    };
+    Ok(data)
+}
+
+fn csr_expand_columns<T: Clone>(csr: CsrMatrix<T>, positions: &[usize], ncols: usize) -> CsrMatrix<T> {
+    let nrows = csr.nrows();
+    let (offsets, indices, values) = csr.disassemble();
+    let indices: Vec<usize> = indices.into_iter().map(|i| positions[i]).collect();
+    let (indices, values) = if positions.windows(2).all(|w| w[0] < w[1]) {
+        (indices, values)
+    } else {
+        let mut sorted_indices = indices.clone();
+        let mut sorted_values = values.clone();
+        let mut workspace = vec![0; indices.len()];
+        offsets.windows(2).for_each(|w| {
+            let (lo, hi) = (w[0], w[1]);
+            sort_lane(
+                &mut sorted_indices[lo..hi],
+                &mut sorted_values[lo..hi],
+                &indices[lo..hi],
+                &values[lo..hi],
+                &mut workspace[lo..hi],
+            );
+        });
+        (sorted_indices, sorted_values)
+    };
+    CsrMatrix::try_from_csr_data(nrows, ncols, offsets, indices, values).unwrap()
+}
+
+fn csc_expand_columns<T: Clone>(csc: CscMatrix<T>, positions: &[usize], ncols: usize) -> CscMatrix<T> {
+    let mut columns = vec![None; ncols];
+    positions.iter().enumerate().for_each(|(i, j)| columns[*j] = Some(i));
+    let mut offsets = Vec::with_capacity(ncols + 1);
+    let mut indices = Vec::with_capacity(csc.nnz());
+    let mut values = Vec::with_capacity(csc.nnz());
+    offsets.push(0);
+    columns.into_iter().for_each(|c| {
+        if let Some(c) = c {
+            let lane = csc.col(c);
+            indices.extend_from_slice(lane.row_indices());
+            values.extend_from_slice(lane.values());
+        }
+        offsets.push(indices.len());
+    });
+    CscMatrix::try_from_csc_data(csc.nrows(), ncols, offsets, indices, values).unwrap()
+}
+
 pub fn from_csr_data<T>(
     nrows: usize,
     ncols: usize,
@@ -10,7 +10,8 @@ use std::borrow::Borrow;
 use std::hash::Hash;
 use std::cmp::Eq;
 
-use super::SelectInfoElemBounds;
+use super::{array::utils::expand_columns, ArrayData, SelectInfoElemBounds, Selectable};
+use anyhow::Result;
 
 #[derive(Clone, Debug)]
 pub enum Index {
@@ -409,6 +410,11 @@ impl VecVecIndex {
     pub fn len(&self) -> usize {
         *self.0.last().unwrap_or(&0)
     }
+
+    /// The range of the flattened view covered by the i-th inner vector.
+    pub fn inner_range(&self, i: usize) -> std::ops::Range<usize> {
+        self.0[i]..self.0[i + 1]
+    }
 }
 
 impl FromIterator<usize> for VecVecIndex {
@@ -426,6 +432,71 @@ impl FromIterator<usize> for VecVecIndex {
     }
 }
 
+/// Mapping from the columns of a joined array to the columns of one of its
+/// components.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ColumnMapping {
+    /// Number of columns in the component.
+    n_cols: usize,
+    /// The column of the component for each column of the joined array.
+    /// `None` means that the column is missing from the component and is
+    /// filled with zeros.
+    columns: Vec<Option<usize>>,
+}
+
+impl ColumnMapping {
+    pub fn new(n_cols: usize, columns: Vec<Option<usize>>) -> Self {
+        Self { n_cols, columns }
+    }
+
+    /// Number of columns in the joined array.
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Number of columns in the component.
+    pub fn n_cols(&self) -> usize {
+        self.n_cols
+    }
+
+    /// Whether the component has exactly the columns of the joined array.
+    pub fn is_identity(&self) -> bool {
+        self.n_cols == self.columns.len()
+            && self.columns.iter().enumerate().all(|(i, c)| *c == Some(i))
+    }
+
+    /// Translate a column selection of the joined array into a selection of
+    /// the component. Also returns the positions of the selected component
+    /// columns in the result and the number of columns of the result.
+    pub fn split_select(&self, select: &SelectInfoElem) -> (SelectInfoElem, Vec<usize>, usize) {
+        let bounds = SelectInfoElemBounds::new(select, self.len());
+        let (positions, columns): (Vec<_>, Vec<_>) = (0..bounds.len())
+            .flat_map(|i| self.columns[bounds.index(i)].map(|c| (i, c)))
+            .unzip();
+        (columns.into(), positions, bounds.len())
+    }
+
+    /// Remap an array containing all columns of the component.
+    pub fn remap(&self, data: ArrayData) -> Result<ArrayData> {
+        if self.is_identity() {
+            return Ok(data);
+        }
+        let (select, positions, n) = self.split_select(&SelectInfoElem::full());
+        expand_columns(data.select_axis(1, select), &positions, n)
+    }
+
+    /// The positions of the component columns in the joined array, or `None`
+    /// if some columns of the component are not part of the joined array.
+    pub fn inverse(&self) -> Option<Vec<usize>> {
+        let mut inv = vec![None; self.n_cols];
+        self.columns
+            .iter()
+            .enumerate()
+            .for_each(|(i, c)| if let Some(c) = c { inv[*c] = Some(i) });
+        inv.into_iter().collect()
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
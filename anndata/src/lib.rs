@@ -4,10 +4,14 @@ pub mod backend;
 pub mod data;
 pub mod container;
 pub mod reader;
+pub mod writer;
 mod macros;
 
-pub use traits::{AnnDataOp, AxisArraysOp, ElemCollectionOp, ArrayElemOp};
-pub use crate::anndata::{AnnData, AnnDataSet, StackedAnnData};
+pub use traits::{AnnDataOp, AxisArraysOp, ElemCollectionOp, ArrayElemOp, RawOp};
+pub use crate::anndata::{
+    AnnData, AnnDataSet, AnnDataView, ArrayElemView, AxisArraysView, ChunkedArrayElemView,
+    ElemCollectionView, Join, Raw, RawView, StackedAnnData, StackedRaw,
+};
 pub use backend::Backend;
 pub use data::{HasShape, Data, Readable, Writable, ArrayData, WritableArray, ReadableArray, Selectable};
 pub use container::{
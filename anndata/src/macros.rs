@@ -9,6 +9,7 @@ macro_rules! dyn_match {
             $enum::U16 => $inner_macro!(U16),
             $enum::U32 => $inner_macro!(U32),
             $enum::U64 => $inner_macro!(U64),
+            $enum::F16 => $inner_macro!(F16),
             $enum::F32 => $inner_macro!(F32),
             $enum::F64 => $inner_macro!(F64),
             $enum::Bool => $inner_macro!(Bool),
@@ -28,6 +29,7 @@ macro_rules! dyn_map {
             $enum::U16(_val) => $inner_macro!(U16, _val),
             $enum::U32(_val) => $inner_macro!(U32, _val),
             $enum::U64(_val) => $inner_macro!(U64, _val),
+            $enum::F16(_val) => $inner_macro!(F16, _val),
             $enum::F32(_val) => $inner_macro!(F32, _val),
             $enum::F64(_val) => $inner_macro!(F64, _val),
             $enum::Bool(_val) => $inner_macro!(Bool, _val),
@@ -47,6 +49,7 @@ macro_rules! dyn_map_fun {
             $enum::U16(_val) => _val.$fun($($arg),*),
             $enum::U32(_val) => _val.$fun($($arg),*),
             $enum::U64(_val) => _val.$fun($($arg),*),
+            $enum::F16(_val) => _val.$fun($($arg),*),
             $enum::F32(_val) => _val.$fun($($arg),*),
             $enum::F64(_val) => _val.$fun($($arg),*),
             $enum::Bool(_val) => _val.$fun($($arg),*),
@@ -1,22 +1,88 @@
+use crate::backend::{Backend, BackendData, DatasetOp, GroupOp, ScalarType};
 use crate::data::utils::to_csr_data;
-use crate::{data::array::DataFrameIndex, AnnDataOp, ArrayData};
+use crate::data::{DynArray, DynCooMatrix, DynCsrMatrix, SelectInfoElem, Selectable};
+use crate::{data::array::DataFrameIndex, AnnDataOp, ArrayData, AxisArraysOp};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use flate2::read::MultiGzDecoder;
 use itertools::Itertools;
 use nalgebra_sparse::{coo::CooMatrix, csr::CsrMatrix};
+use ndarray::{Array2, Ix1};
+use polars::prelude::{DataFrame, Series};
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::iter::Peekable;
+use std::marker::PhantomData;
 use std::path::Path;
 use std::{error::Error, fmt, io};
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
 };
 
+/// Evaluate `$body` with `$T` bound to the Rust type of a scalar type that
+/// Matrix Market values can be stored as.
+macro_rules! with_mm_type {
+    ($ty:expr, $T:ident => $body:expr) => {
+        match $ty {
+            ScalarType::I8 => {
+                type $T = i8;
+                $body
+            }
+            ScalarType::I16 => {
+                type $T = i16;
+                $body
+            }
+            ScalarType::I32 => {
+                type $T = i32;
+                $body
+            }
+            ScalarType::I64 => {
+                type $T = i64;
+                $body
+            }
+            ScalarType::U8 => {
+                type $T = u8;
+                $body
+            }
+            ScalarType::U16 => {
+                type $T = u16;
+                $body
+            }
+            ScalarType::U32 => {
+                type $T = u32;
+                $body
+            }
+            ScalarType::U64 => {
+                type $T = u64;
+                $body
+            }
+            ScalarType::F32 => {
+                type $T = f32;
+                $body
+            }
+            ScalarType::F64 => {
+                type $T = f64;
+                $body
+            }
+            ScalarType::Bool => {
+                type $T = bool;
+                $body
+            }
+            ty => bail!("cannot store Matrix Market values as {}", ty),
+        }
+    };
+}
+
 pub struct MMReader {
     reader: Box<dyn BufRead>,
     obs_names: Option<DataFrameIndex>,
     var_names: Option<DataFrameIndex>,
     sorted: bool,
+    run_size: Option<usize>,
+    dtype: Option<ScalarType>,
+    chunk_size: usize,
 }
 
 impl MMReader {
@@ -26,6 +92,9 @@ impl MMReader {
             obs_names: None,
             var_names: None,
             sorted: false,
+            run_size: None,
+            dtype: None,
+            chunk_size: 2000,
         })
     }
 
@@ -49,28 +118,65 @@ impl MMReader {
         Ok(self)
     }
 
+    /// The entries are sorted by row, so they can be streamed to X. Symmetric
+    /// matrices cannot be streamed, as the mirrored entries are not sorted.
     pub fn is_sorted(mut self) -> Self {
         self.sorted = true;
         self
     }
 
+    /// Read unsorted files with bounded memory. The entries are parsed in
+    /// parallel and sorted in runs of at most `run_size` entries, which are
+    /// spilled to temporary files and merged into chunks of rows.
+    pub fn external_sort(mut self, run_size: usize) -> Self {
+        self.run_size = Some(run_size);
+        self
+    }
+
+    /// The number of rows written to X at a time when the entries are read as
+    /// sorted or with an external sort.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Store the values as `dtype`, e.g., `ScalarType::U32` for counts or
+    /// `ScalarType::U8` for the ones of pattern matrices. By default, integer
+    /// matrices are stored as i64, real matrices as f64 and pattern matrices
+    /// as bool.
+    pub fn dtype(mut self, dtype: ScalarType) -> Self {
+        self.dtype = Some(dtype);
+        self
+    }
+
+    /// Read the whole matrix into memory, e.g., to store a graph in obsp.
+    pub fn read(mut self) -> Result<ArrayData> {
+        let (sym_mode, field) = read_header(&mut self.reader)?;
+        let ty = mm_value_type(field, self.dtype)?;
+        let pattern = field == DataType::Pattern;
+        let data: ArrayData =
+            with_mm_type!(ty, T => read_mtx_body::<T, _>(&mut self.reader, sym_mode, pattern)?.into());
+        Ok(data)
+    }
+
     pub fn finish<O: AnnDataOp>(mut self, output: &O) -> Result<()> {
+        let (sym_mode, field) = read_header(&mut self.reader)?;
+        let ty = mm_value_type(field, self.dtype)?;
+        let pattern = field == DataType::Pattern;
         if self.sorted {
-            let (_, cols, iter) = read_sorted_mm_body_from_bufread::<_, f64>(&mut self.reader);
-            output.set_x_from_iter(
-                iter
-                    .chunk_by(|x| x.0)
-                    .into_iter()
-                    .map(|x| x.1.map(|(_, j, v)| (j, v)).collect::<Vec<_>>())
-                    .chunks(2000)
-                    .into_iter()
-                    .map(|x| {
-                        let (r, c, indptr, indices, data) = to_csr_data(x.into_iter().collect::<Vec<_>>(), cols);
-                        CsrMatrix::try_from_csr_data(r, c, indptr, indices, data).unwrap()
-                    })
-            )?;
+            ensure!(
+                sym_mode == SymmetryMode::General,
+                "symmetric matrices cannot be read as sorted, use `external_sort` instead"
+            );
+            with_mm_type!(ty, T => read_mtx_sorted::<T, _, _>(&mut self.reader, pattern, self.chunk_size, output)?);
+        } else if let Some(run_size) = self.run_size {
+            with_mm_type!(ty, T => read_mtx_external::<T, _, _>(
+                &mut self.reader, sym_mode, pattern, run_size, self.chunk_size, output
+            )?);
         } else {
-            output.set_x(read_matrix_market_from_bufread(&mut self.reader)?)?;
+            let data: ArrayData =
+                with_mm_type!(ty, T => read_mtx_body::<T, _>(&mut self.reader, sym_mode, pattern)?.into());
+            output.set_x(data)?;
         }
         if let Some(obs_names) = self.obs_names {
             output.set_obs_names(obs_names)?;
@@ -82,6 +188,349 @@ impl MMReader {
     }
 }
 
+/// Reader for the HDF5 feature-barcode matrices produced by Cell Ranger, e.g.,
+/// `filtered_feature_bc_matrix.h5`. The counts are stored in the `matrix` group
+/// as a CSC matrix with features as rows and barcodes as columns, which is read
+/// in chunks of barcodes so that barcodes become the observations.
+pub struct TenXReader<B: Backend> {
+    matrix: B::Group,
+    feature_type: Option<String>,
+    chunk_size: usize,
+}
+
+impl<B: Backend> TenXReader<B> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            matrix: B::open(path)?.open_group("matrix")?,
+            feature_type: None,
+            chunk_size: 2000,
+        })
+    }
+
+    /// The distinct feature types in the matrix, e.g., "Gene Expression" and
+    /// "Antibody Capture", in the order in which they first appear.
+    pub fn feature_types(&self) -> Result<Vec<String>> {
+        let types = self.read_feature_strings("feature_type")?;
+        Ok(types.into_iter().unique().collect())
+    }
+
+    /// Only read the features of the given type. Multi-modal matrices can be
+    /// split by reading each feature type into its own file.
+    pub fn feature_type<S: Into<String>>(mut self, feature_type: S) -> Self {
+        self.feature_type = Some(feature_type.into());
+        self
+    }
+
+    /// The number of barcodes read at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn finish<O: AnnDataOp>(self, output: &O) -> Result<()> {
+        let shape: Vec<usize> = self
+            .matrix
+            .open_dataset("shape")?
+            .read_array_cast::<usize, Ix1>()?
+            .to_vec();
+        ensure!(shape.len() == 2, "the shape of the matrix must have length 2");
+        let (n_features, n_barcodes) = (shape[0], shape[1]);
+
+        // Feature indices in the output, or None if the feature is not read.
+        let selected: Vec<usize> = match &self.feature_type {
+            Some(ty) => {
+                let types = self.read_feature_strings("feature_type")?;
+                ensure!(types.len() == n_features, "the number of feature types does not match the matrix");
+                types.iter().positions(|x| x == ty).collect()
+            }
+            None => (0..n_features).collect(),
+        };
+        if selected.is_empty() {
+            bail!("no features of type '{}'", self.feature_type.unwrap_or_default());
+        }
+        let mut mapping = vec![None; n_features];
+        selected.iter().enumerate().for_each(|(k, i)| mapping[*i] = Some(k));
+
+        output.try_set_x_from_iter(
+            (0..n_barcodes)
+                .step_by(self.chunk_size)
+                .map(|start| {
+                    let end = n_barcodes.min(start + self.chunk_size);
+                    self.read_chunk(start, end, &mapping, selected.len())
+                }),
+        )?;
+
+        let barcodes: Vec<String> = self
+            .matrix
+            .open_dataset("barcodes")?
+            .read_array::<String, Ix1>()?
+            .to_vec();
+        output.set_obs_names(barcodes.into())?;
+
+        let features = self.matrix.open_group("features")?;
+        let mut columns = Vec::new();
+        for name in features.list()? {
+            if name == "id" || name.starts_with('_') {
+                continue;
+            }
+            // Newer versions of Cell Ranger also store groups here.
+            let Ok(dataset) = features.open_dataset(&name) else {
+                continue;
+            };
+            if dataset.shape().as_ref() != &[n_features] {
+                continue;
+            }
+            let values = dataset
+                .read_dyn_array()?
+                .select(&[SelectInfoElem::from(&selected)]);
+            let series: Series = values.into();
+            columns.push(series.with_name(name.as_str().into()));
+        }
+        if !columns.is_empty() {
+            output.set_var(DataFrame::new(columns)?)?;
+        }
+        let ids = self.read_feature_strings("id")?;
+        let ids: Vec<String> = selected.iter().map(|i| ids[*i].clone()).collect();
+        output.set_var_names(ids.into())?;
+        Ok(())
+    }
+
+    fn read_feature_strings(&self, name: &str) -> Result<Vec<String>> {
+        Ok(self
+            .matrix
+            .open_group("features")?
+            .open_dataset(name)?
+            .read_array::<String, Ix1>()?
+            .to_vec())
+    }
+
+    /// Read the barcodes in `start..end` as rows of a CSR matrix.
+    fn read_chunk(
+        &self,
+        start: usize,
+        end: usize,
+        mapping: &[Option<usize>],
+        n_vars: usize,
+    ) -> Result<DynCsrMatrix> {
+        let indptr: Vec<usize> = self
+            .matrix
+            .open_dataset("indptr")?
+            .read_array_slice_cast::<usize, Ix1, _>(&[SelectInfoElem::from(start..end + 1)])?
+            .to_vec();
+        let (lo, hi) = (indptr[0], indptr[end - start]);
+        let slice = [SelectInfoElem::from(lo..hi)];
+        let indices: Vec<usize> = self
+            .matrix
+            .open_dataset("indices")?
+            .read_array_slice_cast::<usize, Ix1, _>(&slice)?
+            .to_vec();
+        let data = self.matrix.open_dataset("data")?.read_dyn_array_slice(&slice)?;
+
+        let mut offsets = Vec::with_capacity(indptr.len());
+        let mut new_indices = Vec::with_capacity(indices.len());
+        let mut positions = Vec::with_capacity(indices.len());
+        offsets.push(0);
+        for w in indptr.windows(2) {
+            for p in w[0] - lo..w[1] - lo {
+                match mapping.get(indices[p]) {
+                    Some(Some(j)) => {
+                        new_indices.push(*j);
+                        positions.push(p);
+                    }
+                    Some(None) => {}
+                    None => bail!("feature index {} is out of bounds", indices[p]),
+                }
+            }
+            offsets.push(new_indices.len());
+        }
+        let data = if positions.len() == indices.len() {
+            data
+        } else {
+            data.select(&[SelectInfoElem::from(positions)])
+        };
+
+        macro_rules! fun {
+            ($variant:ident, $value:expr) => {
+                DynCsrMatrix::$variant(
+                    CsrMatrix::try_from_csr_data(
+                        end - start,
+                        n_vars,
+                        offsets,
+                        new_indices,
+                        $value.into_raw_vec_and_offset().0,
+                    )
+                    .map_err(|e| anyhow!("{}", e))?,
+                )
+            };
+        }
+        Ok(crate::macros::dyn_map!(data, DynArray, fun))
+    }
+}
+
+/// Reader for loom files, which store a matrix of genes by cells in `/matrix`
+/// and `/layers`, gene and cell annotations in `/row_attrs` and `/col_attrs`,
+/// and gene and cell graphs in `/row_graphs` and `/col_graphs`. The matrices
+/// are transposed chunk by chunk so that cells become the observations.
+pub struct LoomReader<B: Backend> {
+    store: B::Store,
+    obs_names: String,
+    var_names: String,
+    chunk_size: usize,
+}
+
+impl<B: Backend> LoomReader<B> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            store: B::open(path)?,
+            obs_names: "CellID".to_string(),
+            var_names: "Gene".to_string(),
+            chunk_size: 2000,
+        })
+    }
+
+    /// Column attribute used as the obs names. Defaults to "CellID".
+    pub fn obs_names(mut self, key: &str) -> Self {
+        self.obs_names = key.to_string();
+        self
+    }
+
+    /// Row attribute used as the var names. Defaults to "Gene".
+    pub fn var_names(mut self, key: &str) -> Self {
+        self.var_names = key.to_string();
+        self
+    }
+
+    /// The number of cells read at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn finish<O: AnnDataOp>(self, output: &O) -> Result<()> {
+        let matrix = self.store.open_dataset("matrix")?;
+        let shape = matrix.shape();
+        ensure!(shape.ndim() == 2, "the matrix of a loom file must be 2D, but got a {} array", shape);
+        let (n_vars, n_obs) = (shape[0], shape[1]);
+        output.try_set_x_from_iter(self.read_transposed(&matrix, n_obs))?;
+        if self.store.exists("layers")? {
+            let layers = self.store.open_group("layers")?;
+            for key in layers.list()? {
+                let dataset = layers.open_dataset(&key)?;
+                output
+                    .layers()
+                    .try_add_iter(&key, self.read_transposed(&dataset, n_obs))
+                    .with_context(|| format!("cannot read the layer '{}'", key))?;
+            }
+        }
+
+        if self.store.exists("col_attrs")? {
+            let (names, columns, arrays) =
+                read_loom_attrs::<B>(&self.store.open_group("col_attrs")?, n_obs, &self.obs_names)?;
+            if !columns.is_empty() {
+                output.set_obs(DataFrame::new(columns)?)?;
+            }
+            if let Some(names) = names {
+                output.set_obs_names(names)?;
+            }
+            for (key, arr) in arrays {
+                output.obsm().add(&key, arr)?;
+            }
+        }
+        if self.store.exists("row_attrs")? {
+            let (names, columns, arrays) =
+                read_loom_attrs::<B>(&self.store.open_group("row_attrs")?, n_vars, &self.var_names)?;
+            if !columns.is_empty() {
+                output.set_var(DataFrame::new(columns)?)?;
+            }
+            if let Some(names) = names {
+                output.set_var_names(names)?;
+            }
+            for (key, arr) in arrays {
+                output.varm().add(&key, arr)?;
+            }
+        }
+
+        for (name, n, arrays) in [("col_graphs", n_obs, output.obsp()), ("row_graphs", n_vars, output.varp())] {
+            if self.store.exists(name)? {
+                let graphs = self.store.open_group(name)?;
+                for key in graphs.list()? {
+                    arrays.add(&key, read_loom_graph::<B>(&graphs.open_group(&key)?, n)?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a matrix of genes by cells as chunks of cells by genes.
+    fn read_transposed<'a>(
+        &'a self,
+        dataset: &'a B::Dataset,
+        n_obs: usize,
+    ) -> impl Iterator<Item = Result<DynArray>> + 'a {
+        (0..n_obs).step_by(self.chunk_size).map(move |start| {
+            let end = n_obs.min(start + self.chunk_size);
+            let arr = dataset.read_dyn_array_slice(&[SelectInfoElem::full(), SelectInfoElem::from(start..end)])?;
+            macro_rules! fun {
+                ($variant:ident, $value:expr) => {
+                    DynArray::$variant($value.reversed_axes().as_standard_layout().into_owned())
+                };
+            }
+            Ok(crate::macros::dyn_map!(arr, DynArray, fun))
+        })
+    }
+}
+
+/// Read the attributes of one axis of a loom file, i.e., the names, the
+/// one-dimensional attributes as DataFrame columns, and the multi-dimensional
+/// attributes.
+fn read_loom_attrs<B: Backend>(
+    group: &B::Group,
+    n: usize,
+    index_key: &str,
+) -> Result<(Option<DataFrameIndex>, Vec<Series>, Vec<(String, DynArray)>)> {
+    let mut index = None;
+    let mut columns = Vec::new();
+    let mut arrays = Vec::new();
+    for key in group.list()? {
+        let dataset = group.open_dataset(&key)?;
+        let shape = dataset.shape();
+        ensure!(
+            shape.ndim() > 0 && shape[0] == n,
+            "the attribute '{}' has shape {}, but its length should be {}",
+            key,
+            shape,
+            n
+        );
+        if shape.ndim() > 1 {
+            arrays.push((key, dataset.read_dyn_array()?));
+        } else if key == index_key {
+            let names: Vec<String> = dataset.read_array::<String, Ix1>()?.to_vec();
+            index = Some(names.into());
+        } else {
+            let series: Series = dataset.read_dyn_array()?.into();
+            columns.push(series.with_name(key.as_str().into()));
+        }
+    }
+    Ok((index, columns, arrays))
+}
+
+/// Read a loom graph, i.e., the row indices `a`, the column indices `b` and
+/// the weights `w` of its edges, as an `n` by `n` CSR matrix.
+fn read_loom_graph<B: Backend>(group: &B::Group, n: usize) -> Result<DynCsrMatrix> {
+    let a: Vec<usize> = group.open_dataset("a")?.read_array_cast::<usize, Ix1>()?.to_vec();
+    let b: Vec<usize> = group.open_dataset("b")?.read_array_cast::<usize, Ix1>()?.to_vec();
+    let w = group.open_dataset("w")?.read_dyn_array()?;
+    macro_rules! fun {
+        ($variant:ident, $value:expr) => {
+            DynCooMatrix::$variant(
+                CooMatrix::try_from_triplets(n, n, a, b, $value.into_raw_vec_and_offset().0)
+                    .map_err(|e| anyhow!("{}", e))?,
+            )
+        };
+    }
+    crate::macros::dyn_map!(w, DynArray, fun).to_csr()
+}
+
 fn open_file<P: AsRef<Path>>(file: P) -> Result<Box<dyn BufRead>> {
     fn is_gzipped<P: AsRef<Path>>(file: P) -> Result<bool> {
         Ok(MultiGzDecoder::new(File::open(file)?).header().is_some())
@@ -95,38 +544,215 @@ fn open_file<P: AsRef<Path>>(file: P) -> Result<Box<dyn BufRead>> {
     Ok(reader)
 }
 
-/*
-// TODO: fix dataframe index
-pub fn import_csv<P>(
-    &self,
-    path: P,
+/// Reader for dense tables of cells by features in delimited text files, e.g.,
+/// CSV or TSV files, which may be gzipped. The rows are read and written to X
+/// chunk by chunk.
+pub struct CsvReader {
+    reader: Box<dyn BufRead>,
+    delimiter: char,
     has_header: bool,
     index_column: Option<usize>,
-    delimiter: u8,
-) -> Result<()>
-where
-    P: Into<PathBuf>,
-{
-    let mut df = polars::prelude::CsvReader::from_path(path)?
-        .has_header(has_header)
-        .with_delimiter(delimiter)
-        .finish()?;
-    let mut colnames = df.get_column_names_owned();
-    if let Some(idx_col) = index_column {
-        let series = df.drop_in_place(&colnames.remove(idx_col))?;
-        self.set_obs(Some(DataFrame::new(vec![series])?))?;
-    }
-    if has_header {
-        self.set_var(Some(DataFrame::new(vec![Series::new("Index", colnames)])?))?;
-    }
-    let data: Box<dyn MatrixData> = Box::new(
-        df.to_ndarray::<polars::datatypes::Float64Type>()?
-            .into_dyn(),
-    );
-    self.set_x(Some(data))?;
-    Ok(())
+    max_density: Option<f64>,
+    chunk_size: usize,
+}
+
+impl CsvReader {
+    /// Open a delimited text file. Files whose names end with `.tsv` or `.txt`,
+    /// optionally followed by `.gz`, are tab-delimited and other files are
+    /// comma-delimited.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let name = path.as_ref().to_string_lossy();
+        let name = name.strip_suffix(".gz").unwrap_or(&name);
+        let delimiter = if name.ends_with(".tsv") || name.ends_with(".txt") {
+            '\t'
+        } else {
+            ','
+        };
+        Ok(Self {
+            reader: open_file(path)?,
+            delimiter,
+            has_header: true,
+            index_column: None,
+            max_density: None,
+            chunk_size: 2000,
+        })
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Whether the first line contains the feature names. Defaults to true.
+    /// The header may omit the name of the index column, as in files written
+    /// by R.
+    pub fn has_header(mut self, has_header: bool) -> Self {
+        self.has_header = has_header;
+        self
+    }
+
+    /// Column that contains the cell names.
+    pub fn index_column(mut self, column: usize) -> Self {
+        self.index_column = Some(column);
+        self
+    }
+
+    /// Store X as a CSR matrix if at most `max_density` of the values in the
+    /// first chunk are non-zero.
+    pub fn sparse(mut self, max_density: f64) -> Self {
+        self.max_density = Some(max_density);
+        self
+    }
+
+    /// The number of rows read at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn finish<O: AnnDataOp>(self, output: &O) -> Result<()> {
+        let delimiter = self.delimiter;
+        let index_column = self.index_column;
+        let mut lines = self
+            .reader
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |x| !x.trim().is_empty()));
+        let mut header = if self.has_header {
+            match lines.next() {
+                Some(line) => Some(split_fields(&line?, delimiter)?),
+                None => bail!("the file is empty"),
+            }
+        } else {
+            None
+        };
+
+        let mut obs_names = Vec::new();
+        let mut n_vars = None;
+        let chunks = lines.chunks(self.chunk_size);
+        let mut chunks = chunks
+            .into_iter()
+            .map(|chunk| -> Result<Vec<Vec<f64>>> {
+                chunk
+                    .map(|line| {
+                        let mut values = Vec::new();
+                        for (i, field) in split_fields(&line?, delimiter)?.into_iter().enumerate() {
+                            if Some(i) == index_column {
+                                obs_names.push(field);
+                            } else {
+                                values.push(field.parse().with_context(|| {
+                                    format!("cannot parse '{}' as a number", field)
+                                })?);
+                            }
+                        }
+                        let n = match n_vars {
+                            Some(n) => n,
+                            None => {
+                                // The header is checked before any data is written.
+                                if let Some(header) = header.as_ref() {
+                                    let n_header = header.len() - usize::from(
+                                        index_column.is_some() && header.len() == values.len() + 1,
+                                    );
+                                    ensure!(
+                                        n_header == values.len(),
+                                        "the header has {} columns, but the table has {} features",
+                                        n_header,
+                                        values.len()
+                                    );
+                                }
+                                *n_vars.insert(values.len())
+                            }
+                        };
+                        ensure!(
+                            values.len() == n,
+                            "expected {} values in each row, but found {}",
+                            n,
+                            values.len()
+                        );
+                        Ok(values)
+                    })
+                    .collect()
+            })
+            .peekable();
+
+        let sparse = match (self.max_density, chunks.peek()) {
+            (Some(max_density), Some(Ok(rows))) => {
+                let n: usize = rows.iter().map(|x| x.len()).sum();
+                let nnz = rows.iter().flatten().filter(|x| **x != 0.0).count();
+                n == 0 || nnz as f64 / n as f64 <= max_density
+            }
+            _ => false,
+        };
+        output.try_set_x_from_iter(chunks.map(|chunk| {
+            chunk.map(|rows| -> ArrayData {
+                let ncols = rows.first().map_or(0, |x| x.len());
+                if sparse {
+                    let rows = rows.into_iter().map(|row| {
+                        row.into_iter()
+                            .enumerate()
+                            .filter(|(_, x)| *x != 0.0)
+                            .collect::<Vec<_>>()
+                    });
+                    let (r, c, indptr, indices, data) = to_csr_data(rows.collect::<Vec<_>>(), ncols);
+                    CsrMatrix::try_from_csr_data(r, c, indptr, indices, data).unwrap().into()
+                } else {
+                    let nrows = rows.len();
+                    let values = rows.into_iter().flatten().collect();
+                    Array2::from_shape_vec((nrows, ncols), values).unwrap().into()
+                }
+            })
+        }))?;
+
+        if index_column.is_some() {
+            output.set_obs_names(obs_names.into())?;
+        }
+        if let Some(header) = header.as_mut() {
+            if let (Some(i), Some(n)) = (index_column, n_vars) {
+                if header.len() == n + 1 {
+                    header.remove(i);
+                }
+            }
+            output.set_var_names(std::mem::take(header).into())?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a line into fields. Fields may be enclosed in double quotes, in which
+/// case they can contain the delimiter and escaped quotes (`""`). Whitespace
+/// around the fields is removed.
+fn split_fields(line: &str, delimiter: char) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c != delimiter && c.is_whitespace()).is_some() {}
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => bail!("unterminated quoted field in line: {}", line),
+                }
+            }
+            while chars.next_if(|c| *c != delimiter && c.is_whitespace()).is_some() {}
+            ensure!(
+                chars.peek().map_or(true, |c| *c == delimiter),
+                "unexpected characters after a quoted field in line: {}",
+                line
+            );
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != delimiter) {
+                field.push(c);
+            }
+            field.truncate(field.trim_end().len());
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
 }
-*/
 
 #[derive(Debug)]
 pub(crate) enum IoError {
@@ -141,9 +767,8 @@ impl fmt::Display for IoError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Self::Io(ref err) => err.fmt(f),
-            Self::BadMatrixMarketFile | Self::UnsupportedMatrixMarketFormat => {
-                write!(f, "Bad matrix market file.")
-            }
+            Self::BadMatrixMarketFile => write!(f, "Bad matrix market file."),
+            Self::UnsupportedMatrixMarketFormat => write!(f, "Unsupported matrix market format."),
         }
     }
 }
@@ -156,11 +781,12 @@ impl From<io::Error> for IoError {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub(crate) enum DataType {
     Integer,
     Real,
     Complex,
+    Pattern,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -171,211 +797,535 @@ pub(crate) enum SymmetryMode {
     SkewSymmetric,
 }
 
-fn read_sorted_mm_body_from_bufread<R, T>(
-    reader: &mut R,
-) -> (usize, usize, impl Iterator<Item = (usize, usize, T)> + '_)
-where
-    R: io::BufRead,
-    T: Copy + std::str::FromStr,
-{
+/// The scalar type that the values of a matrix with the given field are
+/// stored as.
+fn mm_value_type(field: DataType, dtype: Option<ScalarType>) -> Result<ScalarType> {
+    let ty = match (field, dtype) {
+        // we currently don't support complex
+        (DataType::Complex, _) => return Err(UnsupportedMatrixMarketFormat.into()),
+        (DataType::Integer, None) => ScalarType::I64,
+        (DataType::Real, None) => ScalarType::F64,
+        (DataType::Pattern, None) => ScalarType::Bool,
+        (DataType::Real, Some(ty @ (ScalarType::F32 | ScalarType::F64))) => ty,
+        (DataType::Real, Some(ty)) => bail!("cannot store real values as {}", ty),
+        (DataType::Integer, Some(ScalarType::Bool)) => bail!("cannot store integer values as bool"),
+        (_, Some(ty)) => ty,
+    };
+    Ok(ty)
+}
+
+/// Types that the values of Matrix Market entries can be stored as.
+trait MMValue: BackendData + Copy + Send + Sync {
+    fn parse(s: &str) -> Option<Self>;
+
+    /// The value of the entries of pattern matrices.
+    fn one() -> Self;
+
+    /// The mirrored value of skew-symmetric matrices, or None if it cannot
+    /// be represented.
+    fn negate(self) -> Option<Self>;
+
+    /// Combine a duplicated entry into this one, or fail if the sum cannot
+    /// be represented.
+    fn merge(&mut self, other: Self) -> Result<()>;
+
+    /// The bits of the value, used to spill entries to disk.
+    fn to_bits(self) -> u64;
+
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! impl_mm_int {
+    ($($ty:ty),*) => {
+        $(
+            impl MMValue for $ty {
+                fn parse(s: &str) -> Option<Self> {
+                    s.parse().ok()
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn negate(self) -> Option<Self> {
+                    (0 as $ty).checked_sub(self)
+                }
+
+                fn merge(&mut self, other: Self) -> Result<()> {
+                    *self = self.checked_add(other).with_context(|| {
+                        format!("duplicated entries overflow {}", stringify!($ty))
+                    })?;
+                    Ok(())
+                }
+
+                fn to_bits(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_bits(bits: u64) -> Self {
+                    bits as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_mm_int!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+macro_rules! impl_mm_float {
+    ($($ty:ident),*) => {
+        $(
+            impl MMValue for $ty {
+                fn parse(s: &str) -> Option<Self> {
+                    s.parse().ok()
+                }
+
+                fn one() -> Self {
+                    1.0
+                }
+
+                fn negate(self) -> Option<Self> {
+                    Some(-self)
+                }
+
+                fn merge(&mut self, other: Self) -> Result<()> {
+                    *self += other;
+                    Ok(())
+                }
+
+                fn to_bits(self) -> u64 {
+                    $ty::to_bits(self) as u64
+                }
+
+                fn from_bits(bits: u64) -> Self {
+                    $ty::from_bits(bits as _)
+                }
+            }
+        )*
+    };
+}
+
+impl_mm_float!(f32, f64);
+
+impl MMValue for bool {
+    fn parse(s: &str) -> Option<Self> {
+        s.parse::<i64>().ok().map(|x| x != 0)
+    }
+
+    fn one() -> Self {
+        true
+    }
+
+    fn negate(self) -> Option<Self> {
+        None
+    }
+
+    fn merge(&mut self, other: Self) -> Result<()> {
+        *self |= other;
+        Ok(())
+    }
+
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        bits != 0
+    }
+}
+
+/// Skip the comments and read the size line, i.e., the number of rows,
+/// columns and entries.
+fn read_mtx_size<R: io::BufRead>(reader: &mut R) -> Result<(usize, usize, usize), IoError> {
     // MatrixMarket format specifies lines of at most 1024 chars
     let mut line = String::with_capacity(1024);
 
     // The header is followed by any number of comment or empty lines, skip
-    'header: loop {
+    loop {
         line.clear();
-        let len = reader.read_line(&mut line).unwrap();
-        if len == 0 || line.starts_with('%') {
-            continue 'header;
+        if reader.read_line(&mut line)? == 0 {
+            return Err(BadMatrixMarketFile);
+        }
+        if !line.starts_with('%') && line.split_whitespace().next().is_some() {
+            break;
         }
-        break;
     }
-    // read shape and number of entries
     // this is a line like:
     // rows cols entries
     // with arbitrary amounts of whitespace
-    let (rows, cols, entries) = {
-        let mut infos = line
-            .split_whitespace()
-            .filter_map(|s| s.parse::<usize>().ok());
-        let rows = infos.next().ok_or(BadMatrixMarketFile).unwrap();
-        let cols = infos.next().ok_or(BadMatrixMarketFile).unwrap();
-        let entries = infos.next().ok_or(BadMatrixMarketFile).unwrap();
-        if infos.next().is_some() {
-            panic!("BadMatrixMarketFile");
-        }
-        (rows, cols, entries)
+    let infos = line
+        .split_whitespace()
+        .map(|s| s.parse::<usize>().or(Err(BadMatrixMarketFile)))
+        .collect::<Result<Vec<_>, _>>()?;
+    match infos[..] {
+        [rows, cols, entries] => Ok((rows, cols, entries)),
+        _ => Err(BadMatrixMarketFile),
+    }
+}
+
+/// Parse an entry, i.e., a line of the form `row col value`, or `row col`
+/// for pattern matrices, with arbitrary amounts of whitespace.
+fn parse_mtx_entry<T: MMValue>(
+    line: &str,
+    shape: (usize, usize),
+    pattern: bool,
+) -> Result<(usize, usize, T), IoError> {
+    let mut entry = line.split_whitespace();
+    let mut index = |n: usize| {
+        entry
+            .next()
+            .and_then(|s| s.parse::<usize>().ok())
+            // MatrixMarket indices are 1-based
+            .and_then(|i| i.checked_sub(1))
+            .filter(|i| *i < n)
+            .ok_or(BadMatrixMarketFile)
     };
-    // one non-zero entry per non-empty line
-    let iter = std::iter::repeat_with(move || {
-        // skip empty lines (no comment line should appear)
-        'empty_lines: loop {
-            line.clear();
-            let len = reader.read_line(&mut line).unwrap();
-            // check for an all whitespace line
-            if len != 0 && line.split_whitespace().next() == None {
-                continue 'empty_lines;
+    let row = index(shape.0)?;
+    let col = index(shape.1)?;
+    let val = if pattern {
+        T::one()
+    } else {
+        entry.next().and_then(T::parse).ok_or(BadMatrixMarketFile)?
+    };
+    if entry.next().is_some() {
+        return Err(BadMatrixMarketFile);
+    }
+    Ok((row, col, val))
+}
+
+/// Push an entry and, for symmetric and skew-symmetric matrices, its mirror
+/// across the diagonal.
+fn push_symmetric<T: MMValue, F: FnMut((usize, usize, T))>(
+    entry: (usize, usize, T),
+    sym_mode: SymmetryMode,
+    mut push: F,
+) -> Result<(), IoError> {
+    let (i, j, v) = entry;
+    match sym_mode {
+        SymmetryMode::General => push(entry),
+        SymmetryMode::Symmetric => {
+            push(entry);
+            if i != j {
+                push((j, i, v));
             }
-            break;
         }
-        // Non-zero entries are lines of the form:
-        // row col value
-        // if the data type is integer of real, and
-        // row col real imag
-        // if the data type is complex.
-        // Again, this is with arbitrary amounts of whitespace
-        let mut entry = line.split_whitespace();
-        let row = entry
-            .next()
-            .ok_or(BadMatrixMarketFile)
-            .and_then(|s| s.parse::<usize>().or(Err(BadMatrixMarketFile)))
-            .unwrap();
-        let col = entry
-            .next()
-            .ok_or(BadMatrixMarketFile)
-            .and_then(|s| s.parse::<usize>().or(Err(BadMatrixMarketFile)))
-            .unwrap();
-        // MatrixMarket indices are 1-based
-        let row = row.checked_sub(1).ok_or(BadMatrixMarketFile).unwrap();
-        let col = col.checked_sub(1).ok_or(BadMatrixMarketFile).unwrap();
-        let val: T = entry
-            .next()
-            .ok_or(BadMatrixMarketFile)
-            .and_then(|s| s.parse::<T>().or(Err(BadMatrixMarketFile)))
-            .unwrap();
+        SymmetryMode::SkewSymmetric => {
+            // The diagonal of skew-symmetric matrices is zero.
+            if i == j {
+                return Err(BadMatrixMarketFile);
+            }
+            push(entry);
+            push((j, i, v.negate().ok_or(UnsupportedMatrixMarketFormat)?));
+        }
+        // support for Hermitian requires complex support
+        SymmetryMode::Hermitian => return Err(UnsupportedMatrixMarketFormat),
+    }
+    Ok(())
+}
 
-        if entry.next().is_some() {
-            panic!("BadMatrixMarketFile");
+/// The entries of the body of a Matrix Market file, in the order of the file.
+/// Reading stops after the first error.
+struct MMEntries<'a, R, T> {
+    reader: &'a mut R,
+    remaining: usize,
+    shape: (usize, usize),
+    pattern: bool,
+    line: String,
+    marker: PhantomData<T>,
+}
+
+impl<'a, R: io::BufRead, T: MMValue> MMEntries<'a, R, T> {
+    fn new(reader: &'a mut R, entries: usize, shape: (usize, usize), pattern: bool) -> Self {
+        Self {
+            reader,
+            remaining: entries,
+            shape,
+            pattern,
+            line: String::with_capacity(1024),
+            marker: PhantomData,
+        }
+    }
+
+    fn read_entry(&mut self) -> Result<(usize, usize, T), IoError> {
+        // skip empty lines (no comment line should appear)
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Err(BadMatrixMarketFile);
+            }
+            if self.line.split_whitespace().next().is_some() {
+                break;
+            }
         }
-        (row, col, val)
-    }).take(entries);
-    (rows, cols, iter)
+        parse_mtx_entry(&self.line, self.shape, self.pattern)
+    }
 }
 
+impl<R: io::BufRead, T: MMValue> Iterator for MMEntries<'_, R, T> {
+    type Item = Result<(usize, usize, T), IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.read_entry();
+        self.remaining = if entry.is_ok() { self.remaining - 1 } else { 0 };
+        Some(entry)
+    }
+}
 
-fn read_matrix_market_from_bufread<R>(reader: &mut R) -> Result<ArrayData, IoError>
+/// Build the rows `start..end` of a CSR matrix from entries sorted by row.
+/// The entries of each row are sorted by column, and duplicated entries are
+/// merged. Fails on the first error of `entries` or on an entry that is not
+/// sorted by row.
+fn sorted_entries_to_csr<T, I, E>(
+    entries: &mut Peekable<I>,
+    start: usize,
+    end: usize,
+    ncols: usize,
+) -> Result<CsrMatrix<T>>
 where
-    R: io::BufRead,
+    T: MMValue,
+    I: Iterator<Item = Result<(usize, usize, T), E>>,
+    E: Into<anyhow::Error>,
 {
-    let (sym_mode, data_type) = read_header(reader)?;
-    if data_type == DataType::Complex {
-        // we currently don't support complex
-        return Err(UnsupportedMatrixMarketFormat);
-    }
-    if sym_mode == SymmetryMode::Hermitian {
-        // support for Hermitian requires complex support
-        return Err(UnsupportedMatrixMarketFormat);
-    }
-    match data_type {
-        DataType::Integer => {
-            let coo: CooMatrix<i64> = read_mtx_body(reader, sym_mode)?;
-            Ok(CsrMatrix::from(&coo).into())
+    let mut offsets = Vec::with_capacity(end - start + 1);
+    let mut indices: Vec<usize> = Vec::new();
+    let mut values: Vec<T> = Vec::new();
+    let mut row = Vec::new();
+    offsets.push(0);
+    for i in start..end {
+        let row_start = indices.len();
+        row.clear();
+        while let Some(Ok((_, j, v))) = entries.next_if(|x| matches!(x, Ok((r, _, _)) if *r == i)) {
+            row.push((j, v));
+        }
+        match entries.peek() {
+            Some(Ok((r, _, _))) if *r < i => bail!("the entries are not sorted by row"),
+            Some(Err(_)) => return Err(entries.next().unwrap().err().unwrap().into()),
+            _ => {}
         }
-        DataType::Real => {
-            let coo: CooMatrix<f64> = read_mtx_body(reader, sym_mode)?;
-            Ok(CsrMatrix::from(&coo).into())
+        // The sort is stable, so duplicates are merged in their original order.
+        row.sort_by_key(|(j, _)| *j);
+        for (j, v) in row.drain(..) {
+            if indices.len() > row_start && indices[indices.len() - 1] == j {
+                values.last_mut().unwrap().merge(v)?;
+            } else {
+                indices.push(j);
+                values.push(v);
+            }
         }
-        DataType::Complex => unreachable!(),
+        offsets.push(indices.len());
     }
+    Ok(CsrMatrix::try_from_csr_data(end - start, ncols, offsets, indices, values).unwrap())
 }
 
-fn read_mtx_body<T, R>(reader: &mut R, sym_mode: SymmetryMode) -> Result<CooMatrix<T>, IoError>
+/// Write entries sorted by row to X in chunks of rows. X is left unchanged if
+/// reading fails or the entries are not sorted.
+fn write_sorted_entries<T, I, E, O>(
+    entries: I,
+    nrows: usize,
+    ncols: usize,
+    chunk_size: usize,
+    output: &O,
+) -> Result<()>
 where
-    R: io::BufRead,
-    T: Copy + std::str::FromStr,
+    T: MMValue,
+    CsrMatrix<T>: Into<ArrayData>,
+    I: Iterator<Item = Result<(usize, usize, T), E>>,
+    E: Into<anyhow::Error>,
+    O: AnnDataOp,
 {
-    // MatrixMarket format specifies lines of at most 1024 chars
-    let mut line = String::with_capacity(1024);
+    let mut entries = entries.peekable();
+    output.try_set_x_from_iter((0..nrows).step_by(chunk_size).map(|start| {
+        let end = nrows.min(start + chunk_size);
+        sorted_entries_to_csr(&mut entries, start, end, ncols)
+    }))
+}
 
-    // The header is followed by any number of comment or empty lines, skip
-    'header: loop {
-        line.clear();
-        let len = reader.read_line(&mut line)?;
-        if len == 0 || line.starts_with('%') {
-            continue 'header;
-        }
-        break;
-    }
-    // read shape and number of entries
-    // this is a line like:
-    // rows cols entries
-    // with arbitrary amounts of whitespace
-    let (rows, cols, entries) = {
-        let mut infos = line
-            .split_whitespace()
-            .filter_map(|s| s.parse::<usize>().ok());
-        let rows = infos.next().ok_or(BadMatrixMarketFile)?;
-        let cols = infos.next().ok_or(BadMatrixMarketFile)?;
-        let entries = infos.next().ok_or(BadMatrixMarketFile)?;
-        if infos.next().is_some() {
-            return Err(BadMatrixMarketFile);
-        }
-        (rows, cols, entries)
-    };
+/// Read the whole body of a Matrix Market file into memory.
+fn read_mtx_body<T, R>(reader: &mut R, sym_mode: SymmetryMode, pattern: bool) -> Result<CsrMatrix<T>>
+where
+    T: MMValue,
+    R: io::BufRead,
+{
+    let (rows, cols, entries) = read_mtx_size(reader)?;
     let nnz_max = if sym_mode == SymmetryMode::General {
         entries
     } else {
         2 * entries
     };
-    let mut row_inds = Vec::with_capacity(nnz_max);
-    let mut col_inds = Vec::with_capacity(nnz_max);
-    let mut data = Vec::with_capacity(nnz_max);
-    // one non-zero entry per non-empty line
-    for _ in 0..entries {
-        // skip empty lines (no comment line should appear)
-        'empty_lines: loop {
+    let mut triplets = Vec::with_capacity(nnz_max);
+    for entry in MMEntries::new(reader, entries, (rows, cols), pattern) {
+        push_symmetric(entry?, sym_mode, |x| triplets.push(x))?;
+    }
+    triplets.par_sort_by_key(|(i, _, _)| *i);
+    let mut triplets = triplets.into_iter().map(Ok::<_, IoError>).peekable();
+    sorted_entries_to_csr(&mut triplets, 0, rows, cols)
+}
+
+/// Stream the body of a Matrix Market file whose entries are sorted by row.
+fn read_mtx_sorted<T, R, O>(reader: &mut R, pattern: bool, chunk_size: usize, output: &O) -> Result<()>
+where
+    T: MMValue,
+    CsrMatrix<T>: Into<ArrayData>,
+    R: io::BufRead,
+    O: AnnDataOp,
+{
+    let (rows, cols, entries) = read_mtx_size(reader)?;
+    let iter = MMEntries::<_, T>::new(reader, entries, (rows, cols), pattern);
+    write_sorted_entries(iter, rows, cols, chunk_size, output)
+}
+
+/// Number of lines that are parsed in parallel at a time.
+const PARSE_BATCH_SIZE: usize = 1 << 16;
+
+/// Read the body of a Matrix Market file in any order and write it to X in
+/// chunks of rows. The entries are sorted in runs of at most `run_size`
+/// entries. All but the last run are spilled to temporary files, and the runs
+/// are merged while the chunks are written.
+fn read_mtx_external<T, R, O>(
+    reader: &mut R,
+    sym_mode: SymmetryMode,
+    pattern: bool,
+    run_size: usize,
+    chunk_size: usize,
+    output: &O,
+) -> Result<()>
+where
+    T: MMValue,
+    CsrMatrix<T>: Into<ArrayData>,
+    R: io::BufRead,
+    O: AnnDataOp,
+{
+    let (nrows, ncols, entries) = read_mtx_size(reader)?;
+    let mut runs = Vec::new();
+    let mut run: Vec<(usize, usize, T)> = Vec::with_capacity(run_size.min(entries));
+    let mut n_read = 0;
+    // The line buffers are reused across batches.
+    let mut batch: Vec<String> = Vec::new();
+    while n_read < entries {
+        let batch_size = PARSE_BATCH_SIZE.min(entries - n_read);
+        let mut n = 0;
+        while n < batch_size {
+            if n == batch.len() {
+                batch.push(String::with_capacity(1024));
+            }
+            let line = &mut batch[n];
             line.clear();
-            let len = reader.read_line(&mut line)?;
-            // check for an all whitespace line
-            if len != 0 && line.split_whitespace().next() == None {
-                continue 'empty_lines;
+            if reader.read_line(line)? == 0 {
+                return Err(BadMatrixMarketFile.into());
+            }
+            if line.split_whitespace().next().is_some() {
+                n += 1;
             }
-            break;
         }
-        // Non-zero entries are lines of the form:
-        // row col value
-        // if the data type is integer of real, and
-        // row col real imag
-        // if the data type is complex.
-        // Again, this is with arbitrary amounts of whitespace
-        let mut entry = line.split_whitespace();
-        let row = entry
-            .next()
-            .ok_or(BadMatrixMarketFile)
-            .and_then(|s| s.parse::<usize>().or(Err(BadMatrixMarketFile)))?;
-        let col = entry
-            .next()
-            .ok_or(BadMatrixMarketFile)
-            .and_then(|s| s.parse::<usize>().or(Err(BadMatrixMarketFile)))?;
-        // MatrixMarket indices are 1-based
-        let row = row.checked_sub(1).ok_or(BadMatrixMarketFile)?;
-        let col = col.checked_sub(1).ok_or(BadMatrixMarketFile)?;
-        let val: T = entry
-            .next()
-            .ok_or(BadMatrixMarketFile)
-            .and_then(|s| s.parse::<T>().or(Err(BadMatrixMarketFile)))?;
-        row_inds.push(row);
-        col_inds.push(col);
-        data.push(val);
-        if sym_mode != SymmetryMode::General && row != col {
-            if sym_mode == SymmetryMode::Hermitian {
-                unreachable!();
-            } else {
-                row_inds.push(col);
-                col_inds.push(row);
-                data.push(val);
+        n_read += n;
+        let parsed = batch[..n]
+            .par_iter()
+            .map(|line| parse_mtx_entry::<T>(line, (nrows, ncols), pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        for entry in parsed {
+            push_symmetric(entry, sym_mode, |x| run.push(x))?;
+            if run.len() >= run_size {
+                runs.push(SortedRun::spill(std::mem::take(&mut run))?);
             }
         }
-        if sym_mode == SymmetryMode::SkewSymmetric && row == col {
-            return Err(BadMatrixMarketFile);
+    }
+    run.par_sort_by_key(|(i, j, _)| (*i, *j));
+    runs.push(SortedRun::Memory(run.into_iter()));
+
+    write_sorted_entries(MergedRuns::new(runs)?, nrows, ncols, chunk_size, output)
+}
+
+/// A run of entries sorted by row and column, either in memory or spilled to
+/// a temporary file as little-endian `(row, col, value)` triplets.
+enum SortedRun<T> {
+    Memory(std::vec::IntoIter<(usize, usize, T)>),
+    File(BufReader<File>),
+}
+
+impl<T: MMValue> SortedRun<T> {
+    fn spill(mut run: Vec<(usize, usize, T)>) -> io::Result<Self> {
+        run.par_sort_by_key(|(i, j, _)| (*i, *j));
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        for (i, j, v) in run {
+            writer.write_all(&(i as u64).to_le_bytes())?;
+            writer.write_all(&(j as u64).to_le_bytes())?;
+            writer.write_all(&v.to_bits().to_le_bytes())?;
         }
-        if entry.next().is_some() {
-            return Err(BadMatrixMarketFile);
+        let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Self::File(BufReader::new(file)))
+    }
+
+    fn next_entry(&mut self) -> io::Result<Option<(usize, usize, T)>> {
+        match self {
+            Self::Memory(iter) => Ok(iter.next()),
+            Self::File(reader) => {
+                let mut buf = [0u8; 24];
+                match reader.read_exact(&mut buf) {
+                    Ok(()) => {
+                        let field = |k: usize| u64::from_le_bytes(buf[8 * k..8 * k + 8].try_into().unwrap());
+                        Ok(Some((field(0) as usize, field(1) as usize, T::from_bits(field(2)))))
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
         }
     }
+}
 
-    CooMatrix::try_from_triplets(rows, cols, row_inds, col_inds, data)
-        .map_err(|_| BadMatrixMarketFile)
+/// K-way merge of sorted runs. Entries with the same row and column are
+/// returned in the order of their runs. Reading stops after the first I/O
+/// error.
+struct MergedRuns<T> {
+    runs: Vec<SortedRun<T>>,
+    heads: Vec<Option<T>>,
+    heap: BinaryHeap<Reverse<(usize, usize, usize)>>,
+    failed: bool,
+}
+
+impl<T: MMValue> MergedRuns<T> {
+    fn new(runs: Vec<SortedRun<T>>) -> io::Result<Self> {
+        let mut merged = Self {
+            heads: vec![None; runs.len()],
+            runs,
+            heap: BinaryHeap::new(),
+            failed: false,
+        };
+        for k in 0..merged.runs.len() {
+            merged.advance(k)?;
+        }
+        Ok(merged)
+    }
+
+    /// Read the next entry of run `k` into the heap.
+    fn advance(&mut self, k: usize) -> io::Result<()> {
+        if let Some((i, j, v)) = self.runs[k].next_entry()? {
+            self.heads[k] = Some(v);
+            self.heap.push(Reverse((i, j, k)));
+        }
+        Ok(())
+    }
+}
+
+impl<T: MMValue> Iterator for MergedRuns<T> {
+    type Item = io::Result<(usize, usize, T)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let Reverse((i, j, k)) = self.heap.pop()?;
+        let v = self.heads[k].take().unwrap();
+        if let Err(e) = self.advance(k) {
+            self.failed = true;
+            return Some(Err(e));
+        }
+        Some(Ok((i, j, v)))
+    }
 }
 
 fn read_header<R>(reader: &mut R) -> Result<(SymmetryMode, DataType), IoError>
@@ -392,28 +1342,26 @@ where
 }
 
 fn parse_header(header: &str) -> Result<(SymmetryMode, DataType), IoError> {
-    if !header.starts_with("%%matrixmarket matrix coordinate") {
+    let mut tags = header.split_whitespace();
+    if tags.next() != Some("%%matrixmarket")
+        || tags.next() != Some("matrix")
+        || tags.next() != Some("coordinate")
+    {
         return Err(BadMatrixMarketFile);
     }
-    let data_type = if header.contains("real") {
-        DataType::Real
-    } else if header.contains("integer") {
-        DataType::Integer
-    } else if header.contains("complex") {
-        DataType::Complex
-    } else {
-        return Err(BadMatrixMarketFile);
+    let data_type = match tags.next() {
+        Some("real") => DataType::Real,
+        Some("integer") => DataType::Integer,
+        Some("complex") => DataType::Complex,
+        Some("pattern") => DataType::Pattern,
+        _ => return Err(BadMatrixMarketFile),
     };
-    let sym_mode = if header.contains("general") {
-        SymmetryMode::General
-    } else if header.contains("symmetric") {
-        SymmetryMode::Symmetric
-    } else if header.contains("skew-symmetric") {
-        SymmetryMode::SkewSymmetric
-    } else if header.contains("hermitian") {
-        SymmetryMode::Hermitian
-    } else {
-        return Err(BadMatrixMarketFile);
+    let sym_mode = match tags.next() {
+        Some("general") => SymmetryMode::General,
+        Some("symmetric") => SymmetryMode::Symmetric,
+        Some("skew-symmetric") => SymmetryMode::SkewSymmetric,
+        Some("hermitian") => SymmetryMode::Hermitian,
+        _ => return Err(BadMatrixMarketFile),
     };
     Ok((sym_mode, data_type))
 }
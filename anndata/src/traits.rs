@@ -1,7 +1,9 @@
-use crate::{anndata::{new_layers, new_obsm, new_obsp, new_varm, new_varp}, backend::GroupOp, container::{ChunkedArrayElem, InnerDataFrameElem, StackedChunkedArrayElem}, data::*, AnnData, AnnDataSet, ArrayElem, AxisArrays, Backend, ElemCollection, StackedArrayElem, StackedAxisArrays};
+use crate::{anndata::{new_layers, new_obsm, new_obsp, new_varm, new_varp, Raw, StackedRaw}, backend::{DataContainer, GroupOp}, container::{ChunkedArrayElem, InnerDataFrameElem, MapChunks, StackedChunkedArrayElem}, data::{array::{dot_chunk, t_dot_chunk, AxisStats}, *}, AnnData, AnnDataSet, ArrayElem, AxisArrays, Backend, ElemCollection, StackedArrayElem, StackedAxisArrays};
 
 use anyhow::{bail, ensure, Context, Result};
+use ndarray::{s, Array1, Array2, ArrayView2};
 use polars::prelude::DataFrame;
+use std::cell::RefCell;
 use smallvec::SmallVec;
 
 /// Trait defining operations on an AnnData container.
@@ -11,19 +13,41 @@ pub trait AnnDataOp {
     where
         Self: 'a;
     type ElemCollectionRef<'a>: ElemCollectionOp
+    where
+        Self: 'a;
+    type RawRef<'a>: RawOp
     where
         Self: 'a;
 
     /// Returns the 'X' element.
     fn x(&self) -> Self::X;
 
-    /// Sets the 'X' element from an iterator.
-    /// Note: The original data will be lost if an error occurs during the writing.
+    /// Sets the 'X' element from an iterator. The original data is kept if an
+    /// error occurs during the writing, and the iterator may read from it.
     fn set_x_from_iter<I, D>(&self, iter: I) -> Result<()>
     where
         I: Iterator<Item = D>,
         D: ArrayChunk + Into<ArrayData>;
 
+    /// Sets the 'X' element from an iterator of chunks that may fail to be
+    /// produced, e.g., chunks parsed from a file. The first error is returned.
+    /// The default implementation streams the chunks into `set_x_from_iter`
+    /// until the first error, so X may then hold the chunks produced before
+    /// it. Implementations should override it to leave X unchanged on errors.
+    fn try_set_x_from_iter<I, D>(&self, iter: I) -> Result<()>
+    where
+        I: Iterator<Item = Result<D>>,
+        D: ArrayChunk + Into<ArrayData>,
+    {
+        let error = RefCell::new(None);
+        let chunks = iter.map_while(|x| x.map_err(|e| *error.borrow_mut() = Some(e)).ok());
+        let result = self.set_x_from_iter(chunks);
+        match error.into_inner() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+
     /// Sets the 'X' element.
     fn set_x<D: Into<ArrayData>>(&self, data: D) -> Result<()>;
 
@@ -83,6 +107,13 @@ pub trait AnnDataOp {
     fn varp(&self) -> Self::AxisArraysRef<'_>;
     /// Returns a reference to the layers.
     fn layers(&self) -> Self::AxisArraysRef<'_>;
+    /// Returns a reference to the raw data, or `None` if the raw slot is not
+    /// present. Use [`AnnDataOp::set_raw_x`] to create the raw slot.
+    fn raw(&self) -> Result<Option<Self::RawRef<'_>>>;
+    /// Creates the raw slot with the given data matrix, replacing the existing
+    /// raw slot if any. The number of rows must be equal to the number of
+    /// observations.
+    fn set_raw_x<D: Into<ArrayData>>(&self, data: D) -> Result<Self::RawRef<'_>>;
 
     /// Sets the unstructured data.
     fn set_uns<I: Iterator<Item = (String, Data)>>(&self, mut data: I) -> Result<()> {
@@ -133,12 +164,92 @@ pub trait AnnDataOp {
     fn del_varp(&self) -> Result<()>;
     /// Deletes the layers.
     fn del_layers(&self) -> Result<()>;
+    /// Deletes the raw data.
+    fn del_raw(&self) -> Result<()>;
+}
+
+/// Trait defining operations on the raw slot of an AnnData container.
+pub trait RawOp {
+    type X: ArrayElemOp;
+    type AxisArraysRef<'a>: AxisArraysOp
+    where
+        Self: 'a;
+
+    /// Returns the 'X' element.
+    fn x(&self) -> Self::X;
+
+    /// Sets the 'X' element from an iterator.
+    fn set_x_from_iter<I, D>(&self, iter: I) -> Result<()>
+    where
+        I: Iterator<Item = D>,
+        D: ArrayChunk + Into<ArrayData>;
+
+    /// Sets the 'X' element. The number of rows must be equal to the number
+    /// of observations of the parent object.
+    fn set_x<D: Into<ArrayData>>(&self, data: D) -> Result<()>;
+
+    /// Deletes the 'X' element.
+    fn del_x(&self) -> Result<()>;
+
+    /// Returns the number of observations (rows).
+    fn n_obs(&self) -> usize;
+    /// Returns the number of variables (columns).
+    fn n_vars(&self) -> usize;
+
+    /// Returns the names of variables.
+    fn var_names(&self) -> DataFrameIndex;
+    /// Changes the names of variables.
+    fn set_var_names(&self, index: DataFrameIndex) -> Result<()>;
+
+    /// Reads the variable annotations.
+    fn read_var(&self) -> Result<DataFrame>;
+    /// Changes the variable annotations.
+    fn set_var(&self, var: DataFrame) -> Result<()>;
+    /// Deletes the variable annotations.
+    fn del_var(&self) -> Result<()>;
+
+    /// Returns a reference to the variable matrix.
+    fn varm(&self) -> Result<Self::AxisArraysRef<'_>>;
+    /// Deletes the variable matrix.
+    fn del_varm(&self) -> Result<()>;
+}
+
+/// Writes the chunks of a new `X` to a temporary element under `location`, and
+/// replaces `X` only after all chunks have been written and `check_shape`
+/// accepts the shape of the new element. The iterator can therefore read the
+/// old `X`, e.g., `adata.try_set_x_from_iter(adata.x().map_chunks(..))`.
+fn replace_x_by_chunk<B, G, I, D, F>(location: &G, x: &ArrayElem<B>, iter: I, check_shape: F) -> Result<()>
+where
+    B: Backend,
+    G: GroupOp<B>,
+    I: Iterator<Item = D>,
+    D: ArrayChunk,
+    F: FnOnce(&Shape) -> Result<()>,
+{
+    const TMP: &str = "__X_new";
+    let written = ArrayChunk::write_by_chunk(iter, location, TMP).and_then(|container| {
+        let new_elem = ArrayElem::<B>::try_from(container)?;
+        let shape = new_elem.inner().shape().clone();
+        new_elem.drop();
+        check_shape(&shape)
+    });
+    if let Err(e) = written {
+        if location.exists(TMP)? {
+            location.delete(TMP)?;
+        }
+        return Err(e);
+    }
+    x.clear()?;
+    location.rename(TMP, "X")?;
+    x.swap(&ArrayElem::try_from(DataContainer::open(location, "X")?)?);
+    Ok(())
 }
 
 impl<B: Backend> AnnDataOp for AnnData<B> {
     type X = ArrayElem<B>;
     type AxisArraysRef<'a> = &'a AxisArrays<B>;
     type ElemCollectionRef<'a> = &'a ElemCollection<B>;
+    type RawRef<'a> = &'a Raw<B>;
 
     fn x(&self) -> Self::X {
         self.x.clone()
@@ -147,23 +258,31 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
     fn set_x_from_iter<I: Iterator<Item = D>, D: ArrayChunk>(&self, iter: I) -> Result<()> {
         let mut obs_lock = self.n_obs.lock();
         let mut vars_lock = self.n_vars.lock();
-        self.del_x()?;
-        let new_elem =
-            ArrayElem::try_from(ArrayChunk::write_by_chunk(iter, &self.file, "X")?)?;
-        let shape = new_elem.inner().shape().clone();
+        replace_x_by_chunk(&self.file, &self.x, iter, |shape| {
+            obs_lock.try_set(shape[0]).and(vars_lock.try_set(shape[1]))
+        })
+    }
 
-        match obs_lock
-            .try_set(shape[0])
-            .and(vars_lock.try_set(shape[1]))
-        {
-            Ok(_) => {
-                self.x.swap(&new_elem);
-                Ok(())
-            }
-            Err(e) => {
-                new_elem.clear()?;
-                Err(e)
+    fn try_set_x_from_iter<I, D>(&self, iter: I) -> Result<()>
+    where
+        I: Iterator<Item = Result<D>>,
+        D: ArrayChunk + Into<ArrayData>,
+    {
+        // The iteration stops at the first error, which is then raised before
+        // the new element replaces X.
+        let error = RefCell::new(None);
+        let chunks = iter.map_while(|x| x.map_err(|e| *error.borrow_mut() = Some(e)).ok());
+        let mut obs_lock = self.n_obs.lock();
+        let mut vars_lock = self.n_vars.lock();
+        let result = replace_x_by_chunk(&self.file, &self.x, chunks, |shape| {
+            if let Some(e) = error.borrow_mut().take() {
+                return Err(e);
             }
+            obs_lock.try_set(shape[0]).and(vars_lock.try_set(shape[1]))
+        });
+        match error.into_inner() {
+            Some(e) => Err(e),
+            None => result,
         }
     }
 
@@ -201,7 +320,8 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
         let mut n_obs = self.n_obs.lock();
         if let Err(e) = n_obs.try_set(n) {
             if self.x().is_none() && self.obs.is_none() &&
-               self.obsm().is_empty() && self.obsp().is_empty() && self.layers().is_empty() 
+               self.obsm().is_empty() && self.obsp().is_empty() && self.layers().is_empty() &&
+               self.raw.x.is_none()
             {
                 n_obs.set(n);
             } else {
@@ -403,6 +523,18 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
         }
         &self.layers
     }
+    fn raw(&self) -> Result<Option<Self::RawRef<'_>>> {
+        Ok(if self.raw.is_none() { None } else { Some(&self.raw) })
+    }
+    fn set_raw_x<D: Into<ArrayData>>(&self, data: D) -> Result<Self::RawRef<'_>> {
+        self.raw.clear()?;
+        self.raw.init(&self.file)?;
+        if let Err(e) = (&self.raw).set_x(data) {
+            self.raw.clear()?;
+            return Err(e);
+        }
+        Ok(&self.raw)
+    }
 
     fn del_uns(&self) -> Result<()> {
         self.uns.clear()
@@ -422,12 +554,20 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
     fn del_layers(&self) -> Result<()> {
         self.layers.clear()
     }
+    fn del_raw(&self) -> Result<()> {
+        self.raw.clear()
+    }
 }
 
 impl<B: Backend> AnnDataOp for AnnDataSet<B> {
     type X = StackedArrayElem<B>;
-    type AxisArraysRef<'a> = &'a AxisArrays<B>;
+    /// The axis arrays of an AnnDataSet are [`StackedAxisArrays`] rather than
+    /// [`AxisArrays`]: `layers` are concatenated from the components, and the
+    /// other axis arrays are views of the annotation file. Their elements are
+    /// therefore [`StackedArrayElem`]s.
+    type AxisArraysRef<'a> = &'a StackedAxisArrays<B>;
     type ElemCollectionRef<'a> = &'a ElemCollection<B>;
+    type RawRef<'a> = StackedRaw<'a, B>;
 
     fn x(&self) -> Self::X {
         self.anndatas.inner().x.clone()
@@ -500,19 +640,39 @@ impl<B: Backend> AnnDataOp for AnnDataSet<B> {
         self.annotation.uns()
     }
     fn obsm(&self) -> Self::AxisArraysRef<'_> {
-        self.annotation.obsm()
+        // Create the group in the annotation file if it does not exist yet.
+        self.annotation.obsm();
+        &self.obsm
     }
     fn obsp(&self) -> Self::AxisArraysRef<'_> {
-        self.annotation.obsp()
+        self.annotation.obsp();
+        &self.obsp
     }
     fn varm(&self) -> Self::AxisArraysRef<'_> {
-        self.annotation.varm()
+        self.annotation.varm();
+        &self.varm
     }
     fn varp(&self) -> Self::AxisArraysRef<'_> {
-        self.annotation.varp()
+        self.annotation.varp();
+        &self.varp
     }
     fn layers(&self) -> Self::AxisArraysRef<'_> {
-        self.annotation.layers()
+        self.annotation.layers();
+        &self.layers
+    }
+    /// Returns the raw slot, whose X stacks the raw X of the components by
+    /// rows. Its variable annotations are stored in the annotation file.
+    fn raw(&self) -> Result<Option<Self::RawRef<'_>>> {
+        let raw_x = self.anndatas.inner().raw_x.clone();
+        match (raw_x, self.annotation.raw()?) {
+            (Some(x), Some(raw)) => Ok(Some(StackedRaw { x, raw })),
+            (None, None) => Ok(None),
+            (Some(_), None) => bail!("the raw slot is missing from the annotation file of AnnDataSet"),
+            (None, Some(_)) => bail!("some AnnData objects in AnnDataSet do not have a raw X"),
+        }
+    }
+    fn set_raw_x<D: Into<ArrayData>>(&self, _: D) -> Result<Self::RawRef<'_>> {
+        bail!("cannot set raw X in AnnDataSet")
     }
 
     fn set_uns<I: Iterator<Item = (String, Data)>>(&self, data: I) -> Result<()> {
@@ -546,9 +706,125 @@ impl<B: Backend> AnnDataOp for AnnDataSet<B> {
     fn del_varp(&self) -> Result<()> {
         self.annotation.del_varp()
     }
+    /// Deletes the layers from the components, as `layers().remove` does for
+    /// a single key, and from the annotation file.
     fn del_layers(&self) -> Result<()> {
+        self.layers
+            .keys()
+            .into_iter()
+            .try_for_each(|key| self.layers.remove_data(&key))?;
         self.annotation.del_layers()
     }
+    fn del_raw(&self) -> Result<()> {
+        bail!("cannot delete raw in AnnDataSet")
+    }
+}
+
+impl<B: Backend> RawOp for &Raw<B> {
+    type X = ArrayElem<B>;
+    type AxisArraysRef<'a> = &'a AxisArrays<B> where Self: 'a;
+
+    fn x(&self) -> Self::X {
+        self.x.clone()
+    }
+
+    fn set_x_from_iter<I: Iterator<Item = D>, D: ArrayChunk>(&self, iter: I) -> Result<()> {
+        let mut obs_lock = self.n_obs.lock();
+        let mut vars_lock = self.n_vars.lock();
+        replace_x_by_chunk(&*self.group.inner(), &self.x, iter, |shape| {
+            obs_lock.try_set(shape[0]).and(vars_lock.try_set(shape[1]))
+        })
+    }
+
+    fn set_x<D: Into<ArrayData>>(&self, data: D) -> Result<()> {
+        let data = data.into();
+        let shape = data.shape();
+        ensure!(
+            shape.ndim() >= 2,
+            "X must be a N dimensional array, where N >= 2"
+        );
+        self.n_obs.try_set(shape[0])?;
+        self.n_vars.try_set(shape[1])?;
+
+        if !self.x.is_none() {
+            self.x.inner().save(data)?;
+        } else {
+            let new_elem = ArrayElem::try_from(data.write(&*self.group.inner(), "X")?)?;
+            self.x.swap(&new_elem);
+        }
+        Ok(())
+    }
+
+    fn del_x(&self) -> Result<()> {
+        self.x.clear()
+    }
+
+    fn n_obs(&self) -> usize {
+        self.n_obs.get()
+    }
+    fn n_vars(&self) -> usize {
+        self.n_vars.get()
+    }
+
+    fn var_names(&self) -> DataFrameIndex {
+        self.var
+            .lock()
+            .as_ref()
+            .map_or(DataFrameIndex::empty(), |var| var.index.clone())
+    }
+
+    fn set_var_names(&self, index: DataFrameIndex) -> Result<()> {
+        self.n_vars.try_set(index.len())?;
+        if self.var.is_none() {
+            let df = InnerDataFrameElem::new(&*self.group.inner(), "var", Some(index), &DataFrame::empty())?;
+            self.var.insert(df);
+        } else {
+            self.var.inner().set_index(index)?;
+        }
+        Ok(())
+    }
+
+    fn read_var(&self) -> Result<DataFrame> {
+        self.var
+            .lock()
+            .as_mut()
+            .map_or(Ok(DataFrame::empty()), |x| x.data().map(Clone::clone))
+    }
+
+    fn set_var(&self, var: DataFrame) -> Result<()> {
+        let nrows = var.height();
+        if nrows != 0 {
+            self.n_vars.try_set(nrows)?;
+            if self.var.is_none() {
+                self.var.insert(InnerDataFrameElem::new(
+                    &*self.group.inner(),
+                    "var",
+                    None,
+                    &var,
+                )?);
+            } else {
+                self.var.inner().save(var)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn del_var(&self) -> Result<()> {
+        self.var.clear()
+    }
+
+    fn varm(&self) -> Result<Self::AxisArraysRef<'_>> {
+        ensure!(!self.group.is_none(), "raw is not set");
+        if self.varm.is_none() {
+            let varm = new_varm(self.group.inner().new_group("varm")?, &self.n_vars)?;
+            self.varm.swap(&varm);
+        }
+        Ok(&self.varm)
+    }
+
+    fn del_varm(&self) -> Result<()> {
+        self.varm.clear()
+    }
 }
 
 
@@ -622,6 +898,26 @@ pub trait AxisArraysOp {
         I: Iterator<Item = D>,
         D: ArrayChunk + Into<ArrayData>;
 
+    /// Adds array data from an iterator of chunks that may fail to be
+    /// produced. The first error is returned and nothing is stored under `key`.
+    fn try_add_iter<I, D>(&self, key: &str, data: I) -> Result<()>
+    where
+        I: Iterator<Item = Result<D>>,
+        D: ArrayChunk + Into<ArrayData>,
+    {
+        let mut error = None;
+        let result = self.add_iter(key, data.map_while(|x| x.map_err(|e| error = Some(e)).ok()));
+        match error {
+            Some(e) => {
+                if self.get(key).is_some() {
+                    self.remove(key)?;
+                }
+                Err(e)
+            }
+            None => result,
+        }
+    }
+
     /// Removes data by key.
     fn remove(&self, key: &str) -> Result<()>;
 }
@@ -662,27 +958,27 @@ impl<B: Backend> AxisArraysOp for &StackedAxisArrays<B> {
     type ArrayElem = StackedArrayElem<B>;
 
     fn keys(&self) -> Vec<String> {
-        self.data.keys().cloned().collect()
+        StackedAxisArrays::keys(self)
     }
 
     fn get(&self, key: &str) -> Option<Self::ArrayElem> {
-        self.data.get(key).cloned()
+        StackedAxisArrays::get(self, key)
     }
 
-    fn add<D: Into<ArrayData>>(&self, _key: &str, _data: D) -> Result<()> {
-        todo!()
+    fn add<D: Into<ArrayData>>(&self, key: &str, data: D) -> Result<()> {
+        self.add_data(key, data)
     }
 
-    fn add_iter<I, D>(&self, _key: &str, _data: I) -> Result<()>
+    fn add_iter<I, D>(&self, key: &str, data: I) -> Result<()>
     where
         I: Iterator<Item = D>,
-        D: ArrayChunk,
+        D: ArrayChunk + Into<ArrayData>,
     {
-        todo!()
+        self.add_data_from_iter(key, data)
     }
 
-    fn remove(&self, _key: &str) -> Result<()> {
-        todo!()
+    fn remove(&self, key: &str) -> Result<()> {
+        self.remove_data(key)
     }
 }
 
@@ -690,7 +986,7 @@ impl<B: Backend> AxisArraysOp for &StackedAxisArrays<B> {
 
 /// Trait for operations on array elements.
 pub trait ArrayElemOp {
-    type ArrayIter: ExactSizeIterator<Item = (ArrayData, usize, usize)>;
+    type ArrayIter: ExactSizeIterator<Item = Result<(ArrayData, usize, usize)>>;
 
     /// Returns the shape of the array.
     fn shape(&self) -> Option<Shape>;
@@ -729,8 +1025,151 @@ pub trait ArrayElemOp {
             .transpose()
     }
 
-    /// Returns an iterator over the data.
+    /// Returns an iterator over chunks of `chunk_size` rows, together with the
+    /// range of rows of each chunk. A chunk that cannot be read is returned as
+    /// an error.
     fn iter(&self, chunk_size: usize) -> Self::ArrayIter;
+
+    /// Applies `f` to every chunk of `chunk_size` rows. The chunks are read
+    /// and transformed lazily, so the result can be passed to
+    /// `AnnDataOp::try_set_x_from_iter` or `AxisArraysOp::try_add_iter` to
+    /// write a new element without loading the whole array into memory.
+    /// `try_set_x_from_iter` may read from X itself, but an element of an axis
+    /// array must not be overwritten by chunks read from it, as `try_add_iter`
+    /// removes the old data before the new data is written.
+    fn map_chunks<F>(&self, chunk_size: usize, f: F) -> MapChunks<Self::ArrayIter, F>
+    where
+        F: FnMut(ArrayData) -> ArrayData,
+    {
+        MapChunks::new(self.iter(chunk_size), f)
+    }
+
+    /// Computes a reduction along an axis of a 2D array. Axis 0 produces one
+    /// value per column and axis 1 produces one value per row. The data is read
+    /// `chunk_size` rows at a time, and each chunk is processed in parallel.
+    fn reduce(&self, reduction: Reduction, axis: usize, chunk_size: usize) -> Result<Array1<f64>> {
+        Ok(axis_stats(self, axis, chunk_size)?.finish(reduction))
+    }
+
+    /// Sum of the elements along an axis.
+    fn sum_axis(&self, axis: usize, chunk_size: usize) -> Result<Array1<f64>> {
+        self.reduce(Reduction::Sum, axis, chunk_size)
+    }
+
+    /// Mean of the elements along an axis.
+    fn mean_axis(&self, axis: usize, chunk_size: usize) -> Result<Array1<f64>> {
+        self.reduce(Reduction::Mean, axis, chunk_size)
+    }
+
+    /// Population variance of the elements along an axis.
+    fn var_axis(&self, axis: usize, chunk_size: usize) -> Result<Array1<f64>> {
+        self.reduce(Reduction::Var, axis, chunk_size)
+    }
+
+    /// Minimum of the elements along an axis.
+    fn min_axis(&self, axis: usize, chunk_size: usize) -> Result<Array1<f64>> {
+        self.reduce(Reduction::Min, axis, chunk_size)
+    }
+
+    /// Maximum of the elements along an axis.
+    fn max_axis(&self, axis: usize, chunk_size: usize) -> Result<Array1<f64>> {
+        self.reduce(Reduction::Max, axis, chunk_size)
+    }
+
+    /// Number of non-zero elements along an axis.
+    fn nnz_axis(&self, axis: usize, chunk_size: usize) -> Result<Array1<usize>> {
+        Ok(axis_stats(self, axis, chunk_size)?.nnz())
+    }
+
+    /// Computes `X · rhs`, where X is this 2D array and `rhs` is a dense matrix
+    /// with one row per column of X. X is read `chunk_size` rows at a time,
+    /// and the rows of each chunk are multiplied in parallel.
+    fn dot(&self, rhs: ArrayView2<f64>, chunk_size: usize) -> Result<Array2<f64>> {
+        let shape = self.shape().context("cannot multiply an empty element")?;
+        ensure!(
+            shape.ndim() == 2 && shape[1] == rhs.nrows(),
+            "cannot multiply a {} array with a {:?} matrix",
+            shape,
+            rhs.shape()
+        );
+        let mut out = Array2::zeros((shape[0], rhs.ncols()));
+        self.iter(chunk_size).try_for_each(|x| {
+            let (chunk, start, end) = x?;
+            out.slice_mut(s![start..end, ..]).assign(&dot_chunk(&chunk, rhs)?);
+            anyhow::Ok(())
+        })?;
+        Ok(out)
+    }
+
+    /// Computes `Xᵀ · rhs`, where X is this 2D array and `rhs` is a dense matrix
+    /// with one row per row of X. X is read `chunk_size` rows at a time, and
+    /// the product of each chunk is computed in parallel.
+    fn t_dot(&self, rhs: ArrayView2<f64>, chunk_size: usize) -> Result<Array2<f64>> {
+        let shape = self.shape().context("cannot multiply an empty element")?;
+        ensure!(
+            shape.ndim() == 2 && shape[0] == rhs.nrows(),
+            "cannot multiply the transpose of a {} array with a {:?} matrix",
+            shape,
+            rhs.shape()
+        );
+        let mut out = Array2::zeros((shape[1], rhs.ncols()));
+        self.iter(chunk_size).try_for_each(|x| {
+            let (chunk, start, end) = x?;
+            t_dot_chunk(&chunk, rhs.slice(s![start..end, ..]), &mut out)
+        })?;
+        Ok(out)
+    }
+
+    /// Computes `X · rhs` like `dot`, but writes the product under `key` in
+    /// `target` one chunk of rows at a time instead of returning it, e.g., to
+    /// project the cells of a large dataset into `obsm`. Nothing is stored
+    /// under `key` if the product fails. As with `map_chunks`, the product
+    /// must not overwrite the element it is computed from.
+    fn dot_into<A: AxisArraysOp>(
+        &self,
+        rhs: ArrayView2<f64>,
+        chunk_size: usize,
+        target: A,
+        key: &str,
+    ) -> Result<()> {
+        let shape = self.shape().context("cannot multiply an empty element")?;
+        ensure!(
+            shape.ndim() == 2 && shape[1] == rhs.nrows(),
+            "cannot multiply a {} array with a {:?} matrix",
+            shape,
+            rhs.shape()
+        );
+        let chunks = self
+            .iter(chunk_size)
+            .map(|x| x.and_then(|(chunk, _, _)| dot_chunk(&chunk, rhs)));
+        target.try_add_iter(key, chunks)
+    }
+
+    /// Computes `Xᵀ · rhs` like `t_dot` and stores the product under `key` in
+    /// `target`. Every chunk of X contributes to all rows of the product, so
+    /// it is accumulated in memory before being written, which takes one row
+    /// per column of X.
+    fn t_dot_into<A: AxisArraysOp>(
+        &self,
+        rhs: ArrayView2<f64>,
+        chunk_size: usize,
+        target: A,
+        key: &str,
+    ) -> Result<()> {
+        target.add(key, self.t_dot(rhs, chunk_size)?)
+    }
+}
+
+/// Accumulates the statistics of an array element along an axis, reading
+/// `chunk_size` rows at a time.
+fn axis_stats<A: ArrayElemOp + ?Sized>(elem: &A, axis: usize, chunk_size: usize) -> Result<AxisStats> {
+    let shape = elem.shape().context("cannot reduce an empty element")?;
+    let mut stats = AxisStats::new(&shape, axis)?;
+    elem.iter(chunk_size).try_for_each(|x| {
+        let (chunk, start, _) = x?;
+        stats.update(&chunk, start)
+    })?;
+    Ok(stats)
 }
 
 impl<B: Backend> ArrayElemOp for ArrayElem<B> {
@@ -0,0 +1,684 @@
+use crate::backend::{Backend, BackendData, DataType, DatasetOp, GroupOp, ScalarType, StoreOp};
+use crate::data::{
+    ArrayData, CsrNonCanonical, DynArray, DynCooMatrix, DynCscMatrix, DynCsrMatrix,
+    DynCsrNonCanonical, SelectInfoElem, Shape,
+};
+use crate::{AnnDataOp, ArrayElemOp, AxisArraysOp};
+
+use anyhow::{bail, ensure, Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use half::f16;
+use nalgebra_sparse::{coo::CooMatrix, csc::CscMatrix, csr::CsrMatrix};
+use ndarray::{Array1, Array2, ArrayD};
+use polars::prelude::{DataFrame, DataType as PolarsDataType, Series};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes 2D arrays in the Matrix Market coordinate format. The array is read
+/// and written chunk by chunk, so it never has to fit in memory. Files whose
+/// names end with `.gz` are gzipped.
+pub struct MMWriter {
+    transpose: bool,
+    chunk_size: usize,
+}
+
+impl Default for MMWriter {
+    fn default() -> Self {
+        Self {
+            transpose: false,
+            chunk_size: 2000,
+        }
+    }
+}
+
+impl MMWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the transpose of the array, i.e., one row per column of the
+    /// array. 10x Genomics matrices are stored as features by barcodes.
+    pub fn transpose(mut self) -> Self {
+        self.transpose = true;
+        self
+    }
+
+    /// Number of rows that are read at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn write<A: ArrayElemOp, P: AsRef<Path>>(&self, elem: &A, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let shape = elem.shape().context("cannot write an empty element")?;
+        ensure!(
+            shape.ndim() == 2,
+            "only 2D arrays can be written in the Matrix Market format, but got a {} array",
+            shape
+        );
+        let (nrows, ncols) = if self.transpose {
+            (shape[1], shape[0])
+        } else {
+            (shape[0], shape[1])
+        };
+
+        // The header contains the number of entries, which is only known once
+        // all chunks have been written. So the entries are written to a
+        // temporary file and appended to the header afterwards. This works for
+        // gzipped files too, as a gzip file may consist of several members.
+        let mut body_path = path.as_os_str().to_owned();
+        body_path.push(".entries");
+        let body_path = PathBuf::from(body_path);
+        let result = self
+            .write_entries(elem, &body_path, is_gzipped(path))
+            .and_then(|(field, nnz)| {
+                let mut header = Output::create(path)?;
+                writeln!(header, "%%MatrixMarket matrix coordinate {} general", field)?;
+                writeln!(header, "{} {} {}", nrows, ncols, nnz)?;
+                header.finish()?;
+                let mut output = OpenOptions::new().append(true).open(path)?;
+                io::copy(&mut File::open(&body_path)?, &mut output)?;
+                Ok(())
+            });
+        if body_path.exists() {
+            fs::remove_file(&body_path)?;
+        }
+        result.with_context(|| format!("cannot write matrix market file: {}", path.display()))
+    }
+
+    /// Writes the entries of all chunks, and returns the field of the header
+    /// and the number of entries.
+    fn write_entries<A: ArrayElemOp>(
+        &self,
+        elem: &A,
+        path: &Path,
+        gzip: bool,
+    ) -> Result<(&'static str, usize)> {
+        let mut output = if gzip {
+            Output::gzip(path)?
+        } else {
+            Output::plain(path)?
+        };
+        let mut field = "real";
+        let mut nnz = 0;
+        for x in elem.iter(self.chunk_size) {
+            let (chunk, start, _) = x?;
+            field = mm_field(&chunk)?;
+            let mut entries = Entries {
+                writer: &mut output,
+                offset: start,
+                transpose: self.transpose,
+                count: 0,
+            };
+            entries.write_chunk(&chunk)?;
+            nnz += entries.count;
+        }
+        output.finish()?;
+        Ok((field, nnz))
+    }
+}
+
+/// Exports X or a layer of an AnnData object as a 10x Genomics MEX directory,
+/// i.e., a gzipped `matrix.mtx.gz` of features by barcodes together with
+/// `barcodes.tsv.gz` and `features.tsv.gz` built from obs and var.
+pub struct MexWriter {
+    layer: Option<String>,
+    feature_names: Option<String>,
+    feature_type: String,
+    chunk_size: usize,
+}
+
+impl Default for MexWriter {
+    fn default() -> Self {
+        Self {
+            layer: None,
+            feature_names: None,
+            feature_type: "Gene Expression".to_string(),
+            chunk_size: 2000,
+        }
+    }
+}
+
+impl MexWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Export a layer instead of X.
+    pub fn layer(mut self, key: &str) -> Self {
+        self.layer = Some(key.to_string());
+        self
+    }
+
+    /// Column of var used as the second column of `features.tsv.gz`, e.g.,
+    /// gene symbols. The var names are used by default.
+    pub fn feature_names(mut self, column: &str) -> Self {
+        self.feature_names = Some(column.to_string());
+        self
+    }
+
+    /// Feature type written in the third column of `features.tsv.gz`.
+    /// Defaults to "Gene Expression".
+    pub fn feature_type(mut self, feature_type: &str) -> Self {
+        self.feature_type = feature_type.to_string();
+        self
+    }
+
+    /// Number of rows that are read at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn write<A: AnnDataOp, P: AsRef<Path>>(&self, adata: &A, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let writer = MMWriter::new().transpose().chunk_size(self.chunk_size);
+        let matrix = dir.join("matrix.mtx.gz");
+        if let Some(key) = &self.layer {
+            let layer = adata
+                .layers()
+                .get(key)
+                .with_context(|| format!("layer '{}' does not exist", key))?;
+            writer.write(&layer, matrix)?;
+        } else {
+            writer.write(&adata.x(), matrix)?;
+        }
+
+        let mut barcodes = Output::create(dir.join("barcodes.tsv.gz"))?;
+        for name in adata.obs_names() {
+            writeln!(barcodes, "{}", name)?;
+        }
+        barcodes.finish()?;
+
+        let var_names = adata.var_names().into_vec();
+        let names: Vec<String> = if let Some(column) = &self.feature_names {
+            let var = adata.read_var()?;
+            let names = var.column(column)?.cast(&PolarsDataType::String)?;
+            names
+                .str()?
+                .into_iter()
+                .map(|x| x.unwrap_or_default().to_string())
+                .collect()
+        } else {
+            var_names.clone()
+        };
+        let mut features = Output::create(dir.join("features.tsv.gz"))?;
+        for (id, name) in var_names.iter().zip(names.iter()) {
+            writeln!(features, "{}\t{}\t{}", id, name, self.feature_type)?;
+        }
+        features.finish()?;
+        Ok(())
+    }
+}
+
+/// Writes AnnData objects as loom files. X and the layers are transposed to
+/// genes by cells and written chunk by chunk. obs and var become the column
+/// and row attributes, obsm and varm are written as multi-dimensional
+/// attributes, and obsp and varp become the column and row graphs.
+pub struct LoomWriter {
+    obs_names: String,
+    var_names: String,
+    chunk_size: usize,
+}
+
+impl Default for LoomWriter {
+    fn default() -> Self {
+        Self {
+            obs_names: "CellID".to_string(),
+            var_names: "Gene".to_string(),
+            chunk_size: 2000,
+        }
+    }
+}
+
+impl LoomWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Column attribute that stores the obs names. Defaults to "CellID".
+    pub fn obs_names(mut self, key: &str) -> Self {
+        self.obs_names = key.to_string();
+        self
+    }
+
+    /// Row attribute that stores the var names. Defaults to "Gene".
+    pub fn var_names(mut self, key: &str) -> Self {
+        self.var_names = key.to_string();
+        self
+    }
+
+    /// Number of rows that are read at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn write<B: Backend, A: AnnDataOp, P: AsRef<Path>>(&self, adata: &A, path: P) -> Result<()> {
+        let store = B::new(path)?;
+        write_transposed::<B, _, _>(&store, "matrix", &adata.x(), self.chunk_size)
+            .context("cannot write X")?;
+        {
+            let layers = store.new_group("layers")?;
+            for key in adata.layers().keys() {
+                let layer = adata.layers().get(&key).unwrap();
+                write_transposed::<B, _, _>(&layers, &key, &layer, self.chunk_size)
+                    .with_context(|| format!("cannot write layer '{}'", key))?;
+            }
+        }
+
+        write_loom_attrs::<B, _, _>(
+            &store.new_group("col_attrs")?,
+            &self.obs_names,
+            adata.obs_names().into_vec(),
+            adata.read_obs()?,
+            adata.obsm(),
+        )?;
+        write_loom_attrs::<B, _, _>(
+            &store.new_group("row_attrs")?,
+            &self.var_names,
+            adata.var_names().into_vec(),
+            adata.read_var()?,
+            adata.varm(),
+        )?;
+        write_loom_graphs::<B, _, _>(&store.new_group("col_graphs")?, adata.obsp())?;
+        write_loom_graphs::<B, _, _>(&store.new_group("row_graphs")?, adata.varp())?;
+
+        store
+            .new_group("attrs")?
+            .new_scalar_dataset("LOOM_SPEC_VERSION", &"3.0.0".to_string())?;
+        store.close()
+    }
+}
+
+/// Write the transpose of a 2D array as a dense dataset, chunk by chunk.
+fn write_transposed<B: Backend, G: GroupOp<B>, A: ArrayElemOp>(
+    group: &G,
+    name: &str,
+    elem: &A,
+    chunk_size: usize,
+) -> Result<()> {
+    let shape = elem.shape().context("cannot write an empty element")?;
+    ensure!(
+        shape.ndim() == 2,
+        "only 2D arrays can be written to loom files, but got a {} array",
+        shape
+    );
+    let shape: Shape = vec![shape[1], shape[0]].into();
+    let mut dataset = None;
+    for x in elem.iter(chunk_size) {
+        let (chunk, start, end) = x?;
+        let chunk = to_dense(chunk)?;
+        macro_rules! fun {
+            ($variant:ident, $value:expr) => {{
+                if dataset.is_none() {
+                    dataset = Some(new_dataset_like::<B, _, _>(group, name, &shape, &$value)?);
+                }
+                let arr = $value.reversed_axes();
+                dataset.as_ref().unwrap().write_array_slice(
+                    arr.as_standard_layout(),
+                    &[SelectInfoElem::full(), SelectInfoElem::from(start..end)],
+                )?;
+            }};
+        }
+        crate::macros::dyn_map!(chunk, DynArray, fun);
+    }
+    if dataset.is_none() {
+        group.new_empty_dataset::<f32>(name, &shape, Default::default())?;
+    }
+    Ok(())
+}
+
+fn new_dataset_like<B: Backend, G: GroupOp<B>, T: BackendData>(
+    group: &G,
+    name: &str,
+    shape: &Shape,
+    _: &ArrayD<T>,
+) -> Result<B::Dataset> {
+    group.new_empty_dataset::<T>(name, shape, Default::default())
+}
+
+/// Write the names, the columns of a DataFrame and the multi-dimensional
+/// arrays of one axis as loom attributes.
+fn write_loom_attrs<B: Backend, G: GroupOp<B>, A: AxisArraysOp>(
+    group: &G,
+    index_key: &str,
+    index: Vec<String>,
+    df: DataFrame,
+    arrays: A,
+) -> Result<()> {
+    group.new_array_dataset(index_key, index.into(), Default::default())?;
+    for series in df.get_columns() {
+        if series.name().as_str() != index_key {
+            write_dyn_array::<B, _>(group, series.name().as_str(), series_to_array(series)?)?;
+        }
+    }
+    for key in arrays.keys() {
+        if let Some(data) = arrays.get_item::<ArrayData>(&key)? {
+            write_dyn_array::<B, _>(group, &key, to_dense(data)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write pairwise arrays as loom graphs, i.e., groups with the row indices
+/// `a`, the column indices `b` and the weights `w` of the non-zero entries.
+fn write_loom_graphs<B: Backend, G: GroupOp<B>, A: AxisArraysOp>(group: &G, arrays: A) -> Result<()> {
+    for key in arrays.keys() {
+        let csr = match arrays.get_item::<ArrayData>(&key)? {
+            Some(ArrayData::CsrMatrix(x)) => x,
+            Some(ArrayData::CooMatrix(x)) => x.to_csr()?,
+            Some(ArrayData::CsrNonCanonical(x)) => match x.canonicalize() {
+                Ok(x) => x,
+                Err(_) => bail!("cannot write a CSR matrix with duplicated entries as a loom graph"),
+            },
+            Some(x) => bail!("cannot write {} as a loom graph", x.data_type()),
+            None => continue,
+        };
+        let graph = group.new_group(&key)?;
+        macro_rules! fun {
+            ($variant:ident, $value:expr) => {{
+                let mut a = Vec::with_capacity($value.nnz());
+                let mut b = Vec::with_capacity($value.nnz());
+                let mut w = Vec::with_capacity($value.nnz());
+                $value.triplet_iter().for_each(|(i, j, v)| {
+                    a.push(i as i64);
+                    b.push(j as i64);
+                    w.push(v.clone());
+                });
+                graph.new_array_dataset("a", a.into(), Default::default())?;
+                graph.new_array_dataset("b", b.into(), Default::default())?;
+                graph.new_array_dataset("w", w.into(), Default::default())?;
+            }};
+        }
+        crate::macros::dyn_map!(csr, DynCsrMatrix, fun);
+    }
+    Ok(())
+}
+
+fn write_dyn_array<B: Backend, G: GroupOp<B>>(group: &G, name: &str, arr: DynArray) -> Result<()> {
+    macro_rules! fun {
+        ($variant:ident, $value:expr) => {
+            group.new_array_dataset(name, $value.into(), Default::default())?
+        };
+    }
+    crate::macros::dyn_map!(arr, DynArray, fun);
+    Ok(())
+}
+
+/// Convert a DataFrame column to an array. Categorical columns are written
+/// as strings, and missing values as NaN or the default value of the type.
+fn series_to_array(series: &Series) -> Result<DynArray> {
+    macro_rules! fun {
+        ($variant:ident, $ty:ident, $missing:expr) => {
+            DynArray::$variant(
+                series
+                    .$ty()?
+                    .into_iter()
+                    .map(|x| x.unwrap_or($missing))
+                    .collect::<Array1<_>>()
+                    .into_dyn(),
+            )
+        };
+    }
+    let arr = match series.dtype() {
+        PolarsDataType::Int8 => fun!(I8, i8, 0),
+        PolarsDataType::Int16 => fun!(I16, i16, 0),
+        PolarsDataType::Int32 => fun!(I32, i32, 0),
+        PolarsDataType::Int64 => fun!(I64, i64, 0),
+        PolarsDataType::UInt8 => fun!(U8, u8, 0),
+        PolarsDataType::UInt16 => fun!(U16, u16, 0),
+        PolarsDataType::UInt32 => fun!(U32, u32, 0),
+        PolarsDataType::UInt64 => fun!(U64, u64, 0),
+        PolarsDataType::Float32 => fun!(F32, f32, f32::NAN),
+        PolarsDataType::Float64 => fun!(F64, f64, f64::NAN),
+        PolarsDataType::Boolean => fun!(Bool, bool, false),
+        _ => DynArray::String(
+            series
+                .cast(&PolarsDataType::String)?
+                .str()?
+                .into_iter()
+                .map(|x| x.unwrap_or_default().to_string())
+                .collect::<Array1<_>>()
+                .into_dyn(),
+        ),
+    };
+    Ok(arr)
+}
+
+/// Convert a chunk of a 2D array to a dense array.
+fn to_dense(data: ArrayData) -> Result<DynArray> {
+    macro_rules! fun {
+        ($variant:ident, $value:expr) => {
+            DynArray::$variant(triplets_to_dense(
+                ($value.nrows(), $value.ncols()),
+                $value.triplet_iter(),
+            ))
+        };
+    }
+    let arr = match data {
+        ArrayData::Array(x) => x,
+        ArrayData::CsrMatrix(x) => crate::macros::dyn_map!(x, DynCsrMatrix, fun),
+        ArrayData::CsrNonCanonical(x) => match x.canonicalize() {
+            Ok(x) => crate::macros::dyn_map!(x, DynCsrMatrix, fun),
+            Err(_) => bail!("cannot densify a CSR matrix with duplicated entries"),
+        },
+        ArrayData::CscMatrix(x) => crate::macros::dyn_map!(x, DynCscMatrix, fun),
+        ArrayData::CooMatrix(x) => {
+            let x = x.to_csr()?;
+            crate::macros::dyn_map!(x, DynCsrMatrix, fun)
+        }
+        ArrayData::Nullable(_) => bail!("cannot densify a nullable array"),
+        ArrayData::DataFrame(_) => bail!("cannot densify a DataFrame"),
+    };
+    Ok(arr)
+}
+
+fn triplets_to_dense<'a, T, I>(shape: (usize, usize), triplets: I) -> ArrayD<T>
+where
+    T: Clone + Default + 'a,
+    I: Iterator<Item = (usize, usize, &'a T)>,
+{
+    let mut arr = Array2::default(shape);
+    triplets.for_each(|(i, j, v)| arr[[i, j]] = v.clone());
+    arr.into_dyn()
+}
+
+fn is_gzipped(path: &Path) -> bool {
+    path.extension().map_or(false, |x| x == "gz")
+}
+
+/// A buffered file that is gzipped or written as is.
+enum Output {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Output {
+    /// Create a file, which is gzipped if its name ends with `.gz`.
+    fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if is_gzipped(path.as_ref()) {
+            Self::gzip(path)
+        } else {
+            Self::plain(path)
+        }
+    }
+
+    fn plain<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::Plain(BufWriter::new(File::create(path)?)))
+    }
+
+    fn gzip<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self::Gzip(GzEncoder::new(file, Compression::default())))
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Plain(mut w) => w.flush()?,
+            Self::Gzip(w) => w.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(w) => w.write(buf),
+            Self::Gzip(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            Self::Gzip(w) => w.flush(),
+        }
+    }
+}
+
+/// The field of the Matrix Market header for the data type of a chunk.
+fn mm_field(chunk: &ArrayData) -> Result<&'static str> {
+    let ty = match chunk.data_type() {
+        DataType::Array(ty)
+        | DataType::CsrMatrix(ty)
+        | DataType::CscMatrix(ty)
+        | DataType::CooMatrix(ty) => ty,
+        ty => bail!("cannot write {} in the Matrix Market format", ty),
+    };
+    match ty {
+        ScalarType::F16 | ScalarType::F32 | ScalarType::F64 => Ok("real"),
+        ScalarType::String => bail!("cannot write strings in the Matrix Market format"),
+        _ => Ok("integer"),
+    }
+}
+
+/// Values that can be written as Matrix Market entries.
+trait MMValue {
+    fn is_zero(&self) -> bool;
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+}
+
+macro_rules! impl_mm_value {
+    ($($ty:ty),*) => {
+        $(
+            impl MMValue for $ty {
+                fn is_zero(&self) -> bool {
+                    *self == <$ty>::default()
+                }
+
+                fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                    write!(writer, "{}", self)
+                }
+            }
+        )*
+    };
+}
+
+impl_mm_value!(i8, i16, i32, i64, u8, u16, u32, u64, f16, f32, f64);
+
+impl MMValue for bool {
+    fn is_zero(&self) -> bool {
+        !*self
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "{}", u8::from(*self))
+    }
+}
+
+/// Writes the entries of a chunk of rows that starts at row `offset`.
+struct Entries<'a, W> {
+    writer: &'a mut W,
+    offset: usize,
+    transpose: bool,
+    count: usize,
+}
+
+impl<W: Write> Entries<'_, W> {
+    fn push<T: MMValue>(&mut self, i: usize, j: usize, value: &T) -> io::Result<()> {
+        let i = i + self.offset;
+        // Matrix Market indices are 1-based.
+        let (row, col) = if self.transpose { (j + 1, i + 1) } else { (i + 1, j + 1) };
+        write!(self.writer, "{} {} ", row, col)?;
+        value.write_to(self.writer)?;
+        writeln!(self.writer)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn dense<T: MMValue>(&mut self, arr: &ArrayD<T>) -> Result<()> {
+        for (idx, v) in arr.indexed_iter() {
+            if !v.is_zero() {
+                self.push(idx[0], idx[1], v)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn csr<T: MMValue>(&mut self, csr: &CsrMatrix<T>) -> Result<()> {
+        csr.triplet_iter().try_for_each(|(i, j, v)| self.push(i, j, v))?;
+        Ok(())
+    }
+
+    fn csr_noncanonical<T: MMValue>(&mut self, csr: &CsrNonCanonical<T>) -> Result<()> {
+        let (offsets, indices, values) = csr.csr_data();
+        for (i, w) in offsets.windows(2).enumerate() {
+            (w[0]..w[1]).try_for_each(|k| self.push(i, indices[k], &values[k]))?;
+        }
+        Ok(())
+    }
+
+    fn csc<T: MMValue>(&mut self, csc: &CscMatrix<T>) -> Result<()> {
+        csc.triplet_iter().try_for_each(|(i, j, v)| self.push(i, j, v))?;
+        Ok(())
+    }
+
+    fn coo<T: MMValue>(&mut self, coo: &CooMatrix<T>) -> Result<()> {
+        coo.triplet_iter().try_for_each(|(i, j, v)| self.push(i, j, v))?;
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, chunk: &ArrayData) -> Result<()> {
+        macro_rules! entries {
+            ($data:expr, $enum:ident, $fun:ident) => {
+                match $data {
+                    $enum::I8(x) => self.$fun(x),
+                    $enum::I16(x) => self.$fun(x),
+                    $enum::I32(x) => self.$fun(x),
+                    $enum::I64(x) => self.$fun(x),
+                    $enum::U8(x) => self.$fun(x),
+                    $enum::U16(x) => self.$fun(x),
+                    $enum::U32(x) => self.$fun(x),
+                    $enum::U64(x) => self.$fun(x),
+                    $enum::F16(x) => self.$fun(x),
+                    $enum::F32(x) => self.$fun(x),
+                    $enum::F64(x) => self.$fun(x),
+                    $enum::Bool(x) => self.$fun(x),
+                    $enum::String(_) => {
+                        bail!("cannot write strings in the Matrix Market format")
+                    }
+                }
+            };
+        }
+        match chunk {
+            ArrayData::Array(x) => {
+                ensure!(x.ndim() == 2, "only 2D arrays can be written in the Matrix Market format");
+                entries!(x, DynArray, dense)
+            }
+            ArrayData::CsrMatrix(x) => entries!(x, DynCsrMatrix, csr),
+            ArrayData::CsrNonCanonical(x) => entries!(x, DynCsrNonCanonical, csr_noncanonical),
+            ArrayData::CscMatrix(x) => entries!(x, DynCscMatrix, csc),
+            ArrayData::CooMatrix(x) => entries!(x, DynCooMatrix, coo),
+            ArrayData::Nullable(_) => {
+                bail!("cannot write a nullable array in the Matrix Market format")
+            }
+            ArrayData::DataFrame(_) => bail!("cannot write a DataFrame in the Matrix Market format"),
+        }
+    }
+}
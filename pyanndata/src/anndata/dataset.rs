@@ -32,6 +32,10 @@ use super::backed::StackedAnnData;
         File name of the output file containing the AnnDataSet object.
     add_key: str
         The column name in obs to store the keys
+    join: Literal['inner', 'outer']
+        How to combine the variables of the AnnData objects. "inner" keeps
+        the variables present in all objects, "outer" keeps the union of the
+        variables and fills missing values with zeros.
     backend: str
         The backend to use for the AnnDataSet object.
 
@@ -117,13 +121,19 @@ pub enum AnnDataFile<'py> {
 #[pymethods]
 impl AnnDataSet {
     #[new]
-    #[pyo3(signature = (adatas, *, filename, add_key="sample", backend=None))]
+    #[pyo3(signature = (adatas, *, filename, add_key="sample", join="inner", backend=None))]
     pub fn new(
         adatas: Vec<(String, AnnDataFile)>,
         filename: PathBuf,
         add_key: &str,
+        join: &str,
         backend: Option<&str>,
     ) -> Result<Self> {
+        let join = match join {
+            "inner" => anndata::Join::Inner,
+            "outer" => anndata::Join::Outer,
+            _ => bail!("join must be 'inner' or 'outer', but got '{}'", join),
+        };
         match backend.unwrap_or(H5::NAME) {
             H5::NAME => {
                 let anndatas = adatas.into_iter().map(|(key, data_file)| {
@@ -135,7 +145,7 @@ impl AnnDataSet {
                     };
                     (key, adata)
                 });
-                Ok(anndata::AnnDataSet::new(anndatas, filename, add_key)?.into())
+                Ok(anndata::AnnDataSet::new_with_join(anndatas, filename, add_key, join)?.into())
             }
             _ => todo!(),
         }
@@ -7,7 +7,7 @@ use pyo3::exceptions::PyTypeError;
 use pyo3::types::IntoPyDict;
 use pyo3_polars::PyDataFrame;
 use anndata::{self, Selectable, ElemCollectionOp, ArrayElemOp};
-use anndata::{AnnDataOp, AxisArraysOp, ArrayData, Data, Backend, HasShape};
+use anndata::{AnnDataOp, AxisArraysOp, ArrayData, Data, Backend, HasShape, RawOp};
 use anndata::data::{ArrayChunk, DataFrameIndex, SelectInfoElem, Shape, Stackable};
 use anyhow::{Result, bail};
 
@@ -103,6 +103,25 @@ impl<'py> PyAnnData<'py> {
                 .into_iter()
                 .try_for_each(|k| adata.varp().add(&k, inner.varp().get_item::<ArrayData>(&k)?.unwrap()))?;
         }
+        {
+            // Set raw
+            let raw = inner.get_raw();
+            if let Some(x) = raw.x().get::<ArrayData>()? {
+                let py_raw = adata.set_raw_x(x)?;
+                let var_names = raw.var_names();
+                if !var_names.is_empty() {
+                    py_raw.set_var_names(var_names)?;
+                }
+                let var = raw.read_var()?;
+                if var.width() > 0 {
+                    py_raw.set_var(var)?;
+                }
+                let (varm, py_varm) = (raw.varm()?, py_raw.varm()?);
+                varm.keys()
+                    .into_iter()
+                    .try_for_each(|k| py_varm.add(&k, varm.get_item::<ArrayData>(&k)?.unwrap()))?;
+            }
+        }
         Ok(adata)
     }
 }
@@ -111,6 +130,7 @@ impl<'py> AnnDataOp for PyAnnData<'py> {
     type X = ArrayElem<'py>;
     type ElemCollectionRef<'a> = ElemCollection<'a> where Self: 'a;
     type AxisArraysRef<'a> = AxisArrays<'a> where Self: 'a;
+    type RawRef<'a> = PyRaw<'a> where Self: 'a;
 
     fn x(&self) -> Self::X {
         ArrayElem(self.0.getattr("X").unwrap())
@@ -129,6 +149,17 @@ impl<'py> AnnDataOp for PyAnnData<'py> {
         Ok(())
     }
 
+    /// X is built in memory anyway, so the chunks are stacked only once all of
+    /// them have been produced, leaving X unchanged on errors.
+    fn try_set_x_from_iter<I, D>(&self, iter: I) -> Result<()>
+    where
+        I: Iterator<Item = Result<D>>,
+        D: ArrayChunk + Into<ArrayData>,
+    {
+        let chunks = iter.map(|x| x.map(Into::into)).collect::<Result<Vec<ArrayData>>>()?;
+        self.set_x_from_iter(chunks.into_iter())
+    }
+
     fn set_x<D: Into<ArrayData>>(&self, data: D) -> Result<()> {
         let data = data.into();
         let py = self.py();
@@ -276,28 +307,28 @@ impl<'py> AnnDataOp for PyAnnData<'py> {
     fn obsm(&self) -> Self::AxisArraysRef<'_> {
         AxisArrays {
             arrays: self.getattr("obsm").unwrap(),
-            adata: self,
+            adata: Some(self),
             axis: 0,
         }
     }
     fn obsp(&self) -> Self::AxisArraysRef<'_> {
         AxisArrays {
             arrays: self.getattr("obsp").unwrap(),
-            adata: self,
+            adata: Some(self),
             axis: 0,
         }
     }
     fn varm(&self) -> Self::AxisArraysRef<'_> {
         AxisArrays {
             arrays: self.getattr("varm").unwrap(),
-            adata: self,
+            adata: Some(self),
             axis: 1,
         }
     }
     fn varp(&self) -> Self::AxisArraysRef<'_> {
         AxisArrays {
             arrays: self.getattr("varp").unwrap(),
-            adata: self,
+            adata: Some(self),
             axis: 1,
         }
     }
@@ -305,11 +336,22 @@ impl<'py> AnnDataOp for PyAnnData<'py> {
     fn layers(&self) -> Self::AxisArraysRef<'_> {
         AxisArrays {
             arrays: self.getattr("layers").unwrap(),
-            adata: self,
+            adata: Some(self),
             axis: 2,
         }
     }
 
+    fn raw(&self) -> Result<Option<Self::RawRef<'_>>> {
+        let raw = PyRaw(self);
+        Ok(raw.get()?.map(|_| raw))
+    }
+    fn set_raw_x<D: Into<ArrayData>>(&self, data: D) -> Result<Self::RawRef<'_>> {
+        self.del_raw()?;
+        let raw = PyRaw(self);
+        raw.set_x(data)?;
+        Ok(raw)
+    }
+
     fn del_uns(&self) -> Result<()> {
         self.0.setattr("uns", pyo3::types::PyDict::new_bound(self.py()))?;
         Ok(())
@@ -339,6 +381,145 @@ impl<'py> AnnDataOp for PyAnnData<'py> {
         self.0.setattr("layers", None::<PyObject>)?;
         Ok(())
     }
+
+    fn del_raw(&self) -> Result<()> {
+        self.0.setattr("raw", None::<PyObject>)?;
+        Ok(())
+    }
+}
+
+/// The `raw` attribute of a Python AnnData object.
+pub struct PyRaw<'a>(&'a PyAnnData<'a>);
+
+impl<'a> PyRaw<'a> {
+    fn get(&self) -> Result<Option<Bound<'a, PyAny>>> {
+        let raw = self.0.getattr("raw")?;
+        Ok(if raw.is_none() { None } else { Some(raw) })
+    }
+
+    fn get_or_err(&self) -> Result<Bound<'a, PyAny>> {
+        match self.get()? {
+            Some(raw) => Ok(raw),
+            None => bail!("raw is not set"),
+        }
+    }
+}
+
+impl<'a> RawOp for PyRaw<'a> {
+    type X = ArrayElem<'a>;
+    type AxisArraysRef<'b> = AxisArrays<'b> where Self: 'b;
+
+    fn x(&self) -> Self::X {
+        match self.get().unwrap() {
+            Some(raw) => ArrayElem(raw.getattr("X").unwrap()),
+            None => ArrayElem(self.0.py().None().into_bound(self.0.py())),
+        }
+    }
+
+    fn set_x_from_iter<I, D>(&self, iter: I) -> Result<()>
+    where
+        I: Iterator<Item = D>,
+        D: Into<ArrayData>,
+    {
+        self.set_x(Stackable::vstack(iter.map(|x| x.into()))?)
+    }
+
+    fn set_x<D: Into<ArrayData>>(&self, data: D) -> Result<()> {
+        let data = data.into();
+        let py = self.0.py();
+        let shape = data.shape();
+        self.0.set_n_obs(shape[0])?;
+        // Keep the existing annotations if they are compatible with the new matrix.
+        let (var, varm) = match self.get()? {
+            Some(raw) if raw.getattr("n_vars")?.extract::<usize>()? == shape[1] => {
+                let varm = py.import_bound("builtins")?.call_method1("dict", (raw.getattr("varm")?,))?;
+                (raw.getattr("var")?.into_py(py), varm.into_py(py))
+            }
+            _ => (py.None(), py.None()),
+        };
+        let raw = py.import_bound("anndata")?.call_method(
+            "Raw",
+            (self.0.0.clone(),),
+            Some(&[
+                ("X", PyArrayData::from(data).into_py(py)),
+                ("var", var),
+                ("varm", varm),
+            ].into_py_dict_bound(py)),
+        )?;
+        self.0.setattr("_raw", raw)?;
+        Ok(())
+    }
+
+    fn del_x(&self) -> Result<()> {
+        bail!("cannot delete X of raw in Python AnnData, delete raw instead")
+    }
+
+    fn n_obs(&self) -> usize {
+        self.0.n_obs()
+    }
+    fn n_vars(&self) -> usize {
+        self.get().unwrap().map_or(0, |raw| raw.getattr("n_vars").unwrap().extract().unwrap())
+    }
+
+    fn var_names(&self) -> DataFrameIndex {
+        self.get().unwrap().map_or(DataFrameIndex::empty(), |raw|
+            raw.getattr("var_names").unwrap().extract::<Vec<String>>().unwrap().into()
+        )
+    }
+    fn set_var_names(&self, index: DataFrameIndex) -> Result<()> {
+        self.get_or_err()?.getattr("var")?.setattr("index", index.into_vec())?;
+        Ok(())
+    }
+
+    fn read_var(&self) -> Result<DataFrame> {
+        match self.get()? {
+            Some(raw) => {
+                let df: PyDataFrame = self.0.py()
+                    .import_bound("polars")?
+                    .call_method1("from_pandas", (raw.getattr("var")?,))?
+                    .extract()?;
+                Ok(df.into())
+            }
+            None => Ok(DataFrame::empty()),
+        }
+    }
+    fn set_var(&self, var: DataFrame) -> Result<()> {
+        let py = self.0.py();
+        let raw = self.get_or_err()?;
+        let index = raw.getattr("var")?.getattr("index")?;
+        let df = PyDataFrame(var).into_py(py)
+            .call_method0(py, "to_pandas")?
+            .call_method1(py, "set_index", (index,))?;
+        raw.setattr("_var", df)?;
+        Ok(())
+    }
+    fn del_var(&self) -> Result<()> {
+        let py = self.0.py();
+        let raw = self.get_or_err()?;
+        let index = raw.getattr("var")?.getattr("index")?;
+        let df = py.import_bound("pandas")?.call_method(
+            "DataFrame",
+            (),
+            Some(&[("index", index)].into_py_dict_bound(py)),
+        )?;
+        raw.setattr("_var", df)?;
+        Ok(())
+    }
+
+    fn varm(&self) -> Result<Self::AxisArraysRef<'_>> {
+        Ok(AxisArrays {
+            arrays: self.get_or_err()?.getattr("varm")?,
+            adata: None,
+            axis: 1,
+        })
+    }
+    fn del_varm(&self) -> Result<()> {
+        let raw = self.get_or_err()?;
+        let keys: Vec<String> = raw.getattr("varm")?.call_method0("keys")?.iter()?
+            .map(|x| x?.extract()).collect::<PyResult<_>>()?;
+        keys.into_iter().try_for_each(|k| raw.getattr("varm")?.del_item(k))?;
+        Ok(())
+    }
 }
 
 pub struct PyArrayIterator {
@@ -362,7 +543,7 @@ impl PyArrayIterator {
 
 impl Iterator for PyArrayIterator
 {
-    type Item = (ArrayData, usize, usize);
+    type Item = Result<(ArrayData, usize, usize)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_row >= self.total_rows {
@@ -373,7 +554,7 @@ impl Iterator for PyArrayIterator
             self.current_row = j;
             let slice = SelectInfoElem::from(i..j);
             let data = self.array.select_axis(0, slice);
-            Some((data, i, j))
+            Some(Ok((data, i, j)))
         }
     }
 }
@@ -433,7 +614,9 @@ impl ElemCollectionOp for ElemCollection<'_> {
 
 pub struct AxisArrays<'a> {
     arrays: Bound<'a, PyAny>,
-    adata: &'a PyAnnData<'a>,
+    /// The parent AnnData object whose dimensions are checked when adding data.
+    /// None means the dimensions are validated by Python.
+    adata: Option<&'a PyAnnData<'a>>,
     axis: u8,
 }
 
@@ -457,13 +640,15 @@ impl<'py> AxisArraysOp for AxisArrays<'py> {
         let data = data.into();
         let py = self.arrays.py();
         let shape = data.shape();
-        if self.axis == 0 {
-            self.adata.set_n_obs(shape[0])?;
-        } else if self.axis == 1 {
-            self.adata.set_n_vars(shape[0])?;
-        } else {
-            self.adata.set_n_obs(shape[0])?;
-            self.adata.set_n_vars(shape[1])?;
+        if let Some(adata) = self.adata {
+            if self.axis == 0 {
+                adata.set_n_obs(shape[0])?;
+            } else if self.axis == 1 {
+                adata.set_n_vars(shape[0])?;
+            } else {
+                adata.set_n_obs(shape[0])?;
+                adata.set_n_vars(shape[1])?;
+            }
         }
         let d = PyArrayData::from(data).into_py(py);
         let new_d = if isinstance_of_polars(d.bind(py))? {
@@ -483,13 +668,15 @@ impl<'py> AxisArraysOp for AxisArrays<'py> {
         let py = self.arrays.py();
         let array = Stackable::vstack(data.map(|x| x.into()))?;
         let shape = array.shape();
-        if self.axis == 0 {
-            self.adata.set_n_obs(shape[0])?;
-        } else if self.axis == 1 {
-            self.adata.set_n_vars(shape[0])?;
-        } else {
-            self.adata.set_n_obs(shape[0])?;
-            self.adata.set_n_vars(shape[1])?;
+        if let Some(adata) = self.adata {
+            if self.axis == 0 {
+                adata.set_n_obs(shape[0])?;
+            } else if self.axis == 1 {
+                adata.set_n_vars(shape[0])?;
+            } else {
+                adata.set_n_obs(shape[0])?;
+                adata.set_n_vars(shape[1])?;
+            }
         }
         self.arrays
             .call_method1("__setitem__", (key, PyArrayData::from(array).into_py(py)))?;
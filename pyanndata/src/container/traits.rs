@@ -284,33 +284,28 @@ impl<B: Backend + 'static> AxisArrayTrait for AxisArrays<B> {
 
 impl<B: Backend + 'static> AxisArrayTrait for StackedAxisArrays<B> {
     fn keys(&self) -> Vec<String> {
-        self.deref().keys().map(|x| x.to_string()).collect()
+        StackedAxisArrays::keys(self)
     }
 
     fn contains(&self, key: &str) -> bool {
-        self.deref().contains_key(key)
+        self.contains_key(key)
     }
 
     fn get(&self, key: &str) -> Result<PyArrayData> {
-        Ok(self
-            .deref()
-            .get(key)
+        Ok(StackedAxisArrays::get(self, key)
             .context(format!("No such key: {}", key))?
             .data::<ArrayData>()?.unwrap()
             .into())
     }
 
     fn el(&self, key: &str) -> Result<PyArrayElem> {
-        Ok(self
-            .deref()
-            .get(key)
+        Ok(StackedAxisArrays::get(self, key)
             .context(format!("No such key: {}", key))?
-            .clone()
             .into())
     }
 
-    fn set(&self, _: &str, _: PyArrayData) -> Result<()> {
-        bail!("mutations are not allowed on stacked axis arrays")
+    fn set(&self, key: &str, data: PyArrayData) -> Result<()> {
+        self.add_data::<ArrayData>(key, data.into())
     }
 
     fn show(&self) -> String {
@@ -366,7 +361,7 @@ impl<B: Backend + 'static> ElemCollectionTrait for ElemCollection<B> {
     }
 }
 
-pub trait ChunkedArrayTrait: ExactSizeIterator<Item = (ArrayData, usize, usize)> + Send {}
+pub trait ChunkedArrayTrait: ExactSizeIterator<Item = Result<(ArrayData, usize, usize)>> + Send {}
 
 impl<B: Backend> ChunkedArrayTrait for ChunkedArrayElem<B> {}
 impl<B: Backend> ChunkedArrayTrait for StackedChunkedArrayElem<B> {}
\ No newline at end of file
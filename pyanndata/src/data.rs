@@ -3,7 +3,7 @@ mod slice;
 mod array;
 
 pub(crate) use instance::*;
-use pyo3_polars::PyDataFrame;
+use pyo3_polars::{PyDataFrame, PySeries};
 pub use slice::{to_select_info, to_select_elem};
 
 use std::{collections::HashMap, ops::Deref};
@@ -72,6 +72,8 @@ impl IntoPy<PyObject> for PyArrayData {
             ArrayData::CsrMatrix(csr) => csr.into_python(py).unwrap(),
             ArrayData::CsrNonCanonical(csr) => csr.into_python(py).unwrap(),
             ArrayData::CscMatrix(csc) => csc.into_python(py).unwrap(),
+            ArrayData::CooMatrix(coo) => coo.into_python(py).unwrap(),
+            ArrayData::Nullable(arr) => PySeries(arr.into()).into_py(py),
             ArrayData::DataFrame(df) => PyDataFrame(df).into_py(py),
         }
     }
@@ -155,6 +157,7 @@ impl IntoPython for DynScalar {
             DynScalar::U16(s) => Ok(s.into_py(py)),
             DynScalar::U32(s) => Ok(s.into_py(py)),
             DynScalar::U64(s) => Ok(s.into_py(py)),
+            DynScalar::F16(s) => Ok(s.to_f32().into_py(py)),
             DynScalar::F32(s) => Ok(s.into_py(py)),
             DynScalar::F64(s) => Ok(s.into_py(py)),
             DynScalar::Bool(s) => Ok(s.into_py(py)),
@@ -1,9 +1,10 @@
 use crate::data::{isinstance_of_csc, isinstance_of_csr, FromPython, IntoPython};
 
+use half::f16;
 use ndarray::ArrayD;
-use nalgebra_sparse::{CsrMatrix, CscMatrix};
+use nalgebra_sparse::{CooMatrix, CsrMatrix, CscMatrix};
 use pyo3::{exceptions::PyTypeError, prelude::*};
-use anndata::data::{DynArray, DynCsrMatrix, DynCscMatrix, DynCsrNonCanonical, CsrNonCanonical};
+use anndata::data::{DynArray, DynCooMatrix, DynCsrMatrix, DynCscMatrix, DynCsrNonCanonical, CsrNonCanonical};
 use numpy::{PyReadonlyArrayDyn, IntoPyArray, PyArrayMethods};
 
 macro_rules! proc_py_numeric {
@@ -41,6 +42,10 @@ macro_rules! proc_py_numeric {
                 let x: $ty_anno<u64> = $data;
                 x.into()
             }
+            "float16" => {
+                let x: $ty_anno<f16> = $data;
+                x.into()
+            }
             "float32" => {
                 let x: $ty_anno<f32> = $data;
                 x.into()
@@ -244,6 +249,7 @@ impl IntoPython for DynArray {
             DynArray::U16(arr) => arr.into_pyarray_bound(py).to_object(py),
             DynArray::U32(arr) => arr.into_pyarray_bound(py).to_object(py),
             DynArray::U64(arr) => arr.into_pyarray_bound(py).to_object(py),
+            DynArray::F16(arr) => arr.into_pyarray_bound(py).to_object(py),
             DynArray::F32(arr) => arr.into_pyarray_bound(py).to_object(py),
             DynArray::F64(arr) => arr.into_pyarray_bound(py).to_object(py),
             DynArray::Bool(arr) => arr.into_pyarray_bound(py).to_object(py),
@@ -282,6 +288,7 @@ impl IntoPython for DynCsrMatrix {
             DynCsrMatrix::U16(csr) => helper(csr, py),
             DynCsrMatrix::U32(csr) => helper(csr, py),
             DynCsrMatrix::U64(csr) => helper(csr, py),
+            DynCsrMatrix::F16(csr) => helper(csr, py),
             DynCsrMatrix::F32(csr) => helper(csr, py),
             DynCsrMatrix::F64(csr) => helper(csr, py),
             DynCsrMatrix::Bool(csr) => helper(csr, py),
@@ -319,6 +326,7 @@ impl IntoPython for DynCsrNonCanonical {
             DynCsrNonCanonical::U16(csr) => helper(csr, py),
             DynCsrNonCanonical::U32(csr) => helper(csr, py),
             DynCsrNonCanonical::U64(csr) => helper(csr, py),
+            DynCsrNonCanonical::F16(csr) => helper(csr, py),
             DynCsrNonCanonical::F32(csr) => helper(csr, py),
             DynCsrNonCanonical::F64(csr) => helper(csr, py),
             DynCsrNonCanonical::Bool(csr) => helper(csr, py),
@@ -357,10 +365,50 @@ impl IntoPython for DynCscMatrix {
             DynCscMatrix::U16(csc) => helper(csc, py),
             DynCscMatrix::U32(csc) => helper(csc, py),
             DynCscMatrix::U64(csc) => helper(csc, py),
+            DynCscMatrix::F16(csc) => helper(csc, py),
             DynCscMatrix::F32(csc) => helper(csc, py),
             DynCscMatrix::F64(csc) => helper(csc, py),
             DynCscMatrix::Bool(csc) => helper(csc, py),
             DynCscMatrix::String(_) => todo!(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl IntoPython for DynCooMatrix {
+    fn into_python(self, py: Python<'_>) -> PyResult<PyObject> {
+        fn helper<T: numpy::Element>(coo: CooMatrix<T>, py: Python<'_>) -> PyResult<PyObject> {
+            let n = coo.nrows();
+            let m = coo.ncols();
+            let (row, col, data) = coo.disassemble();
+            let scipy = PyModule::import_bound(py, "scipy.sparse")?;
+            Ok(scipy
+                .getattr("coo_matrix")?
+                .call1((
+                    (
+                        data.into_pyarray_bound(py),
+                        (row.into_pyarray_bound(py), col.into_pyarray_bound(py)),
+                    ),
+                    (n, m),
+                ))?
+                .to_object(py))
+        }
+
+        match self {
+            DynCooMatrix::I8(coo) => helper(coo, py),
+            DynCooMatrix::I16(coo) => helper(coo, py),
+            DynCooMatrix::I32(coo) => helper(coo, py),
+            DynCooMatrix::I64(coo) => helper(coo, py),
+            DynCooMatrix::U8(coo) => helper(coo, py),
+            DynCooMatrix::U16(coo) => helper(coo, py),
+            DynCooMatrix::U32(coo) => helper(coo, py),
+            DynCooMatrix::U64(coo) => helper(coo, py),
+            DynCooMatrix::F16(coo) => helper(coo, py),
+            DynCooMatrix::F32(coo) => helper(coo, py),
+            DynCooMatrix::F64(coo) => helper(coo, py),
+            DynCooMatrix::Bool(coo) => helper(coo, py),
+            DynCooMatrix::String(_) => Err(PyTypeError::new_err(
+                "COO matrices of strings cannot be converted to scipy",
+            )),
+        }
+    }
+}